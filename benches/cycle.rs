@@ -0,0 +1,45 @@
+use chip8::core::processor::Processor;
+use chip8::core::rom::Rom;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+// Instructions per frame when benchmarking, high enough that per-frame work like timers and
+// events does not dominate
+const CYCLES_PER_FRAME: usize = 10_000;
+const FRAMES: usize = 10;
+
+// Programs that keep running without input, so every frame executes all of its cycles
+const ROMS: [&str; 4] = ["IBM Logo", "Maze", "Pong", "Sierpinski"];
+
+fn load(name: &str) -> Processor {
+    let rom = Rom::new(&format!("roms/{}.ch8", name)).expect("Missing ROM");
+    let mut processor = Processor::new(Default::default(), Default::default(), Default::default());
+    processor.load_rom(&rom).expect("ROM does not fit");
+    processor
+}
+
+fn instructions_per_second(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cycle");
+    for name in ROMS {
+        // Measure how many instructions a run actually executes, ROMs may halt or wait
+        let mut processor = load(name);
+        for _ in 0..FRAMES {
+            processor.run_frame(CYCLES_PER_FRAME).expect("ROM failed");
+        }
+        group.throughput(Throughput::Elements(processor.instructions));
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || load(name),
+                |processor| {
+                    for _ in 0..FRAMES {
+                        processor.run_frame(CYCLES_PER_FRAME).expect("ROM failed");
+                    }
+                },
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, instructions_per_second);
+criterion_main!(benches);
@@ -0,0 +1,31 @@
+// With the ffi feature, generates chip8.h from the extern "C" functions in src/ffi.rs into
+// OUT_DIR. The copy in include/ is committed, a test in src/ffi.rs checks it is up to date
+fn main() {
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let out_dir = std::env::var("OUT_DIR").unwrap();
+        let mut config = cbindgen::Config {
+            language: cbindgen::Language::C,
+            include_guard: Some(String::from("CHIP8_H")),
+            cpp_compat: true,
+            ..Default::default()
+        };
+        config.enumeration.rename_variants = cbindgen::RenameRule::QualifiedScreamingSnakeCase;
+        match cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(format!("{}/src/ffi.rs", crate_dir))
+            .generate()
+        {
+            Ok(bindings) => {
+                bindings.write_to_file(format!("{}/chip8.h", out_dir));
+            }
+            // The library still builds, the header test then fails with the reason
+            Err(e) => {
+                println!("cargo:warning=Could not generate chip8.h: {}", e);
+                std::fs::write(format!("{}/chip8.h", out_dir), "").unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+// Drives a 128x64 monochrome panel, the size of an SSD1306, through embedded-graphics. The
+// panel here is a framebuffer printed to the terminal at the end, on hardware it would be the
+// display driver's DrawTarget instead:
+//
+//     cargo run --example embedded --no-default-features --features embedded-graphics
+use chip8::consts;
+use chip8::core::events::Event;
+use chip8::core::processor::Processor;
+use chip8::core::rom::Rom;
+use chip8::core::screen::Screen;
+use chip8::external::embedded::EmbeddedDisplay;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use std::convert::Infallible;
+use std::error::Error;
+
+const WIDTH: usize = 128;
+const HEIGHT: usize = 64;
+const FRAMES: u64 = 60;
+
+// One bit per pixel as kept by the panel's controller
+struct Panel {
+    pixels: [[bool; WIDTH]; HEIGHT],
+}
+
+impl OriginDimensions for Panel {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for Panel {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let (Ok(x @ 0..WIDTH), Ok(y @ 0..HEIGHT)) =
+                (usize::try_from(point.x), usize::try_from(point.y))
+            {
+                self.pixels[y][x] = color.is_on();
+            }
+        }
+        Ok(())
+    }
+}
+
+// Runs the program for a number of frames, showing every frame that changed the display
+fn run<S: Screen>(chip8: &mut Processor, screen: &mut S, frames: u64) -> Result<(), Box<dyn Error>>
+where
+    S::Error: Error + 'static,
+{
+    let events = chip8.events.subscribe();
+    for _ in 0..frames {
+        chip8.run_frame(consts::CYCLES_PER_FRAME)?;
+        let redraw = events
+            .try_iter()
+            .any(|event| matches!(event, Event::FrameCompleted { redraw: true }));
+        if redraw {
+            screen.show(&chip8.display_buffer)?;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("roms/IBM Logo.ch8"));
+    let rom = Rom::new(&path)?;
+    let mut chip8 = Processor::builder().build();
+    chip8.load_rom(&rom)?;
+
+    let panel = Panel {
+        pixels: [[false; WIDTH]; HEIGHT],
+    };
+    let mut screen = EmbeddedDisplay::new(panel, BinaryColor::On, BinaryColor::Off);
+    screen.fit(&chip8.display_buffer);
+    run(&mut chip8, &mut screen, FRAMES)?;
+
+    for row in screen.target.pixels.iter() {
+        let line: String = row.iter().map(|&on| if on { '#' } else { '.' }).collect();
+        println!("{}", line);
+    }
+    Ok(())
+}
@@ -0,0 +1,27 @@
+#![no_main]
+
+use chip8::consts;
+use chip8::core::processor::Processor;
+use chip8::core::rom::Rom;
+use libfuzzer_sys::fuzz_target;
+
+// Runs arbitrary ROMs on a headless processor, run with `cargo fuzz run cpu`. The first
+// two bytes pick the held keys, the rest is the program. Errors are fine, panics are not
+fuzz_target!(|data: &[u8]| {
+    let Some((keys, program)) = data.split_first_chunk::<2>() else {
+        return;
+    };
+    let Ok(rom) = Rom::from_bytes(program) else {
+        return;
+    };
+    let mut processor = Processor::new(Default::default(), Default::default(), Default::default());
+    if processor.load_rom(&rom).is_err() {
+        return;
+    }
+    processor.keyboard_buffer.keys = u16::from_le_bytes(*keys);
+    for _ in 0..120 {
+        if processor.run_frame(consts::CYCLES_PER_FRAME).is_err() {
+            break;
+        }
+    }
+});
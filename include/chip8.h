@@ -0,0 +1,40 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum Chip8Status {
+  CHIP8_STATUS_OK,
+  CHIP8_STATUS_REDRAW,
+  CHIP8_STATUS_HALTED,
+  CHIP8_STATUS_ERROR,
+} Chip8Status;
+
+typedef struct Chip8 Chip8;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+struct Chip8 *chip8_new(void);
+
+void chip8_free(struct Chip8 *chip8);
+
+enum Chip8Status chip8_load(struct Chip8 *chip8, const uint8_t *data, uintptr_t len);
+
+enum Chip8Status chip8_step(struct Chip8 *chip8);
+
+const uint8_t *chip8_framebuffer(struct Chip8 *chip8, uintptr_t *width, uintptr_t *height);
+
+void chip8_set_keys(struct Chip8 *chip8, uint16_t keys);
+
+bool chip8_sound_active(const struct Chip8 *chip8);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIP8_H */
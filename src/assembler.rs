@@ -0,0 +1,541 @@
+use crate::cli::AsmOptions;
+use crate::consts;
+use std::collections::HashMap;
+use std::fmt;
+
+// Assembles the core of the Octo language: labels, :const, :alias, register arithmetic,
+// if/then, if/begin/else/end, loop/again, the SCHIP display commands and raw data bytes
+
+#[derive(Debug)]
+pub struct AssembleError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let code = line.split('#').next().unwrap_or_default();
+        for text in code.split_whitespace() {
+            tokens.push(Token { text, line: i + 1 });
+        }
+    }
+    tokens
+}
+
+fn parse_number(text: &str) -> Option<i32> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i32::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i32::from_str_radix(bin, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    output: Vec<u8>,
+    labels: HashMap<&'a str, u16>,
+    constants: HashMap<&'a str, i32>,
+    aliases: HashMap<&'a str, u8>,
+    // Opcodes waiting for a label defined further down, patched once everything is read
+    fixups: Vec<(usize, Token<'a>)>,
+    loops: Vec<u16>,
+    // Jumps emitted by begin and else, patched by the matching else or end
+    branches: Vec<usize>,
+}
+
+impl<'a> Assembler<'a> {
+    fn error<T>(&self, token: Token, message: String) -> Result<T, AssembleError> {
+        Err(AssembleError {
+            line: token.line,
+            message,
+        })
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, AssembleError> {
+        match self.tokens.get(self.pos) {
+            Some(&token) => {
+                self.pos += 1;
+                Ok(token)
+            }
+            None => Err(AssembleError {
+                line: self.tokens.last().map_or(1, |t| t.line),
+                message: String::from("Unexpected end of file"),
+            }),
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AssembleError> {
+        let token = self.next()?;
+        if token.text != text {
+            return self.error(token, format!("Expected {}, found {}", text, token.text));
+        }
+        Ok(())
+    }
+
+    fn here(&self) -> u16 {
+        (consts::PROG_OFFSET + self.output.len()) as u16
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.output.extend_from_slice(&opcode.to_be_bytes());
+    }
+
+    fn patch(&mut self, at: usize, address: u16) {
+        let opcode = u16::from_be_bytes([self.output[at], self.output[at + 1]]) | address;
+        self.output[at..at + 2].copy_from_slice(&opcode.to_be_bytes());
+    }
+
+    fn as_register(&self, text: &str) -> Option<u8> {
+        if let Some(&register) = self.aliases.get(text) {
+            return Some(register);
+        }
+        let digit = text.strip_prefix('v').or_else(|| text.strip_prefix('V'))?;
+        match digit.len() {
+            1 => u8::from_str_radix(digit, 16).ok(),
+            _ => None,
+        }
+    }
+
+    fn register(&mut self) -> Result<u8, AssembleError> {
+        let token = self.next()?;
+        match self.as_register(token.text) {
+            Some(register) => Ok(register),
+            None => self.error(token, format!("Expected a register, found {}", token.text)),
+        }
+    }
+
+    fn number(&self, token: Token) -> Option<i32> {
+        parse_number(token.text).or_else(|| self.constants.get(token.text).copied())
+    }
+
+    fn value(&mut self, min: i32, max: i32) -> Result<i32, AssembleError> {
+        let token = self.next()?;
+        match self.number(token) {
+            Some(value) if (min..=max).contains(&value) => Ok(value),
+            Some(value) => self.error(token, format!("{} is out of range", value)),
+            None => self.error(token, format!("Expected a number, found {}", token.text)),
+        }
+    }
+
+    fn byte(&mut self) -> Result<u16, AssembleError> {
+        Ok(self.value(-128, 255)? as u8 as u16)
+    }
+
+    // Emits an opcode taking a 12 bit address, which may refer to a label defined later
+    fn emit_address(&mut self, base: u16, token: Token<'a>) -> Result<(), AssembleError> {
+        let address = match self.number(token) {
+            Some(value) if (0..=0xFFF).contains(&value) => value as u16,
+            Some(value) => return self.error(token, format!("{} is out of range", value)),
+            None => match self.labels.get(token.text) {
+                Some(&address) => address,
+                None => {
+                    self.fixups.push((self.output.len(), token));
+                    0
+                }
+            },
+        };
+        self.emit(base | address);
+        Ok(())
+    }
+
+    // Returns the opcode that skips the next instruction when the condition does not hold
+    fn condition(&mut self) -> Result<u16, AssembleError> {
+        let x = self.register()? as u16;
+        let op = self.next()?;
+        let opcode = match op.text {
+            "key" => 0xE0A1 | x << 8,
+            "-key" => 0xE09E | x << 8,
+            "==" | "!=" => {
+                let rhs = self.next()?;
+                let equal = op.text == "==";
+                match self.as_register(rhs.text) {
+                    Some(y) => (if equal { 0x9000 } else { 0x5000 }) | x << 8 | (y as u16) << 4,
+                    None => {
+                        self.pos -= 1;
+                        (if equal { 0x4000 } else { 0x3000 }) | x << 8 | self.byte()?
+                    }
+                }
+            }
+            _ => return self.error(op, format!("Unknown comparison {}", op.text)),
+        };
+        Ok(opcode)
+    }
+
+    fn conditional(&mut self) -> Result<(), AssembleError> {
+        let skip = self.condition()?;
+        let keyword = self.next()?;
+        match keyword.text {
+            "then" => self.emit(skip),
+            "begin" => {
+                // Skip over the jump to else/end when the condition holds
+                let inverted = match skip & 0xF000 {
+                    0x3000 => skip + 0x1000,
+                    0x4000 => skip - 0x1000,
+                    0x5000 => skip + 0x4000,
+                    0x9000 => skip - 0x4000,
+                    _ => skip ^ 0x003F,
+                };
+                self.emit(inverted);
+                self.branches.push(self.output.len());
+                self.emit(0x1000);
+            }
+            _ => {
+                return self.error(
+                    keyword,
+                    format!("Expected then or begin, found {}", keyword.text),
+                )
+            }
+        }
+        Ok(())
+    }
+
+    fn assignment(&mut self, x: u16) -> Result<(), AssembleError> {
+        let op = self.next()?;
+        let rhs = self.next()?;
+        if let Some(y) = self.as_register(rhs.text) {
+            let n = match op.text {
+                ":=" => 0,
+                "|=" => 1,
+                "&=" => 2,
+                "^=" => 3,
+                "+=" => 4,
+                "-=" => 5,
+                ">>=" => 6,
+                "=-" => 7,
+                "<<=" => 0xE,
+                _ => return self.error(op, format!("Unknown operator {}", op.text)),
+            };
+            self.emit(0x8000 | x << 8 | (y as u16) << 4 | n);
+            return Ok(());
+        }
+        match (op.text, rhs.text) {
+            (":=", "random") => {
+                let mask = self.byte()?;
+                self.emit(0xC000 | x << 8 | mask);
+            }
+            (":=", "delay") => self.emit(0xF007 | x << 8),
+            (":=", "key") => self.emit(0xF00A | x << 8),
+            (":=", _) | ("+=", _) | ("-=", _) => {
+                self.pos -= 1;
+                let value = self.byte()?;
+                match op.text {
+                    ":=" => self.emit(0x6000 | x << 8 | value),
+                    "+=" => self.emit(0x7000 | x << 8 | value),
+                    _ => self.emit(0x7000 | x << 8 | (value as u8).wrapping_neg() as u16),
+                }
+            }
+            _ => return self.error(op, format!("Unknown operator {}", op.text)),
+        }
+        Ok(())
+    }
+
+    fn index(&mut self) -> Result<(), AssembleError> {
+        let op = self.next()?;
+        match op.text {
+            "+=" => {
+                let x = self.register()? as u16;
+                self.emit(0xF01E | x << 8);
+            }
+            ":=" => {
+                let rhs = self.next()?;
+                match rhs.text {
+                    "hex" => {
+                        let x = self.register()? as u16;
+                        self.emit(0xF029 | x << 8);
+                    }
+                    "bighex" => {
+                        let x = self.register()? as u16;
+                        self.emit(0xF030 | x << 8);
+                    }
+                    _ => self.emit_address(0xA000, rhs)?,
+                }
+            }
+            _ => return self.error(op, format!("Unknown operator {}", op.text)),
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), AssembleError> {
+        let token = self.next()?;
+        match token.text {
+            ":" => {
+                let name = self.next()?;
+                let here = self.here();
+                if self.labels.insert(name.text, here).is_some() {
+                    return self.error(name, format!("Label {} defined twice", name.text));
+                }
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.value(-0x8000, 0xFFFF)?;
+                self.constants.insert(name.text, value);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = self.register()?;
+                self.aliases.insert(name.text, register);
+            }
+            "clear" => self.emit(0x00E0),
+            "return" | ";" => self.emit(0x00EE),
+            "scroll-down" => {
+                let n = self.value(0, 0xF)? as u16;
+                self.emit(0x00C0 | n);
+            }
+            "scroll-right" => self.emit(0x00FB),
+            "scroll-left" => self.emit(0x00FC),
+            "exit" => self.emit(0x00FD),
+            "lores" => self.emit(0x00FE),
+            "hires" => self.emit(0x00FF),
+            "jump" => {
+                let target = self.next()?;
+                self.emit_address(0x1000, target)?;
+            }
+            "jump0" => {
+                let target = self.next()?;
+                self.emit_address(0xB000, target)?;
+            }
+            ":call" => {
+                let target = self.next()?;
+                self.emit_address(0x2000, target)?;
+            }
+            "i" => self.index()?,
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()? as u16;
+                let base = match token.text {
+                    "delay" => 0xF015,
+                    "buzzer" => 0xF018,
+                    _ => 0xF03A,
+                };
+                self.emit(base | x << 8);
+            }
+            "bcd" | "save" | "load" => {
+                let x = self.register()? as u16;
+                let base = match token.text {
+                    "bcd" => 0xF033,
+                    "save" => 0xF055,
+                    _ => 0xF065,
+                };
+                self.emit(base | x << 8);
+            }
+            "sprite" => {
+                let x = self.register()? as u16;
+                let y = self.register()? as u16;
+                let n = self.value(0, 0xF)? as u16;
+                self.emit(0xD000 | x << 8 | y << 4 | n);
+            }
+            "if" => self.conditional()?,
+            "else" => {
+                let Some(branch) = self.branches.pop() else {
+                    return self.error(token, String::from("else without begin"));
+                };
+                self.branches.push(self.output.len());
+                self.emit(0x1000);
+                let here = self.here();
+                self.patch(branch, here);
+            }
+            "end" => {
+                let Some(branch) = self.branches.pop() else {
+                    return self.error(token, String::from("end without begin"));
+                };
+                let here = self.here();
+                self.patch(branch, here);
+            }
+            "loop" => {
+                let here = self.here();
+                self.loops.push(here);
+            }
+            "again" => {
+                let Some(start) = self.loops.pop() else {
+                    return self.error(token, String::from("again without loop"));
+                };
+                self.emit(0x1000 | start);
+            }
+            text => {
+                if let Some(x) = self.as_register(text) {
+                    self.assignment(x as u16)?;
+                } else if let Some(value) = self.number(token) {
+                    if !(-128..=255).contains(&value) {
+                        return self.error(token, format!("{} does not fit in a byte", value));
+                    }
+                    self.output.push(value as u8);
+                } else if text.starts_with(':') {
+                    return self.error(token, format!("Unsupported directive {}", text));
+                } else {
+                    // A bare name calls the subroutine at that label
+                    self.emit_address(0x2000, token)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssembleError> {
+    Ok(assemble_with_labels(source)?.0)
+}
+
+pub type Labels = Vec<(String, u16)>;
+
+// Also returns every label with its address, which doubles as a symbol map for the program
+pub fn assemble_with_labels(source: &str) -> Result<(Vec<u8>, Labels), AssembleError> {
+    let mut assembler = Assembler {
+        tokens: tokenize(source),
+        pos: 0,
+        output: Vec::new(),
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        fixups: Vec::new(),
+        loops: Vec::new(),
+        branches: Vec::new(),
+    };
+    while assembler.pos < assembler.tokens.len() {
+        assembler.statement()?;
+    }
+    if let Some(&start) = assembler.loops.last() {
+        let line = assembler.tokens.last().map_or(1, |t| t.line);
+        return Err(AssembleError {
+            line,
+            message: format!("loop at {:#05X} is never closed with again", start),
+        });
+    }
+    if !assembler.branches.is_empty() {
+        let line = assembler.tokens.last().map_or(1, |t| t.line);
+        return Err(AssembleError {
+            line,
+            message: String::from("begin is never closed with end"),
+        });
+    }
+    for (at, token) in std::mem::take(&mut assembler.fixups) {
+        match assembler.labels.get(token.text) {
+            Some(&address) => assembler.patch(at, address),
+            None => return assembler.error(token, format!("Undefined label {}", token.text)),
+        }
+    }
+    let labels = assembler
+        .labels
+        .iter()
+        .map(|(name, &address)| (name.to_string(), address))
+        .collect();
+    Ok((assembler.output, labels))
+}
+
+// Entry point of `chip8 asm`, the symbol file uses Octo's :const format
+pub fn export(options: &AsmOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let source = std::fs::read_to_string(&options.source_path)?;
+    let (program, labels) =
+        assemble_with_labels(&source).map_err(|e| format!("{}: {}", options.source_path, e))?;
+    let output = match &options.output {
+        Some(path) => std::path::PathBuf::from(path),
+        None => std::path::Path::new(&options.source_path).with_extension("ch8"),
+    };
+    std::fs::write(&output, &program)?;
+    if let Some(path) = &options.symbols {
+        let symbols: String = labels
+            .iter()
+            .map(|(name, address)| format!(":const {} 0x{:03X}\n", name, address))
+            .collect();
+        std::fs::write(path, symbols)?;
+    }
+    println!("Wrote {} bytes to {}", program.len(), output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::assemble;
+
+    #[test]
+    fn test_assemble_statements() -> Result<(), Box<dyn std::error::Error>> {
+        let program = assemble(
+            "
+            : main
+                clear
+                v0 := 5       # 6005
+                v1 += v0      # 8104
+                i := hex v1
+                sprite v0 v1 5
+                if v0 != 3 then v2 := key
+                jump main
+            ",
+        )?;
+        assert_eq!(
+            program,
+            [
+                0x00, 0xE0, 0x60, 0x05, 0x81, 0x04, 0xF1, 0x29, 0xD0, 0x15, 0x30, 0x03, 0xF2, 0x0A,
+                0x12, 0x00
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_assemble_forward_labels_and_data() -> Result<(), Box<dyn std::error::Error>> {
+        let program = assemble(
+            "
+            :const SIZE 2
+            i := sprite
+            draw
+            : draw return
+            : sprite 0xFF 0b10000001 SIZE
+            ",
+        )?;
+        assert_eq!(
+            program,
+            [0xA2, 0x06, 0x22, 0x04, 0x00, 0xEE, 0xFF, 0x81, 0x02]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_assemble_control_flow() -> Result<(), Box<dyn std::error::Error>> {
+        let program = assemble(
+            "
+            loop
+                if v0 == 1 begin
+                    v1 := 1
+                else
+                    v1 := 2
+                end
+            again
+            ",
+        )?;
+        assert_eq!(
+            program,
+            [0x30, 0x01, 0x12, 0x08, 0x61, 0x01, 0x12, 0x0A, 0x61, 0x02, 0x12, 0x00]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_assemble_error_line() {
+        let error = assemble("clear\n\nv0 := 300\n").unwrap_err();
+        assert_eq!(error.line, 3);
+        let error = assemble("clear\njump nowhere\n").unwrap_err();
+        assert_eq!(error.line, 2);
+    }
+}
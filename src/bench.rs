@@ -0,0 +1,75 @@
+use crate::cli::BenchOptions;
+use crate::core::events::Event;
+use crate::core::processor::Processor;
+use crate::core::rom::Rom;
+use crate::database;
+use std::time::{Duration, Instant};
+
+// Entry point of `chip8 bench`, runs the ROM flat out with the same settings as `chip8 run` but
+// with no window, sound or frame pacing, and reports the speed reached
+pub fn run(mut options: BenchOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let path = options.run.rom_path.clone().unwrap_or_default();
+    let prog = Rom::new(&path)?;
+    if options.run.use_database {
+        if let Some(entry) = database::lookup(&prog.sha1, options.run.database.as_deref())? {
+            options.run.merge(entry);
+        }
+    }
+    let platform = options.run.platform();
+    let mut chip8 = Processor::builder()
+        .quirks(options.run.quirks()?)
+        .start_pc(platform.load_address())
+        .resolution(platform.resolution())
+        .fonts(platform.fonts())
+        .rng(0)
+        .build();
+    chip8.load_rom(&prog)?;
+    let events = chip8.events.subscribe();
+    let cycles_per_frame = options.run.cycles_per_frame();
+
+    let limit = Duration::from_secs_f64(options.seconds);
+    let mut halted = false;
+    let started = Instant::now();
+    while !halted
+        && started.elapsed() < limit
+        && options.frames.is_none_or(|frames| chip8.frames < frames)
+    {
+        chip8.run_frame(cycles_per_frame)?;
+        halted = events
+            .try_iter()
+            .any(|event| matches!(event, Event::Halted));
+    }
+    let seconds = started.elapsed().as_secs_f64();
+    println!(
+        "{}: {} frames, {} instructions in {:.3}s{}",
+        path,
+        chip8.frames,
+        chip8.instructions,
+        seconds,
+        if halted {
+            ", stopped when it halted"
+        } else {
+            ""
+        }
+    );
+    println!(
+        "{:.0} instructions/s, {:.0} frames/s",
+        chip8.instructions as f64 / seconds,
+        chip8.frames as f64 / seconds
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::BenchOptions;
+
+    #[test]
+    fn test_bench() -> Result<(), Box<dyn std::error::Error>> {
+        let args: Vec<String> = ["roms/IBM Logo.ch8", "--frames", "10"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        super::run(BenchOptions::parse(&args)?)
+    }
+}
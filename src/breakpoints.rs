@@ -0,0 +1,502 @@
+use crate::core::events::Event;
+use crate::core::processor::Processor;
+use std::sync::mpsc::Receiver;
+
+// Conditional breakpoints, written as C-like expressions over the machine state:
+//   pc==0x3A4 && v[3]>10
+//   mem[I]==0xFF || dt==0
+// Operands are numbers (decimal or 0x hex), pc, i, sp, dt, st, v[N] or vN and mem[EXPR].
+// Comparisons are == != < <= > >=, combined with && and || and grouped with parentheses.
+// Every value is an unsigned 16 bit number and a bare operand is true when non-zero.
+// Breakpoints on writes are given as write screen X Y WIDTH HEIGHT or write mem FIRST LAST
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Number(u16),
+    Pc,
+    Index,
+    StackPointer,
+    DelayTimer,
+    SoundTimer,
+    Register(Box<Expr>),
+    Memory(Box<Expr>),
+    Compare(Box<Expr>, Comparison, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(u16),
+    Name(String),
+    Operator(&'static str),
+}
+
+const OPERATORS: [&str; 12] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "[", "]", "(", ")",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(Token::Operator(op));
+            rest = &rest[op.len()..];
+        } else {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            if let (0, Some(c)) = (end, rest.chars().next()) {
+                return Err(format!("Unexpected {}", c));
+            }
+            let word = &rest[..end];
+            let number = match word.strip_prefix("0x") {
+                Some(hex) => Some(u16::from_str_radix(hex, 16)),
+                None if word.starts_with(|c: char| c.is_ascii_digit()) => Some(word.parse()),
+                None => None,
+            };
+            tokens.push(match number {
+                Some(Ok(n)) => Token::Number(n),
+                Some(Err(_)) => return Err(format!("Invalid number {}", word)),
+                None => Token::Name(word.to_lowercase()),
+            });
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn accept(&mut self, op: &'static str) -> bool {
+        let found = self.peek() == Some(&Token::Operator(op));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, op: &'static str) -> Result<(), String> {
+        match self.accept(op) {
+            true => Ok(()),
+            false => Err(format!("Expected {}", op)),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.accept("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.comparison()?;
+        while self.accept("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.comparison()?));
+        }
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.operand()?;
+        let comparison = match self.peek() {
+            Some(Token::Operator("==")) => Comparison::Equal,
+            Some(Token::Operator("!=")) => Comparison::NotEqual,
+            Some(Token::Operator("<")) => Comparison::Less,
+            Some(Token::Operator("<=")) => Comparison::LessEqual,
+            Some(Token::Operator(">")) => Comparison::Greater,
+            Some(Token::Operator(">=")) => Comparison::GreaterEqual,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        let right = self.operand()?;
+        Ok(Expr::Compare(Box::new(left), comparison, Box::new(right)))
+    }
+
+    fn operand(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Number(n)),
+            Some(Token::Operator("(")) => {
+                let expr = self.or()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Name(name)) => match name.as_str() {
+                "pc" => Ok(Expr::Pc),
+                "i" => Ok(Expr::Index),
+                "sp" => Ok(Expr::StackPointer),
+                "dt" => Ok(Expr::DelayTimer),
+                "st" => Ok(Expr::SoundTimer),
+                "v" | "mem" => {
+                    self.expect("[")?;
+                    let inner = Box::new(self.or()?);
+                    self.expect("]")?;
+                    Ok(match name.as_str() {
+                        "v" => Expr::Register(inner),
+                        _ => Expr::Memory(inner),
+                    })
+                }
+                _ => match name.strip_prefix('v').map(|d| u16::from_str_radix(d, 16)) {
+                    Some(Ok(n)) if n < 16 => Ok(Expr::Register(Box::new(Expr::Number(n)))),
+                    _ => Err(format!("Unknown name {}", name)),
+                },
+            },
+            Some(Token::Operator(op)) => Err(format!("Unexpected {}", op)),
+            None => Err(String::from("Unexpected end of expression")),
+        }
+    }
+}
+
+impl std::str::FromStr for Expr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            position: 0,
+        };
+        let expr = parser.or()?;
+        match parser.next() {
+            None => Ok(expr),
+            Some(_) => Err(String::from("Unexpected input after the expression")),
+        }
+    }
+}
+
+impl Expr {
+    pub fn evaluate(&self, chip8: &Processor) -> u16 {
+        match self {
+            Expr::Number(n) => *n,
+            Expr::Pc => chip8.pc,
+            Expr::Index => chip8.idx_register,
+            Expr::StackPointer => chip8.stack_pointer as u16,
+            Expr::DelayTimer => chip8.delay_timer as u16,
+            Expr::SoundTimer => chip8.sound_timer as u16,
+            Expr::Register(n) => chip8.registers[(n.evaluate(chip8) & 0xF) as usize] as u16,
+            // Addresses past the end of RAM read as zero
+            Expr::Memory(address) => chip8
+                .ram
+                .read(address.evaluate(chip8) as usize)
+                .unwrap_or(0) as u16,
+            Expr::Compare(left, comparison, right) => {
+                let (left, right) = (left.evaluate(chip8), right.evaluate(chip8));
+                let result = match comparison {
+                    Comparison::Equal => left == right,
+                    Comparison::NotEqual => left != right,
+                    Comparison::Less => left < right,
+                    Comparison::LessEqual => left <= right,
+                    Comparison::Greater => left > right,
+                    Comparison::GreaterEqual => left >= right,
+                };
+                result as u16
+            }
+            Expr::And(left, right) => (left.is_true(chip8) && right.is_true(chip8)) as u16,
+            Expr::Or(left, right) => (left.is_true(chip8) || right.is_true(chip8)) as u16,
+        }
+    }
+
+    pub fn is_true(&self, chip8: &Processor) -> bool {
+        self.evaluate(chip8) != 0
+    }
+
+    // The address the whole condition requires PC to be at, if any
+    fn required_pc(&self) -> Option<u16> {
+        match self {
+            Expr::Compare(left, Comparison::Equal, right) => match (&**left, &**right) {
+                (Expr::Pc, Expr::Number(n)) | (Expr::Number(n), Expr::Pc) => Some(*n),
+                _ => None,
+            },
+            Expr::And(left, right) => left.required_pc().or_else(|| right.required_pc()),
+            _ => None,
+        }
+    }
+}
+
+// What makes a breakpoint fire
+#[derive(Clone, Debug)]
+pub enum Trigger {
+    // Checked after every instruction, conditions that pin PC only at that address
+    Condition {
+        condition: Expr,
+        address: Option<u16>,
+    },
+    // write screen X Y WIDTH HEIGHT, DXYN drawing a sprite that overlaps the rectangle
+    Screen {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    },
+    // write mem FIRST LAST, FX33 or FX55 storing to any address in the inclusive range
+    Memory {
+        first: usize,
+        last: usize,
+    },
+}
+
+fn field(words: &mut std::str::SplitWhitespace) -> Result<usize, String> {
+    let word = words.next().ok_or("Missing number")?;
+    match word.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => word.parse(),
+    }
+    .map_err(|_| format!("Invalid number {}", word))
+}
+
+impl Trigger {
+    fn write(spec: &str) -> Result<Self, String> {
+        let mut words = spec.split_whitespace();
+        let trigger = match words.next() {
+            Some("screen") => Trigger::Screen {
+                x: field(&mut words)?,
+                y: field(&mut words)?,
+                width: field(&mut words)?,
+                height: field(&mut words)?,
+            },
+            Some("mem") => {
+                let (first, last) = (field(&mut words)?, field(&mut words)?);
+                if first > last {
+                    return Err(format!("Empty range {:#X}-{:#X}", first, last));
+                }
+                Trigger::Memory { first, last }
+            }
+            _ => {
+                return Err(String::from(
+                    "Expected write screen X Y W H or write mem FIRST LAST",
+                ))
+            }
+        };
+        match words.next() {
+            Some(extra) => Err(format!("Unexpected {}", extra)),
+            None => Ok(trigger),
+        }
+    }
+
+    fn written(&self, event: &Event) -> bool {
+        match (self, event) {
+            (
+                Trigger::Screen {
+                    x,
+                    y,
+                    width,
+                    height,
+                },
+                Event::SpriteDrawn(draw),
+            ) => {
+                // The region is whatever was typed, so its far edges may not fit in a usize
+                draw.x < x.saturating_add(*width)
+                    && *x < draw.x.saturating_add(draw.width)
+                    && draw.y < y.saturating_add(*height)
+                    && *y < draw.y.saturating_add(draw.height)
+            }
+            (Trigger::Memory { first, last }, Event::MemoryWritten { address, len }) => {
+                let start = *address as usize;
+                start <= *last && *first < start + *len as usize
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Breakpoint {
+    pub source: String,
+    pub trigger: Trigger,
+}
+
+impl std::str::FromStr for Breakpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trigger = match s.trim().strip_prefix("write ") {
+            Some(spec) => Trigger::write(spec)?,
+            None => {
+                let condition: Expr = s.parse()?;
+                Trigger::Condition {
+                    address: condition.required_pc(),
+                    condition,
+                }
+            }
+        };
+        Ok(Breakpoint {
+            source: s.trim().to_string(),
+            trigger,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Breakpoints {
+    pub breakpoints: Vec<Breakpoint>,
+    // Write breakpoints learn about DXYN and memory stores from the processor's events
+    events: Option<Receiver<Event>>,
+}
+
+impl Breakpoints {
+    // Needed for write breakpoints, conditions work without it
+    pub fn attach(&mut self, chip8: &mut Processor) {
+        self.events = Some(chip8.events.subscribe());
+    }
+
+    pub fn add(&mut self, source: &str) -> Result<(), String> {
+        self.breakpoints.push(source.parse()?);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<Breakpoint, String> {
+        match index < self.breakpoints.len() {
+            true => Ok(self.breakpoints.remove(index)),
+            false => Err(format!("No breakpoint {}", index)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    // Index of the first breakpoint that fired since the last call, meant to be called after
+    // every instruction. Events are consumed, so a write is only reported once
+    pub fn hit(&self, chip8: &Processor) -> Option<usize> {
+        let mut written = None;
+        for event in self.events.iter().flat_map(|e| e.try_iter()) {
+            written = written.or_else(|| {
+                self.breakpoints
+                    .iter()
+                    .position(|b| b.trigger.written(&event))
+            });
+        }
+        written.or_else(|| {
+            self.breakpoints.iter().position(|b| match &b.trigger {
+                Trigger::Condition { condition, address } => {
+                    address.is_none_or(|address| address == chip8.pc) && condition.is_true(chip8)
+                }
+                _ => false,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Breakpoints, Expr};
+    use crate::core::events::{Event, SpriteDraw};
+    use crate::core::processor::Processor;
+
+    #[test]
+    fn test_parse() -> Result<(), String> {
+        let expr: Expr = "pc==0x3A4 && v[3]>10".parse()?;
+        assert_eq!(expr.required_pc(), Some(0x3A4));
+        let expr: Expr = "(v3 > 10 && 0x3A4 == PC) || dt == 0".parse()?;
+        assert_eq!(expr.required_pc(), None);
+        assert!("mem[I]==0xFF".parse::<Expr>().is_ok());
+        assert!("pc==".parse::<Expr>().is_err());
+        assert!("vg == 1".parse::<Expr>().is_err());
+        assert!("pc == 1 1".parse::<Expr>().is_err());
+        assert!("mem[i".parse::<Expr>().is_err());
+        assert!("pc = 1".parse::<Expr>().is_err());
+        assert_eq!(
+            "pc == é".parse::<Expr>().map(|_| ()),
+            Err(String::from("Unexpected é"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_hit() -> Result<(), String> {
+        let mut chip8 = Processor::default();
+        let mut breakpoints = Breakpoints::default();
+        breakpoints.add("pc==0x3A4 && v[3]>10")?;
+        breakpoints.add("mem[I]==0xFF")?;
+        assert_eq!(breakpoints.hit(&chip8), None);
+
+        chip8.pc = 0x3A4;
+        chip8.registers[3] = 10;
+        assert_eq!(breakpoints.hit(&chip8), None);
+        chip8.registers[3] = 11;
+        assert_eq!(breakpoints.hit(&chip8), Some(0));
+
+        chip8.pc = 0x200;
+        chip8.idx_register = 0x400;
+        chip8.ram.buffer[0x400] = 0xFF;
+        assert_eq!(breakpoints.hit(&chip8), Some(1));
+        breakpoints.remove(1)?;
+        assert_eq!(breakpoints.hit(&chip8), None);
+        assert!(breakpoints.remove(1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_breakpoints() -> Result<(), String> {
+        let mut chip8 = Processor::default();
+        let mut breakpoints = Breakpoints::default();
+        breakpoints.attach(&mut chip8);
+        breakpoints.add("write screen 10 10 4 4")?;
+        breakpoints.add("write mem 0x300 0x30F")?;
+        assert!(breakpoints.add("write mem 0x30F 0x300").is_err());
+        assert!(breakpoints.add("write screen 1 2 3").is_err());
+
+        let draw = |x, y| {
+            Event::SpriteDrawn(SpriteDraw {
+                x,
+                y,
+                width: 8,
+                height: 5,
+                address: 0,
+            })
+        };
+        chip8.events.publish(draw(0, 0));
+        chip8.events.publish(draw(40, 10));
+        assert_eq!(breakpoints.hit(&chip8), None);
+        chip8.events.publish(draw(3, 6));
+        assert_eq!(breakpoints.hit(&chip8), Some(0));
+        assert_eq!(breakpoints.hit(&chip8), None);
+
+        chip8.events.publish(Event::MemoryWritten {
+            address: 0x2FD,
+            len: 3,
+        });
+        assert_eq!(breakpoints.hit(&chip8), None);
+        chip8.events.publish(Event::MemoryWritten {
+            address: 0x30F,
+            len: 3,
+        });
+        assert_eq!(breakpoints.hit(&chip8), Some(1));
+
+        // Regions reaching past usize::MAX are compared without overflowing
+        let mut breakpoints = Breakpoints::default();
+        breakpoints.attach(&mut chip8);
+        breakpoints.add("write screen 18446744073709551615 0 1 1")?;
+        breakpoints.add("write screen 2 2 18446744073709551615 18446744073709551615")?;
+        chip8.events.publish(draw(3, 6));
+        assert_eq!(breakpoints.hit(&chip8), Some(1));
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use crate::consts;
+use crate::core::events::Event;
+use crate::core::processor::Processor;
+
+// One active subroutine, target is unknown for frames that were already on the stack when
+// tracking started, e.g. after loading a saved state
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub call_site: u16,
+    pub target: Option<u16>,
+}
+
+// Follows 2NNN/00EE pairs from the processor's events to show which subroutines are active.
+// CHIP-8 programs sometimes leave a subroutine with a jump instead of returning, which
+// slowly fills the 16 entry stack, so suspicious returns and a nearly full stack are
+// reported as warnings
+#[derive(Clone, Debug, Default)]
+pub struct CallStack {
+    pub frames: Vec<Frame>,
+    // Most recent problem noticed, kept until the next reset
+    pub warning: Option<String>,
+}
+
+impl CallStack {
+    // Starts from whatever is on the processor's stack right now
+    pub fn sync(&mut self, chip8: &Processor) {
+        self.frames = chip8.stack[..chip8.stack_pointer as usize]
+            .iter()
+            .map(|&return_address| Frame {
+                call_site: return_address.wrapping_sub(consts::OP_CODE_BYTES as u16),
+                target: None,
+            })
+            .collect();
+        self.warning = None;
+    }
+
+    pub fn handle(&mut self, event: &Event) {
+        match *event {
+            Event::Called { from, to } => {
+                self.frames.push(Frame {
+                    call_site: from,
+                    target: Some(to),
+                });
+                if self.frames.len() >= consts::STACK_SIZE - 1 {
+                    self.warning = Some(format!(
+                        "Stack is {} of {} deep after the call at {:03X}, subroutines may be \
+                         jumping out instead of returning",
+                        self.frames.len(),
+                        consts::STACK_SIZE,
+                        from
+                    ));
+                }
+            }
+            Event::Returned { from, to } => match self.frames.pop() {
+                Some(frame) if frame.call_site.wrapping_add(consts::OP_CODE_BYTES as u16) != to => {
+                    self.warning = Some(format!(
+                        "Return at {:03X} went to {:03X}, the call at {:03X} expected {:03X}",
+                        from,
+                        to,
+                        frame.call_site,
+                        frame.call_site + consts::OP_CODE_BYTES as u16
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    self.warning = Some(format!("Return at {:03X} without a matching call", from))
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CallStack, Frame};
+    use crate::core::events::Event;
+    use crate::core::processor::Processor;
+
+    #[test]
+    fn test_calls_and_returns() {
+        let mut calls = CallStack::default();
+        calls.handle(&Event::Called {
+            from: 0x200,
+            to: 0x300,
+        });
+        calls.handle(&Event::Called {
+            from: 0x304,
+            to: 0x400,
+        });
+        assert_eq!(
+            calls.frames,
+            [
+                Frame {
+                    call_site: 0x200,
+                    target: Some(0x300)
+                },
+                Frame {
+                    call_site: 0x304,
+                    target: Some(0x400)
+                }
+            ]
+        );
+        calls.handle(&Event::Returned {
+            from: 0x402,
+            to: 0x306,
+        });
+        assert_eq!(calls.frames.len(), 1);
+        assert_eq!(calls.warning, None);
+
+        calls.handle(&Event::Returned {
+            from: 0x310,
+            to: 0x250,
+        });
+        assert!(calls.warning.take().is_some());
+        calls.handle(&Event::Returned {
+            from: 0x310,
+            to: 0x250,
+        });
+        assert!(calls.warning.is_some());
+    }
+
+    #[test]
+    fn test_stack_nearly_full() {
+        let mut calls = CallStack::default();
+        // A loop that calls itself and never returns
+        for _ in 0..14 {
+            calls.handle(&Event::Called {
+                from: 0x200,
+                to: 0x200,
+            });
+        }
+        assert_eq!(calls.warning, None);
+        calls.handle(&Event::Called {
+            from: 0x200,
+            to: 0x200,
+        });
+        assert!(calls.warning.is_some());
+    }
+
+    #[test]
+    fn test_sync() {
+        let mut chip8 = Processor::default();
+        chip8.stack[0] = 0x202;
+        chip8.stack[1] = 0x40A;
+        chip8.stack_pointer = 2;
+        let mut calls = CallStack::default();
+        calls.sync(&chip8);
+        assert_eq!(
+            calls.frames.iter().map(|f| f.call_site).collect::<Vec<_>>(),
+            [0x200, 0x408]
+        );
+        assert!(calls.frames.iter().all(|f| f.target.is_none()));
+    }
+}
@@ -0,0 +1,172 @@
+use crate::consts;
+use crate::core::processor::Processor;
+use std::path::Path;
+
+// Cheat files hold one code per line, # starts a comment:
+//   poke 0x3F2 9     writes the value once, whenever cheats are switched on
+//   freeze v3 5      writes the value after every frame
+// Targets are a RAM address or a register v0-vf
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Memory(usize),
+    Register(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cheat {
+    pub target: Target,
+    pub value: u8,
+    pub freeze: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Cheats {
+    pub cheats: Vec<Cheat>,
+    pub enabled: bool,
+    // Pokes are written on the first frame after cheats are switched on
+    poke_pending: bool,
+}
+
+fn number(text: &str) -> Option<usize> {
+    match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+fn target(text: &str) -> Option<Target> {
+    if let Some(digit) = text.strip_prefix('v').or_else(|| text.strip_prefix('V')) {
+        if digit.len() == 1 {
+            return usize::from_str_radix(digit, 16).ok().map(Target::Register);
+        }
+        return None;
+    }
+    number(text)
+        .filter(|&address| address < consts::RAM_BYTES)
+        .map(Target::Memory)
+}
+
+impl Cheats {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut cheats = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (kind, location, value) = match fields[..] {
+                [] => continue,
+                [kind, location, value] => (kind, location, value),
+                _ => return Err(format!("line {}: Expected poke|freeze TARGET VALUE", i + 1)),
+            };
+            let freeze = match kind {
+                "poke" => false,
+                "freeze" => true,
+                _ => return Err(format!("line {}: Unknown cheat {}", i + 1, kind)),
+            };
+            let target =
+                target(location).ok_or(format!("line {}: Invalid target {}", i + 1, location))?;
+            let value = number(value)
+                .and_then(|v| u8::try_from(v).ok())
+                .ok_or(format!("line {}: Invalid value {}", i + 1, value))?;
+            cheats.push(Cheat {
+                target,
+                value,
+                freeze,
+            });
+        }
+        Ok(Cheats {
+            cheats,
+            enabled: true,
+            poke_pending: true,
+        })
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Cheats::parse(&text).map_err(|e| format!("{}: {}", path, e))?)
+    }
+
+    // Cheats for a ROM live next to it with the .cht extension
+    pub fn for_rom(rom_path: &str) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let path = Path::new(rom_path).with_extension("cht");
+        match path.to_str() {
+            Some(p) if path.is_file() => Ok(Some(Cheats::load(p)?)),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn toggle(&mut self) -> bool {
+        self.enabled = !self.enabled;
+        self.poke_pending = self.enabled;
+        self.enabled
+    }
+
+    // Called after each batch of cycles so frozen values hold whatever the program wrote
+    pub fn apply(&mut self, chip8: &mut Processor) {
+        if !self.enabled {
+            return;
+        }
+        for cheat in &self.cheats {
+            if cheat.freeze || self.poke_pending {
+                match cheat.target {
+                    Target::Memory(address) => chip8.ram.buffer[address] = cheat.value,
+                    Target::Register(register) => chip8.registers[register] = cheat.value,
+                }
+            }
+        }
+        self.poke_pending = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cheat, Cheats, Target};
+    use crate::core::processor::Processor;
+
+    #[test]
+    fn test_parse_cheats() -> Result<(), String> {
+        let cheats = Cheats::parse("# lives\nfreeze v3 5\npoke 0x3F2 0x09\n")?;
+        assert_eq!(
+            cheats.cheats,
+            [
+                Cheat {
+                    target: Target::Register(3),
+                    value: 5,
+                    freeze: true
+                },
+                Cheat {
+                    target: Target::Memory(0x3F2),
+                    value: 9,
+                    freeze: false
+                }
+            ]
+        );
+        assert!(Cheats::parse("freeze v3 300").is_err());
+        assert!(Cheats::parse("freeze 0x1000 1").is_err());
+        assert!(Cheats::parse("set v3 1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_cheats() -> Result<(), String> {
+        let mut chip8: Processor = Default::default();
+        let mut cheats = Cheats::parse("freeze v3 5\npoke 0x3F2 9")?;
+        cheats.apply(&mut chip8);
+        assert_eq!((chip8.registers[3], chip8.ram.buffer[0x3F2]), (5, 9));
+
+        // Pokes only happen once, freezes every frame
+        chip8.registers[3] = 1;
+        chip8.ram.buffer[0x3F2] = 1;
+        cheats.apply(&mut chip8);
+        assert_eq!((chip8.registers[3], chip8.ram.buffer[0x3F2]), (5, 1));
+
+        cheats.toggle();
+        chip8.registers[3] = 1;
+        cheats.apply(&mut chip8);
+        assert_eq!(chip8.registers[3], 1);
+        cheats.toggle();
+        cheats.apply(&mut chip8);
+        assert_eq!((chip8.registers[3], chip8.ram.buffer[0x3F2]), (5, 9));
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+use crate::cli::CheckOptions;
+use crate::consts;
+use crate::core::rom::Rom;
+use crate::symbols::Symbols;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Extension {
+    Chip8,
+    Schip,
+    Xochip,
+}
+
+impl Extension {
+    // Suggested --platform value for a ROM using these opcodes
+    pub fn platform(self) -> &'static str {
+        match self {
+            Extension::Chip8 => "chip8",
+            Extension::Schip => "schip-modern",
+            Extension::Xochip => "xochip",
+        }
+    }
+}
+
+// Which instruction set an opcode belongs to, None for opcodes no interpreter defines
+fn classify(opcode: u16) -> Option<Extension> {
+    let n = opcode & 0xF;
+    let nn = opcode & 0xFF;
+    match opcode >> 12 {
+        0x0 => match opcode & 0xFFF {
+            0x0E0 | 0x0EE => Some(Extension::Chip8),
+            0x0C0..=0x0CF | 0x0FB..=0x0FF => Some(Extension::Schip),
+            0x0D0..=0x0DF => Some(Extension::Xochip),
+            _ => None,
+        },
+        0x5 | 0x9 if n == 0 => Some(Extension::Chip8),
+        0x5 if n == 2 || n == 3 => Some(Extension::Xochip),
+        0x5 | 0x9 => None,
+        0x8 => match n {
+            0x0..=0x7 | 0xE => Some(Extension::Chip8),
+            _ => None,
+        },
+        0xD if n == 0 => Some(Extension::Schip),
+        0xE => match nn {
+            0x9E | 0xA1 => Some(Extension::Chip8),
+            _ => None,
+        },
+        0xF => match nn {
+            0x07 | 0x0A | 0x15 | 0x18 | 0x1E | 0x29 | 0x33 | 0x55 | 0x65 => Some(Extension::Chip8),
+            0x30 | 0x75 | 0x85 => Some(Extension::Schip),
+            0x00 if opcode == 0xF000 => Some(Extension::Xochip),
+            0x01 | 0x3A => Some(Extension::Xochip),
+            0x02 if opcode == 0xF002 => Some(Extension::Xochip),
+            _ => None,
+        },
+        _ => Some(Extension::Chip8),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Finding {
+    pub address: u16,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+    // Highest instruction set used along any reachable path
+    pub extension: Option<Extension>,
+    pub extension_address: u16,
+}
+
+struct Checker<'a> {
+    program: &'a [u8],
+    report: Report,
+    code: BTreeSet<u16>,
+    // Stores (FX55/FX33) made while I held a known value: instruction address, first and
+    // last byte written
+    writes: Vec<(u16, u16, u16)>,
+    reported: HashSet<(u16, &'static str)>,
+}
+
+impl<'a> Checker<'a> {
+    fn end(&self) -> u16 {
+        (consts::PROG_OFFSET + self.program.len()) as u16
+    }
+
+    fn report(&mut self, address: u16, kind: &'static str, message: String) {
+        if self.reported.insert((address, kind)) {
+            self.report.findings.push(Finding { address, message });
+        }
+    }
+
+    fn check_target(&mut self, address: u16, target: u16) -> bool {
+        if (target as usize) < consts::PROG_OFFSET || target >= self.end() {
+            self.report(
+                address,
+                "target",
+                format!("jumps to {:#05X}, outside the loaded program", target),
+            );
+            return false;
+        }
+        true
+    }
+
+    fn run(&mut self) {
+        // Each path carries its call depth and the value of I when it is statically known
+        let mut pending = vec![(consts::PROG_OFFSET as u16, 0usize, None::<u16>)];
+        let mut visited = HashSet::new();
+        while let Some((address, depth, index)) = pending.pop() {
+            if !visited.insert((address, depth)) {
+                continue;
+            }
+            let offset = address as usize - consts::PROG_OFFSET;
+            let opcode = match self.program.get(offset..offset + 2) {
+                Some(bytes) => u16::from_be_bytes([bytes[0], bytes[1]]),
+                None => {
+                    self.report(
+                        address,
+                        "end",
+                        String::from("runs past the end of the program"),
+                    );
+                    continue;
+                }
+            };
+            self.code.insert(address);
+            let extension = match classify(opcode) {
+                Some(e) => e,
+                None => {
+                    self.report(address, "opcode", format!("unknown opcode {:04X}", opcode));
+                    continue;
+                }
+            };
+            if self.report.extension.is_none_or(|e| extension > e) {
+                self.report.extension = Some(extension);
+                self.report.extension_address = address;
+            }
+
+            let x = (opcode >> 8) & 0xF;
+            let nnn = opcode & 0xFFF;
+            // F000 NNNN carries its address in the following word
+            let next = if opcode == 0xF000 {
+                address + 4
+            } else {
+                address + 2
+            };
+            let mut successors = vec![next];
+            let mut index = index;
+            match opcode >> 12 {
+                0x0 if opcode == 0x00EE => {
+                    if depth == 0 {
+                        self.report(
+                            address,
+                            "stack",
+                            String::from("returns with an empty stack"),
+                        );
+                    }
+                    successors.clear();
+                }
+                0x0 if opcode == 0x00FD => successors.clear(),
+                0x1 => {
+                    successors.clear();
+                    if self.check_target(address, nnn) {
+                        pending.push((nnn, depth, index));
+                    }
+                }
+                0x2 => {
+                    if depth + 1 > consts::STACK_SIZE {
+                        self.report(
+                            address,
+                            "stack",
+                            format!("calls nest deeper than {} levels", consts::STACK_SIZE),
+                        );
+                    } else if self.check_target(address, nnn) {
+                        pending.push((nnn, depth + 1, index));
+                    }
+                }
+                0xA => index = Some(nnn),
+                0xB => {
+                    self.check_target(address, nnn);
+                    successors.clear();
+                }
+                0x3 | 0x4 | 0x5 | 0x9 | 0xE => successors.push(next + 2),
+                0xF => match opcode & 0xFF {
+                    0x1E | 0x29 | 0x30 | 0x65 => index = None,
+                    0x00 if opcode == 0xF000 => index = None,
+                    0x33 | 0x55 => {
+                        if let Some(i) = index {
+                            let last = if opcode & 0xFF == 0x33 { i + 2 } else { i + x };
+                            self.writes.push((address, i, last));
+                        }
+                        index = None;
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+            for successor in successors {
+                if successor >= self.end() {
+                    self.report(
+                        address,
+                        "end",
+                        String::from("runs past the end of the program"),
+                    );
+                } else {
+                    pending.push((successor, depth, index));
+                }
+            }
+        }
+
+        for (address, first, last) in std::mem::take(&mut self.writes) {
+            if let Some(&target) = self.code.range(first.saturating_sub(1)..=last).next() {
+                self.report(
+                    address,
+                    "write",
+                    format!("writes into code at {:#05X} (self-modifying code)", target),
+                );
+            }
+        }
+        self.report.findings.sort_by_key(|f| f.address);
+    }
+}
+
+pub fn check(program: &[u8]) -> Report {
+    let mut checker = Checker {
+        program,
+        report: Default::default(),
+        code: BTreeSet::new(),
+        writes: Vec::new(),
+        reported: HashSet::new(),
+    };
+    checker.run();
+    checker.report
+}
+
+pub fn run(options: &CheckOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let rom = Rom::new(&options.rom_path)?;
+    let symbols = Symbols::for_rom(&options.rom_path)?;
+    let report = check(&rom.buffer[..rom.size]);
+
+    let mut by_address: BTreeMap<u16, Vec<&str>> = BTreeMap::new();
+    for finding in &report.findings {
+        by_address
+            .entry(finding.address)
+            .or_default()
+            .push(&finding.message);
+    }
+    for (address, messages) in &by_address {
+        for message in messages {
+            println!("{}: {}", symbols.describe(*address), message);
+        }
+    }
+    if let Some(extension) = report.extension {
+        if extension > Extension::Chip8 {
+            println!(
+                "{} uses {} instructions, first at {}, run it with --platform {}",
+                options.rom_path,
+                if extension == Extension::Schip {
+                    "SCHIP"
+                } else {
+                    "XO-CHIP"
+                },
+                symbols.describe(report.extension_address),
+                extension.platform()
+            );
+        }
+    }
+    if !report.findings.is_empty() {
+        return Err(format!("{} problems found", report.findings.len()).into());
+    }
+    println!("{}: no problems found", options.rom_path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, Extension};
+    use crate::assembler::assemble;
+
+    #[test]
+    fn test_check_reports_problems() -> Result<(), Box<dyn std::error::Error>> {
+        let program = assemble(
+            "
+            : main
+                i := main
+                save v1       # overwrites main
+                return        # nothing to return to
+            ",
+        )?;
+        let report = check(&program);
+        let addresses: Vec<u16> = report.findings.iter().map(|f| f.address).collect();
+        assert_eq!(addresses, [0x202, 0x204]);
+        assert!(report.findings[0].message.contains("self-modifying"));
+        assert_eq!(report.extension, Some(Extension::Chip8));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_extensions_and_targets() -> Result<(), Box<dyn std::error::Error>> {
+        let report = check(&[0x00, 0xFF, 0x5A, 0xB1, 0x00, 0x00]);
+        assert_eq!(report.extension, Some(Extension::Schip));
+        assert_eq!(report.findings[0].address, 0x202);
+        assert!(report.findings[0].message.contains("unknown opcode 5AB1"));
+
+        let report = check(&assemble("jump 0x100")?);
+        assert!(report.findings[0]
+            .message
+            .contains("outside the loaded program"));
+        let report = check(&assemble(": main main")?);
+        assert!(report.findings.iter().any(|f| f.message.contains("deeper")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_bundled_roms() -> Result<(), Box<dyn std::error::Error>> {
+        let program = std::fs::read(
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("roms/IBM Logo.ch8"),
+        )?;
+        assert!(check(&program).findings.is_empty());
+        Ok(())
+    }
+}
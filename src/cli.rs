@@ -0,0 +1,637 @@
+use crate::consts;
+use crate::core::platform::Platform;
+use crate::core::quirks::Quirks;
+use crate::external::headless::Limits;
+use crate::external::input::{KeyLayout, TouchGrid, Turbo};
+use crate::external::netplay::Role;
+use crate::external::output::{AudioBackend, DisplayMode, Palette, Tone, Waveform};
+use std::slice::Iter;
+
+// What the emulator is shown in when not running the gui
+#[derive(Debug, PartialEq)]
+pub enum Backend {
+    Sdl,
+    // Terminal debugger, needs the tui feature
+    DebugTui,
+    // No window, sound or input, for scripts, see --max-frames and --until-halt
+    Headless,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sdl" => Ok(Backend::Sdl),
+            "debug-tui" => Ok(Backend::DebugTui),
+            "headless" => Ok(Backend::Headless),
+            _ => Err(format!("Unknown backend {}", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub rom_path: Option<String>,
+    pub demo: Option<String>,
+    pub gui: bool,
+    pub backend: Backend,
+    pub record: Option<String>,
+    // Buzzer settings, left unset so a ROM's profile can choose them, see tone and volume
+    pub waveform: Option<Waveform>,
+    pub frequency: Option<f32>,
+    pub volume: Option<f32>,
+    pub mute: bool,
+    pub audio_backend: AudioBackend,
+    pub visual_bell: bool,
+    // Starts with the register overlay shown, Ctrl+F1 toggles it either way
+    pub overlay: bool,
+    // Clickable keypad below the game
+    pub keypad: bool,
+    // Which key each touch screen region presses, and whether the regions are drawn
+    pub touch_grid: TouchGrid,
+    pub touch_overlay: bool,
+    // Continues from the state saved when this ROM was last closed
+    pub resume: bool,
+    // Writes the machine state as JSON here on exit
+    pub dump_state: Option<String>,
+    // When the headless backend stops, and whether it prints the screen and registers then
+    pub limits: Limits,
+    pub dump: bool,
+    // Timed key presses merged with the keyboard, see KeyScript
+    pub keyscript: Option<String>,
+    // Saves the screen on exit, as PBM for a .pbm path and as text otherwise
+    pub export_display: Option<String>,
+    // Left unset when not given so a ROM database entry can fill them in
+    pub cycles_per_frame: Option<usize>,
+    pub platform: Option<Platform>,
+    pub quirk_overrides: Vec<(String, bool)>,
+    // Second instance for the split view, a different ROM and/or quirks on top of the above
+    pub compare: Option<String>,
+    pub compare_quirk_overrides: Vec<(String, bool)>,
+    pub palette: Option<Palette>,
+    // Starting viewing aids, all of them can be changed with hotkeys while running
+    pub display_mode: DisplayMode,
+    pub turbo: Option<Turbo>,
+    pub keymap: Option<KeyLayout>,
+    // Keeps this command line's machine settings as the ROM's profile, see profiles
+    pub save_profile: bool,
+    pub use_profile: bool,
+    pub database: Option<String>,
+    pub use_database: bool,
+    pub symbols: Option<String>,
+    pub cheats: Option<String>,
+    pub remote: Option<String>,
+    // The remote control on a Unix socket instead of a TCP port
+    pub control_socket: Option<String>,
+    // Reads the same commands as the remote control from stdin
+    pub repl: bool,
+    pub stream: Option<String>,
+    pub netplay: Option<Role>,
+    pub crowd: Option<String>,
+    pub stats: Option<String>,
+    // Counts executed instructions from the start rather than from the first export
+    pub histogram: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            rom_path: None,
+            demo: None,
+            gui: false,
+            backend: Backend::Sdl,
+            record: None,
+            waveform: None,
+            frequency: None,
+            volume: None,
+            mute: false,
+            audio_backend: AudioBackend::Sdl,
+            visual_bell: false,
+            overlay: false,
+            keypad: false,
+            touch_grid: Default::default(),
+            touch_overlay: false,
+            resume: false,
+            dump_state: None,
+            limits: Default::default(),
+            dump: false,
+            export_display: None,
+            keyscript: None,
+            cycles_per_frame: None,
+            platform: None,
+            quirk_overrides: Vec::new(),
+            compare: None,
+            compare_quirk_overrides: Vec::new(),
+            palette: None,
+            display_mode: Default::default(),
+            turbo: None,
+            keymap: None,
+            save_profile: false,
+            use_profile: true,
+            database: None,
+            use_database: true,
+            symbols: None,
+            cheats: None,
+            remote: None,
+            control_socket: None,
+            repl: false,
+            stream: None,
+            netplay: None,
+            crowd: None,
+            stats: None,
+            histogram: false,
+        }
+    }
+}
+
+fn value(args: &mut Iter<String>, flag: &str) -> Result<String, Box<dyn std::error::Error>> {
+    match args.next() {
+        Some(v) => Ok(v.clone()),
+        None => Err(format!("Missing value for {}", flag).into()),
+    }
+}
+
+fn quirk(args: &mut Iter<String>, flag: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let name = value(args, flag)?;
+    Quirks::default().set(&name, true)?;
+    Ok(name)
+}
+
+// chip8 disasm rom.ch8 [-o rom.asm] [--symbols rom.sym]
+#[derive(Debug, Default)]
+pub struct DisasmOptions {
+    pub rom_path: String,
+    pub output: Option<String>,
+    pub symbols: Option<String>,
+}
+
+impl DisasmOptions {
+    pub fn parse(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut options: DisasmOptions = Default::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" | "--output" => options.output = Some(value(&mut args, arg)?),
+                "--symbols" => options.symbols = Some(value(&mut args, arg)?),
+                flag if flag.starts_with('-') && flag != "-" => {
+                    return Err(format!("Unknown option {}", flag).into())
+                }
+                path => {
+                    if !options.rom_path.is_empty() {
+                        return Err("Only one rom path can be specified".into());
+                    }
+                    options.rom_path = path.to_string();
+                }
+            }
+        }
+        if options.rom_path.is_empty() {
+            return Err("Need to specify rom path".into());
+        }
+        Ok(options)
+    }
+}
+
+// chip8 check rom.ch8
+#[derive(Debug, Default)]
+pub struct CheckOptions {
+    pub rom_path: String,
+}
+
+impl CheckOptions {
+    pub fn parse(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        match args {
+            [path] if !path.starts_with("--") => Ok(CheckOptions {
+                rom_path: path.clone(),
+            }),
+            _ => Err("Usage: chip8 check <rom>".into()),
+        }
+    }
+}
+
+// chip8 info rom.ch8 [--database PATH]
+#[derive(Debug, Default)]
+pub struct InfoOptions {
+    pub rom_path: String,
+    pub database: Option<String>,
+}
+
+impl InfoOptions {
+    pub fn parse(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut options: InfoOptions = Default::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--database" => options.database = Some(value(&mut args, arg)?),
+                flag if flag.starts_with('-') && flag != "-" => {
+                    return Err(format!("Unknown option {}", flag).into())
+                }
+                path => {
+                    if !options.rom_path.is_empty() {
+                        return Err("Only one rom path can be specified".into());
+                    }
+                    options.rom_path = path.to_string();
+                }
+            }
+        }
+        if options.rom_path.is_empty() {
+            return Err("Need to specify rom path".into());
+        }
+        Ok(options)
+    }
+}
+
+// chip8 asm game.8o [-o game.ch8] [--symbols game.sym]
+#[derive(Debug, Default)]
+pub struct AsmOptions {
+    pub source_path: String,
+    // Next to the source with a .ch8 extension unless given
+    pub output: Option<String>,
+    pub symbols: Option<String>,
+}
+
+impl AsmOptions {
+    pub fn parse(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut options: AsmOptions = Default::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" | "--output" => options.output = Some(value(&mut args, arg)?),
+                "--symbols" => options.symbols = Some(value(&mut args, arg)?),
+                flag if flag.starts_with('-') => {
+                    return Err(format!("Unknown option {}", flag).into())
+                }
+                path => {
+                    if !options.source_path.is_empty() {
+                        return Err("Only one source file can be specified".into());
+                    }
+                    options.source_path = path.to_string();
+                }
+            }
+        }
+        if options.source_path.is_empty() {
+            return Err("Need to specify source path".into());
+        }
+        Ok(options)
+    }
+}
+
+// chip8 bench rom.ch8 [--seconds S] [--frames N] followed by any of the machine options of
+// run, stopping at whichever limit comes first
+#[derive(Debug)]
+pub struct BenchOptions {
+    pub seconds: f64,
+    pub frames: Option<u64>,
+    pub run: Options,
+}
+
+impl BenchOptions {
+    pub fn parse(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let (mut seconds, mut frames) = (consts::BENCH_SECONDS, None);
+        let mut rest = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seconds" => {
+                    seconds = value(&mut args, arg)?.parse()?;
+                    if !seconds.is_finite() || seconds <= 0.0 {
+                        return Err("Seconds must be positive".into());
+                    }
+                }
+                "--frames" => frames = Some(value(&mut args, arg)?.parse()?),
+                _ => rest.push(arg.clone()),
+            }
+        }
+        let run = Options::parse(&rest)?;
+        if run.rom_path.is_none() {
+            return Err("Need to specify rom path".into());
+        }
+        Ok(BenchOptions {
+            seconds,
+            frames,
+            run,
+        })
+    }
+}
+
+// chip8 config [NAME VALUE], without arguments the current settings are printed
+#[derive(Debug, Default)]
+pub struct ConfigOptions {
+    pub setting: Option<(String, String)>,
+}
+
+impl ConfigOptions {
+    pub fn parse(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        match args {
+            [] => Ok(Default::default()),
+            [name, value] => Ok(ConfigOptions {
+                setting: Some((name.clone(), value.clone())),
+            }),
+            _ => Err("Usage: chip8 config [<name> <value>]".into()),
+        }
+    }
+}
+
+pub const USAGE: &str = "Usage: chip8 <command> [options]
+
+Commands:
+  run <rom>        Run a ROM, the default when no command is given
+  debug <rom>      Run a ROM in the terminal debugger
+  disasm <rom>     Write out a ROM as Octo source
+  asm <source>     Assemble Octo source into a ROM
+  check <rom>      Look for problems in a ROM without running it
+  info <rom>       Describe a ROM: size, hashes, platform and database entry
+  bench <rom>      Measure instructions and frames per second without a window
+  config           Show or change the settings kept between runs
+  help             Show this message
+";
+
+// The first argument names the tool to use, anything else runs the emulator as before so
+// `chip8 game.ch8` keeps working
+#[derive(Debug)]
+pub enum Command {
+    Run(Box<Options>),
+    Disasm(DisasmOptions),
+    Asm(AsmOptions),
+    Check(CheckOptions),
+    Info(InfoOptions),
+    Bench(Box<BenchOptions>),
+    Config(ConfigOptions),
+    Help,
+}
+
+impl Command {
+    pub fn parse(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let rest = args.get(1..).unwrap_or_default();
+        match args.first().map(|a| a.as_str()) {
+            Some("run") => Ok(Command::Run(Box::new(Options::parse(rest)?))),
+            Some("debug") => {
+                let mut options = Options::parse(rest)?;
+                options.backend = Backend::DebugTui;
+                Ok(Command::Run(Box::new(options)))
+            }
+            Some("disasm") => Ok(Command::Disasm(DisasmOptions::parse(rest)?)),
+            Some("asm") => Ok(Command::Asm(AsmOptions::parse(rest)?)),
+            Some("check") => Ok(Command::Check(CheckOptions::parse(rest)?)),
+            Some("info") => Ok(Command::Info(InfoOptions::parse(rest)?)),
+            Some("bench") => Ok(Command::Bench(Box::new(BenchOptions::parse(rest)?))),
+            Some("config") => Ok(Command::Config(ConfigOptions::parse(rest)?)),
+            Some("help" | "--help" | "-h") => Ok(Command::Help),
+            _ => Ok(Command::Run(Box::new(Options::parse(args)?))),
+        }
+    }
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut options: Options = Default::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--gui" => options.gui = true,
+                "--backend" => options.backend = value(&mut args, arg)?.parse()?,
+                "--demo" => options.demo = Some(value(&mut args, arg)?),
+                "--record" => options.record = Some(value(&mut args, arg)?),
+                "--waveform" => options.waveform = Some(value(&mut args, arg)?.parse()?),
+                "--tone" => options.frequency = Some(value(&mut args, arg)?.parse()?),
+                "--volume" => {
+                    let volume: f32 = value(&mut args, arg)?.parse()?;
+                    if !(0.0..=1.0).contains(&volume) {
+                        return Err("Volume must be between 0 and 1".into());
+                    }
+                    options.volume = Some(volume);
+                }
+                "--mute" => options.mute = true,
+                "--audio" => options.audio_backend = value(&mut args, arg)?.parse()?,
+                "--visual-bell" => options.visual_bell = true,
+                "--overlay" => options.overlay = true,
+                "--keypad" => options.keypad = true,
+                "--histogram" => options.histogram = true,
+                "--touch-grid" => options.touch_grid = TouchGrid::parse(&value(&mut args, arg)?)?,
+                "--touch-overlay" => options.touch_overlay = true,
+                "--turbo" => options.turbo = Some(value(&mut args, arg)?.parse()?),
+                "--keymap" => options.keymap = Some(KeyLayout::parse(&value(&mut args, arg)?)?),
+                "--layout" => options.keymap = Some(KeyLayout::preset(&value(&mut args, arg)?)?),
+                "--save-profile" => options.save_profile = true,
+                "--no-profile" => options.use_profile = false,
+                "--high-contrast" => options.display_mode.high_contrast = true,
+                "--invert" => options.display_mode.inverted = true,
+                "--brightness" => {
+                    let brightness = value(&mut args, arg)?.parse()?;
+                    if !(consts::MIN_BRIGHTNESS..=100).contains(&brightness) {
+                        return Err(format!(
+                            "Brightness must be between {} and 100",
+                            consts::MIN_BRIGHTNESS
+                        )
+                        .into());
+                    }
+                    options.display_mode.brightness = brightness;
+                }
+                "--resume" => options.resume = true,
+                "--dump-state" => options.dump_state = Some(value(&mut args, arg)?),
+                "--max-frames" => options.limits.max_frames = Some(value(&mut args, arg)?.parse()?),
+                "--until-halt" => options.limits.until_halt = true,
+                "--dump" => options.dump = true,
+                "--keyscript" => options.keyscript = Some(value(&mut args, arg)?),
+                "--export-display" => options.export_display = Some(value(&mut args, arg)?),
+                "--ipf" => {
+                    let cycles_per_frame = value(&mut args, arg)?.parse()?;
+                    let range = consts::MIN_CYCLES_PER_FRAME..=consts::MAX_CYCLES_PER_FRAME;
+                    if !range.contains(&cycles_per_frame) {
+                        return Err(format!(
+                            "Instructions per frame must be between {} and {}",
+                            range.start(),
+                            range.end()
+                        )
+                        .into());
+                    }
+                    options.cycles_per_frame = Some(cycles_per_frame);
+                }
+                "--platform" => options.platform = Some(value(&mut args, arg)?.parse()?),
+                // Individual quirk flags refine the platform preset regardless of their position
+                "--quirk" => options.quirk_overrides.push((quirk(&mut args, arg)?, true)),
+                "--no-quirk" => options
+                    .quirk_overrides
+                    .push((quirk(&mut args, arg)?, false)),
+                "--compare" => options.compare = Some(value(&mut args, arg)?),
+                "--compare-quirk" => options
+                    .compare_quirk_overrides
+                    .push((quirk(&mut args, arg)?, true)),
+                "--compare-no-quirk" => options
+                    .compare_quirk_overrides
+                    .push((quirk(&mut args, arg)?, false)),
+                "--palette" => options.palette = Some(value(&mut args, arg)?.parse()?),
+                "--database" => options.database = Some(value(&mut args, arg)?),
+                "--no-database" => options.use_database = false,
+                "--symbols" => options.symbols = Some(value(&mut args, arg)?),
+                "--cheats" => options.cheats = Some(value(&mut args, arg)?),
+                "--remote" => options.remote = Some(value(&mut args, arg)?),
+                "--control-socket" => options.control_socket = Some(value(&mut args, arg)?),
+                "--repl" => options.repl = true,
+                "--stream" => options.stream = Some(value(&mut args, arg)?),
+                "--host" => options.netplay = Some(Role::Host(value(&mut args, arg)?)),
+                "--join" => options.netplay = Some(Role::Join(value(&mut args, arg)?)),
+                "--crowd" => options.crowd = Some(value(&mut args, arg)?),
+                "--stats" => options.stats = Some(value(&mut args, arg)?),
+                flag if flag.starts_with("--") => {
+                    return Err(format!("Unknown option {}", flag).into())
+                }
+                path => {
+                    if options.rom_path.is_some() {
+                        return Err("Only one rom path can be specified".into());
+                    }
+                    options.rom_path = Some(path.to_string());
+                }
+            }
+        }
+        Ok(options)
+    }
+
+    // Fills in the machine settings not given on the command line, quirks from the defaults
+    // only apply when they are tuned for the platform that ends up being used
+    pub fn merge(&mut self, defaults: Options) {
+        if self.platform.is_none() {
+            self.platform = defaults.platform;
+            let overrides = std::mem::take(&mut self.quirk_overrides);
+            self.quirk_overrides = defaults.quirk_overrides;
+            self.quirk_overrides.extend(overrides);
+        }
+        self.cycles_per_frame = self.cycles_per_frame.or(defaults.cycles_per_frame);
+        self.palette = self.palette.or(defaults.palette);
+        self.turbo = self.turbo.take().or(defaults.turbo);
+        self.keymap = self.keymap.take().or(defaults.keymap);
+        self.waveform = self.waveform.or(defaults.waveform);
+        self.frequency = self.frequency.or(defaults.frequency);
+        self.volume = self.volume.or(defaults.volume);
+    }
+
+    pub fn tone(&self) -> Tone {
+        let tone = Tone::default();
+        Tone {
+            waveform: self.waveform.unwrap_or(tone.waveform),
+            frequency: self.frequency.unwrap_or(tone.frequency),
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume.unwrap_or(consts::BUZZER_VOLUME)
+    }
+
+    pub fn platform(&self) -> Platform {
+        self.platform.unwrap_or_default()
+    }
+
+    pub fn quirks(&self) -> Result<Quirks, String> {
+        let mut quirks = self.platform().quirks();
+        for (name, enabled) in &self.quirk_overrides {
+            quirks.set(name, *enabled)?;
+        }
+        Ok(quirks)
+    }
+
+    // Quirk flags for a split view instead of a second ROM compare the same one against itself
+    pub fn split_view(&self) -> bool {
+        self.compare.is_some() || !self.compare_quirk_overrides.is_empty()
+    }
+
+    pub fn compare_quirks(&self) -> Result<Quirks, String> {
+        let mut quirks = self.quirks()?;
+        for (name, enabled) in &self.compare_quirk_overrides {
+            quirks.set(name, *enabled)?;
+        }
+        Ok(quirks)
+    }
+
+    pub fn cycles_per_frame(&self) -> usize {
+        self.cycles_per_frame
+            .unwrap_or_else(|| self.platform().cycles_per_frame())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backend, Command, Options};
+    use crate::core::platform::Platform;
+
+    fn parse(args: &[&str]) -> Command {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        Command::parse(&args).unwrap()
+    }
+
+    #[test]
+    fn test_subcommands() {
+        for args in [&["game.ch8"][..], &["run", "game.ch8"]] {
+            match parse(args) {
+                Command::Run(options) => {
+                    assert_eq!(options.rom_path.as_deref(), Some("game.ch8"));
+                    assert_eq!(options.backend, Backend::Sdl);
+                }
+                command => panic!("{:?}", command),
+            }
+        }
+        assert!(
+            matches!(parse(&["debug", "game.ch8"]), Command::Run(o) if o.backend == Backend::DebugTui)
+        );
+        assert!(matches!(
+            parse(&["asm", "game.8o", "-o", "out.ch8"]),
+            Command::Asm(o) if o.output.as_deref() == Some("out.ch8")
+        ));
+        assert!(matches!(
+            parse(&["bench", "game.ch8", "--seconds", "2", "--ipf", "100"]),
+            Command::Bench(o) if o.seconds == 2.0 && o.frames.is_none() && o.run.cycles_per_frame == Some(100)
+        ));
+        assert!(matches!(parse(&["help"]), Command::Help));
+        assert!(Command::parse(&[String::from("bench")]).is_err());
+    }
+
+    fn options(args: &[&str]) -> Options {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args).unwrap()
+    }
+
+    #[test]
+    fn test_merge_precedence() -> Result<(), String> {
+        // Merged as main does: command line, then the profile, then the database
+        let mut merged = options(&["game.ch8", "--ipf", "30"]);
+        merged.merge(options(&[
+            "--ipf",
+            "20",
+            "--palette",
+            "ffffff,000000",
+            "--volume",
+            "0.5",
+        ]));
+        merged.merge(options(&[
+            "--ipf",
+            "10",
+            "--palette",
+            "ff0000,000000",
+            "--tone",
+            "220",
+            "--platform",
+            "chip8",
+        ]));
+        assert_eq!(merged.cycles_per_frame, Some(30));
+        assert_eq!(
+            merged.palette,
+            options(&["--palette", "ffffff,000000"]).palette
+        );
+        assert_eq!((merged.volume, merged.frequency), (Some(0.5), Some(220.0)));
+        assert_eq!(merged.platform, Some(Platform::Chip8));
+        assert_eq!(merged.rom_path.as_deref(), Some("game.ch8"));
+
+        // Quirk flags given along with the chosen platform stay with it, the ones given on
+        // the command line are applied last
+        let mut merged = options(&["--no-quirk", "vf-reset"]);
+        merged.merge(options(&["--platform", "chip8", "--no-quirk", "shift-vy"]));
+        let quirks = merged.quirks()?;
+        assert!(!quirks.vf_reset && !quirks.shift_vy);
+        assert!(quirks.memory_increment);
+
+        // A platform from the command line keeps the defaults' quirk flags out
+        let mut merged = options(&["--platform", "chip8"]);
+        merged.merge(options(&["--platform", "modern", "--quirk", "wrap"]));
+        assert_eq!(merged.quirks()?, Platform::Chip8.quirks());
+        Ok(())
+    }
+}
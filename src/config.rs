@@ -0,0 +1,218 @@
+use crate::cli::ConfigOptions;
+use crate::consts;
+use crate::external::output::AudioSettings;
+use crate::saves::data_dir;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Settings that persist across runs regardless of the ROM, kept as "name value" lines in
+// config.txt in the data directory and changed with `chip8 config NAME VALUE`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    // Accessibility speed, the whole machine runs this much slower or faster, timers included,
+    // so reaction based games stay playable without changing how they behave
+    pub speed: f64,
+    pub audio: AudioSettings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            speed: 1.0,
+            audio: Default::default(),
+        }
+    }
+}
+
+impl Config {
+    // "audio" is a preset that sets the other audio settings at once and is not saved itself
+    pub const NAMES: [&'static str; 4] = ["speed", "audio-samples", "sample-rate", "audio"];
+
+    pub fn path() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join("config.txt"))
+    }
+
+    // Defaults when there is no data directory or no file yet
+    pub fn load() -> Result<Self, String> {
+        let path = match Config::path() {
+            Some(p) if p.exists() => p,
+            _ => return Ok(Default::default()),
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        Config::parse(&text)
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Config::path().ok_or("No data directory to keep the config in")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config: Config = Default::default();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            match line.split_once(char::is_whitespace) {
+                Some((name, value)) => config.set(name, value.trim())?,
+                None => return Err(format!("Config line {} has no value", line)),
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn to_text(&self) -> String {
+        let samples = match self.audio.samples {
+            Some(n) => n.to_string(),
+            None => String::from("auto"),
+        };
+        format!(
+            "speed {}\naudio-samples {}\nsample-rate {}\n",
+            self.speed, samples, self.audio.sample_rate
+        )
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "speed" => {
+                let speed: f64 = value
+                    .parse()
+                    .map_err(|_| format!("Invalid speed {}", value))?;
+                if !(consts::MIN_SPEED..=consts::MAX_SPEED).contains(&speed) {
+                    return Err(format!(
+                        "Speed must be between {} and {}",
+                        consts::MIN_SPEED,
+                        consts::MAX_SPEED
+                    ));
+                }
+                self.speed = speed;
+            }
+            // auto leaves the buffer size to the audio driver
+            "audio-samples" => {
+                self.audio.samples = match value {
+                    "auto" => None,
+                    _ => {
+                        let samples: u16 = value
+                            .parse()
+                            .map_err(|_| format!("Invalid buffer size {}", value))?;
+                        if !samples.is_power_of_two()
+                            || !(consts::MIN_AUDIO_SAMPLES..=consts::MAX_AUDIO_SAMPLES)
+                                .contains(&samples)
+                        {
+                            return Err(format!(
+                                "Buffer size must be a power of two between {} and {}",
+                                consts::MIN_AUDIO_SAMPLES,
+                                consts::MAX_AUDIO_SAMPLES
+                            ));
+                        }
+                        Some(samples)
+                    }
+                }
+            }
+            "sample-rate" => {
+                let rate: i32 = value
+                    .parse()
+                    .map_err(|_| format!("Invalid sample rate {}", value))?;
+                if !(consts::MIN_SAMPLE_RATE..=consts::MAX_SAMPLE_RATE).contains(&rate) {
+                    return Err(format!(
+                        "Sample rate must be between {} and {}",
+                        consts::MIN_SAMPLE_RATE,
+                        consts::MAX_SAMPLE_RATE
+                    ));
+                }
+                self.audio.sample_rate = rate;
+            }
+            "audio" => {
+                self.audio = match value {
+                    "low-latency" => AudioSettings::LOW_LATENCY,
+                    "default" => Default::default(),
+                    _ => return Err(format!("Unknown audio preset {}", value)),
+                }
+            }
+            _ => return Err(format!("Unknown setting {}", name)),
+        }
+        Ok(())
+    }
+
+    // Time between frames and between timer ticks on a platform with the given timer rate
+    pub fn frame_period(&self, timer_freq: u64) -> Duration {
+        Duration::from_secs_f64(1.0 / (timer_freq as f64 * self.speed))
+    }
+}
+
+// Entry point of `chip8 config`
+pub fn run(options: ConfigOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = Config::load()?;
+    match options.setting {
+        Some((name, value)) => {
+            config.set(&name, &value)?;
+            config.save()?;
+        }
+        None => {
+            print!("{}", config.to_text());
+            match config.audio.latency() {
+                Some(latency) => println!(
+                    "# audio buffer latency {:.1} ms",
+                    latency.as_secs_f64() * 1000.0
+                ),
+                None => println!("# audio buffer latency chosen by the driver"),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::consts;
+    use crate::external::output::AudioSettings;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse("# slower for now\nspeed 0.5\n").unwrap();
+        assert_eq!(config.speed, 0.5);
+        assert_eq!(Config::parse(&config.to_text()), Ok(config));
+        assert_eq!(Config::parse(""), Ok(Config::default()));
+        assert!(Config::parse("speed 0").is_err());
+        assert!(Config::parse("speed").is_err());
+        assert!(Config::parse("volume 1").is_err());
+    }
+
+    #[test]
+    fn test_audio_settings() -> Result<(), String> {
+        let config = Config::parse("audio-samples 512\nsample-rate 48000")?;
+        assert_eq!(config.audio.samples, Some(512));
+        assert_eq!(config.audio.sample_rate, 48000);
+        assert_eq!(Config::parse(&config.to_text()), Ok(config.clone()));
+        assert_eq!(Config::parse("audio-samples auto")?.audio.samples, None);
+        assert!(Config::parse("audio-samples 500").is_err());
+        assert!(Config::parse("audio-samples 16384").is_err());
+        assert!(Config::parse("sample-rate 1000").is_err());
+        let mut config = Config::parse("audio low-latency")?;
+        assert_eq!(config.audio, AudioSettings::LOW_LATENCY);
+        config.set("audio", "default")?;
+        assert_eq!(config.audio, AudioSettings::default());
+        assert!(config.set("audio", "fast").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_period() {
+        let config = Config {
+            speed: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.frame_period(consts::TIMER_FREQ),
+            Duration::from_secs_f64(1.0 / 30.0)
+        );
+    }
+}
@@ -1,5 +1,8 @@
 pub const CHIP8_WIDTH: usize = 64;
 pub const CHIP8_HEIGHT: usize = 32;
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+pub const ETI660_HEIGHT: usize = 48;
 pub const OP_CODE_BYTES: usize = 2;
 pub const RAM_BYTES: usize = 4096;
 pub const REG_COUNT: usize = 16;
@@ -13,11 +16,107 @@ pub const FONT_SET: [u8; 80] = [
     0xF0, 0xE0, 0x90, 0x90, 0x90, 0xE0, 0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80,
 ];
 pub const FONT_SET_SIZE: usize = 80;
+// Digits as drawn by the COSMAC VIP interpreter, which some programs of the era rely on
+pub const VIP_FONT_SET: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, 0x60, 0x20, 0x20, 0x20, 0x70, 0xF0, 0x10, 0xF0, 0x80, 0xF0, 0xF0,
+    0x10, 0x70, 0x10, 0xF0, 0xA0, 0xA0, 0xF0, 0x20, 0x20, 0xF0, 0x80, 0xF0, 0x10, 0xF0, 0xF0, 0x80,
+    0xF0, 0x90, 0xF0, 0xF0, 0x10, 0x10, 0x10, 0x10, 0xF0, 0x90, 0xF0, 0x90, 0xF0, 0xF0, 0x90, 0xF0,
+    0x10, 0xF0, 0xF0, 0x90, 0xF0, 0x90, 0x90, 0xF0, 0x50, 0x70, 0x50, 0xF0, 0xF0, 0x80, 0x80, 0x80,
+    0xF0, 0xF0, 0x50, 0x50, 0x50, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80,
+];
+pub const FONT_GLYPH_BYTES: usize = 5;
+// SUPER-CHIP 8x10 digits, stored right after the small font
+pub const BIG_FONT_SET: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x18, 0x78, 0x78, 0x18, 0x18, 0x18,
+    0x18, 0x18, 0xFF, 0xFF, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xFF, 0xFF,
+    0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03,
+    0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xFF, 0xFF, 0xC0, 0xC0,
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18,
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,
+    0x03, 0x03, 0xFF, 0xFF, 0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xFC, 0xFC,
+    0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3,
+    0xFF, 0x3C, 0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, 0xFF, 0xFF, 0xC0, 0xC0,
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0,
+];
+pub const BIG_FONT_SET_SIZE: usize = 160;
+pub const BIG_FONT_OFFSET: usize = FONT_SET_SIZE;
+pub const BIG_FONT_GLYPH_BYTES: usize = 10;
 pub const PROG_OFFSET: usize = 512;
+pub const CHIP8X_PROG_OFFSET: usize = 0x300;
+pub const ETI660_PROG_OFFSET: usize = 0x600;
+
+// VP-590 color card palette used by CHIP-8X, backgrounds cycle through a subset of it
+pub const CHIP8X_PALETTE: [(u8, u8, u8); 8] = [
+    (0, 0, 0),
+    (255, 0, 0),
+    (0, 0, 255),
+    (255, 0, 255),
+    (0, 255, 0),
+    (255, 255, 0),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+pub const CHIP8X_BACKGROUNDS: [u8; 4] = [2, 0, 4, 1];
+pub const CHIP8X_FOREGROUND: u8 = 1;
 
 pub const SCALE_FACTOR: u32 = 20;
 pub const DISPL_WIDTH: u32 = (CHIP8_WIDTH as u32) * SCALE_FACTOR;
 pub const DISPL_HEIGHT: u32 = (CHIP8_HEIGHT as u32) * SCALE_FACTOR;
 
 pub const KEYBOARD_SIZE: usize = 16;
-pub const CLOCK_PERIOD: usize = 2;
+// Host keys for CHIP-8 keys 0-F, the left 4x4 block of a QWERTY keyboard
+pub const KEY_LAYOUT: [char; KEYBOARD_SIZE] = [
+    'x', '1', '2', '3', 'q', 'w', 'e', 'a', 's', 'd', 'z', 'c', '4', 'r', 'f', 'v',
+];
+// Keypad keys as laid out on the COSMAC VIP, row by row, for the on-screen keypad
+pub const KEYPAD_GRID: [usize; KEYBOARD_SIZE] = [
+    0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+];
+// Crowd play: main loop iterations an injected key stays down, and how many keys are taken
+// in per second overall and per user
+pub const CROWD_TAP_FRAMES: u32 = 6;
+pub const CROWD_KEYS_PER_SECOND: usize = 10;
+pub const CROWD_USER_COOLDOWN_MS: u64 = 500;
+pub const CYCLES_PER_FRAME: usize = 10;
+pub const MIN_CYCLES_PER_FRAME: usize = 1;
+pub const MAX_CYCLES_PER_FRAME: usize = 1000;
+pub const TIMER_FREQ: u64 = 60;
+// The ETI-660 timers follow the 50Hz PAL video interrupt
+pub const ETI660_TIMER_FREQ: u64 = 50;
+// Upper bound on frames caught up in one go after a stall, so a long hitch does not fast-forward
+pub const MAX_FRAME_SKIP: u32 = 5;
+// Frames run for each one due while fast-forwarding
+pub const FAST_FORWARD_FRAMES: u32 = 4;
+// Instructions kept for crash reports
+pub const TRACE_LENGTH: usize = 64;
+// How long confirmations such as a saved state stay on screen
+pub const MESSAGE_MILLIS: u64 = 2000;
+// How long chip8 bench runs when not told otherwise
+pub const BENCH_SECONDS: f64 = 5.0;
+// Taps per second of a turbo key when the rate is not given
+pub const TURBO_RATE: u32 = 10;
+// Each tap has to be down for one frame and up for the next to be seen at all
+pub const MAX_TURBO_RATE: u32 = TIMER_FREQ as u32 / 2;
+// Bounds of the accessibility speed in the config file
+pub const MIN_SPEED: f64 = 0.25;
+pub const MAX_SPEED: f64 = 1.0;
+// Display brightness in percent, changed in steps at runtime
+pub const MIN_BRIGHTNESS: u8 = 10;
+pub const BRIGHTNESS_STEP: u8 = 10;
+// Save state previews are scaled down to at most this many pixels across
+pub const THUMBNAIL_WIDTH: usize = 64;
+
+pub const AUDIO_FREQ: i32 = 44100;
+// Bounds of the audio settings in the config file, buffers are a power of two in between
+pub const MIN_AUDIO_SAMPLES: u16 = 64;
+pub const MAX_AUDIO_SAMPLES: u16 = 8192;
+pub const MIN_SAMPLE_RATE: i32 = 8000;
+pub const MAX_SAMPLE_RATE: i32 = 192000;
+pub const BUZZER_TONE: f32 = 440.0;
+pub const BUZZER_VOLUME: f32 = 0.25;
+// XO-CHIP audio plays at 4000Hz until FX3A moves the pitch away from 64
+pub const DEFAULT_PITCH: u8 = 64;
+pub const DEFAULT_PLAYBACK_RATE: f32 = 4000.0;
+// Audio samples and frames of sound timer history shown by the oscilloscope
+pub const SCOPE_SAMPLES: usize = 1024;
+pub const SCOPE_FRAMES: usize = 120;
@@ -0,0 +1,82 @@
+use crate::core::ram::Ram;
+
+// Memory as the processor sees it. Ram is the plain implementation, wrapping it lets tools
+// see or change every access, e.g. for watchpoints, memory mapped peripherals or access logs.
+// The processor checks addresses against RAM_BYTES before every access, so implementations
+// can assume they are in range
+pub trait Bus {
+    fn read8(&self, address: usize) -> u8;
+    fn write8(&mut self, address: usize, value: u8);
+    fn read_slice(&self, address: usize, len: usize) -> &[u8];
+
+    // One write8 per byte unless the implementation has something faster
+    fn write_slice(&mut self, address: usize, bytes: &[u8]) {
+        for (offset, &value) in bytes.iter().enumerate() {
+            self.write8(address + offset, value);
+        }
+    }
+}
+
+impl Bus for Ram {
+    fn read8(&self, address: usize) -> u8 {
+        self.buffer[address]
+    }
+
+    fn write8(&mut self, address: usize, value: u8) {
+        self.buffer[address] = value;
+    }
+
+    fn read_slice(&self, address: usize, len: usize) -> &[u8] {
+        &self.buffer[address..address + len]
+    }
+
+    fn write_slice(&mut self, address: usize, bytes: &[u8]) {
+        self.buffer[address..address + bytes.len()].copy_from_slice(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Bus;
+    use crate::core::processor::Processor;
+    use crate::core::ram::Ram;
+
+    // Records every write on top of plain memory
+    #[derive(Debug, Default)]
+    struct LoggingBus {
+        ram: Ram,
+        writes: Vec<(usize, u8)>,
+    }
+
+    impl Bus for LoggingBus {
+        fn read8(&self, address: usize) -> u8 {
+            self.ram.read8(address)
+        }
+
+        fn write8(&mut self, address: usize, value: u8) {
+            self.writes.push((address, value));
+            self.ram.write8(address, value);
+        }
+
+        fn read_slice(&self, address: usize, len: usize) -> &[u8] {
+            self.ram.read_slice(address, len)
+        }
+    }
+
+    #[test]
+    fn test_custom_bus() -> Result<(), &'static str> {
+        let mut chip8: Processor<LoggingBus> =
+            Processor::builder().build_with_bus(Default::default());
+        // v0 := 0x2A, i := 0x300, save v0
+        chip8
+            .ram
+            .write_slice(0x200, &[0x60, 0x2A, 0xA3, 0x00, 0xF0, 0x55]);
+        chip8.ram.writes.clear();
+        for _ in 0..3 {
+            chip8.cycle()?;
+        }
+        assert_eq!(chip8.ram.writes, [(0x300, 0x2A)]);
+        assert_eq!(chip8.ram.read8(0x300), 0x2A);
+        Ok(())
+    }
+}
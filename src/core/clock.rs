@@ -0,0 +1,125 @@
+use crate::consts;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Source of time for frame pacing, so the loops can be driven by hand in tests
+pub trait Clock {
+    // Time since some fixed point, only differences matter
+    fn now(&self) -> Duration;
+    fn sleep(&mut self, duration: Duration);
+}
+
+pub struct RealClock {
+    started: Instant,
+}
+
+impl Default for RealClock {
+    fn default() -> Self {
+        RealClock {
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Clock for RealClock {
+    fn now(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+// Only moves when told to, sleeping returns at once after moving it forward
+#[derive(Default)]
+pub struct ManualClock {
+    pub now: Duration,
+}
+
+impl ManualClock {
+    pub fn advance(&mut self, duration: Duration) {
+        self.now += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+// Emulation advances in fixed frames at the platform's timer rate, the accumulator carries
+// over leftover time so the speed does not depend on how long drawing takes
+pub struct FramePacer {
+    pub frame_period: Duration,
+    accumulator: Duration,
+    previous: Duration,
+}
+
+impl FramePacer {
+    pub fn new(frame_period: Duration, clock: &impl Clock) -> Self {
+        FramePacer {
+            frame_period,
+            accumulator: Duration::ZERO,
+            previous: clock.now(),
+        }
+    }
+
+    // Frames due since the last call. Falling far behind, e.g. while the window was being
+    // dragged, skips time instead of fast-forwarding through it
+    pub fn due(&mut self, clock: &impl Clock) -> u32 {
+        let now = clock.now();
+        self.accumulator = (self.accumulator + now.saturating_sub(self.previous))
+            .min(self.frame_period * consts::MAX_FRAME_SKIP);
+        self.previous = now;
+        let mut frames = 0;
+        while self.accumulator >= self.frame_period {
+            self.accumulator -= self.frame_period;
+            frames += 1;
+        }
+        frames
+    }
+
+    // How long until the next frame is due
+    pub fn until_next(&self, clock: &impl Clock) -> Duration {
+        let elapsed = clock.now().saturating_sub(self.previous);
+        self.frame_period.saturating_sub(self.accumulator + elapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, FramePacer, ManualClock};
+    use crate::consts;
+    use std::time::Duration;
+
+    const PERIOD: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn test_frames_due() {
+        let mut clock = ManualClock::default();
+        let mut pacer = FramePacer::new(PERIOD, &clock);
+        assert_eq!(pacer.due(&clock), 0);
+        clock.advance(Duration::from_millis(25));
+        assert_eq!(pacer.due(&clock), 2);
+        // The leftover 5ms counts towards the next frame
+        assert_eq!(pacer.until_next(&clock), Duration::from_millis(5));
+        clock.sleep(pacer.until_next(&clock));
+        assert_eq!(pacer.due(&clock), 1);
+        assert_eq!(pacer.until_next(&clock), PERIOD);
+    }
+
+    #[test]
+    fn test_stalls_are_skipped() {
+        let mut clock = ManualClock::default();
+        let mut pacer = FramePacer::new(PERIOD, &clock);
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(pacer.due(&clock), consts::MAX_FRAME_SKIP);
+        assert_eq!(pacer.due(&clock), 0);
+    }
+}
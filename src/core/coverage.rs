@@ -0,0 +1,173 @@
+use crate::consts;
+use crate::core::instruction::Instruction;
+use crate::core::processor::Processor;
+use std::fmt::Write;
+use std::ops::Range;
+
+// Memory read as data by DXYN and FX65. Executed addresses come from the histogram, so
+// together they tell code, data and dead bytes apart once the histogram is counting
+#[derive(Clone, Debug, PartialEq)]
+pub struct Coverage {
+    pub read: Vec<bool>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Coverage {
+            read: vec![false; consts::RAM_BYTES],
+        }
+    }
+}
+
+impl Coverage {
+    pub fn mark_read(&mut self, range: Range<usize>) {
+        self.read[range].fill(true);
+    }
+
+    pub fn clear(&mut self) {
+        self.read.fill(false);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    Executed,
+    Read,
+    Untouched,
+}
+
+impl Access {
+    fn class(self) -> &'static str {
+        match self {
+            Access::Executed => "executed",
+            Access::Read => "read",
+            Access::Untouched => "untouched",
+        }
+    }
+}
+
+// Executing wins over reading, so code that is also read as a sprite counts as code
+pub fn access(chip8: &Processor, address: usize) -> Access {
+    let hits = &chip8.histogram.addresses;
+    if hits[address] > 0 || (address > 0 && hits[address - 1] > 0) {
+        Access::Executed
+    } else if chip8.coverage.read[address] {
+        Access::Read
+    } else {
+        Access::Untouched
+    }
+}
+
+const REPORT_BYTES_PER_LINE: usize = 16;
+
+// Standalone HTML page with every byte of the program colored by how it was used
+pub fn report(chip8: &Processor, rom: &str) -> String {
+    let program = chip8.load_address()..chip8.load_address() + chip8.rom_size();
+    let accesses: Vec<Access> = program.clone().map(|a| access(chip8, a)).collect();
+    let count = |kind: Access| accesses.iter().filter(|&&a| a == kind).count();
+    let percent = |n: usize| n as f64 * 100.0 / accesses.len().max(1) as f64;
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Coverage of {rom}</title>\n<style>\n\
+         body {{ font-family: monospace; background: #111; color: #ddd; }}\n\
+         span {{ padding: 0 2px; }}\n\
+         .executed {{ background: #2e7d32; }}\n\
+         .read {{ background: #1565c0; }}\n\
+         .untouched {{ color: #666; }}\n\
+         </style>\n</head>\n<body>\n<h1>Coverage of {rom}</h1>\n<p>",
+        rom = escape(rom)
+    );
+    for kind in [Access::Executed, Access::Read, Access::Untouched] {
+        let n = count(kind);
+        let _ = write!(
+            html,
+            "<span class=\"{}\">{}: {} bytes ({:.1}%)</span> ",
+            kind.class(),
+            kind.class(),
+            n,
+            percent(n)
+        );
+    }
+    html += "</p>\n<pre>\n";
+    for (line, chunk) in program
+        .clone()
+        .zip(&accesses)
+        .collect::<Vec<_>>()
+        .chunks(REPORT_BYTES_PER_LINE)
+        .enumerate()
+    {
+        let _ = write!(
+            html,
+            "{:03X} ",
+            program.start + line * REPORT_BYTES_PER_LINE
+        );
+        for &(address, &kind) in chunk {
+            let byte = chip8.ram.buffer[address];
+            // Instructions show their mnemonic on hover
+            let title = match kind {
+                Access::Executed if chip8.histogram.addresses[address] > 0 => {
+                    let opcode = u16::from_be_bytes([
+                        byte,
+                        chip8.ram.buffer[(address + 1) % consts::RAM_BYTES],
+                    ]);
+                    format!(
+                        " title=\"{:03X}: {}\"",
+                        address,
+                        escape(&Instruction::decode(opcode, chip8.quirks()).to_string())
+                    )
+                }
+                _ => format!(" title=\"{:03X}\"", address),
+            };
+            let _ = write!(
+                html,
+                "<span class=\"{}\"{}>{:02X}</span>",
+                kind.class(),
+                title,
+                byte
+            );
+        }
+        html += "\n";
+    }
+    html += "</pre>\n</body>\n</html>\n";
+    html
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{access, report, Access};
+    use crate::consts;
+    use crate::core::processor::Processor;
+    use crate::core::rom::Rom;
+
+    #[test]
+    fn test_coverage() -> Result<(), &'static str> {
+        // i := 0x208, sprite v0 v0 1, jump 0x204, then one byte drawn and one never used
+        let program = [0xA2, 0x08, 0xD0, 0x01, 0x12, 0x04, 0x00, 0x00, 0x80, 0xFF];
+        let rom = Rom::from_bytes(&program).map_err(|_| "Could not build ROM")?;
+        let mut chip8 = Processor::default();
+        chip8.histogram.enabled = true;
+        chip8.init_ram(&rom, &consts::FONT_SET, consts::PROG_OFFSET)?;
+        chip8.run_frame(10)?;
+        let kinds: Vec<Access> = (0x200..0x20A).map(|a| access(&chip8, a)).collect();
+        assert_eq!(kinds[..6], [Access::Executed; 6]);
+        assert_eq!(kinds[6..8], [Access::Untouched; 2]);
+        assert_eq!(kinds[8..], [Access::Read, Access::Untouched]);
+
+        let html = report(&chip8, "<test>.ch8");
+        assert!(html.contains("Coverage of &lt;test&gt;.ch8"));
+        assert!(html.contains("executed: 6 bytes (60.0%)"));
+        assert!(html.contains("<span class=\"read\" title=\"208\">80</span>"));
+        assert!(html.contains("title=\"200: LD I, #208\""));
+
+        chip8.reset();
+        assert_eq!(access(&chip8, 0x208), Access::Untouched);
+        Ok(())
+    }
+}
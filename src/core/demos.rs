@@ -0,0 +1,17 @@
+// Public domain ROMs compiled into the binary, so the emulator can be tried without any files
+pub const DEMOS: [(&str, &[u8]); 3] = [
+    ("ibm", include_bytes!("../../roms/IBM Logo.ch8")),
+    ("maze", include_bytes!("../../roms/Maze.ch8")),
+    ("pong", include_bytes!("../../roms/Pong.ch8")),
+];
+
+pub fn find(name: &str) -> Option<&'static [u8]> {
+    DEMOS
+        .iter()
+        .find(|(demo, _)| *demo == name)
+        .map(|(_, bytes)| *bytes)
+}
+
+pub fn names() -> Vec<&'static str> {
+    DEMOS.iter().map(|(name, _)| *name).collect()
+}
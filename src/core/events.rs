@@ -0,0 +1,59 @@
+use crate::core::processor::Pitch;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+// Notifications published by the processor, frontends and tools subscribe to the ones they
+// care about instead of inspecting return values
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    // A frame worth of cycles ran and the timers ticked, redraw is set if the display changed
+    FrameCompleted { redraw: bool },
+    // The sound timer went from zero to running
+    SoundStarted,
+    // FX18 loaded the sound timer, audio backends count the beep out in samples from here
+    SoundTimerSet(u8),
+    // FX3A changed the audio playback rate
+    PitchSet(Pitch),
+    // FX0A started blocking until a key is pressed
+    KeyWaited,
+    RomLoaded,
+    // The program executed 00FD
+    Halted,
+    // DXYN drew a sprite, for debuggers that show what changed
+    SpriteDrawn(SpriteDraw),
+    // 2NNN at from jumped to the subroutine at to
+    Called { from: u16, to: u16 },
+    // 00EE at from went back to to
+    Returned { from: u16, to: u16 },
+    // FX33 or FX55 stored len bytes starting at address
+    MemoryWritten { address: u16, len: u16 },
+}
+
+// Screen position and size of a DXYN sprite, before clipping or wrapping, and the address
+// of the sprite data it read
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteDraw {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub address: u16,
+}
+
+#[derive(Debug, Default)]
+pub struct EventBus {
+    subscribers: Vec<Sender<Event>>,
+}
+
+impl EventBus {
+    pub fn subscribe(&mut self) -> Receiver<Event> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    // Subscribers that dropped their receiver are forgotten
+    pub fn publish(&mut self, event: Event) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event).is_ok());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::consts;
+use std::fmt::Write;
+
+// Kinds never have bits 8 to 11 set, so the group and the low byte index a dense table
+const KINDS: usize = 16 * 256;
+
+// Execution counts for the session, per kind of instruction and per address, for studying
+// what programs actually spend their time on
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    // Indexed by the opcode with its operands masked out, see kind and slot. Recording
+    // runs for every instruction, so this is a flat table rather than a map
+    pub opcodes: Vec<u64>,
+    pub addresses: Vec<u64>,
+    // Counting costs time on every instruction, so it is off until asked for with
+    // --histogram or a first export
+    pub enabled: bool,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            opcodes: vec![0; KINDS],
+            addresses: vec![0; consts::RAM_BYTES],
+            enabled: false,
+        }
+    }
+}
+
+impl Histogram {
+    // Inlined like Trace::push, it runs for every instruction
+    #[inline]
+    pub fn record(&mut self, address: usize, opcode: u16) {
+        self.opcodes[slot(kind(opcode))] += 1;
+        self.addresses[address] += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.opcodes.fill(0);
+        self.addresses.fill(0);
+    }
+
+    // One table for both counts, so it loads straight into a spreadsheet or dataframe.
+    // Addresses that never ran are left out
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("table,key,count\n");
+        let mut opcodes: Vec<(u16, u64)> = self
+            .opcodes
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(slot, &count)| (kind_at(slot), count))
+            .collect();
+        opcodes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        for (key, count) in opcodes {
+            let _ = writeln!(csv, "opcode,{},{}", pattern(key), count);
+        }
+        for (address, count) in self.addresses.iter().enumerate() {
+            if *count > 0 {
+                let _ = writeln!(csv, "address,{:#05X},{}", address, count);
+            }
+        }
+        csv
+    }
+}
+
+// The opcode with everything that is an operand zeroed, so all instructions of one kind
+// share a key
+#[inline]
+pub fn kind(opcode: u16) -> u16 {
+    match opcode >> 12 {
+        0x0 if opcode & 0xFF00 != 0 => 0x0000,
+        0x0 if matches!(opcode & 0xF0, 0xC0 | 0xD0) => opcode & 0xFFF0,
+        0x0 => opcode,
+        0x5 | 0x8 | 0x9 => opcode & 0xF00F,
+        0xE | 0xF => opcode & 0xF0FF,
+        _ => opcode & 0xF000,
+    }
+}
+
+#[inline]
+fn slot(kind: u16) -> usize {
+    ((kind >> 4) & 0xF00 | kind & 0xFF) as usize
+}
+
+fn kind_at(slot: usize) -> u16 {
+    ((slot & 0xF00) << 4 | slot & 0xFF) as u16
+}
+
+// How a kind is written in opcode tables, e.g. 8XY4 or FX1E
+pub fn pattern(kind: u16) -> String {
+    let group = kind >> 12;
+    match group {
+        0x0 if kind == 0 => String::from("0NNN"),
+        0x0 if matches!(kind & 0xF0, 0xC0 | 0xD0) => format!("00{:X}N", (kind >> 4) & 0xF),
+        0x0 => format!("{:04X}", kind),
+        0x1 | 0x2 | 0xA | 0xB => format!("{:X}NNN", group),
+        0x5 | 0x8 | 0x9 => format!("{:X}XY{:X}", group, kind & 0xF),
+        0xD => String::from("DXYN"),
+        // XO-CHIP's long load has no register operand
+        0xF if kind == 0xF000 => String::from("F000"),
+        0xE | 0xF => format!("{:X}X{:02X}", group, kind & 0xFF),
+        _ => format!("{:X}XNN", group),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{kind, kind_at, pattern, slot, Histogram};
+    use crate::core::bus::Bus;
+    use crate::core::processor::Processor;
+
+    #[test]
+    fn test_patterns() {
+        let name = |opcode| pattern(kind(opcode));
+        assert_eq!(name(0x00E0), "00E0");
+        assert_eq!(name(0x00C4), "00CN");
+        assert_eq!(name(0x0123), "0NNN");
+        assert_eq!(name(0x2ABC), "2NNN");
+        assert_eq!(name(0x6A05), "6XNN");
+        assert_eq!(name(0x8AB4), "8XY4");
+        assert_eq!(name(0x5122), "5XY2");
+        assert_eq!(name(0xD015), "DXYN");
+        assert_eq!(name(0xE3A1), "EXA1");
+        assert_eq!(name(0xF51E), "FX1E");
+        assert_eq!(name(0xF000), "F000");
+    }
+
+    #[test]
+    fn test_slots() {
+        for opcode in 0..=u16::MAX {
+            assert_eq!(kind_at(slot(kind(opcode))), kind(opcode), "{:04X}", opcode);
+        }
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut histogram = Histogram::default();
+        histogram.record(0x200, 0x6001);
+        histogram.record(0x202, 0x8014);
+        histogram.record(0x204, 0x1202);
+        histogram.record(0x202, 0x8124);
+        assert_eq!(
+            histogram.to_csv(),
+            "table,key,count\n\
+             opcode,8XY4,2\n\
+             opcode,1NNN,1\n\
+             opcode,6XNN,1\n\
+             address,0x200,1\n\
+             address,0x202,2\n\
+             address,0x204,1\n"
+        );
+        histogram.clear();
+        assert_eq!(histogram.to_csv(), "table,key,count\n");
+    }
+
+    #[test]
+    fn test_off_until_enabled() -> Result<(), &'static str> {
+        // Jumps to itself at 0x200
+        let mut chip8: Processor = Processor::default();
+        chip8.ram.write_slice(0x200, &[0x12, 0x00]);
+        chip8.pc = 0x200;
+        chip8.run_frame(10)?;
+        assert!(chip8.histogram.opcodes.iter().all(|&count| count == 0));
+
+        chip8.histogram.enabled = true;
+        chip8.run_frame(10)?;
+        assert_eq!(chip8.histogram.addresses[0x200], 10);
+        Ok(())
+    }
+}
@@ -0,0 +1,431 @@
+use crate::consts;
+use crate::core::quirks::Quirks;
+use crate::utils;
+use std::fmt;
+
+// An opcode decoded the way the processor executes it. X and Y are register numbers, the
+// other operands immediate values
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    // 00E0, 00EE, 00CN, 00FB-00FF
+    Clear,
+    Return,
+    ScrollDown(u8),
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    Lores,
+    Hires,
+    // CHIP-8X 02A0
+    NextBackground,
+    // 1NNN, 2NNN
+    Jump(u16),
+    Call(u16),
+    // 3XNN, 4XNN, 5XY0, 9XY0
+    SkipEqual(u8, u8),
+    SkipNotEqual(u8, u8),
+    SkipRegistersEqual(u8, u8),
+    SkipRegistersNotEqual(u8, u8),
+    // CHIP-8X 5XY1
+    AddNibbles(u8, u8),
+    // 6XNN, 7XNN
+    Set(u8, u8),
+    Add(u8, u8),
+    // 8XY0-8XYE
+    Move(u8, u8),
+    Or(u8, u8),
+    And(u8, u8),
+    Xor(u8, u8),
+    AddRegisters(u8, u8),
+    Sub(u8, u8),
+    ShiftRight(u8, u8),
+    SubReverse(u8, u8),
+    ShiftLeft(u8, u8),
+    // ANNN
+    SetIndex(u16),
+    // BNNN, the register is V0 or VX under the jump quirk
+    JumpOffset(u8, u16),
+    // CHIP-8X BXYN
+    ColorZones(u8, u8, u8),
+    // CXNN, DXYN
+    Random(u8, u8),
+    Draw(u8, u8, u8),
+    // EX9E, EXA1
+    SkipKey(u8),
+    SkipNotKey(u8),
+    // FX07-FX65
+    GetDelay(u8),
+    WaitKey(u8),
+    SetDelay(u8),
+    SetSound(u8),
+    AddIndex(u8),
+    Font(u8),
+    BigFont(u8),
+    // XO-CHIP FX3A
+    Pitch(u8),
+    Bcd(u8),
+    Store(u8),
+    Load(u8),
+    // Machine language routines and opcodes no interpreter defines
+    Unknown(u16),
+}
+
+impl Instruction {
+    // Quirks decide between the CHIP-8X encodings and the ones they replace
+    #[inline]
+    pub fn decode(opcode: u16, quirks: &Quirks) -> Instruction {
+        let (high, x, y, n) = utils::nibble_split(&opcode.to_be_bytes());
+        let nn = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+        match (high, x, y, n) {
+            (0x0, 0x0, 0xE, 0x0) => Instruction::Clear,
+            (0x0, 0x0, 0xE, 0xE) => Instruction::Return,
+            (0x0, 0x0, 0xC, _) => Instruction::ScrollDown(n),
+            (0x0, 0x0, 0xF, 0xB) => Instruction::ScrollRight,
+            (0x0, 0x0, 0xF, 0xC) => Instruction::ScrollLeft,
+            (0x0, 0x0, 0xF, 0xD) => Instruction::Exit,
+            (0x0, 0x0, 0xF, 0xE) => Instruction::Lores,
+            (0x0, 0x0, 0xF, 0xF) => Instruction::Hires,
+            (0x0, 0x2, 0xA, 0x0) if quirks.chip8x => Instruction::NextBackground,
+            (0x1, ..) => Instruction::Jump(nnn),
+            (0x2, ..) => Instruction::Call(nnn),
+            (0x3, ..) => Instruction::SkipEqual(x, nn),
+            (0x4, ..) => Instruction::SkipNotEqual(x, nn),
+            (0x5, _, _, 0x0) => Instruction::SkipRegistersEqual(x, y),
+            (0x5, _, _, 0x1) if quirks.chip8x => Instruction::AddNibbles(x, y),
+            (0x6, ..) => Instruction::Set(x, nn),
+            (0x7, ..) => Instruction::Add(x, nn),
+            (0x8, _, _, 0x0) => Instruction::Move(x, y),
+            (0x8, _, _, 0x1) => Instruction::Or(x, y),
+            (0x8, _, _, 0x2) => Instruction::And(x, y),
+            (0x8, _, _, 0x3) => Instruction::Xor(x, y),
+            (0x8, _, _, 0x4) => Instruction::AddRegisters(x, y),
+            (0x8, _, _, 0x5) => Instruction::Sub(x, y),
+            (0x8, _, _, 0x6) => Instruction::ShiftRight(x, y),
+            (0x8, _, _, 0x7) => Instruction::SubReverse(x, y),
+            (0x8, _, _, 0xE) => Instruction::ShiftLeft(x, y),
+            (0x9, _, _, 0x0) => Instruction::SkipRegistersNotEqual(x, y),
+            (0xA, ..) => Instruction::SetIndex(nnn),
+            (0xB, ..) if quirks.chip8x => Instruction::ColorZones(x, y, n),
+            (0xB, ..) if quirks.jump_vx => Instruction::JumpOffset(x, nnn),
+            (0xB, ..) => Instruction::JumpOffset(0, nnn),
+            (0xC, ..) => Instruction::Random(x, nn),
+            (0xD, ..) => Instruction::Draw(x, y, n),
+            (0xE, _, 0x9, 0xE) => Instruction::SkipKey(x),
+            (0xE, _, 0xA, 0x1) => Instruction::SkipNotKey(x),
+            (0xF, _, 0x0, 0x7) => Instruction::GetDelay(x),
+            (0xF, _, 0x0, 0xA) => Instruction::WaitKey(x),
+            (0xF, _, 0x1, 0x5) => Instruction::SetDelay(x),
+            (0xF, _, 0x1, 0x8) => Instruction::SetSound(x),
+            (0xF, _, 0x1, 0xE) => Instruction::AddIndex(x),
+            (0xF, _, 0x2, 0x9) => Instruction::Font(x),
+            (0xF, _, 0x3, 0x0) => Instruction::BigFont(x),
+            (0xF, _, 0x3, 0xA) => Instruction::Pitch(x),
+            (0xF, _, 0x3, 0x3) => Instruction::Bcd(x),
+            (0xF, _, 0x5, 0x5) => Instruction::Store(x),
+            (0xF, _, 0x6, 0x5) => Instruction::Load(x),
+            _ => Instruction::Unknown(opcode),
+        }
+    }
+
+    // Registers the instruction stores to, one bit per register with V0 in the lowest
+    #[inline]
+    pub fn writes(&self, quirks: &Quirks) -> u16 {
+        let register = |x: u8| 1_u16 << x;
+        let flag = register(0xF);
+        match *self {
+            Instruction::Set(x, _)
+            | Instruction::Add(x, _)
+            | Instruction::Move(x, _)
+            | Instruction::AddNibbles(x, _)
+            | Instruction::Random(x, _)
+            | Instruction::GetDelay(x)
+            | Instruction::WaitKey(x) => register(x),
+            Instruction::Or(x, _) | Instruction::And(x, _) | Instruction::Xor(x, _) => {
+                register(x) | if quirks.vf_reset { flag } else { 0 }
+            }
+            Instruction::AddRegisters(x, _)
+            | Instruction::Sub(x, _)
+            | Instruction::SubReverse(x, _)
+            | Instruction::ShiftRight(x, _)
+            | Instruction::ShiftLeft(x, _) => register(x) | flag,
+            Instruction::Draw(..) => flag,
+            Instruction::AddIndex(_) if quirks.fx1e_carry => flag,
+            Instruction::Load(x) => u16::MAX >> (0xF - x),
+            _ => 0,
+        }
+    }
+
+    // Octo syntax as understood by the assembler, with addresses written by target so a
+    // listing can use labels. None when the assembler has no way to write the instruction
+    pub fn to_octo(&self, target: &dyn Fn(u16) -> String) -> Option<String> {
+        let text = match *self {
+            Instruction::Clear => String::from("clear"),
+            Instruction::Return => String::from("return"),
+            Instruction::ScrollDown(n) => format!("scroll-down {}", n),
+            Instruction::ScrollRight => String::from("scroll-right"),
+            Instruction::ScrollLeft => String::from("scroll-left"),
+            Instruction::Exit => String::from("exit"),
+            Instruction::Lores => String::from("lores"),
+            Instruction::Hires => String::from("hires"),
+            Instruction::Jump(nnn) => format!("jump {}", target(nnn)),
+            Instruction::Call(nnn) => format!(":call {}", target(nnn)),
+            // Octo writes skips as the condition under which the next instruction runs
+            Instruction::SkipEqual(x, nn) => format!("if v{:x} != {:#04X} then", x, nn),
+            Instruction::SkipNotEqual(x, nn) => format!("if v{:x} == {:#04X} then", x, nn),
+            Instruction::SkipRegistersEqual(x, y) => format!("if v{:x} != v{:x} then", x, y),
+            Instruction::SkipRegistersNotEqual(x, y) => {
+                format!("if v{:x} == v{:x} then", x, y)
+            }
+            Instruction::Set(x, nn) => format!("v{:x} := {:#04X}", x, nn),
+            Instruction::Add(x, nn) => format!("v{:x} += {:#04X}", x, nn),
+            Instruction::Move(x, y) => format!("v{:x} := v{:x}", x, y),
+            Instruction::Or(x, y) => format!("v{:x} |= v{:x}", x, y),
+            Instruction::And(x, y) => format!("v{:x} &= v{:x}", x, y),
+            Instruction::Xor(x, y) => format!("v{:x} ^= v{:x}", x, y),
+            Instruction::AddRegisters(x, y) => format!("v{:x} += v{:x}", x, y),
+            Instruction::Sub(x, y) => format!("v{:x} -= v{:x}", x, y),
+            Instruction::ShiftRight(x, y) => format!("v{:x} >>= v{:x}", x, y),
+            Instruction::SubReverse(x, y) => format!("v{:x} =- v{:x}", x, y),
+            Instruction::ShiftLeft(x, y) => format!("v{:x} <<= v{:x}", x, y),
+            Instruction::SetIndex(nnn) => format!("i := {}", target(nnn)),
+            Instruction::JumpOffset(0, nnn) => format!("jump0 {}", target(nnn)),
+            Instruction::Random(x, nn) => format!("v{:x} := random {:#04X}", x, nn),
+            Instruction::Draw(x, y, n) => format!("sprite v{:x} v{:x} {}", x, y, n),
+            Instruction::SkipKey(x) => format!("if v{:x} -key then", x),
+            Instruction::SkipNotKey(x) => format!("if v{:x} key then", x),
+            Instruction::GetDelay(x) => format!("v{:x} := delay", x),
+            Instruction::WaitKey(x) => format!("v{:x} := key", x),
+            Instruction::SetDelay(x) => format!("delay := v{:x}", x),
+            Instruction::SetSound(x) => format!("buzzer := v{:x}", x),
+            Instruction::AddIndex(x) => format!("i += v{:x}", x),
+            Instruction::Font(x) => format!("i := hex v{:x}", x),
+            Instruction::BigFont(x) => format!("i := bighex v{:x}", x),
+            Instruction::Pitch(x) => format!("pitch := v{:x}", x),
+            Instruction::Bcd(x) => format!("bcd v{:x}", x),
+            Instruction::Store(x) => format!("save v{:x}", x),
+            Instruction::Load(x) => format!("load v{:x}", x),
+            Instruction::NextBackground
+            | Instruction::AddNibbles(..)
+            | Instruction::JumpOffset(..)
+            | Instruction::ColorZones(..)
+            | Instruction::Unknown(_) => return None,
+        };
+        Some(text)
+    }
+}
+
+// The classic mnemonics from Cowgod's reference, with the SUPER-CHIP and CHIP-8X additions
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Clear => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::ScrollDown(n) => write!(f, "SCD {}", n),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::Lores => write!(f, "LOW"),
+            Instruction::Hires => write!(f, "HIGH"),
+            Instruction::NextBackground => write!(f, "BGC"),
+            Instruction::Jump(nnn) => write!(f, "JP #{:03X}", nnn),
+            Instruction::Call(nnn) => write!(f, "CALL #{:03X}", nnn),
+            Instruction::SkipEqual(x, nn) => write!(f, "SE V{:X}, #{:02X}", x, nn),
+            Instruction::SkipNotEqual(x, nn) => write!(f, "SNE V{:X}, #{:02X}", x, nn),
+            Instruction::SkipRegistersEqual(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::SkipRegistersNotEqual(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::AddNibbles(x, y) => write!(f, "ADDN V{:X}, V{:X}", x, y),
+            Instruction::Set(x, nn) => write!(f, "LD V{:X}, #{:02X}", x, nn),
+            Instruction::Add(x, nn) => write!(f, "ADD V{:X}, #{:02X}", x, nn),
+            Instruction::Move(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddRegisters(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubReverse(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SetIndex(nnn) => write!(f, "LD I, #{:03X}", nnn),
+            Instruction::JumpOffset(x, nnn) => write!(f, "JP V{:X}, #{:03X}", x, nnn),
+            Instruction::ColorZones(x, y, n) => write!(f, "COL V{:X}, V{:X}, {}", x, y, n),
+            Instruction::Random(x, nn) => write!(f, "RND V{:X}, #{:02X}", x, nn),
+            Instruction::Draw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipKey(x) => write!(f, "SKP V{:X}", x),
+            Instruction::SkipNotKey(x) => write!(f, "SKNP V{:X}", x),
+            Instruction::GetDelay(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddIndex(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::Font(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::BigFont(x) => write!(f, "LD HF, V{:X}", x),
+            Instruction::Pitch(x) => write!(f, "PITCH V{:X}", x),
+            Instruction::Bcd(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::Store(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::Load(x) => write!(f, "LD V{:X}, [I]", x),
+            // 0NNN called a machine language routine on the original hardware
+            Instruction::Unknown(opcode) if opcode >> 12 == 0 => write!(f, "SYS #{:03X}", opcode),
+            Instruction::Unknown(opcode) => write!(f, "DW #{:04X}", opcode),
+        }
+    }
+}
+
+// What cycle() needs of an opcode, worked out once per address
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decoded {
+    pub opcode: u16,
+    pub instruction: Instruction,
+    // See Instruction::writes
+    pub writes: u16,
+}
+
+impl Decoded {
+    fn new(opcode: u16, quirks: &Quirks) -> Self {
+        let instruction = Instruction::decode(opcode, quirks);
+        Decoded {
+            opcode,
+            instruction,
+            writes: instruction.writes(quirks),
+        }
+    }
+}
+
+// Decoded instructions by address. An entry is only used while memory still holds the
+// opcode it was decoded from, so self-modifying code, pokes and loaded states need no
+// invalidation. Decoding depends on the quirks though, so changing them has to clear it
+#[derive(Clone, Debug)]
+pub struct DecodeCache {
+    entries: Vec<Decoded>,
+}
+
+// Opcode 0 decodes the same under any quirks, which makes it a safe placeholder
+impl Default for DecodeCache {
+    fn default() -> Self {
+        DecodeCache {
+            entries: vec![Decoded::new(0, &Quirks::default()); consts::RAM_BYTES],
+        }
+    }
+}
+
+impl DecodeCache {
+    // Checking the quirks on every fetch would cost about as much as the cache saves
+    #[inline]
+    pub fn get(&mut self, address: usize, opcode: u16, quirks: &Quirks) -> Decoded {
+        let entry = &mut self.entries[address];
+        if entry.opcode != opcode {
+            *entry = Decoded::new(opcode, quirks);
+        }
+        *entry
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(Decoded::new(0, &Quirks::default()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Instruction;
+    use crate::core::quirks::Quirks;
+
+    #[test]
+    fn test_decode() {
+        let quirks = Quirks::default();
+        assert_eq!(Instruction::decode(0x00E0, &quirks), Instruction::Clear);
+        assert_eq!(
+            Instruction::decode(0x00C4, &quirks),
+            Instruction::ScrollDown(4)
+        );
+        assert_eq!(
+            Instruction::decode(0x2ABC, &quirks),
+            Instruction::Call(0xABC)
+        );
+        assert_eq!(
+            Instruction::decode(0x8AB7, &quirks),
+            Instruction::SubReverse(0xA, 0xB)
+        );
+        assert_eq!(
+            Instruction::decode(0xD125, &quirks),
+            Instruction::Draw(1, 2, 5)
+        );
+        assert_eq!(Instruction::decode(0xF365, &quirks), Instruction::Load(3));
+        assert_eq!(Instruction::decode(0xF43A, &quirks), Instruction::Pitch(4));
+        assert_eq!(
+            Instruction::decode(0x8AB8, &quirks),
+            Instruction::Unknown(0x8AB8)
+        );
+        assert_eq!(
+            Instruction::decode(0x0123, &quirks),
+            Instruction::Unknown(0x0123)
+        );
+
+        // Quirks pick between encodings that share an opcode
+        assert_eq!(
+            Instruction::decode(0xB2F0, &quirks),
+            Instruction::JumpOffset(0, 0x2F0)
+        );
+        let jump_vx = Quirks {
+            jump_vx: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Instruction::decode(0xB2F0, &jump_vx),
+            Instruction::JumpOffset(2, 0x2F0)
+        );
+        let chip8x = Quirks {
+            chip8x: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Instruction::decode(0x02A0, &quirks),
+            Instruction::Unknown(0x02A0)
+        );
+        assert_eq!(
+            Instruction::decode(0x02A0, &chip8x),
+            Instruction::NextBackground
+        );
+        assert_eq!(
+            Instruction::decode(0xB2F0, &chip8x),
+            Instruction::ColorZones(2, 0xF, 0)
+        );
+    }
+
+    #[test]
+    fn test_writes() {
+        let quirks = Quirks::default();
+        assert_eq!(Instruction::Set(3, 1).writes(&quirks), 0b1000);
+        assert_eq!(Instruction::AddRegisters(1, 2).writes(&quirks), 0x8002);
+        assert_eq!(Instruction::Or(1, 2).writes(&quirks), 0b10);
+        assert_eq!(Instruction::Load(2).writes(&quirks), 0b111);
+        assert_eq!(Instruction::Load(0xF).writes(&quirks), 0xFFFF);
+        assert_eq!(Instruction::Store(2).writes(&quirks), 0);
+        let vf_reset = Quirks {
+            vf_reset: true,
+            ..Default::default()
+        };
+        assert_eq!(Instruction::Or(1, 2).writes(&vf_reset), 0x8002);
+    }
+
+    #[test]
+    fn test_mnemonics() {
+        let quirks = Quirks::default();
+        let text = |opcode| Instruction::decode(opcode, &quirks).to_string();
+        assert_eq!(text(0x630A), "LD V3, #0A");
+        assert_eq!(text(0xD125), "DRW V1, V2, 5");
+        assert_eq!(text(0xA2F0), "LD I, #2F0");
+        assert_eq!(text(0x8AB6), "SHR VA, VB");
+        assert_eq!(text(0xF355), "LD [I], V3");
+        assert_eq!(text(0xB2F0), "JP V0, #2F0");
+        assert_eq!(text(0x0123), "SYS #123");
+        assert_eq!(text(0xFFFF), "DW #FFFF");
+
+        let octo = |opcode| {
+            Instruction::decode(opcode, &quirks).to_octo(&|address| format!("{:#05X}", address))
+        };
+        assert_eq!(octo(0x630A), Some(String::from("v3 := 0x0A")));
+        assert_eq!(octo(0x3A01), Some(String::from("if va != 0x01 then")));
+        assert_eq!(octo(0x2ABC), Some(String::from(":call 0xABC")));
+        assert_eq!(octo(0xFFFF), None);
+    }
+}
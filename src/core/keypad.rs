@@ -0,0 +1,188 @@
+use crate::core::ram::KeyboardBuffer;
+
+// Where the processor reads the hex keypad from. KeyboardBuffer is the plain implementation
+// that frontends fill from SDL, the terminal or the network, other sources such as recorded
+// input can drive the processor directly
+pub trait KeyProvider {
+    // Held keys as a bitmask with key 0 in the lowest bit
+    fn keys(&self) -> u16;
+
+    fn is_pressed(&self, key: usize) -> bool {
+        self.keys() & (1 << key) != 0
+    }
+
+    // Called after every completed frame, sources that play input back over time advance here
+    fn frame_completed(&mut self) {}
+}
+
+impl KeyProvider for KeyboardBuffer {
+    fn keys(&self) -> u16 {
+        self.keys
+    }
+}
+
+// Plays back one keypad state per frame, then holds nothing once it runs out
+#[derive(Debug, Default)]
+pub struct Playback {
+    pub frames: Vec<u16>,
+    pub position: usize,
+}
+
+impl Playback {
+    // One hex bitmask per line, blank lines and lines starting with # are skipped
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut frames = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let keys = u16::from_str_radix(line.trim_start_matches("0x"), 16)
+                .map_err(|_| format!("Invalid key mask on line {}: {}", number + 1, line))?;
+            frames.push(keys);
+        }
+        Ok(Playback {
+            frames,
+            position: 0,
+        })
+    }
+
+    pub fn finished(&self) -> bool {
+        self.position >= self.frames.len()
+    }
+}
+
+impl KeyProvider for Playback {
+    fn keys(&self) -> u16 {
+        self.frames.get(self.position).copied().unwrap_or(0)
+    }
+
+    fn frame_completed(&mut self) {
+        if !self.finished() {
+            self.position += 1;
+        }
+    }
+}
+
+// Hand written key presses and releases at given frames, e.g. "frame 120: press 5", merged
+// with whatever else holds keys. # starts a comment
+#[derive(Debug, Default)]
+pub struct KeyScript {
+    // (frame, key, pressed) in frame order
+    pub events: Vec<(u64, usize, bool)>,
+    next: usize,
+    held: u16,
+}
+
+impl KeyScript {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut events = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || format!("Invalid key script line {}: {}", number + 1, line);
+            let (frame, command) = line
+                .strip_prefix("frame")
+                .and_then(|rest| rest.split_once(':'))
+                .ok_or_else(invalid)?;
+            let frame: u64 = frame.trim().parse().map_err(|_| invalid())?;
+            let pressed = match command.split_whitespace().collect::<Vec<_>>()[..] {
+                ["press", key] => (key, true),
+                ["release", key] => (key, false),
+                _ => return Err(invalid()),
+            };
+            let key = match usize::from_str_radix(pressed.0, 16) {
+                Ok(key) if key < 16 => key,
+                _ => return Err(invalid()),
+            };
+            events.push((frame, key, pressed.1));
+        }
+        // Stable, so commands for the same frame keep their order
+        events.sort_by_key(|&(frame, _, _)| frame);
+        Ok(KeyScript {
+            events,
+            ..Default::default()
+        })
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(KeyScript::parse(&text).map_err(|e| format!("{}: {}", path, e))?)
+    }
+
+    // Keys the script holds during the given frame. Going back in time, e.g. after loading a
+    // state, replays the script from the start
+    pub fn keys_at(&mut self, frame: u64) -> u16 {
+        if self.next > 0 && self.events[self.next - 1].0 > frame {
+            self.next = 0;
+            self.held = 0;
+        }
+        while let Some(&(at, key, pressed)) = self.events.get(self.next) {
+            if at > frame {
+                break;
+            }
+            match pressed {
+                true => self.held |= 1 << key,
+                false => self.held &= !(1 << key),
+            }
+            self.next += 1;
+        }
+        self.held
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyProvider, KeyScript, Playback};
+    use crate::core::processor::Processor;
+    use crate::core::ram::Ram;
+
+    #[test]
+    fn test_parse() {
+        let playback = Playback::parse("# title screen\n0000\n\n0x0010\n").unwrap();
+        assert_eq!(playback.frames, [0x0000, 0x0010]);
+        assert!(!playback.is_pressed(0));
+        assert_eq!(playback.keys(), 0);
+        assert!(Playback::parse("zz").is_err());
+    }
+
+    #[test]
+    fn test_key_script() {
+        let mut script = KeyScript::parse(
+            "# start the game\nframe 130: release 5\nframe 120: press 5\nframe 125: press a\n",
+        )
+        .unwrap();
+        assert_eq!(script.keys_at(0), 0);
+        assert_eq!(script.keys_at(120), 1 << 5);
+        assert_eq!(script.keys_at(127), 1 << 5 | 1 << 0xA);
+        assert_eq!(script.keys_at(200), 1 << 0xA);
+        assert_eq!(script.keys_at(121), 1 << 5);
+        for invalid in [
+            "frame x: press 5",
+            "frame 1 press 5",
+            "frame 1: hold 5",
+            "frame 1: press g",
+        ] {
+            assert!(KeyScript::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_playback_drives_processor() -> Result<(), &'static str> {
+        let playback = Playback::parse("0\n0\n0x0020\n").unwrap();
+        let mut chip8: Processor<Ram, Playback> =
+            Processor::builder().build_with(Default::default(), playback);
+        // v3 := key
+        chip8.ram.buffer[0x200..0x202].copy_from_slice(&[0xF3, 0x0A]);
+        for _ in 0..2 {
+            chip8.run_frame(10)?;
+            assert_eq!(chip8.pc, 0x200);
+        }
+        chip8.run_frame(1)?;
+        assert_eq!(chip8.registers[3], 5);
+        assert!(chip8.keyboard_buffer.finished());
+        Ok(())
+    }
+}
@@ -1,3 +1,19 @@
+pub mod bus;
+pub mod clock;
+pub mod coverage;
+#[cfg(feature = "bundled-roms")]
+pub mod demos;
+pub mod events;
+pub mod histogram;
+pub mod instruction;
+pub mod keypad;
+pub mod observers;
+pub mod platform;
 pub mod processor;
+pub mod quirks;
 pub mod ram;
 pub mod rom;
+pub mod screen;
+pub mod snapshot;
+pub mod timers;
+pub mod trace;
@@ -0,0 +1,91 @@
+use std::fmt;
+
+type Callback = Box<dyn FnMut(usize, u8) + Send>;
+
+// Callbacks the processor runs as it writes, unlike events which are queued for whoever reads
+// them later. With none installed a write costs one emptiness check
+#[derive(Default)]
+pub struct Observers {
+    register_write: Vec<Callback>,
+    ram_write: Vec<Callback>,
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Observers")
+            .field("register_write", &self.register_write.len())
+            .field("ram_write", &self.ram_write.len())
+            .finish()
+    }
+}
+
+impl Observers {
+    // Called with the register number and its new value
+    pub fn on_register_write(&mut self, observer: impl FnMut(usize, u8) + Send + 'static) {
+        self.register_write.push(Box::new(observer));
+    }
+
+    // Called with the address and new value of every byte the program stores, writes from
+    // outside the program such as loading a ROM or a poke are not included
+    pub fn on_ram_write(&mut self, observer: impl FnMut(usize, u8) + Send + 'static) {
+        self.ram_write.push(Box::new(observer));
+    }
+
+    pub fn clear(&mut self) {
+        self.register_write.clear();
+        self.ram_write.clear();
+    }
+
+    // Registers given as a bitmask with V0 in the lowest bit
+    #[inline]
+    pub(crate) fn registers_written(&mut self, written: u16, registers: &[u8]) {
+        if self.register_write.is_empty() || written == 0 {
+            return;
+        }
+        for (register, &value) in registers.iter().enumerate() {
+            if written & (1 << register) != 0 {
+                for observer in self.register_write.iter_mut() {
+                    observer(register, value);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn ram_written(&mut self, address: usize, bytes: &[u8]) {
+        for observer in self.ram_write.iter_mut() {
+            for (offset, &value) in bytes.iter().enumerate() {
+                observer(address + offset, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::processor::Processor;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_observers() -> Result<(), &'static str> {
+        let mut chip8 = Processor::builder().build();
+        // v0 := 0x2A, v1 := 0x07, i := 0x300, save v1
+        chip8.ram.buffer[0x200..0x208]
+            .copy_from_slice(&[0x60, 0x2A, 0x61, 0x07, 0xA3, 0x00, 0xF1, 0x55]);
+        let registers = Arc::new(Mutex::new(Vec::new()));
+        let ram = Arc::new(Mutex::new(Vec::new()));
+        let seen = registers.clone();
+        chip8
+            .observers
+            .on_register_write(move |register, value| seen.lock().unwrap().push((register, value)));
+        let seen = ram.clone();
+        chip8
+            .observers
+            .on_ram_write(move |address, value| seen.lock().unwrap().push((address, value)));
+        for _ in 0..4 {
+            chip8.cycle()?;
+        }
+        assert_eq!(*registers.lock().unwrap(), [(0, 0x2A), (1, 0x07)]);
+        assert_eq!(*ram.lock().unwrap(), [(0x300, 0x2A), (0x301, 0x07)]);
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+use crate::consts;
+use crate::core::quirks::Quirks;
+use crate::core::ram::Resolution;
+
+// Named presets over the quirks so users can pick an interpreter instead of individual flags
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Platform {
+    #[default]
+    Modern,
+    Chip8,
+    Chip48,
+    Chip8x,
+    Eti660,
+    SchipLegacy,
+    SchipModern,
+    Xochip,
+}
+
+// Behaviour of the original COSMAC VIP interpreter, which the other RCA era platforms share
+fn cosmac_vip() -> Quirks {
+    Quirks {
+        vf_reset: true,
+        memory_increment: true,
+        shift_vy: true,
+        display_wait: true,
+        ..Default::default()
+    }
+}
+
+impl Platform {
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::Modern => Default::default(),
+            Platform::Chip8 => cosmac_vip(),
+            // HP-48 interpreter: BXNN jumps, shifts in place, FX55/FX65 leave I off by one and
+            // sprites are clipped at the screen edges
+            Platform::Chip48 => Quirks {
+                jump_vx: true,
+                memory_increment_x: true,
+                ..Default::default()
+            },
+            // VIP with the VP-590 color card, otherwise behaving like the original interpreter
+            Platform::Chip8x => Quirks {
+                chip8x: true,
+                ..cosmac_vip()
+            },
+            Platform::Eti660 => cosmac_vip(),
+            // SUPER-CHIP 1.1 on the HP-48, including its lores scrolling and collision counting
+            Platform::SchipLegacy => Quirks {
+                jump_vx: true,
+                half_scroll: true,
+                collision_rows: true,
+                ..Default::default()
+            },
+            // SUPER-CHIP as most modern SCHIP games expect it
+            Platform::SchipModern => Quirks {
+                jump_vx: true,
+                ..Default::default()
+            },
+            // Octo's XO-CHIP, sprites wrap and the register transfers advance I
+            Platform::Xochip => Quirks {
+                shift_vy: true,
+                memory_increment: true,
+                wrap: true,
+                ..Default::default()
+            },
+        }
+    }
+
+    // Default speed, roughly that of the original hardware
+    pub fn cycles_per_frame(&self) -> usize {
+        match self {
+            Platform::Modern => consts::CYCLES_PER_FRAME,
+            Platform::Chip8 | Platform::Chip8x | Platform::Eti660 => 15,
+            Platform::Chip48 | Platform::SchipLegacy | Platform::SchipModern => 30,
+            Platform::Xochip => 100,
+        }
+    }
+
+    pub fn load_address(&self) -> usize {
+        match self {
+            Platform::Chip8x => consts::CHIP8X_PROG_OFFSET,
+            Platform::Eti660 => consts::ETI660_PROG_OFFSET,
+            _ => consts::PROG_OFFSET,
+        }
+    }
+
+    // Display mode the platform starts in, the SCHIP platforms switch to hires with 00FF
+    pub fn resolution(&self) -> Resolution {
+        match self {
+            Platform::Eti660 => Resolution::Eti660,
+            _ => Resolution::Low,
+        }
+    }
+
+    // Small font, the RCA era platforms keep the VIP's digits
+    pub fn fonts(&self) -> [u8; consts::FONT_SET_SIZE] {
+        match self {
+            Platform::Chip8 | Platform::Chip8x | Platform::Eti660 => consts::VIP_FONT_SET,
+            _ => consts::FONT_SET,
+        }
+    }
+
+    // As the platform is usually written, for display rather than the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Modern => "Modern CHIP-8",
+            Platform::Chip8 => "COSMAC VIP",
+            Platform::Chip48 => "CHIP-48",
+            Platform::Chip8x => "CHIP-8X",
+            Platform::Eti660 => "ETI-660",
+            Platform::SchipLegacy => "SCHIP 1.1",
+            Platform::SchipModern => "Modern SCHIP",
+            Platform::Xochip => "XO-CHIP",
+        }
+    }
+
+    pub fn timer_freq(&self) -> u64 {
+        match self {
+            Platform::Eti660 => consts::ETI660_TIMER_FREQ,
+            _ => consts::TIMER_FREQ,
+        }
+    }
+}
+
+impl std::str::FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "modern" => Ok(Platform::Modern),
+            "chip8" => Ok(Platform::Chip8),
+            "chip48" => Ok(Platform::Chip48),
+            "chip8x" => Ok(Platform::Chip8x),
+            "eti660" => Ok(Platform::Eti660),
+            "schip-legacy" => Ok(Platform::SchipLegacy),
+            "schip-modern" => Ok(Platform::SchipModern),
+            "xochip" => Ok(Platform::Xochip),
+            _ => Err(format!("Unknown platform {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Platform;
+    use crate::consts;
+    use crate::core::ram::Resolution;
+
+    #[test]
+    fn test_chip8() {
+        let platform: Platform = "chip8".parse().unwrap();
+        let quirks = platform.quirks();
+        assert!(quirks.vf_reset && quirks.memory_increment && quirks.shift_vy);
+        assert!(quirks.display_wait && !quirks.jump_vx && !quirks.wrap);
+        assert_eq!(platform.resolution(), Resolution::Low);
+        assert_eq!(platform.fonts(), consts::VIP_FONT_SET);
+        assert_eq!(platform.cycles_per_frame(), 15);
+        assert_eq!(platform.load_address(), consts::PROG_OFFSET);
+    }
+
+    #[test]
+    fn test_schip_legacy() {
+        let platform: Platform = "schip-legacy".parse().unwrap();
+        let quirks = platform.quirks();
+        assert!(quirks.jump_vx && quirks.half_scroll && quirks.collision_rows);
+        assert!(!quirks.vf_reset && !quirks.memory_increment && !quirks.shift_vy);
+        assert_eq!(platform.resolution(), Resolution::Low);
+        assert_eq!(platform.fonts(), consts::FONT_SET);
+        assert_eq!(platform.cycles_per_frame(), 30);
+    }
+
+    #[test]
+    fn test_schip_modern() {
+        let platform: Platform = "schip-modern".parse().unwrap();
+        let quirks = platform.quirks();
+        assert!(quirks.jump_vx && !quirks.half_scroll && !quirks.collision_rows);
+        assert!(!quirks.vf_reset && !quirks.memory_increment && !quirks.shift_vy);
+        assert_eq!(platform.resolution(), Resolution::Low);
+        assert_eq!(platform.fonts(), consts::FONT_SET);
+        assert_eq!(platform.cycles_per_frame(), 30);
+    }
+
+    #[test]
+    fn test_xochip() {
+        let platform: Platform = "xochip".parse().unwrap();
+        let quirks = platform.quirks();
+        assert!(quirks.shift_vy && quirks.memory_increment && quirks.wrap);
+        assert!(!quirks.vf_reset && !quirks.jump_vx && !quirks.display_wait);
+        assert_eq!(platform.resolution(), Resolution::Low);
+        assert_eq!(platform.fonts(), consts::FONT_SET);
+        assert_eq!(platform.cycles_per_frame(), 100);
+    }
+
+    #[test]
+    fn test_other_platforms() {
+        assert_eq!("modern".parse(), Ok(Platform::Modern));
+        assert_eq!(Platform::Modern.fonts(), consts::FONT_SET);
+        assert_eq!(Platform::Chip8x.fonts(), consts::VIP_FONT_SET);
+        assert_eq!(Platform::Eti660.resolution(), Resolution::Eti660);
+        assert_eq!(Platform::Eti660.load_address(), consts::ETI660_PROG_OFFSET);
+        assert!(Platform::Chip48.quirks().memory_increment_x);
+        assert_eq!(
+            "vip".parse::<Platform>(),
+            Err(String::from("Unknown platform vip"))
+        );
+    }
+}
@@ -1,336 +1,932 @@
 use crate::consts;
+use crate::core::bus::Bus;
+use crate::core::coverage::Coverage;
+use crate::core::events::{Event, EventBus, SpriteDraw};
+use crate::core::histogram::Histogram;
+use crate::core::instruction::{DecodeCache, Decoded, Instruction};
+use crate::core::keypad::KeyProvider;
+use crate::core::observers::Observers;
+use crate::core::quirks::Quirks;
+use crate::core::trace::{Trace, TraceEntry};
 use crate::core::{ram, rom};
 use crate::utils;
-use rand::rngs::ThreadRng;
-use rand::Rng;
-use std::borrow::BorrowMut;
-use std::cell::RefCell;
-use std::rc::Rc;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-pub enum CycleStatus {
+// Bumped when to_json_state changes in a way from_json_state has to tell apart
+const JSON_STATE_VERSION: u64 = 1;
+
+// What an instruction handler did, summed up in Step by cycle()
+enum CycleStatus {
     RedrawScreen,
     Continue,
     Waiting,
+    Halted,
+}
+
+// What one instruction did, for tracers, debuggers and scripts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step {
+    pub instruction: Instruction,
+    pub opcode: u16,
+    pub pc_before: u16,
+    pub pc_after: u16,
+    // One bit per register with V0 in the lowest, nothing while waiting
+    pub registers_written: u16,
+    // The display changed
+    pub drew: bool,
+    // Blocked on a key or the display wait quirk, the instruction runs again next time
+    pub waited: bool,
+    pub halted: bool,
+}
+
+impl Step {
+    // False for anything past VF
+    pub fn wrote(&self, register: usize) -> bool {
+        register < consts::REG_COUNT && self.registers_written & (1 << register) != 0
+    }
+}
+
+// Random source for CXNN, seeded explicitly when runs have to be reproducible
+#[derive(Debug)]
+struct Random(StdRng);
+
+impl Default for Random {
+    fn default() -> Self {
+        Random(StdRng::from_entropy())
+    }
+}
+
+// Small font at the bottom of memory, kept so load_rom can put back the one built with
+#[derive(Clone, Copy, Debug)]
+struct Fonts([u8; consts::FONT_SET_SIZE]);
+
+impl Default for Fonts {
+    fn default() -> Self {
+        Fonts(consts::FONT_SET)
+    }
 }
 
+// XO-CHIP audio playback rate as set by FX3A, 64 plays at DEFAULT_PLAYBACK_RATE and every
+// 48 steps either way doubles or halves it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pitch(pub u8);
+
+impl Default for Pitch {
+    fn default() -> Self {
+        Pitch(consts::DEFAULT_PITCH)
+    }
+}
+
+impl Pitch {
+    // In Hz, 4000*2^((pitch-64)/48)
+    pub fn playback_rate(self) -> f32 {
+        let steps = self.0 as f32 - consts::DEFAULT_PITCH as f32;
+        consts::DEFAULT_PLAYBACK_RATE * (steps / 48.0).exp2()
+    }
+}
+
+// Generic over the memory bus so tools can intercept accesses, see Bus, and over where the
+// keypad is read from, see KeyProvider
 #[derive(Default, Debug)]
-pub struct Processor {
+pub struct Processor<B = ram::Ram, K = ram::KeyboardBuffer> {
     pub stack: [u16; consts::STACK_SIZE],
     pub registers: [u8; consts::REG_COUNT],
     pub idx_register: u16,
     pub pc: u16,
     pub stack_pointer: u8,
     pub delay_timer: u8,
-    pub sound_timer: Rc<RefCell<u8>>,
-    pub ram: ram::Ram,
-    pub display_buffer: Rc<RefCell<ram::DisplayBuffer>>,
-    pub keyboard_buffer: Rc<RefCell<ram::KeyboardBuffer>>,
-    rng: ThreadRng,
+    pub sound_timer: u8,
+    pub pitch: Pitch,
+    pub ram: B,
+    pub display_buffer: ram::DisplayBuffer,
+    pub keyboard_buffer: K,
+    // Keys EX9E, EXA1 and FX0A looked at since the current frame started, one bit per key,
+    // for frontends that show what the program is listening for
+    pub polled_keys: u16,
+    // Configured through ProcessorBuilder, read through the accessors below
+    quirks: Quirks,
+    // Where programs are loaded and execution starts, set by init_ram
+    load_address: usize,
+    rom_size: usize,
+    fonts: Fonts,
+    pub events: EventBus,
+    pub observers: Observers,
+    // Totals since power-on, reported by the stats endpoint
+    pub instructions: u64,
+    pub frames: u64,
+    // What ran and where and what was read as data, exported on request
+    pub histogram: Histogram,
+    pub coverage: Coverage,
+    // The last instructions executed, dumped in crash reports
+    pub trace: Trace,
+    decoded: DecodeCache,
+    // Set at the start of every frame and cleared by DXYN under the display wait quirk
+    vblank: bool,
+    // Set while FX0A blocks, so KeyWaited is only published once per wait
+    key_wait: bool,
+    // Set once a TimerDriver owns the timers, run_frame leaves them alone from then on
+    external_timers: bool,
+    rng: Random,
+}
+
+// Configures a processor before it starts, everything not set keeps the defaults of a
+// COSMAC VIP style machine with the program at PROG_OFFSET
+#[derive(Default)]
+pub struct ProcessorBuilder {
+    quirks: Quirks,
+    start_pc: Option<usize>,
+    seed: Option<u64>,
+    fonts: Fonts,
+    resolution: ram::Resolution,
+}
+
+impl ProcessorBuilder {
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+    // Where programs are loaded and execution starts
+    pub fn start_pc(mut self, address: usize) -> Self {
+        self.start_pc = Some(address);
+        self
+    }
+    // Seeds CXNN so runs are reproducible, otherwise it is seeded from the OS
+    pub fn rng(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+    pub fn fonts(mut self, fonts: [u8; consts::FONT_SET_SIZE]) -> Self {
+        self.fonts = Fonts(fonts);
+        self
+    }
+    // Starting display geometry, platforms like the ETI-660 have a taller screen
+    pub fn resolution(mut self, resolution: ram::Resolution) -> Self {
+        self.resolution = resolution;
+        self
+    }
+    // The fonts are in memory right away, the program follows with load_rom
+    pub fn build(self) -> Processor {
+        self.build_with_bus(Default::default())
+    }
+    pub fn build_with_bus<B: Bus + Default>(self, bus: B) -> Processor<B> {
+        self.build_with(bus, Default::default())
+    }
+    pub fn build_with<B: Bus + Default, K: KeyProvider + Default>(
+        self,
+        bus: B,
+        keys: K,
+    ) -> Processor<B, K> {
+        let mut chip8 = Processor {
+            ram: bus,
+            keyboard_buffer: keys,
+            display_buffer: ram::DisplayBuffer::new(self.resolution),
+            ..Default::default()
+        };
+        let load_address = self.start_pc.unwrap_or(consts::PROG_OFFSET);
+        chip8.pc = load_address as u16;
+        chip8.load_address = load_address;
+        chip8.quirks = self.quirks;
+        chip8.fonts = self.fonts;
+        chip8.load_fonts();
+        if let Some(seed) = self.seed {
+            chip8.seed_rng(seed);
+        }
+        chip8
+    }
 }
 
 impl Processor {
+    pub fn builder() -> ProcessorBuilder {
+        Default::default()
+    }
     pub fn new(
         ram_: ram::Ram,
         display_ram_: ram::DisplayBuffer,
         keyboard_buffer_: ram::KeyboardBuffer,
     ) -> Self {
         Processor {
-            pc: 0x200,
+            pc: consts::PROG_OFFSET as u16,
+            load_address: consts::PROG_OFFSET,
             ram: ram_,
-            display_buffer: Rc::new(RefCell::new(display_ram_)),
-            keyboard_buffer: Rc::new(RefCell::new(keyboard_buffer_)),
-            rng: rand::thread_rng(),
+            display_buffer: display_ram_,
+            keyboard_buffer: keyboard_buffer_,
             ..Default::default()
         }
     }
-    pub fn init_ram(&mut self, rom: &rom::Rom, fonts: &[u8]) -> Result<(), &'static str> {
-        self.ram.buffer[0..consts::FONT_SET_SIZE].clone_from_slice(fonts);
-        self.ram.buffer[consts::PROG_OFFSET..].clone_from_slice(&rom.buffer);
+    // Builds a processor from to_json_state output. The stack pointer follows from the length
+    // of the stack, the display starts cleared and quirks are left to the caller
+    pub fn from_json_state(json: &str) -> Result<Processor, String> {
+        let state: serde_json::Value =
+            serde_json::from_str(json).map_err(|e| format!("Invalid state: {}", e))?;
+        let number = |key: &str, max: u64| match state[key].as_u64() {
+            Some(value) if value <= max => Ok(value),
+            Some(_) => Err(format!("State field {} is out of range", key)),
+            None => Err(format!("State field {} is missing", key)),
+        };
+        let list = |key: &str, max_len: usize, max: u64| {
+            let values = state[key]
+                .as_array()
+                .ok_or(format!("State field {} is missing", key))?;
+            if values.len() > max_len {
+                return Err(format!("State field {} has too many entries", key));
+            }
+            values
+                .iter()
+                .map(|v| v.as_u64().filter(|&v| v <= max))
+                .collect::<Option<Vec<u64>>>()
+                .ok_or(format!("State field {} has an invalid entry", key))
+        };
+        match state["version"].as_u64() {
+            Some(JSON_STATE_VERSION) => {}
+            Some(version) => return Err(format!("Unsupported state version {}", version)),
+            None => return Err(String::from("State field version is missing")),
+        }
+        let ram = BASE64
+            .decode(state["ram"].as_str().ok_or("State field ram is missing")?)
+            .map_err(|e| format!("State field ram is not base64: {}", e))?;
+        let mut chip8 = Processor::new(Default::default(), Default::default(), Default::default());
+        chip8.ram.buffer = ram
+            .try_into()
+            .map_err(|_| format!("State field ram must be {} bytes", consts::RAM_BYTES))?;
+        let address = consts::RAM_BYTES as u64 - 1;
+        chip8.pc = number("pc", address)? as u16;
+        chip8.idx_register = number("i", u16::MAX as u64)? as u16;
+        chip8.delay_timer = number("delay", u8::MAX as u64)? as u8;
+        chip8.sound_timer = number("sound", u8::MAX as u64)? as u8;
+        if !state["load_address"].is_null() {
+            chip8.load_address = number("load_address", address)? as usize;
+        }
+        if !state["pitch"].is_null() {
+            chip8.pitch = Pitch(number("pitch", u8::MAX as u64)? as u8);
+        }
+        let registers = list("registers", consts::REG_COUNT, u8::MAX as u64)?;
+        if registers.len() != consts::REG_COUNT {
+            return Err(format!(
+                "State field registers must have {} entries",
+                consts::REG_COUNT
+            ));
+        }
+        for (register, value) in chip8.registers.iter_mut().zip(registers) {
+            *register = value as u8;
+        }
+        let stack = list("stack", consts::STACK_SIZE, address)?;
+        chip8.stack_pointer = stack.len() as u8;
+        for (entry, value) in chip8.stack.iter_mut().zip(stack) {
+            *entry = value as u16;
+        }
+        Ok(chip8)
+    }
+}
+
+impl<B: Bus, K: KeyProvider> Processor<B, K> {
+    // Loads the fonts and the program, which starts at load_address on the emulated platform
+    pub fn init_ram(
+        &mut self,
+        rom: &rom::Rom,
+        fonts: &[u8],
+        load_address: usize,
+    ) -> Result<(), &'static str> {
+        if load_address > consts::RAM_BYTES || rom.size > consts::RAM_BYTES - load_address {
+            return Err("ROM does not fit in memory at the platform's load address");
+        }
+        self.fonts.0.copy_from_slice(fonts);
+        self.load_fonts();
+        self.ram.write_slice(load_address, &rom.buffer[..rom.size]);
+        self.pc = load_address as u16;
+        self.load_address = load_address;
+        self.rom_size = rom.size;
+        self.events.publish(Event::RomLoaded);
         Ok(())
     }
-    pub fn cycle(&mut self) -> Option<CycleStatus> {
-        let instr_nibbles = utils::nibble_split(
-            &(self.ram.buffer
-                [(self.pc) as usize..((self.pc + (consts::OP_CODE_BYTES as u16)) as usize)]),
-        );
-        self.pc += consts::OP_CODE_BYTES as u16;
-        let (opcode, x, y, n) = instr_nibbles;
-        let nn = (y << 4) | n;
-        let nnn = ((x as u16) << 8) | ((y as u16) << 4) | (n as u16);
-        let keyboard = self.keyboard_buffer.borrow().buffer;
-
-        match (opcode, x, y, n) {
-            // Halt till keyboard interrupt
-            (0xF, _, 0, 0xA) => {
-                if keyboard.iter().all(|x| *x == 0) {
-                    self.pc -= consts::OP_CODE_BYTES as u16;
-                    return Some(CycleStatus::Waiting);
-                } else {
-                    for i in keyboard {
-                        if i == 1 {
-                            self.registers[x as usize] = i as u8;
-                            break;
-                        }
-                    }
+    fn load_fonts(&mut self) {
+        self.ram.write_slice(0, &self.fonts.0);
+        self.ram
+            .write_slice(consts::BIG_FONT_OFFSET, &consts::BIG_FONT_SET);
+    }
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+    // Quirks can change while a program runs, e.g. from the gui's settings
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+        self.decoded.clear();
+    }
+    pub fn load_address(&self) -> usize {
+        self.load_address
+    }
+    // Size of the program loaded last, zero before one is
+    pub fn rom_size(&self) -> usize {
+        self.rom_size
+    }
+    // Makes CXNN produce the same sequence on every run, e.g. for netplay lockstep
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Random(StdRng::seed_from_u64(seed));
+    }
+    // Restores the power-on state while keeping memory and the buffers shared with the drivers
+    pub fn reset(&mut self) {
+        self.stack = [0; consts::STACK_SIZE];
+        self.registers = [0; consts::REG_COUNT];
+        self.idx_register = 0;
+        self.pc = self.load_address as u16;
+        self.stack_pointer = 0;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.pitch = Default::default();
+        self.polled_keys = 0;
+        self.vblank = false;
+        self.key_wait = false;
+        self.instructions = 0;
+        self.frames = 0;
+        self.histogram.clear();
+        self.coverage.clear();
+        self.trace.clear();
+        // Hires mode is only ever entered by programs, other geometries come from the platform
+        let resolution = match self.display_buffer.resolution {
+            ram::Resolution::High => ram::Resolution::Low,
+            resolution => resolution,
+        };
+        self.display_buffer.set_resolution(resolution);
+    }
+    // Switches to another program in place, at the load address and with the fonts of the
+    // current one
+    pub fn load_rom(&mut self, rom: &rom::Rom) -> Result<(), &'static str> {
+        self.reset();
+        self.ram.write_slice(0, &[0; consts::RAM_BYTES]);
+        let fonts = self.fonts.0;
+        self.init_ram(rom, &fonts, self.load_address)
+    }
+    // Registers, stack, timers and memory as pretty-printed JSON with RAM in base64, for
+    // looking at a state by hand or keeping it as a test fixture
+    pub fn to_json_state(&self) -> String {
+        let state = serde_json::json!({
+            "version": JSON_STATE_VERSION,
+            "pc": self.pc,
+            "i": self.idx_register,
+            "registers": self.registers,
+            "stack": self.stack[..self.stack_pointer as usize],
+            "delay": self.delay_timer,
+            "sound": self.sound_timer,
+            "pitch": self.pitch.0,
+            "load_address": self.load_address,
+            "ram": BASE64.encode(self.ram.read_slice(0, consts::RAM_BYTES)),
+        });
+        serde_json::to_string_pretty(&state).unwrap_or_default()
+    }
+    pub fn set_external_timers(&mut self, external: bool) {
+        self.external_timers = external;
+    }
+    // Counts both timers down, once per frame unless a TimerDriver does it on its own schedule
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+    }
+    // Executes one frame worth of instructions and then ticks the timers if nothing else does,
+    // publishing FrameCompleted, or Halted if the program exited
+    pub fn run_frame(&mut self, cycles: usize) -> Result<(), &'static str> {
+        self.run_frame_until(cycles, |_| false).map(|_| ())
+    }
+    // Like run_frame, but checks stop after every instruction and returns true as soon as it
+    // holds, with the rest of the frame and the timer tick left undone. Debuggers use this for
+    // breakpoints, stopping before the instruction at the new PC runs
+    pub fn run_frame_until(
+        &mut self,
+        cycles: usize,
+        mut stop: impl FnMut(&Processor<B, K>) -> bool,
+    ) -> Result<bool, &'static str> {
+        let mut redraw = false;
+        let sound_was_off = self.sound_timer == 0;
+        self.vblank = true;
+        self.polled_keys = 0;
+        for _ in 0..cycles {
+            match self.step()?.1 {
+                CycleStatus::Halted => {
+                    self.events.publish(Event::Halted);
+                    return Ok(false);
                 }
-                return Some(CycleStatus::Continue);
+                CycleStatus::RedrawScreen => redraw = true,
+                // Nothing changes until the next frame once the processor is waiting
+                CycleStatus::Waiting => break,
+                CycleStatus::Continue => {}
             }
-            (_, _, _, _) => {
-                if *self.sound_timer.as_ref().borrow() > 0 {
-                    *self.sound_timer.as_ref().borrow_mut() -= 1
-                }
-                if self.delay_timer > 0 {
-                    self.delay_timer -= 1
-                }
+            if stop(self) {
+                return Ok(true);
             }
         }
+        if sound_was_off && self.sound_timer > 0 {
+            self.events.publish(Event::SoundStarted);
+        }
+        if !self.external_timers {
+            self.tick_timers();
+        }
+        self.keyboard_buffer.frame_completed();
+        self.frames += 1;
+        self.events.publish(Event::FrameCompleted { redraw });
+        Ok(false)
+    }
+    // Where I ends up after FX55/FX65 depends on the interpreter being emulated
+    fn increment_index_after_transfer(&mut self, x: u8) {
+        if self.quirks.memory_increment_x {
+            self.idx_register = self.idx_register.wrapping_add(x as u16);
+        } else if self.quirks.memory_increment {
+            self.idx_register = self.idx_register.wrapping_add(x as u16 + 1);
+        }
+    }
+    // SCHIP 1.1 scrolls by hires pixels, which only covers half as many pixels in lores mode
+    fn scroll_distance(&self, resolution: ram::Resolution, pixels: usize) -> usize {
+        match resolution {
+            ram::Resolution::High => pixels,
+            _ if self.quirks.half_scroll => pixels / 2,
+            _ => pixels,
+        }
+    }
+    // Bytes of memory starting at I, for instructions that read or write through it
+    fn index_range(&self, len: usize) -> Result<std::ops::Range<usize>, &'static str> {
+        let start = self.idx_register as usize;
+        if start + len > consts::RAM_BYTES {
+            return Err("Memory access past the end of RAM");
+        }
+        Ok(start..start + len)
+    }
+    // Only the low nibble of VX selects a key
+    fn key_pressed(&mut self, x: u8) -> bool {
+        let key = (self.registers[x as usize] & 0xF) as usize;
+        self.polled_keys |= 1 << key;
+        self.keyboard_buffer.is_pressed(key)
+    }
+    // Executes one instruction. Anything a ROM can do wrong is reported as an error rather
+    // than a panic, so untrusted programs cannot bring down the frontend
+    pub fn cycle(&mut self) -> Result<Step, &'static str> {
+        let pc_before = self.pc;
+        let (decoded, status) = self.step()?;
+        let waited = matches!(status, CycleStatus::Waiting);
+        Ok(Step {
+            instruction: decoded.instruction,
+            opcode: decoded.opcode,
+            pc_before,
+            pc_after: self.pc,
+            registers_written: if waited { 0 } else { decoded.writes },
+            drew: matches!(status, CycleStatus::RedrawScreen),
+            waited,
+            halted: matches!(status, CycleStatus::Halted),
+        })
+    }
+    // cycle() without the Step, which run_frame_until has no use for
+    #[inline(always)]
+    fn step(&mut self) -> Result<(Decoded, CycleStatus), &'static str> {
+        let pc = self.pc as usize;
+        if pc + consts::OP_CODE_BYTES > consts::RAM_BYTES {
+            return Err("Program counter ran past the end of RAM");
+        }
+        let bytes = self.ram.read_slice(pc, consts::OP_CODE_BYTES);
+        let raw = u16::from_be_bytes([bytes[0], bytes[1]]);
+        if self.histogram.enabled {
+            self.histogram.record(pc, raw);
+        }
+        self.trace.push(TraceEntry {
+            pc: self.pc,
+            opcode: raw,
+            index: self.idx_register,
+            registers: self.registers,
+        });
+        self.pc += consts::OP_CODE_BYTES as u16;
+        self.instructions += 1;
+        // The cache only hands back an instruction while memory still holds the opcode it
+        // was decoded from, so self-modifying programs always run what is in memory. Quirks
+        // are the ones the instruction ran under
+        let decoded = self.decoded.get(pc, raw, &self.quirks);
+        let status = self.execute(decoded.instruction)?;
+        if !matches!(status, CycleStatus::Waiting) {
+            self.observers
+                .registers_written(decoded.writes, &self.registers);
+        }
+        Ok((decoded, status))
+    }
 
-        match (opcode, x, y, n) {
-            // Clears screen
-            (0, 0, 0xE, 0) => {
-                self.display_buffer
-                    .as_ref()
-                    .borrow_mut()
-                    .buffer
-                    .iter_mut()
-                    .for_each(|x| *x = [0_u8; consts::CHIP8_WIDTH]);
-                return Some(CycleStatus::RedrawScreen);
+    // One arm per instruction, the match on the decoded instruction compiles to a jump table
+    fn execute(&mut self, instruction: Instruction) -> Result<CycleStatus, &'static str> {
+        let redraw = Ok(CycleStatus::RedrawScreen);
+        let next = Ok(CycleStatus::Continue);
+        match instruction {
+            Instruction::Clear => {
+                self.display_buffer.clear();
+                redraw
             }
-
-            // Draw on display
-            (0xD, _, _, _) => {
-                let x_coord = self.registers[x as usize] % (consts::CHIP8_WIDTH as u8);
-                let y_coord = self.registers[y as usize] % (consts::CHIP8_HEIGHT as u8);
-                let sprite_vals = &self.ram.buffer
-                    [(self.idx_register as usize)..((self.idx_register + (n as u16)) as usize)];
-                let mut display_buffer = self.display_buffer.as_ref().borrow_mut();
-                let vram: &mut [[u8; consts::CHIP8_WIDTH]; consts::CHIP8_HEIGHT] =
-                    display_buffer.borrow_mut().buffer.borrow_mut();
-                for i in 0..n {
-                    let curr_sprite_val = sprite_vals[i as usize];
-                    for shift_pos in 0..8 {
-                        if utils::bounds_check(
-                            (x_coord + shift_pos) as usize,
-                            (y_coord + i) as usize,
-                            consts::CHIP8_WIDTH,
-                            consts::CHIP8_HEIGHT,
-                        ) {
-                            let mask = (1 << (7 - shift_pos)) as u8;
-                            let should_flip = (mask & curr_sprite_val) >> (7 - shift_pos);
-                            if should_flip == 1 {
-                                if vram[(y_coord + i) as usize][(x_coord + shift_pos) as usize] == 1
-                                {
-                                    self.registers[0xF] = 1;
-                                }
-                                vram[(y_coord + i) as usize][(x_coord + shift_pos) as usize] ^= 1;
-                            }
-                        } else {
-                            break;
-                        }
-                    }
-                }
-                return Some(CycleStatus::RedrawScreen);
+            Instruction::Return => {
+                self.stack_pointer = self.stack_pointer.checked_sub(1).ok_or("Stack underflow")?;
+                let from = self.pc - consts::OP_CODE_BYTES as u16;
+                self.pc = self.stack[self.stack_pointer as usize];
+                self.events.publish(Event::Returned { from, to: self.pc });
+                next
+            }
+            // Exit the interpreter, pc stays put so the program remains halted
+            Instruction::Exit => {
+                self.pc -= consts::OP_CODE_BYTES as u16;
+                Ok(CycleStatus::Halted)
             }
 
-            // Jump to subroutine
-            (1, _, _, _) => {
-                self.pc = nnn;
+            // Scroll the display
+            Instruction::ScrollDown(n) => {
+                let rows = self.scroll_distance(self.display_buffer.resolution, n as usize);
+                self.display_buffer.scroll_down(rows);
+                redraw
+            }
+            Instruction::ScrollRight => {
+                let cols = self.scroll_distance(self.display_buffer.resolution, 4);
+                self.display_buffer.scroll_right(cols);
+                redraw
             }
-            (0xB, _, _, _) => {
-                self.pc = nnn.wrapping_add(self.registers[0] as u16);
+            Instruction::ScrollLeft => {
+                let cols = self.scroll_distance(self.display_buffer.resolution, 4);
+                self.display_buffer.scroll_left(cols);
+                redraw
             }
 
-            // Subroutines: enter and exit
-            (0, 0, 0xE, 0xE) => {
-                self.stack_pointer -= 1;
-                self.pc = self.stack[self.stack_pointer as usize];
+            // CHIP-8X: step the background through blue, black, green and red
+            Instruction::NextBackground => {
+                self.display_buffer.colors_mut().next_background();
+                redraw
             }
-            (2, _, _, _) => {
-                self.stack[self.stack_pointer as usize] = self.pc;
-                self.stack_pointer += 1;
-                self.pc = nnn;
+
+            // Switch between 64x32 and 128x64 display modes
+            Instruction::Lores => {
+                self.display_buffer.set_resolution(ram::Resolution::Low);
+                redraw
+            }
+            Instruction::Hires => {
+                self.display_buffer.set_resolution(ram::Resolution::High);
+                redraw
             }
 
-            // Conditional skips
-            (3, _, _, _) => {
-                let vx_data = self.registers[x as usize];
-                if vx_data == nn {
-                    self.pc += consts::OP_CODE_BYTES as u16;
-                }
+            Instruction::Jump(nnn) => {
+                self.pc = nnn;
+                next
             }
-            (4, _, _, _) => {
-                let vx_data = self.registers[x as usize];
-                if vx_data != nn {
-                    self.pc += consts::OP_CODE_BYTES as u16;
-                }
+            Instruction::Call(nnn) => self.call(nnn),
+
+            Instruction::SkipEqual(x, nn) => self.skip_if(self.registers[x as usize] == nn),
+            Instruction::SkipNotEqual(x, nn) => self.skip_if(self.registers[x as usize] != nn),
+            Instruction::SkipRegistersEqual(x, y) => {
+                self.skip_if(self.registers[x as usize] == self.registers[y as usize])
             }
-            (5, _, _, 0) => {
-                let vx_data = self.registers[x as usize];
-                let vy_data = self.registers[y as usize];
-                if vx_data == vy_data {
-                    self.pc += consts::OP_CODE_BYTES as u16;
-                }
+            Instruction::SkipRegistersNotEqual(x, y) => {
+                self.skip_if(self.registers[x as usize] != self.registers[y as usize])
             }
-            (9, _, _, 0) => {
-                let vx_data = self.registers[x as usize];
-                let vy_data = self.registers[y as usize];
-                if vx_data != vy_data {
-                    self.pc += consts::OP_CODE_BYTES as u16;
-                }
+            // CHIP-8X: add each nibble separately, carries are dropped at 3 bits
+            Instruction::AddNibbles(x, y) => {
+                let (vx, vy) = (self.registers[x as usize], self.registers[y as usize]);
+                self.registers[x as usize] = ((vx & 0x77) + (vy & 0x77)) & 0x77;
+                next
             }
 
-            // Set register
-            (6, _, _, _) => {
+            Instruction::Set(x, nn) => {
                 self.registers[x as usize] = nn;
+                next
             }
-            (8, _, _, 0) => {
-                self.registers[x as usize] = self.registers[y as usize];
-            }
-            (0xA, _, _, _) => {
-                self.idx_register = nnn;
+            Instruction::Add(x, nn) => {
+                self.registers[x as usize] = self.registers[x as usize].wrapping_add(nn);
+                next
             }
 
-            // Add/subtract instructions
-            (7, _, _, _) => {
-                self.registers[x as usize] = self.registers[x as usize].wrapping_add(nn);
+            // Register to register arithmetic, logic and shifts
+            Instruction::Move(x, y) => {
+                self.registers[x as usize] = self.registers[y as usize];
+                next
             }
-            (8, _, _, 4) => {
-                if ((self.registers[x as usize] as u16) + (self.registers[y as usize] as u16)) > 255
-                {
-                    self.registers[0xF_usize] = 1;
-                } else {
-                    self.registers[0xF_usize] = 0;
-                }
-                self.registers[x as usize] =
-                    self.registers[x as usize].wrapping_add(self.registers[y as usize]);
+            Instruction::Or(x, y) => {
+                self.logic(x, self.registers[x as usize] | self.registers[y as usize])
             }
-            (8, _, _, 5) => {
-                if self.registers[x as usize] >= self.registers[y as usize] {
-                    self.registers[0xF_usize] = 1;
-                } else {
-                    self.registers[0xF_usize] = 0;
-                }
-                self.registers[x as usize] =
-                    self.registers[x as usize].wrapping_sub(self.registers[y as usize]);
+            Instruction::And(x, y) => {
+                self.logic(x, self.registers[x as usize] & self.registers[y as usize])
             }
-            (8, _, _, 7) => {
-                if self.registers[y as usize] >= self.registers[x as usize] {
-                    self.registers[0xF_usize] = 1;
-                } else {
-                    self.registers[0xF_usize] = 0;
-                }
-                self.registers[x as usize] =
-                    self.registers[y as usize].wrapping_sub(self.registers[x as usize]);
+            Instruction::Xor(x, y) => {
+                self.logic(x, self.registers[x as usize] ^ self.registers[y as usize])
             }
 
-            // Logical instructions
-            (8, _, _, 1) => {
-                self.registers[x as usize] |= self.registers[y as usize];
+            // VF may be an operand, so the flag is only written after the result
+            Instruction::AddRegisters(x, y) => {
+                let (result, carry) =
+                    self.registers[x as usize].overflowing_add(self.registers[y as usize]);
+                self.registers[x as usize] = result;
+                self.registers[0xF] = carry as u8;
+                next
             }
-            (8, _, _, 2) => {
-                self.registers[x as usize] &= self.registers[y as usize];
+            Instruction::Sub(x, y) => {
+                let (result, borrow) =
+                    self.registers[x as usize].overflowing_sub(self.registers[y as usize]);
+                self.registers[x as usize] = result;
+                self.registers[0xF] = !borrow as u8;
+                next
             }
-            (8, _, _, 3) => {
-                self.registers[x as usize] ^= self.registers[y as usize];
+            Instruction::SubReverse(x, y) => {
+                let (result, borrow) =
+                    self.registers[y as usize].overflowing_sub(self.registers[x as usize]);
+                self.registers[x as usize] = result;
+                self.registers[0xF] = !borrow as u8;
+                next
             }
 
             // Shifting instructions
-            (8, _, _, 6) => {
-                self.registers[0xF] = self.registers[x as usize] & 0b00000001;
-                self.registers[x as usize] >>= 1;
+            Instruction::ShiftRight(x, y) => {
+                let source = if self.quirks.shift_vy { y } else { x };
+                let value = self.registers[source as usize];
+                self.registers[x as usize] = value >> 1;
+                self.registers[0xF] = value & 0b00000001;
+                next
+            }
+            Instruction::ShiftLeft(x, y) => {
+                let source = if self.quirks.shift_vy { y } else { x };
+                let value = self.registers[source as usize];
+                self.registers[x as usize] = value << 1;
+                self.registers[0xF] = (value & 0b10000000) >> 7;
+                next
             }
-            (8, _, _, 0xE) => {
-                self.registers[0xF] = (self.registers[x as usize] & 0b10000000) >> 7;
-                self.registers[x as usize] <<= 1;
+
+            Instruction::SetIndex(nnn) => {
+                self.idx_register = nnn;
+                next
             }
+            // The offset register was picked by the decoder, V0 or VX under the jump quirk
+            Instruction::JumpOffset(x, nnn) => {
+                self.pc = nnn.wrapping_add(self.registers[x as usize] as u16);
+                next
+            }
+            Instruction::ColorZones(x, y, n) => self.color_zones(x, y, n),
 
-            // Generate randomness
-            (0xC, _, _, _) => {
-                let rand_val: u8 = self.rng.gen();
+            Instruction::Random(x, nn) => {
+                let rand_val: u8 = self.rng.0.gen();
                 self.registers[x as usize] = nn & rand_val;
+                next
             }
+            Instruction::Draw(x, y, n) => self.draw(x, y, n),
 
             // Skip on keypress
-            (0xE, _, 9, 0xE) => {
-                if keyboard[self.registers[x as usize] as usize] == 1 {
-                    self.pc += consts::OP_CODE_BYTES as u16;
-                }
+            Instruction::SkipKey(x) => {
+                let pressed = self.key_pressed(x);
+                self.skip_if(pressed)
             }
-            (0xE, _, 0xA, 1) => {
-                if keyboard[self.registers[x as usize] as usize] != 1 {
-                    self.pc += consts::OP_CODE_BYTES as u16;
-                }
+            Instruction::SkipNotKey(x) => {
+                let pressed = self.key_pressed(x);
+                self.skip_if(!pressed)
             }
 
             // Change timers (delay/sound)
-            (0xF, _, 0, 7) => {
+            Instruction::GetDelay(x) => {
                 self.registers[x as usize] = self.delay_timer;
+                next
             }
-            (0xF, _, 1, 5) => {
+            Instruction::WaitKey(x) => self.wait_key(x),
+            Instruction::SetDelay(x) => {
                 self.delay_timer = self.registers[x as usize];
+                next
             }
-            (0xF, _, 1, 8) => {
-                *self.sound_timer.as_ref().borrow_mut() = self.registers[x as usize];
+            Instruction::SetSound(x) => {
+                self.sound_timer = self.registers[x as usize];
+                self.events.publish(Event::SoundTimerSet(self.sound_timer));
+                next
             }
 
             // Update index register
-            (0xF, _, 1, 0xE) => {
+            Instruction::AddIndex(x) => {
                 self.idx_register = self
                     .idx_register
                     .wrapping_add(self.registers[x as usize] as u16);
+                if self.quirks.fx1e_carry {
+                    self.registers[0xF] = (self.idx_register > 0xFFF) as u8;
+                }
+                next
             }
 
             // Point index to font character
-            (0xF, _, 2, 9) => {
-                self.idx_register = (self.registers[x as usize] * 5) as u16;
+            Instruction::Font(x) => {
+                let digit = (self.registers[x as usize] & 0xF) as usize;
+                self.idx_register = (digit * consts::FONT_GLYPH_BYTES) as u16;
+                next
+            }
+            Instruction::BigFont(x) => {
+                let digit = (self.registers[x as usize] & 0xF) as usize;
+                self.idx_register =
+                    (consts::BIG_FONT_OFFSET + digit * consts::BIG_FONT_GLYPH_BYTES) as u16;
+                next
             }
 
-            // Binary byte to decimal string representation conversion
-            (0xF, _, 3, 3) => {
-                let num = self.registers[x as usize];
-                let first_digit = num / 100;
-                let second_digit = (num % 100) / 10;
-                let third_digit = num % 10;
-                let ram_ref: &mut [u8] = self.ram.borrow_mut().buffer.borrow_mut();
-                ram_ref[self.idx_register as usize] = first_digit;
-                ram_ref[(self.idx_register + 1) as usize] = second_digit;
-                ram_ref[(self.idx_register + 2) as usize] = third_digit;
+            // XO-CHIP audio playback rate
+            Instruction::Pitch(x) => {
+                self.pitch = Pitch(self.registers[x as usize]);
+                self.events.publish(Event::PitchSet(self.pitch));
+                next
             }
 
-            // Store and load memory
-            (0xF, _, 5, 5) => {
-                let ram_ref: &mut [u8] = self.ram.borrow_mut().buffer.borrow_mut();
-                for i in 0..(x + 1) {
-                    ram_ref[(self.idx_register + i as u16) as usize] = self.registers[i as usize];
-                }
+            Instruction::Bcd(x) => self.bcd(x),
+            Instruction::Store(x) => self.store(x),
+            Instruction::Load(x) => {
+                let range = self.index_range(x as usize + 1)?;
+                self.coverage.mark_read(range.clone());
+                self.registers[..=x as usize]
+                    .copy_from_slice(self.ram.read_slice(range.start, range.len()));
+                self.increment_index_after_transfer(x);
+                next
+            }
+
+            Instruction::Unknown(opcode) if opcode >> 12 == 0 => {
+                Err("Machine language routines are not supported")
             }
-            (0xF, _, 6, 5) => {
-                let ram_ref = self.ram.buffer;
-                for i in 0..(x + 1) {
-                    self.registers[i as usize] = ram_ref[(self.idx_register + i as u16) as usize];
+            Instruction::Unknown(_) => Err("Invalid instruction"),
+        }
+    }
+
+    // 2NNN
+    fn call(&mut self, nnn: u16) -> Result<CycleStatus, &'static str> {
+        *self
+            .stack
+            .get_mut(self.stack_pointer as usize)
+            .ok_or("Stack overflow")? = self.pc;
+        self.stack_pointer += 1;
+        self.events.publish(Event::Called {
+            from: self.pc - consts::OP_CODE_BYTES as u16,
+            to: nnn,
+        });
+        self.pc = nnn;
+        Ok(CycleStatus::Continue)
+    }
+
+    fn skip_if(&mut self, condition: bool) -> Result<CycleStatus, &'static str> {
+        if condition {
+            self.pc += consts::OP_CODE_BYTES as u16;
+        }
+        Ok(CycleStatus::Continue)
+    }
+
+    // 8XY1-8XY3 store the result, the original interpreter also clobbered VF
+    fn logic(&mut self, x: u8, result: u8) -> Result<CycleStatus, &'static str> {
+        self.registers[x as usize] = result;
+        if self.quirks.vf_reset {
+            self.registers[0xF] = 0;
+        }
+        Ok(CycleStatus::Continue)
+    }
+
+    // CHIP-8X BXYN colors the zones given by VX and VY with the palette entry in VX+1
+    fn color_zones(&mut self, x: u8, y: u8, n: u8) -> Result<CycleStatus, &'static str> {
+        let (vx, vy) = (self.registers[x as usize], self.registers[y as usize]);
+        let color = self.registers[(x as usize + 1) % consts::REG_COUNT] & 0x7;
+        let display_buffer = &mut self.display_buffer;
+        let (width, height) = (display_buffer.width(), display_buffer.height());
+        // BXY0 works on 8x4 zones given as start and extent nibbles, BXYN colors N
+        // pixel rows from VY within the 8 pixel column holding VX
+        let (cols, rows) = if n == 0 {
+            let (col, row) = ((vx & 0xF) as usize * 8, (vy & 0xF) as usize * 4);
+            (
+                col..col + ((vx >> 4) as usize + 1) * 8,
+                row..row + ((vy >> 4) as usize + 1) * 4,
+            )
+        } else {
+            let col = (vx as usize % width) / 8 * 8;
+            let row = vy as usize % height;
+            (col..col + 8, row..row + n as usize)
+        };
+        let colors = display_buffer.colors_mut();
+        for row in rows.filter(|&r| r < height) {
+            for col in cols.clone().filter(|&c| c < width) {
+                colors.foreground[row][col] = color;
+            }
+        }
+        Ok(CycleStatus::RedrawScreen)
+    }
+
+    // DXYN
+    fn draw(&mut self, x: u8, y: u8, n: u8) -> Result<CycleStatus, &'static str> {
+        if self.quirks.display_wait {
+            if !self.vblank {
+                self.pc -= consts::OP_CODE_BYTES as u16;
+                return Ok(CycleStatus::Waiting);
+            }
+            self.vblank = false;
+        }
+        // DXY0 draws a 16x16 sprite stored as two bytes per row
+        let (sprite_width, rows) = if n == 0 { (16, 16) } else { (8, n as usize) };
+        let row_bytes = sprite_width / 8;
+        let sprite_range = self.index_range(rows * row_bytes)?;
+        self.coverage.mark_read(sprite_range.clone());
+        let sprite_vals = self.ram.read_slice(sprite_range.start, sprite_range.len());
+        let display_buffer = &mut self.display_buffer;
+        let (width, height) = (display_buffer.width(), display_buffer.height());
+        let x_coord = self.registers[x as usize] as usize % width;
+        let y_coord = self.registers[y as usize] as usize % height;
+        let vram = &mut display_buffer.buffer;
+        let (mut collided_rows, mut clipped_rows) = (0, 0);
+        for i in 0..rows {
+            let row = &sprite_vals[i * row_bytes..(i + 1) * row_bytes];
+            let bits =
+                row.iter().fold(0_u16, |acc, &b| (acc << 8) | b as u16) << (16 - sprite_width);
+            if y_coord + i >= height && !self.quirks.wrap {
+                clipped_rows += 1;
+                continue;
+            }
+            let mut collided = false;
+            for shift_pos in 0..sprite_width {
+                let (mut px, mut py) = (x_coord + shift_pos, y_coord + i);
+                if !utils::bounds_check(px, py, width, height) {
+                    if !self.quirks.wrap {
+                        break;
+                    }
+                    (px, py) = (px % width, py % height);
                 }
+                if bits & (0x8000 >> shift_pos) != 0 {
+                    if vram[py][px] == 1 {
+                        collided = true;
+                    }
+                    vram[py][px] ^= 1;
+                }
+            }
+            if collided {
+                collided_rows += 1;
             }
+        }
+        let hires = display_buffer.resolution == ram::Resolution::High;
+        self.registers[0xF] = if self.quirks.collision_rows && hires {
+            collided_rows + clipped_rows
+        } else {
+            (collided_rows > 0) as u8
+        };
+        self.events.publish(Event::SpriteDrawn(SpriteDraw {
+            x: x_coord,
+            y: y_coord,
+            width: sprite_width,
+            height: rows,
+            address: self.idx_register,
+        }));
+        Ok(CycleStatus::RedrawScreen)
+    }
 
-            // Invalid/unsupported opcodes
-            (0, _, _, _) => {
-                panic!("Calling machine language routine, unsupported on this architecture")
+    // FX0A, halt till keyboard interrupt
+    fn wait_key(&mut self, x: u8) -> Result<CycleStatus, &'static str> {
+        // The lowest held key wins when several are down
+        self.polled_keys = u16::MAX;
+        match self.keyboard_buffer.keys() {
+            0 => {
+                self.pc -= consts::OP_CODE_BYTES as u16;
+                if !self.key_wait {
+                    self.key_wait = true;
+                    self.events.publish(Event::KeyWaited);
+                }
+                Ok(CycleStatus::Waiting)
             }
-            (_, _, _, _) => {
-                panic!(
-                    "Invalid instruction, received opcode: {}, x: {}, y: {}, n: {}",
-                    opcode, x, y, n
-                )
+            keys => {
+                self.key_wait = false;
+                self.registers[x as usize] = keys.trailing_zeros() as u8;
+                Ok(CycleStatus::Continue)
             }
         }
-        Some(CycleStatus::Continue)
+    }
+
+    // FX33, binary byte to decimal string representation conversion
+    fn bcd(&mut self, x: u8) -> Result<CycleStatus, &'static str> {
+        let num = self.registers[x as usize];
+        let first_digit = num / 100;
+        let second_digit = (num % 100) / 10;
+        let third_digit = num % 10;
+        let range = self.index_range(3)?;
+        let digits = [first_digit, second_digit, third_digit];
+        self.ram.write_slice(range.start, &digits);
+        self.observers.ram_written(range.start, &digits);
+        self.events.publish(Event::MemoryWritten {
+            address: self.idx_register,
+            len: 3,
+        });
+        Ok(CycleStatus::Continue)
+    }
+
+    // FX55
+    fn store(&mut self, x: u8) -> Result<CycleStatus, &'static str> {
+        let range = self.index_range(x as usize + 1)?;
+        self.ram
+            .write_slice(range.start, &self.registers[..=x as usize]);
+        self.observers
+            .ram_written(range.start, &self.registers[..=x as usize]);
+        self.events.publish(Event::MemoryWritten {
+            address: self.idx_register,
+            len: x as u16 + 1,
+        });
+        self.increment_index_after_transfer(x);
+        Ok(CycleStatus::Continue)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::consts;
-    use crate::processor::Processor;
-    use crate::{ram, rom};
+    use crate::core::events::{Event, SpriteDraw};
+    use crate::core::instruction::Instruction;
+    use crate::core::processor::{Pitch, Processor};
+    use crate::core::{ram, rom};
+    use proptest::prelude::*;
     use std::borrow::BorrowMut;
 
     const START_PC: u16 = 0xF00;
@@ -378,7 +974,8 @@ mod tests {
         assert_eq!(proc.stack_pointer, 0);
         assert_eq!(proc.stack, [0; 16]);
 
-        proc.init_ram(&rom::Rom::default(), &consts::FONT_SET)?;
+        proc.init_ram(&rom::Rom::default(), &consts::FONT_SET, consts::PROG_OFFSET)?;
+        assert_eq!(proc.pc, 0x200);
 
         // First char in font: 0
         assert_eq!(proc.ram.buffer[0..5], [0xF0, 0x90, 0x90, 0x90, 0xF0]);
@@ -387,6 +984,223 @@ mod tests {
             proc.ram.buffer[consts::FONT_SET.len() - 5..consts::FONT_SET.len()],
             [0xF0, 0x80, 0xF0, 0x80, 0x80]
         );
+        // First big char: 0
+        assert_eq!(
+            proc.ram.buffer[consts::BIG_FONT_OFFSET..consts::BIG_FONT_OFFSET + 10],
+            [0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_init_ram_load_address() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let mut rom = rom::Rom::default();
+        rom.buffer[0] = 0x12;
+        rom.buffer[1] = 0x34;
+        rom.size = 2;
+        processor.init_ram(&rom, &consts::FONT_SET, consts::ETI660_PROG_OFFSET)?;
+        assert_eq!(processor.pc, 0x600);
+        assert_eq!(processor.ram.buffer[0x600..0x602], [0x12, 0x34]);
+
+        rom.size = consts::RAM_BYTES - consts::ETI660_PROG_OFFSET + 1;
+        assert!(processor
+            .init_ram(&rom, &consts::FONT_SET, consts::ETI660_PROG_OFFSET)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rom_size() -> Result<(), Box<dyn std::error::Error>> {
+        let mut processor = build_processor()?;
+
+        let rom = rom::Rom::new("roms/Maze.ch8")?;
+        assert_eq!(rom.size, 34);
+        processor.ram.buffer[0x200 + rom.size] = 0xAB;
+        processor.init_ram(&rom, &consts::FONT_SET, consts::PROG_OFFSET)?;
+        assert_eq!(processor.ram.buffer[0x200..0x202], rom.buffer[0..2]);
+        assert_eq!(processor.ram.buffer[0x200 + rom.size], 0xAB);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rom_from_bytes() -> Result<(), Box<dyn std::error::Error>> {
+        let rom = rom::Rom::from_bytes(&[0x12, 0x00])?;
+        assert_eq!(rom.size, 2);
+        assert_eq!(rom.buffer[0..3], [0x12, 0x00, 0x00]);
+        assert!(rom::Rom::from_bytes(&[0; consts::MAX_ROM_BYTES + 1]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        processor.stack_pointer = 2;
+        processor.idx_register = 0x300;
+        processor.delay_timer = 5;
+        processor.sound_timer = 5;
+        processor
+            .display_buffer
+            .set_resolution(ram::Resolution::High);
+        processor.display_buffer.buffer[0][0] = 1;
+        processor.ram.buffer[0x400] = 0xAA;
+
+        processor.reset();
+        assert_eq!(processor.pc, 0x200);
+        assert_eq!(processor.registers, [0; consts::REG_COUNT]);
+        assert_eq!(processor.stack_pointer, 0);
+        assert_eq!(processor.idx_register, 0);
+        assert_eq!(processor.delay_timer, 0);
+        assert_eq!(processor.sound_timer, 0);
+        assert_eq!(processor.display_buffer.resolution, ram::Resolution::Low);
+        assert_eq!(processor.display_buffer.buffer[0][0], 0);
+        assert_eq!(processor.ram.buffer[0x400], 0xAA);
+        Ok(())
+    }
+
+    #[test]
+    fn test_processor_is_send() {
+        // Frontends may run the machine on a worker thread
+        fn assert_send<T: Send>() {}
+        assert_send::<Processor>();
+    }
+
+    #[test]
+    fn test_load_rom() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        processor.ram.buffer[0x400] = 0xAA;
+        processor.registers[3] = 7;
+        let rom = rom::Rom::from_bytes(&[0x12, 0x00]).map_err(|_| "Could not build ROM")?;
+        processor.load_rom(&rom)?;
+        assert_eq!(processor.pc, 0x200);
+        assert_eq!(processor.registers[3], 0);
+        assert_eq!(processor.ram.buffer[0x200..0x202], [0x12, 0x00]);
+        assert_eq!(processor.ram.buffer[0x400], 0);
+        assert_eq!(processor.ram.buffer[0..5], consts::FONT_SET[0..5]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder() -> Result<(), &'static str> {
+        let quirks = crate::core::platform::Platform::SchipLegacy.quirks();
+        let mut fonts = consts::FONT_SET;
+        fonts[0] = 0xAA;
+        let build = || {
+            Processor::builder()
+                .quirks(quirks)
+                .start_pc(consts::ETI660_PROG_OFFSET)
+                .fonts(fonts)
+                .resolution(ram::Resolution::Eti660)
+                .rng(7)
+                .build()
+        };
+        let mut processor = build();
+        assert_eq!(processor.pc as usize, consts::ETI660_PROG_OFFSET);
+        assert_eq!(processor.quirks(), &quirks);
+        assert_eq!(processor.ram.buffer[0], 0xAA);
+        assert_eq!(processor.display_buffer.resolution, ram::Resolution::Eti660);
+
+        // The fonts survive loading a program, and the seed makes CXNN repeatable
+        let rom = rom::Rom::from_bytes(&[0xC0, 0xFF]).map_err(|_| "Could not build ROM")?;
+        processor.load_rom(&rom)?;
+        assert_eq!(processor.ram.buffer[0], 0xAA);
+        assert_eq!(processor.load_address(), consts::ETI660_PROG_OFFSET);
+        processor.cycle()?;
+        let mut other = build();
+        other.load_rom(&rom)?;
+        other.cycle()?;
+        assert_eq!(processor.registers[0], other.registers[0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_info() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+        let program = [0x81, 0x24, 0xD0, 0x01, 0xF3, 0x0A];
+        processor.ram.buffer[START_PC as usize..START_PC as usize + 6].copy_from_slice(&program);
+
+        let step = processor.cycle()?;
+        assert_eq!(step.instruction, Instruction::AddRegisters(1, 2));
+        assert_eq!(
+            (step.opcode, step.pc_before, step.pc_after),
+            (0x8124, START_PC, NEXT_PC)
+        );
+        assert!(step.wrote(1) && step.wrote(0xF) && !step.wrote(2));
+        assert!(!step.wrote(consts::REG_COUNT) && !step.wrote(usize::MAX));
+        assert!(!step.drew && !step.waited && !step.halted);
+
+        assert!(processor.cycle()?.drew);
+
+        // FX0A with no key down stays on the same instruction
+        let step = processor.cycle()?;
+        assert!(step.waited);
+        assert_eq!(step.pc_before, step.pc_after);
+        assert_eq!(step.registers_written, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_state() -> Result<(), String> {
+        let mut processor = build_processor()?;
+        processor.pc = 0x246;
+        processor.idx_register = 0x300;
+        processor.registers[0xA] = 0x42;
+        processor.stack[0] = 0x204;
+        processor.stack_pointer = 1;
+        processor.delay_timer = 30;
+        processor.pitch = Pitch(80);
+        processor.ram.buffer[0xFFF] = 0xEE;
+
+        let json = processor.to_json_state();
+        assert!(json.contains("\"stack\": [\n    516\n  ]"));
+        let restored = Processor::from_json_state(&json)?;
+        assert_eq!(restored.pc, 0x246);
+        assert_eq!(restored.idx_register, 0x300);
+        assert_eq!(restored.registers, processor.registers);
+        assert_eq!(restored.stack[..1], [0x204]);
+        assert_eq!(restored.stack_pointer, 1);
+        assert_eq!((restored.delay_timer, restored.sound_timer), (30, 0));
+        assert_eq!(restored.pitch, Pitch(80));
+        assert_eq!(restored.ram.buffer, processor.ram.buffer);
+
+        let broken = json.replace("\"pc\": 582", "\"pc\": 4096");
+        assert_eq!(
+            Processor::from_json_state(&broken).err(),
+            Some(String::from("State field pc is out of range"))
+        );
+        let broken = json.replace("\"version\": 1", "\"version\": 9");
+        assert_eq!(
+            Processor::from_json_state(&broken).err(),
+            Some(String::from("Unsupported state version 9"))
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "bundled-roms")]
+    #[test]
+    fn test_demo_ibm_logo() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let bytes = crate::core::demos::find("ibm").ok_or("Missing demo")?;
+        let rom = rom::Rom::from_bytes(bytes).map_err(|_| "Could not build ROM")?;
+        processor.load_rom(&rom)?;
+        for _ in 0..10 {
+            processor.run_frame(consts::CYCLES_PER_FRAME)?;
+        }
+        // The logo is drawn once and the program then spins on a jump to itself
+        let pc = processor.pc;
+        processor.run_frame(consts::CYCLES_PER_FRAME)?;
+        assert_eq!(processor.pc, pc);
+        let lit: usize = processor
+            .display_buffer
+            .buffer
+            .iter()
+            .map(|row| row.iter().filter(|&&p| p == 1).count())
+            .sum();
+        assert!(lit > 0);
         Ok(())
     }
 
@@ -396,25 +1210,346 @@ mod tests {
 
         let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
 
-        *processor
+        processor
             .display_buffer
-            .as_ref()
-            .borrow_mut()
             .buffer
-            .borrow_mut() = [[128; consts::CHIP8_WIDTH]; consts::CHIP8_HEIGHT];
+            .iter_mut()
+            .for_each(|row| row.fill(128));
         update_buffer(ram, (START_PC + 1) as usize, 0xE0);
 
-        processor.cycle();
+        processor.cycle()?;
 
         for y in 0..consts::CHIP8_HEIGHT {
             for x in 0..consts::CHIP8_WIDTH {
-                assert_eq!(processor.display_buffer.as_ref().borrow().buffer[y][x], 0);
+                assert_eq!(processor.display_buffer.buffer[y][x], 0);
             }
         }
         assert_eq!(processor.pc, NEXT_PC);
         Ok(())
     }
 
+    #[test]
+    fn test_opcode_00ff_00fe() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        update_buffer(ram, (START_PC + 1) as usize, 0xFF);
+        update_buffer(ram, (START_PC + 3) as usize, 0xFE);
+        processor.display_buffer.buffer[0][0] = 1;
+
+        processor.cycle()?;
+        {
+            let display_buffer = &processor.display_buffer;
+            assert_eq!(display_buffer.resolution, ram::Resolution::High);
+            assert_eq!(display_buffer.buffer.len(), consts::HIRES_HEIGHT);
+            assert_eq!(display_buffer.buffer[0].len(), consts::HIRES_WIDTH);
+            assert_eq!(display_buffer.buffer[0][0], 0);
+        }
+
+        processor.cycle()?;
+        let display_buffer = &processor.display_buffer;
+        assert_eq!(display_buffer.resolution, ram::Resolution::Low);
+        assert_eq!(display_buffer.buffer.len(), consts::CHIP8_HEIGHT);
+        assert_eq!(display_buffer.buffer[0].len(), consts::CHIP8_WIDTH);
+        assert_eq!(processor.pc, SKIPPED_PC);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_00cn() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor
+            .display_buffer
+            .set_resolution(ram::Resolution::High);
+        processor.display_buffer.buffer[0][5] = 1;
+        processor.display_buffer.buffer[63][5] = 1;
+        update_buffer(ram, (START_PC) as usize, 0x00);
+        update_buffer(ram, (START_PC + 1) as usize, 0xC3);
+        processor.cycle()?;
+        let display_buffer = &processor.display_buffer;
+        assert_eq!(display_buffer.buffer[0][5], 0);
+        assert_eq!(display_buffer.buffer[3][5], 1);
+        assert_eq!(display_buffer.buffer[2][5], 0);
+        assert_eq!(processor.pc, NEXT_PC);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_00cn_lores() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.quirks.half_scroll = true;
+        processor.display_buffer.buffer[0][5] = 1;
+        update_buffer(ram, (START_PC) as usize, 0x00);
+        update_buffer(ram, (START_PC + 1) as usize, 0xC4);
+        processor.cycle()?;
+        let display_buffer = &processor.display_buffer;
+        assert_eq!(display_buffer.buffer[0][5], 0);
+        assert_eq!(display_buffer.buffer[2][5], 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_00fb_00fc() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor
+            .display_buffer
+            .set_resolution(ram::Resolution::High);
+        processor.display_buffer.buffer[1][0] = 1;
+        processor.display_buffer.buffer[1][127] = 1;
+        update_buffer(ram, (START_PC) as usize, 0x00);
+        update_buffer(ram, (START_PC + 1) as usize, 0xFB);
+        update_buffer(ram, (START_PC + 2) as usize, 0x00);
+        update_buffer(ram, (START_PC + 3) as usize, 0xFC);
+
+        processor.cycle()?;
+        {
+            let display_buffer = &processor.display_buffer;
+            assert_eq!(display_buffer.buffer[1][0], 0);
+            assert_eq!(display_buffer.buffer[1][4], 1);
+            assert_eq!(display_buffer.buffer[1][127], 0);
+        }
+
+        processor.cycle()?;
+        let display_buffer = &processor.display_buffer;
+        assert_eq!(display_buffer.buffer[1][0], 1);
+        assert_eq!(display_buffer.buffer[1][4], 0);
+        assert_eq!(processor.pc, SKIPPED_PC);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_dxyn_hires() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor
+            .display_buffer
+            .set_resolution(ram::Resolution::High);
+        processor.registers[0] = 120;
+        processor.registers[1] = 60;
+        processor.idx_register = 0;
+        ram[0] = 0xFF;
+        update_buffer(ram, (START_PC) as usize, 0xD0);
+        update_buffer(ram, (START_PC + 1) as usize, 0x11);
+        processor.cycle()?;
+        let display_buffer = &processor.display_buffer;
+        assert_eq!(display_buffer.buffer[60][119], 0);
+        assert!(display_buffer.buffer[60][120..].iter().all(|&p| p == 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_00fd() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        update_buffer(ram, (START_PC + 1) as usize, 0xFD);
+        assert!(processor.cycle()?.halted);
+        assert_eq!(processor.pc, START_PC);
+        let events = processor.events.subscribe();
+        processor.run_frame(10)?;
+        assert_eq!(processor.pc, START_PC);
+        assert_eq!(events.try_iter().collect::<Vec<_>>(), [Event::Halted]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_dxyn_wrap() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.quirks.wrap = true;
+        processor.registers[0] = 60;
+        processor.registers[1] = 31;
+        processor.idx_register = 0;
+        ram[0] = 0xFF;
+        ram[1] = 0xFF;
+        update_buffer(ram, (START_PC) as usize, 0xD0);
+        update_buffer(ram, (START_PC + 1) as usize, 0x12);
+        let events = processor.events.subscribe();
+        processor.cycle()?;
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [Event::SpriteDrawn(SpriteDraw {
+                x: 60,
+                y: 31,
+                width: 8,
+                height: 2,
+                address: 0
+            })]
+        );
+        let display_buffer = &processor.display_buffer;
+        assert_eq!(display_buffer.buffer[31][63], 1);
+        assert_eq!(display_buffer.buffer[31][0], 1);
+        assert_eq!(display_buffer.buffer[31][3], 1);
+        assert_eq!(display_buffer.buffer[31][4], 0);
+        assert_eq!(display_buffer.buffer[0][60], 1);
+        assert_eq!(display_buffer.buffer[0][2], 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_dxy0_collision_flag() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor
+            .display_buffer
+            .set_resolution(ram::Resolution::High);
+        processor.idx_register = 0;
+        ram[0..32].fill(0xFF);
+        for i in 0..2 {
+            update_buffer(ram, (START_PC + 2 * i) as usize, 0xD0);
+            update_buffer(ram, (START_PC + 2 * i + 1) as usize, 0x10);
+        }
+        processor.cycle()?;
+        processor.cycle()?;
+        assert_eq!(processor.registers[0xF], 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_dxy0() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor
+            .display_buffer
+            .set_resolution(ram::Resolution::High);
+        processor.idx_register = 0;
+        processor.quirks.collision_rows = true;
+        ram[0..32].fill(0xFF);
+        // D010 twice, the second draw collides on every row
+        for i in 0..2 {
+            update_buffer(ram, (START_PC + 2 * i) as usize, 0xD0);
+            update_buffer(ram, (START_PC + 2 * i + 1) as usize, 0x10);
+        }
+
+        processor.cycle()?;
+        {
+            let display_buffer = &processor.display_buffer;
+            assert!(display_buffer.buffer[..16]
+                .iter()
+                .all(|row| row[..16].iter().all(|&p| p == 1)));
+            assert_eq!(display_buffer.buffer[0][16], 0);
+            assert_eq!(display_buffer.buffer[16][0], 0);
+        }
+        assert_eq!(processor.registers[0xF], 0);
+
+        processor.cycle()?;
+        assert_eq!(processor.display_buffer.buffer[0][0], 0);
+        assert_eq!(processor.registers[0xF], 16);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_dxy0_clipped_rows() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor
+            .display_buffer
+            .set_resolution(ram::Resolution::High);
+        processor.registers[0] = 0;
+        processor.quirks.collision_rows = true;
+        processor.registers[1] = 60;
+        processor.idx_register = 0;
+        ram[0..32].fill(0xFF);
+        update_buffer(ram, (START_PC) as usize, 0xD0);
+        update_buffer(ram, (START_PC + 1) as usize, 0x10);
+        processor.cycle()?;
+        assert_eq!(processor.registers[0xF], 12);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_02a0() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.quirks.chip8x = true;
+        update_buffer(ram, (START_PC) as usize, 0x02);
+        update_buffer(ram, (START_PC + 1) as usize, 0xA0);
+        processor.cycle()?;
+        let display_buffer = &processor.display_buffer;
+        let colors = display_buffer
+            .colors
+            .as_ref()
+            .ok_or("Color layer missing")?;
+        assert_eq!(colors.background_color(), consts::CHIP8X_BACKGROUNDS[1]);
+        assert_eq!(processor.pc, NEXT_PC);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_bxy0_chip8x() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.quirks.chip8x = true;
+        // Two zones wide starting at column 1, one zone tall at row 2, colored 5
+        processor.registers[0] = 0x11;
+        processor.registers[1] = 5;
+        processor.registers[2] = 0x02;
+        update_buffer(ram, (START_PC) as usize, 0xB0);
+        update_buffer(ram, (START_PC + 1) as usize, 0x20);
+        processor.cycle()?;
+        assert_eq!(processor.pc, NEXT_PC);
+        let display_buffer = &processor.display_buffer;
+        let colors = display_buffer
+            .colors
+            .as_ref()
+            .ok_or("Color layer missing")?;
+        assert_eq!(colors.foreground[8][8], 5);
+        assert_eq!(colors.foreground[11][23], 5);
+        assert_eq!(colors.foreground[7][8], consts::CHIP8X_FOREGROUND);
+        assert_eq!(colors.foreground[8][24], consts::CHIP8X_FOREGROUND);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_bxyn_chip8x() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.quirks.chip8x = true;
+        // Rows 3 and 4 of the column holding x = 20, colored with V1
+        processor.registers[0] = 20;
+        processor.registers[1] = 3;
+        update_buffer(ram, (START_PC) as usize, 0xB0);
+        update_buffer(ram, (START_PC + 1) as usize, 0x12);
+        processor.cycle()?;
+        let display_buffer = &processor.display_buffer;
+        let colors = display_buffer
+            .colors
+            .as_ref()
+            .ok_or("Color layer missing")?;
+        assert_eq!(colors.foreground[3][16], 3);
+        assert_eq!(colors.foreground[4][23], 3);
+        assert_eq!(colors.foreground[5][16], consts::CHIP8X_FOREGROUND);
+        assert_eq!(colors.foreground[3][24], consts::CHIP8X_FOREGROUND);
+        Ok(())
+    }
+
     #[test]
     fn test_opcode_00ee() -> Result<(), &'static str> {
         let mut processor = build_processor()?;
@@ -426,10 +1561,18 @@ mod tests {
         processor.stack_pointer = 3;
         processor.stack[2] = 0x1234;
 
-        processor.cycle();
+        let events = processor.events.subscribe();
+        processor.cycle()?;
 
         assert_eq!(processor.stack_pointer, 2);
         assert_eq!(processor.pc, 0x1234);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [Event::Returned {
+                from: START_PC,
+                to: 0x1234
+            }]
+        );
         Ok(())
     }
 
@@ -441,7 +1584,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x11);
         update_buffer(ram, (START_PC + 1) as usize, 0x23);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, 0x0123);
         assert_eq!(processor.stack_pointer, 0);
         Ok(())
@@ -455,10 +1598,18 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x21);
         update_buffer(ram, (START_PC + 1) as usize, 0x23);
-        processor.cycle();
+        let events = processor.events.subscribe();
+        processor.cycle()?;
         assert_eq!(processor.pc, 0x0123);
         assert_eq!(processor.stack_pointer, 1);
         assert_eq!(processor.stack[0], NEXT_PC);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [Event::Called {
+                from: START_PC,
+                to: 0x0123
+            }]
+        );
         Ok(())
     }
 
@@ -470,7 +1621,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x32);
         update_buffer(ram, (START_PC + 1) as usize, 0x01);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, SKIPPED_PC);
         Ok(())
     }
@@ -483,7 +1634,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x32);
         update_buffer(ram, (START_PC + 1) as usize, 0x00);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         Ok(())
     }
@@ -496,7 +1647,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x42);
         update_buffer(ram, (START_PC + 1) as usize, 0x01);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         Ok(())
     }
@@ -509,7 +1660,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x42);
         update_buffer(ram, (START_PC + 1) as usize, 0x00);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, SKIPPED_PC);
         Ok(())
     }
@@ -521,7 +1672,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x52);
         update_buffer(ram, (START_PC + 1) as usize, 0x20);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, SKIPPED_PC);
         Ok(())
     }
@@ -534,8 +1685,25 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x52);
         update_buffer(ram, (START_PC + 1) as usize, 0x90);
-        processor.cycle();
+        processor.cycle()?;
+        assert_eq!(processor.pc, NEXT_PC);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_5xy1_chip8x() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.quirks.chip8x = true;
+        processor.registers[0] = 0x35;
+        processor.registers[1] = 0x46;
+        update_buffer(ram, (START_PC) as usize, 0x50);
+        update_buffer(ram, (START_PC + 1) as usize, 0x11);
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
+        assert_eq!(processor.registers[0], 0x73);
         Ok(())
     }
 
@@ -547,7 +1715,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x92);
         update_buffer(ram, (START_PC + 1) as usize, 0x20);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         Ok(())
     }
@@ -560,7 +1728,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x92);
         update_buffer(ram, (START_PC + 1) as usize, 0x90);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, SKIPPED_PC);
         Ok(())
     }
@@ -573,7 +1741,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x63);
         update_buffer(ram, (START_PC + 1) as usize, 0xF0);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.registers[3], 0xF0);
         Ok(())
@@ -587,7 +1755,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x73);
         update_buffer(ram, (START_PC + 1) as usize, 0xFF);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.registers[3], 0xFF_u8.wrapping_add(1_u8));
         assert_eq!(processor.registers[0xF], 0);
@@ -602,7 +1770,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x83);
         update_buffer(ram, (START_PC + 1) as usize, 0xF0);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.registers[3], 0);
         assert_eq!(processor.registers[0xF], 0);
@@ -617,7 +1785,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x83);
         update_buffer(ram, (START_PC + 1) as usize, 0x81);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.registers[3], 4 | 1);
         assert_eq!(processor.registers[8], 4);
@@ -632,25 +1800,77 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x86);
         update_buffer(ram, (START_PC + 1) as usize, 0xA2);
-        processor.cycle();
-        assert_eq!(processor.pc, NEXT_PC);
+        processor.cycle()?;
+        assert_eq!(processor.pc, NEXT_PC);
+        assert_eq!(processor.registers[6], 5 & 3);
+        assert_eq!(processor.registers[0xA], 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_8xy3() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        update_buffer(ram, (START_PC) as usize, 0x86);
+        update_buffer(ram, (START_PC + 1) as usize, 0xA3);
+        processor.cycle()?;
+        assert_eq!(processor.pc, NEXT_PC);
+        assert_eq!(processor.registers[6], 5 ^ 3);
+        assert_eq!(processor.registers[0xA], 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_8xy1_vf_reset() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        update_buffer(ram, (START_PC) as usize, 0x83);
+        update_buffer(ram, (START_PC + 1) as usize, 0x81);
+        processor.registers[0xF] = 1;
+        processor.cycle()?;
+        assert_eq!(processor.registers[0xF], 1);
+
+        processor.pc = START_PC;
+        processor.quirks.vf_reset = true;
+        processor.cycle()?;
+        assert_eq!(processor.registers[3], 4 | 1);
+        assert_eq!(processor.registers[0xF], 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_8xy2_vf_reset() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        update_buffer(ram, (START_PC) as usize, 0x86);
+        update_buffer(ram, (START_PC + 1) as usize, 0xA2);
+        processor.registers[0xF] = 1;
+        processor.quirks.vf_reset = true;
+        processor.cycle()?;
         assert_eq!(processor.registers[6], 5 & 3);
-        assert_eq!(processor.registers[0xA], 5);
+        assert_eq!(processor.registers[0xF], 0);
         Ok(())
     }
 
     #[test]
-    fn test_opcode_8xy3() -> Result<(), &'static str> {
+    fn test_opcode_8xy3_vf_reset() -> Result<(), &'static str> {
         let mut processor = build_processor()?;
 
         let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
 
         update_buffer(ram, (START_PC) as usize, 0x86);
         update_buffer(ram, (START_PC + 1) as usize, 0xA3);
-        processor.cycle();
-        assert_eq!(processor.pc, NEXT_PC);
+        processor.registers[0xF] = 1;
+        processor.quirks.vf_reset = true;
+        processor.cycle()?;
         assert_eq!(processor.registers[6], 5 ^ 3);
-        assert_eq!(processor.registers[0xA], 5);
+        assert_eq!(processor.registers[0xF], 0);
         Ok(())
     }
 
@@ -662,7 +1882,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x86);
         update_buffer(ram, (START_PC + 1) as usize, 0xA4);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.registers[6], 5 + 3);
         assert_eq!(processor.registers[0xA], 5);
@@ -679,7 +1899,7 @@ mod tests {
         update_buffer(ram, (START_PC) as usize, 0x86);
         update_buffer(ram, (START_PC + 1) as usize, 0xA4);
         processor.registers[0xA] = 0xFF;
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.registers[6], 2);
         assert_eq!(processor.registers[0xA], 0xFF);
@@ -696,13 +1916,13 @@ mod tests {
         update_buffer(ram, (START_PC) as usize, 0x8A);
         update_buffer(ram, (START_PC + 1) as usize, 0x65);
         processor.registers[0xA] = 6;
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.registers[0xA], 3);
         assert_eq!(processor.registers[6], 3);
         assert_eq!(processor.registers[0xF], 1);
         processor.pc -= consts::OP_CODE_BYTES as u16;
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.registers[0xA], 0);
         assert_eq!(processor.registers[6], 3);
         assert_eq!(processor.registers[0xF], 1);
@@ -717,7 +1937,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x86);
         update_buffer(ram, (START_PC + 1) as usize, 0xA5);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.registers[6], 0xFE);
         assert_eq!(processor.registers[0xA], 5);
@@ -735,14 +1955,14 @@ mod tests {
         update_buffer(ram, (START_PC + 1) as usize, 0xA7);
         processor.registers[0x6] = 3;
         processor.registers[0xA] = 6;
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.registers[0x6], 3);
         assert_eq!(processor.registers[0xA], 6);
         assert_eq!(processor.registers[0xF], 1);
         processor.pc -= consts::OP_CODE_BYTES as u16;
         processor.registers[0xA] = 3;
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.registers[0x6], 0);
         assert_eq!(processor.registers[0xA], 3);
         assert_eq!(processor.registers[0xF], 1);
@@ -757,7 +1977,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0x8A);
         update_buffer(ram, (START_PC + 1) as usize, 0x67);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.registers[0xA], 0xFE);
         assert_eq!(processor.registers[0x6], 3);
@@ -774,14 +1994,14 @@ mod tests {
         processor.registers[0] = 0xFF;
         update_buffer(ram, (START_PC) as usize, 0x80);
         update_buffer(ram, (START_PC + 1) as usize, 0x6E);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.registers[0x0], 0xFE);
         assert_eq!(processor.registers[0x6], 3);
         assert_eq!(processor.registers[0xF], 1);
         processor.pc -= consts::OP_CODE_BYTES as u16;
         processor.registers[0] = 0x7F;
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.registers[0x0], 0xFE);
         assert_eq!(processor.registers[0x6], 3);
         assert_eq!(processor.registers[0xF], 0);
@@ -797,20 +2017,71 @@ mod tests {
         processor.registers[0] = 0xFF;
         update_buffer(ram, (START_PC) as usize, 0x80);
         update_buffer(ram, (START_PC + 1) as usize, 0x66);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.registers[0x0], 0x7F);
         assert_eq!(processor.registers[0x6], 3);
         assert_eq!(processor.registers[0xF], 1);
         processor.pc -= consts::OP_CODE_BYTES as u16;
         processor.registers[0] = 0xFE;
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.registers[0x0], 0x7F);
         assert_eq!(processor.registers[0x6], 3);
         assert_eq!(processor.registers[0xF], 0);
         Ok(())
     }
 
+    #[test]
+    fn test_opcode_8xy6_shift_vy() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.quirks.shift_vy = true;
+        processor.registers[0] = 0xFF;
+        update_buffer(ram, (START_PC) as usize, 0x80);
+        update_buffer(ram, (START_PC + 1) as usize, 0x66);
+        processor.cycle()?;
+        assert_eq!(processor.pc, NEXT_PC);
+        assert_eq!(processor.registers[0x0], 1);
+        assert_eq!(processor.registers[0x6], 3);
+        assert_eq!(processor.registers[0xF], 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_8xye_shift_vy() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.quirks.shift_vy = true;
+        processor.registers[0] = 0x01;
+        processor.registers[6] = 0x81;
+        update_buffer(ram, (START_PC) as usize, 0x80);
+        update_buffer(ram, (START_PC + 1) as usize, 0x6E);
+        processor.cycle()?;
+        assert_eq!(processor.pc, NEXT_PC);
+        assert_eq!(processor.registers[0x0], 0x02);
+        assert_eq!(processor.registers[0x6], 0x81);
+        assert_eq!(processor.registers[0xF], 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_8xy6_flag_overrides_vf() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.registers[0xF] = 0x03;
+        update_buffer(ram, (START_PC) as usize, 0x8F);
+        update_buffer(ram, (START_PC + 1) as usize, 0x06);
+        processor.cycle()?;
+        assert_eq!(processor.registers[0xF], 1);
+        Ok(())
+    }
+
     #[test]
     fn test_opcode_annn() -> Result<(), &'static str> {
         let mut processor = build_processor()?;
@@ -819,7 +2090,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0xA0);
         update_buffer(ram, (START_PC + 1) as usize, 0x12);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.idx_register, 0x0012);
         Ok(())
@@ -833,26 +2104,37 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0xB0);
         update_buffer(ram, (START_PC + 1) as usize, 0x12);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, 0x0012);
         Ok(())
     }
 
+    #[test]
+    fn test_opcode_bxnn_jump_vx() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.quirks.jump_vx = true;
+        processor.registers[0] = 0x10;
+        processor.registers[2] = 0x04;
+        update_buffer(ram, (START_PC) as usize, 0xB2);
+        update_buffer(ram, (START_PC + 1) as usize, 0x30);
+        processor.cycle()?;
+        assert_eq!(processor.pc, 0x0234);
+        Ok(())
+    }
+
     #[test]
     fn test_opcode_ex9e_press() -> Result<(), &'static str> {
         let mut processor = build_processor()?;
 
         let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
 
-        *processor
-            .keyboard_buffer
-            .as_ref()
-            .borrow_mut()
-            .buffer
-            .borrow_mut() = [1; consts::KEYBOARD_SIZE];
+        processor.keyboard_buffer.keys = 0xFFFF;
         update_buffer(ram, (START_PC) as usize, 0xE1);
         update_buffer(ram, (START_PC + 1) as usize, 0x9E);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, SKIPPED_PC);
         Ok(())
     }
@@ -865,7 +2147,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0xE1);
         update_buffer(ram, (START_PC + 1) as usize, 0x9E);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         Ok(())
     }
@@ -876,15 +2158,10 @@ mod tests {
 
         let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
 
-        *processor
-            .keyboard_buffer
-            .as_ref()
-            .borrow_mut()
-            .buffer
-            .borrow_mut() = [1; consts::KEYBOARD_SIZE];
+        processor.keyboard_buffer.keys = 0xFFFF;
         update_buffer(ram, (START_PC) as usize, 0xE1);
         update_buffer(ram, (START_PC + 1) as usize, 0xA1);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         Ok(())
     }
@@ -897,7 +2174,7 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0xE1);
         update_buffer(ram, (START_PC + 1) as usize, 0xA1);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, SKIPPED_PC);
         Ok(())
     }
@@ -911,9 +2188,203 @@ mod tests {
         processor.delay_timer = 10;
         update_buffer(ram, (START_PC) as usize, 0xF1);
         update_buffer(ram, (START_PC + 1) as usize, 0x07);
-        processor.cycle();
+        processor.cycle()?;
+        assert_eq!(processor.pc, NEXT_PC);
+        assert_eq!(processor.registers[1], 10);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tick_timers() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.delay_timer = 2;
+        processor.sound_timer = 1;
+        update_buffer(ram, (START_PC) as usize, 0x60);
+        processor.cycle()?;
+        assert_eq!(processor.delay_timer, 2);
+        assert_eq!(processor.sound_timer, 1);
+
+        processor.tick_timers();
+        assert_eq!(processor.delay_timer, 1);
+        assert_eq!(processor.sound_timer, 0);
+
+        processor.tick_timers();
+        processor.tick_timers();
+        assert_eq!(processor.delay_timer, 0);
+        assert_eq!(processor.sound_timer, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_dxyn_display_wait() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        // D015 twice in a row, drawing the 0 glyph at (0, 0)
+        for i in 0..2 {
+            update_buffer(ram, (START_PC + 2 * i) as usize, 0xD0);
+            update_buffer(ram, (START_PC + 2 * i + 1) as usize, 0x15);
+        }
+        // 1F04: spin on the following address
+        update_buffer(ram, (SKIPPED_PC) as usize, 0x1F);
+        update_buffer(ram, (SKIPPED_PC + 1) as usize, 0x04);
+        ram[0..5].clone_from_slice(&consts::FONT_SET[0..5]);
+        processor.quirks.display_wait = true;
+
+        processor.cycle()?;
+        assert_eq!(processor.pc, START_PC);
+        assert_eq!(processor.display_buffer.buffer[0][0], 0);
+
+        processor.run_frame(10)?;
         assert_eq!(processor.pc, NEXT_PC);
-        assert_eq!(processor.registers[1], 9);
+        assert_eq!(processor.display_buffer.buffer[0][0], 1);
+
+        processor.run_frame(10)?;
+        assert_eq!(processor.pc, SKIPPED_PC);
+        assert_eq!(processor.display_buffer.buffer[0][0], 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_frame() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        // 7001 repeated: V0 += 1
+        for i in 0..4 {
+            update_buffer(ram, (START_PC + 2 * i) as usize, 0x70);
+            update_buffer(ram, (START_PC + 2 * i + 1) as usize, 0x01);
+        }
+        processor.delay_timer = 5;
+        processor.run_frame(3)?;
+        assert_eq!(processor.registers[0], 3);
+        assert_eq!(processor.pc, START_PC + 6);
+        assert_eq!(processor.delay_timer, 4);
+        assert_eq!((processor.instructions, processor.frames), (3, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_cxnn_seeded() -> Result<(), &'static str> {
+        let mut values = Vec::new();
+        for _ in 0..2 {
+            let mut processor = build_processor()?;
+            processor.seed_rng(42);
+
+            let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+            // C0FF, C10F
+            update_buffer(ram, START_PC as usize, 0xC0);
+            update_buffer(ram, (START_PC + 1) as usize, 0xFF);
+            update_buffer(ram, NEXT_PC as usize, 0xC1);
+            update_buffer(ram, (NEXT_PC + 1) as usize, 0x0F);
+            processor.cycle()?;
+            processor.cycle()?;
+            assert!(processor.registers[1] <= 0x0F);
+            values.push((processor.registers[0], processor.registers[1]));
+        }
+        assert_eq!(values[0], values[1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_frame_until() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        // 7001 (v0 += 1), 1F00 (loop)
+        update_buffer(ram, START_PC as usize, 0x70);
+        update_buffer(ram, (START_PC + 1) as usize, 0x01);
+        update_buffer(ram, NEXT_PC as usize, 0x1F);
+        update_buffer(ram, (NEXT_PC + 1) as usize, 0x00);
+        processor.delay_timer = 5;
+
+        let stopped = processor.run_frame_until(10, |p| p.pc == NEXT_PC && p.registers[0] == 3)?;
+        assert!(stopped);
+        assert_eq!((processor.registers[0], processor.pc), (3, NEXT_PC));
+        assert_eq!((processor.delay_timer, processor.frames), (5, 0));
+
+        // Resuming runs the instruction it stopped in front of
+        assert!(!processor.run_frame_until(10, |p| p.pc == NEXT_PC && p.registers[0] == 3)?);
+        assert_eq!((processor.registers[0], processor.delay_timer), (8, 4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_polled_keys() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        // E19E (if v1 -key), E2A1 (if v2 key), then a frame running F00A (v0 := key)
+        update_buffer(ram, START_PC as usize, 0xE1);
+        update_buffer(ram, (START_PC + 1) as usize, 0x9E);
+        update_buffer(ram, NEXT_PC as usize, 0xE2);
+        update_buffer(ram, (NEXT_PC + 1) as usize, 0xA1);
+        update_buffer(ram, SKIPPED_PC as usize, 0xF0);
+        update_buffer(ram, (SKIPPED_PC + 1) as usize, 0x0A);
+        processor.registers[1] = 0x5;
+        processor.registers[2] = 0x1C;
+        processor.keyboard_buffer.keys = 1 << 0xC;
+        processor.cycle()?;
+        processor.cycle()?;
+        assert_eq!(processor.polled_keys, 1 << 0x5 | 1 << 0xC);
+        processor.run_frame(1)?;
+        assert_eq!(processor.polled_keys, u16::MAX);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pitch() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+        let events = processor.events.subscribe();
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        // F23A (pitch := v2)
+        update_buffer(ram, START_PC as usize, 0xF2);
+        update_buffer(ram, (START_PC + 1) as usize, 0x3A);
+        processor.registers[2] = 112;
+        assert_eq!(processor.pitch, Pitch(64));
+        processor.cycle()?;
+        assert_eq!(processor.pitch, Pitch(112));
+        assert_eq!(events.try_recv(), Ok(Event::PitchSet(Pitch(112))));
+        processor.reset();
+        assert_eq!(processor.pitch, Pitch(64));
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_frame_events() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+        let events = processor.events.subscribe();
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        // 00E0, F018 (buzzer := v0), F10A (v1 := key)
+        update_buffer(ram, START_PC as usize, 0x00);
+        update_buffer(ram, (START_PC + 1) as usize, 0xE0);
+        update_buffer(ram, NEXT_PC as usize, 0xF0);
+        update_buffer(ram, (NEXT_PC + 1) as usize, 0x18);
+        update_buffer(ram, SKIPPED_PC as usize, 0xF1);
+        update_buffer(ram, (SKIPPED_PC + 1) as usize, 0x0A);
+        processor.registers[0] = 10;
+
+        processor.run_frame(10)?;
+        processor.run_frame(10)?;
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [
+                Event::SoundTimerSet(10),
+                Event::KeyWaited,
+                Event::SoundStarted,
+                Event::FrameCompleted { redraw: true },
+                Event::FrameCompleted { redraw: false }
+            ]
+        );
         Ok(())
     }
 
@@ -926,7 +2397,7 @@ mod tests {
         processor.registers[1] = 10;
         update_buffer(ram, (START_PC) as usize, 0xF1);
         update_buffer(ram, (START_PC + 1) as usize, 0x15);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.delay_timer, 10);
         Ok(())
@@ -941,9 +2412,9 @@ mod tests {
         processor.registers[1] = 10;
         update_buffer(ram, (START_PC) as usize, 0xF1);
         update_buffer(ram, (START_PC + 1) as usize, 0x18);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
-        assert_eq!(*processor.sound_timer.as_ref().borrow(), 10);
+        assert_eq!(processor.sound_timer, 10);
         Ok(())
     }
 
@@ -957,13 +2428,36 @@ mod tests {
         processor.idx_register = 0xFFFF;
         update_buffer(ram, (START_PC) as usize, 0xF1);
         update_buffer(ram, (START_PC + 1) as usize, 0x1e);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.idx_register, 7);
         assert_eq!(processor.registers[0xF], 0);
         Ok(())
     }
 
+    #[test]
+    fn test_opcode_fx1e_carry() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.quirks.fx1e_carry = true;
+        processor.registers[1] = 0x8;
+        processor.idx_register = 0xFFA;
+        update_buffer(ram, (START_PC) as usize, 0xF1);
+        update_buffer(ram, (START_PC + 1) as usize, 0x1e);
+        update_buffer(ram, (START_PC + 2) as usize, 0xF1);
+        update_buffer(ram, (START_PC + 3) as usize, 0x1e);
+        processor.cycle()?;
+        assert_eq!(processor.idx_register, 0x1002);
+        assert_eq!(processor.registers[0xF], 1);
+        processor.idx_register = 0x100;
+        processor.cycle()?;
+        assert_eq!(processor.idx_register, 0x108);
+        assert_eq!(processor.registers[0xF], 0);
+        Ok(())
+    }
+
     #[test]
     fn test_opcode_fx0a() -> Result<(), &'static str> {
         let mut processor = build_processor()?;
@@ -972,17 +2466,12 @@ mod tests {
 
         update_buffer(ram, (START_PC) as usize, 0xF1);
         update_buffer(ram, (START_PC + 1) as usize, 0x0A);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, START_PC);
 
-        *processor
-            .keyboard_buffer
-            .as_ref()
-            .borrow_mut()
-            .buffer
-            .borrow_mut() = [1; consts::KEYBOARD_SIZE];
+        processor.keyboard_buffer.keys = 0xFFFF;
 
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.registers[1], 0);
         Ok(())
@@ -998,11 +2487,52 @@ mod tests {
         processor.registers[4] = 156;
         update_buffer(ram, (START_PC) as usize, 0xF4);
         update_buffer(ram, (START_PC + 1) as usize, 0x33);
-        processor.cycle();
+        let events = processor.events.subscribe();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.ram.buffer[25], 1);
         assert_eq!(processor.ram.buffer[26], 5);
         assert_eq!(processor.ram.buffer[27], 6);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [Event::MemoryWritten {
+                address: 25,
+                len: 3
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_fx29() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.registers[3] = 0xB;
+        update_buffer(ram, (START_PC) as usize, 0xF3);
+        update_buffer(ram, (START_PC + 1) as usize, 0x29);
+        processor.cycle()?;
+        assert_eq!(processor.pc, NEXT_PC);
+        assert_eq!(processor.idx_register, 55);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_fx30() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.registers[3] = 0x9;
+        update_buffer(ram, (START_PC) as usize, 0xF3);
+        update_buffer(ram, (START_PC + 1) as usize, 0x30);
+        processor.cycle()?;
+        assert_eq!(processor.pc, NEXT_PC);
+        assert_eq!(
+            processor.idx_register as usize,
+            consts::BIG_FONT_OFFSET + 9 * consts::BIG_FONT_GLYPH_BYTES
+        );
         Ok(())
     }
 
@@ -1019,13 +2549,42 @@ mod tests {
         processor.registers[4] = 14;
         update_buffer(ram, (START_PC) as usize, 0xF4);
         update_buffer(ram, (START_PC + 1) as usize, 0x55);
-        processor.cycle();
+        let events = processor.events.subscribe();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.ram.buffer[25], 12);
         assert_eq!(processor.ram.buffer[26], 25);
         assert_eq!(processor.ram.buffer[27], 13);
         assert_eq!(processor.ram.buffer[28], 1);
         assert_eq!(processor.ram.buffer[29], 14);
+        assert_eq!(processor.idx_register, 25);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [Event::MemoryWritten {
+                address: 25,
+                len: 5
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_fx55_memory_increment() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.quirks.memory_increment = true;
+        processor.idx_register = 25;
+        processor.registers[0] = 12;
+        processor.registers[1] = 25;
+        processor.registers[2] = 13;
+        update_buffer(ram, (START_PC) as usize, 0xF2);
+        update_buffer(ram, (START_PC + 1) as usize, 0x55);
+        processor.cycle()?;
+        assert_eq!(processor.pc, NEXT_PC);
+        assert_eq!(processor.ram.buffer[27], 13);
+        assert_eq!(processor.idx_register, 28);
         Ok(())
     }
 
@@ -1042,13 +2601,231 @@ mod tests {
         ram[4] = 14;
         update_buffer(ram, (START_PC) as usize, 0xF4);
         update_buffer(ram, (START_PC + 1) as usize, 0x65);
-        processor.cycle();
+        processor.cycle()?;
         assert_eq!(processor.pc, NEXT_PC);
         assert_eq!(processor.registers[0], 12);
         assert_eq!(processor.registers[1], 25);
         assert_eq!(processor.registers[2], 13);
         assert_eq!(processor.registers[3], 0);
         assert_eq!(processor.registers[4], 14);
+        assert_eq!(processor.idx_register, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_fx55_memory_increment_x() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.quirks.memory_increment_x = true;
+        processor.idx_register = 25;
+        update_buffer(ram, (START_PC) as usize, 0xF2);
+        update_buffer(ram, (START_PC + 1) as usize, 0x55);
+        processor.cycle()?;
+        assert_eq!(processor.pc, NEXT_PC);
+        assert_eq!(processor.idx_register, 27);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_fx65_memory_increment() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        processor.quirks.memory_increment = true;
+        processor.idx_register = 0;
+        ram[0] = 12;
+        ram[1] = 25;
+        update_buffer(ram, (START_PC) as usize, 0xF1);
+        update_buffer(ram, (START_PC + 1) as usize, 0x65);
+        processor.cycle()?;
+        assert_eq!(processor.pc, NEXT_PC);
+        assert_eq!(processor.registers[0], 12);
+        assert_eq!(processor.registers[1], 25);
+        assert_eq!(processor.idx_register, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_self_modifying_code() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+
+        // F155 overwrites the 00E0 that follows it with 6207
+        update_buffer(ram, START_PC as usize, 0xF1);
+        update_buffer(ram, (START_PC + 1) as usize, 0x55);
+        update_buffer(ram, NEXT_PC as usize, 0x00);
+        update_buffer(ram, (NEXT_PC + 1) as usize, 0xE0);
+        processor.registers[0] = 0x62;
+        processor.registers[1] = 0x07;
+        processor.idx_register = NEXT_PC;
+        processor.cycle()?;
+        processor.cycle()?;
+        assert_eq!(processor.registers[2], 0x07);
+        assert_eq!(processor.pc, SKIPPED_PC);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_cache() -> Result<(), &'static str> {
+        let mut processor = build_processor()?;
+
+        // An address that already ran is decoded again once memory changes under it
+        processor.ram.buffer[START_PC as usize..NEXT_PC as usize].copy_from_slice(&[0x61, 0x05]);
+        processor.cycle()?;
+        assert_eq!(processor.registers[1], 0x05);
+        processor.ram.buffer[START_PC as usize..NEXT_PC as usize].copy_from_slice(&[0x62, 0x07]);
+        processor.pc = START_PC;
+        assert_eq!(processor.cycle()?.instruction, Instruction::Set(2, 0x07));
+        assert_eq!(processor.registers[2], 0x07);
+
+        // And again under new quirks, B300 jumps by V3 rather than V0 under the jump quirk
+        processor.ram.buffer[START_PC as usize..NEXT_PC as usize].copy_from_slice(&[0xB3, 0x00]);
+        (processor.registers[0], processor.registers[3]) = (1, 3);
+        processor.pc = START_PC;
+        processor.cycle()?;
+        assert_eq!(processor.pc, 0x301);
+        processor.set_quirks(crate::core::quirks::Quirks {
+            jump_vx: true,
+            ..Default::default()
+        });
+        processor.pc = START_PC;
+        processor.cycle()?;
+        assert_eq!(processor.pc, 0x303);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_programs_error() -> Result<(), &'static str> {
+        // (opcode, I, stack pointer) combinations that used to panic on out of range indexing
+        let cases = [
+            (0x00EE, 0, 0),
+            (0x2300, 0, consts::STACK_SIZE as u8),
+            (0xD01F, 0xFF8, 0),
+            (0xF033, 0xFFE, 0),
+            (0xFF55, 0xFFF, 0),
+            (0xFF65, 0xFFFF, 0),
+            (0x0123, 0, 0),
+            (0x5001, 0, 0),
+        ];
+        for (opcode, idx_register, stack_pointer) in cases {
+            let mut processor = build_processor()?;
+            let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+            update_buffer(ram, START_PC as usize, (opcode >> 8) as u8);
+            update_buffer(ram, (START_PC + 1) as usize, opcode as u8);
+            processor.idx_register = idx_register;
+            processor.stack_pointer = stack_pointer;
+            assert!(processor.cycle().is_err(), "{:04X} did not fail", opcode);
+        }
+
+        let mut processor = build_processor()?;
+        processor.pc = (consts::RAM_BYTES - 1) as u16;
+        assert!(processor.cycle().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_random_programs_do_not_panic() -> Result<(), &'static str> {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0xC8);
+        for _ in 0..200 {
+            let program: Vec<u8> = (0..rng.gen_range(2..256)).map(|_| rng.gen()).collect();
+            let rom = rom::Rom::from_bytes(&program).map_err(|_| "Could not build ROM")?;
+            let mut processor = build_processor()?;
+            processor.load_rom(&rom)?;
+            processor.keyboard_buffer.set(rng.gen_range(0..16));
+            for _ in 0..30 {
+                if processor.run_frame(consts::CYCLES_PER_FRAME).is_err() {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Runs a single instruction with the given registers, for the property tests below
+    fn execute(
+        opcode: u16,
+        registers: [u8; 16],
+        shift_vy: bool,
+    ) -> Result<Processor, &'static str> {
+        let mut processor = build_processor()?;
+        let ram: &mut [u8] = processor.ram.buffer.borrow_mut();
+        update_buffer(ram, START_PC as usize, (opcode >> 8) as u8);
+        update_buffer(ram, (START_PC + 1) as usize, opcode as u8);
+        processor.registers = registers;
+        processor.idx_register = 0x300;
+        processor.quirks.shift_vy = shift_vy;
+        processor.cycle()?;
+        Ok(processor)
+    }
+
+    // Checks VX and VF after an ALU instruction, VF wins when it is also the destination
+    fn check_alu(
+        before: [u8; 16],
+        after: &Processor,
+        x: usize,
+        result: u8,
+        flag: u8,
+    ) -> Result<(), TestCaseError> {
+        for i in (0..consts::REG_COUNT).filter(|&i| i != x && i != 0xF) {
+            prop_assert_eq!(after.registers[i], before[i]);
+        }
+        if x != 0xF {
+            prop_assert_eq!(after.registers[x], result);
+        }
+        prop_assert_eq!(after.registers[0xF], flag);
         Ok(())
     }
+
+    proptest! {
+        #[test]
+        fn prop_opcode_8xy4_carry(x in 0..16_usize, y in 0..16_usize, registers in any::<[u8; 16]>()) {
+            let opcode = 0x8004 | (x << 8 | y << 4) as u16;
+            let processor = execute(opcode, registers, false).map_err(TestCaseError::fail)?;
+            let sum = registers[x] as u16 + registers[y] as u16;
+            check_alu(registers, &processor, x, sum as u8, (sum > 0xFF) as u8)?;
+        }
+
+        #[test]
+        fn prop_opcode_8xy5_8xy7_borrow(x in 0..16_usize, y in 0..16_usize, registers in any::<[u8; 16]>()) {
+            let (vx, vy) = (registers[x], registers[y]);
+            let opcode = 0x8005 | (x << 8 | y << 4) as u16;
+            let processor = execute(opcode, registers, false).map_err(TestCaseError::fail)?;
+            check_alu(registers, &processor, x, vx.wrapping_sub(vy), (vx >= vy) as u8)?;
+
+            let processor = execute(opcode | 0x2, registers, false).map_err(TestCaseError::fail)?;
+            check_alu(registers, &processor, x, vy.wrapping_sub(vx), (vy >= vx) as u8)?;
+        }
+
+        #[test]
+        fn prop_opcode_8xy6_8xye_shifts(
+            x in 0..16_usize,
+            y in 0..16_usize,
+            registers in any::<[u8; 16]>(),
+            shift_vy in any::<bool>(),
+        ) {
+            let source = if shift_vy { registers[y] } else { registers[x] };
+            let opcode = 0x8006 | (x << 8 | y << 4) as u16;
+            let processor = execute(opcode, registers, shift_vy).map_err(TestCaseError::fail)?;
+            check_alu(registers, &processor, x, source / 2, source % 2)?;
+
+            let processor = execute(opcode | 0x8, registers, shift_vy).map_err(TestCaseError::fail)?;
+            check_alu(registers, &processor, x, source.wrapping_mul(2), source / 0x80)?;
+        }
+
+        #[test]
+        fn prop_opcode_fx33_digits(x in 0..16_usize, registers in any::<[u8; 16]>()) {
+            let opcode = 0xF033 | (x << 8) as u16;
+            let processor = execute(opcode, registers, false).map_err(TestCaseError::fail)?;
+            let digits = &processor.ram.buffer[0x300..0x303];
+            prop_assert!(digits.iter().all(|&d| d < 10));
+            let value = digits.iter().fold(0_u16, |acc, &d| acc * 10 + d as u16);
+            prop_assert_eq!(value, registers[x] as u16);
+            prop_assert_eq!(processor.registers, registers);
+        }
+    }
 }
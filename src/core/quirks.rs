@@ -0,0 +1,70 @@
+// Behaviours that differ between CHIP-8 interpreters, all disabled by default which matches
+// the modern interpretation most ROMs expect
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Quirks {
+    // DXYN waits for the next 60Hz frame before drawing, as on the COSMAC VIP
+    pub display_wait: bool,
+    // 8XY1, 8XY2 and 8XY3 clear VF as a side effect of the VIP's ALU routines
+    pub vf_reset: bool,
+    // 8XY6 and 8XYE shift VY and store the result in VX instead of shifting VX in place
+    pub shift_vy: bool,
+    // FX55 and FX65 leave I pointing past the last register transferred
+    pub memory_increment: bool,
+    // FX55 and FX65 leave I incremented by X only, an off-by-one in the CHIP-48 interpreter
+    pub memory_increment_x: bool,
+    // BNNN is read as BXNN and jumps to XNN + VX, as on CHIP-48 and SUPER-CHIP
+    pub jump_vx: bool,
+    // FX1E sets VF when I overflows past 0xFFF, as on the Amiga interpreter
+    pub fx1e_carry: bool,
+    // CHIP-8X color commands 02A0, 5XY1 and BXYN are decoded, the latter replacing BNNN
+    pub chip8x: bool,
+    // Sprites wrap around the screen edges instead of being clipped, as on XO-CHIP
+    pub wrap: bool,
+    // Scrolling in lores mode moves by half the distance, as on SCHIP 1.1
+    pub half_scroll: bool,
+    // Hires DXYN sets VF to the number of rows that collided or were clipped, as on SCHIP 1.1
+    pub collision_rows: bool,
+}
+
+impl Quirks {
+    pub const NAMES: [&'static str; 11] = [
+        "display-wait",
+        "vf-reset",
+        "shift-vy",
+        "memory-increment",
+        "memory-increment-x",
+        "jump-vx",
+        "fx1e-carry",
+        "chip8x",
+        "wrap",
+        "half-scroll",
+        "collision-rows",
+    ];
+
+    pub fn flag_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "display-wait" => Some(&mut self.display_wait),
+            "vf-reset" => Some(&mut self.vf_reset),
+            "shift-vy" => Some(&mut self.shift_vy),
+            "memory-increment" => Some(&mut self.memory_increment),
+            "memory-increment-x" => Some(&mut self.memory_increment_x),
+            "jump-vx" => Some(&mut self.jump_vx),
+            "fx1e-carry" => Some(&mut self.fx1e_carry),
+            "chip8x" => Some(&mut self.chip8x),
+            "wrap" => Some(&mut self.wrap),
+            "half-scroll" => Some(&mut self.half_scroll),
+            "collision-rows" => Some(&mut self.collision_rows),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        match self.flag_mut(name) {
+            Some(flag) => {
+                *flag = enabled;
+                Ok(())
+            }
+            None => Err(format!("Unknown quirk {}", name)),
+        }
+    }
+}
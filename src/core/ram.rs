@@ -13,20 +13,252 @@ impl Default for Ram {
     }
 }
 
-#[derive(Debug)]
+// Bounds checked access for tools that inspect or patch memory from outside the program
+impl Ram {
+    pub fn read(&self, address: usize) -> Option<u8> {
+        self.buffer.get(address).copied()
+    }
+    pub fn write(&mut self, address: usize, value: u8) -> Result<(), &'static str> {
+        match self.buffer.get_mut(address) {
+            Some(byte) => {
+                *byte = value;
+                Ok(())
+            }
+            None => Err("Address past the end of RAM"),
+        }
+    }
+    // rows * width bytes with address roughly in the middle, starting on a row boundary and
+    // kept inside RAM. Returns the address of the first byte along with them
+    pub fn rows_around(&self, address: usize, rows: usize, width: usize) -> (usize, &[u8]) {
+        let start = (address.min(consts::RAM_BYTES - 1) / width)
+            .saturating_sub(rows / 2)
+            .min((consts::RAM_BYTES / width).saturating_sub(rows))
+            * width;
+        let end = (start + rows * width).min(consts::RAM_BYTES);
+        (start, &self.buffer[start..end])
+    }
+    // Copies a memory dump back in at address. Restoring one at the wrong place easily
+    // clobbers the fonts below the program, so writing over them needs force
+    pub fn load(&mut self, address: usize, bytes: &[u8], force: bool) -> Result<(), String> {
+        let end = address + bytes.len();
+        if end > consts::RAM_BYTES {
+            return Err(format!(
+                "{} bytes at {:#X} run past the end of memory",
+                bytes.len(),
+                address
+            ));
+        }
+        let fonts_end = consts::BIG_FONT_OFFSET + consts::BIG_FONT_SET_SIZE;
+        if address < fonts_end && !bytes.is_empty() && !force {
+            return Err(String::from(
+                "Refusing to overwrite the fonts, add force to do it anyway",
+            ));
+        }
+        self.buffer[address..end].copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Resolution {
+    #[default]
+    Low,
+    High,
+    Eti660,
+}
+
+impl Resolution {
+    pub fn dimensions(&self) -> (usize, usize) {
+        match self {
+            Resolution::Low => (consts::CHIP8_WIDTH, consts::CHIP8_HEIGHT),
+            Resolution::High => (consts::HIRES_WIDTH, consts::HIRES_HEIGHT),
+            Resolution::Eti660 => (consts::CHIP8_WIDTH, consts::ETI660_HEIGHT),
+        }
+    }
+}
+
+// CHIP-8X color attributes, the background is an index into CHIP8X_BACKGROUNDS and every
+// pixel has its own foreground palette entry
+#[derive(Clone, Debug)]
+pub struct ColorLayer {
+    pub background: usize,
+    pub foreground: Vec<Vec<u8>>,
+}
+
+impl ColorLayer {
+    pub fn new(width: usize, height: usize) -> Self {
+        ColorLayer {
+            background: 0,
+            foreground: vec![vec![consts::CHIP8X_FOREGROUND; width]; height],
+        }
+    }
+    pub fn background_color(&self) -> u8 {
+        consts::CHIP8X_BACKGROUNDS[self.background]
+    }
+    pub fn next_background(&mut self) {
+        self.background = (self.background + 1) % consts::CHIP8X_BACKGROUNDS.len();
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct DisplayBuffer {
-    pub buffer: [[u8; consts::CHIP8_WIDTH]; consts::CHIP8_HEIGHT],
+    pub resolution: Resolution,
+    pub buffer: Vec<Vec<u8>>,
+    // Only present once a CHIP-8X program has used a color command
+    pub colors: Option<ColorLayer>,
 }
 
 impl Default for DisplayBuffer {
     fn default() -> Self {
+        DisplayBuffer::new(Resolution::Low)
+    }
+}
+
+impl DisplayBuffer {
+    pub fn new(resolution: Resolution) -> Self {
+        let (width, height) = resolution.dimensions();
         DisplayBuffer {
-            buffer: [[0; consts::CHIP8_WIDTH]; consts::CHIP8_HEIGHT],
+            resolution,
+            buffer: vec![vec![0; width]; height],
+            colors: None,
         }
     }
+    pub fn width(&self) -> usize {
+        self.resolution.dimensions().0
+    }
+    pub fn height(&self) -> usize {
+        self.resolution.dimensions().1
+    }
+    pub fn clear(&mut self) {
+        self.buffer.iter_mut().for_each(|row| row.fill(0));
+    }
+    // Whether each pixel is lit, row by row from the top left, as (x, y, lit)
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize, bool)> + '_ {
+        self.buffer.iter().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(move |(x, &pixel)| (x, y, pixel != 0))
+        })
+    }
+    // One character per pixel and one line per row, handy for bug reports and golden tests
+    pub fn to_ascii(&self) -> String {
+        let mut text = String::with_capacity((self.width() + 1) * self.height());
+        for (x, _, lit) in self.pixels() {
+            text.push(if lit { '#' } else { '.' });
+            if x == self.width() - 1 {
+                text.push('\n');
+            }
+        }
+        text
+    }
+    // Plain text PBM, where 1 is black, so lit pixels come out black on white
+    pub fn to_pbm(&self) -> String {
+        let mut text = format!("P1\n{} {}\n", self.width(), self.height());
+        for (x, _, lit) in self.pixels() {
+            text.push(if lit { '1' } else { '0' });
+            text.push(if x == self.width() - 1 { '\n' } else { ' ' });
+        }
+        text
+    }
+    pub fn scroll_down(&mut self, rows: usize) {
+        let rows = rows.min(self.height());
+        self.buffer.rotate_right(rows);
+        self.buffer[..rows].iter_mut().for_each(|row| row.fill(0));
+    }
+    pub fn scroll_left(&mut self, cols: usize) {
+        let cols = cols.min(self.width());
+        for row in self.buffer.iter_mut() {
+            row.rotate_left(cols);
+            let width = row.len();
+            row[width - cols..].fill(0);
+        }
+    }
+    pub fn scroll_right(&mut self, cols: usize) {
+        let cols = cols.min(self.width());
+        for row in self.buffer.iter_mut() {
+            row.rotate_right(cols);
+            row[..cols].fill(0);
+        }
+    }
+    pub fn colors_mut(&mut self) -> &mut ColorLayer {
+        let (width, height) = self.resolution.dimensions();
+        self.colors
+            .get_or_insert_with(|| ColorLayer::new(width, height))
+    }
+    // Switching modes reallocates the buffer, which also clears the screen
+    pub fn set_resolution(&mut self, resolution: Resolution) {
+        *self = DisplayBuffer::new(resolution);
+    }
 }
 
-#[derive(Default, Debug)]
+// Bit n is set while key n is held
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
 pub struct KeyboardBuffer {
-    pub buffer: [u8; consts::KEYBOARD_SIZE],
+    pub keys: u16,
+}
+
+impl KeyboardBuffer {
+    pub fn is_pressed(&self, key: usize) -> bool {
+        self.keys & (1 << key) != 0
+    }
+    pub fn set(&mut self, key: usize) {
+        self.keys |= 1 << key;
+    }
+    pub fn clear(&mut self, key: usize) {
+        self.keys &= !(1 << key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DisplayBuffer, Ram, Resolution};
+    use crate::consts;
+
+    #[test]
+    fn test_display_export() {
+        let mut display = DisplayBuffer::new(Resolution::Low);
+        display.buffer[0][1] = 1;
+        display.buffer[31][63] = 1;
+        let ascii = display.to_ascii();
+        let lines: Vec<&str> = ascii.lines().collect();
+        assert_eq!(lines.len(), 32);
+        assert_eq!(&lines[0][..3], ".#.");
+        assert!(lines[31].ends_with(".#"));
+        let pbm = display.to_pbm();
+        assert!(pbm.starts_with("P1\n64 32\n0 1 0 0"));
+        assert!(pbm.ends_with("0 1\n"));
+        assert_eq!(display.pixels().filter(|&(_, _, lit)| lit).count(), 2);
+    }
+
+    #[test]
+    fn test_read_write() {
+        let mut ram = Ram::default();
+        assert_eq!(ram.write(0x200, 0xAB), Ok(()));
+        assert_eq!(ram.read(0x200), Some(0xAB));
+        assert!(ram.write(consts::RAM_BYTES, 0).is_err());
+        assert_eq!(ram.read(consts::RAM_BYTES), None);
+    }
+
+    #[test]
+    fn test_rows_around() {
+        let ram = Ram::default();
+        let (start, bytes) = ram.rows_around(0x203, 4, 8);
+        assert_eq!((start, bytes.len()), (0x1F0, 32));
+        assert_eq!(ram.rows_around(0x001, 4, 8).0, 0);
+        assert_eq!(ram.rows_around(0xFFF, 4, 8).0, consts::RAM_BYTES - 32);
+        assert_eq!(ram.rows_around(0xFFFF, 4, 8).0, consts::RAM_BYTES - 32);
+    }
+
+    #[test]
+    fn test_load() {
+        let mut ram = Ram::default();
+        assert_eq!(ram.load(0x300, &[1, 2, 3], false), Ok(()));
+        assert_eq!(ram.buffer[0x300..0x303], [1, 2, 3]);
+        assert!(ram.load(0xFFE, &[1, 2, 3], false).is_err());
+        assert!(ram.load(0, &[0; consts::RAM_BYTES], false).is_err());
+        assert!(ram.load(0xEF, &[9], false).is_err());
+        assert_eq!(ram.load(0xF0, &[9], false), Ok(()));
+        assert_eq!(ram.load(0, &[7; consts::RAM_BYTES], true), Ok(()));
+        assert_eq!(ram.buffer[0xFFF], 7);
+    }
 }
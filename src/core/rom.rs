@@ -1,29 +1,59 @@
 use std::fs::File;
 use std::io::prelude::*;
 
+use crate::assembler;
 use crate::consts;
 
 #[derive(Debug)]
 pub struct Rom {
     pub buffer: [u8; consts::MAX_ROM_BYTES],
+    // Number of bytes actually loaded into buffer
+    pub size: usize,
+    // Hex SHA-1 of the file contents, identifies the game in the ROM database
+    pub sha1: String,
 }
 
 impl Default for Rom {
     fn default() -> Self {
         Rom {
             buffer: [0; consts::MAX_ROM_BYTES],
+            size: 0,
+            sha1: String::new(),
         }
     }
 }
 
 impl Rom {
+    // Reads the ROM from a file, or from stdin when the path is -. Octo sources (.8o) are
+    // assembled first
     pub fn new(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let mut data: Rom = Default::default();
-        let mut file = File::open(path)?;
-        let status = file.read(&mut data.buffer)?;
-        if status <= data.buffer.len() {
-            return Ok(data);
+        let mut contents = Vec::new();
+        if path == "-" {
+            std::io::stdin().read_to_end(&mut contents)?;
+        } else {
+            File::open(path)?.read_to_end(&mut contents)?;
+        }
+        if path.ends_with(".8o") {
+            let source = String::from_utf8(contents)?;
+            let program = assembler::assemble(&source).map_err(|e| format!("{}: {}", path, e))?;
+            return Rom::from_bytes(&program);
+        }
+        Rom::from_bytes(&contents)
+    }
+
+    pub fn from_bytes(contents: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        if contents.len() > consts::MAX_ROM_BYTES {
+            return Err(format!(
+                "ROM is {} bytes, at most {} fit in memory",
+                contents.len(),
+                consts::MAX_ROM_BYTES
+            )
+            .into());
         }
-        Err("Mismatch in expected ROM size and number of bytes read".into())
+        let mut data: Rom = Default::default();
+        data.buffer[..contents.len()].clone_from_slice(contents);
+        data.size = contents.len();
+        data.sha1 = sha1_smol::Sha1::from(contents).digest().to_string();
+        Ok(data)
     }
 }
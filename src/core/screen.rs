@@ -0,0 +1,10 @@
+use crate::core::ram::DisplayBuffer;
+
+// Where a frontend shows the display buffer, so a loop can drive an SDL window or a small
+// panel through embedded-graphics the same way
+pub trait Screen {
+    type Error;
+
+    // Shows the whole buffer, called once per frame that changed the display
+    fn show(&mut self, display: &DisplayBuffer) -> Result<(), Self::Error>;
+}
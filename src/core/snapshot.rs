@@ -0,0 +1,476 @@
+use crate::consts;
+use crate::core::events::Event;
+use crate::core::processor::Pitch;
+use crate::core::{processor, ram};
+
+// Save files are a container that older and newer versions can make sense of:
+//   "C8ST", format version byte, SHA-1 of the ROM as 40 hex digits
+//   chunks of a 4 byte tag, a 32 bit little endian length and that many bytes
+//   SHA-1 of everything before it
+// MACH holds the machine state and THMB an optional preview. Readers skip tags they do not
+// know, so new chunks can be added without a version bump, which is kept for changes older
+// readers cannot handle
+const MAGIC: &[u8; 4] = b"C8ST";
+const VERSION: u8 = 3;
+// Version 2 states end MACH before the pitch and play at the default one
+const OLDEST_VERSION: u8 = 2;
+const ROM_HASH_BYTES: usize = 40;
+const HEADER_BYTES: usize = MAGIC.len() + 1 + ROM_HASH_BYTES;
+const CHECKSUM_BYTES: usize = 20;
+const CHUNK_TAG_BYTES: usize = 4;
+const MACHINE_TAG: &[u8] = b"MACH";
+const THUMBNAIL_TAG: &[u8] = b"THMB";
+
+// ROM hashes are stored fixed size, anything that is not a SHA-1 ends up as zeroes
+fn rom_hash(sha1: &str) -> [u8; ROM_HASH_BYTES] {
+    let mut hash = [0; ROM_HASH_BYTES];
+    if sha1.len() == ROM_HASH_BYTES {
+        hash.copy_from_slice(sha1.to_ascii_lowercase().as_bytes());
+    }
+    hash
+}
+
+fn push_chunk(bytes: &mut Vec<u8>, tag: &[u8], chunk: &[u8]) {
+    bytes.extend_from_slice(tag);
+    bytes.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(chunk);
+}
+
+// Small picture of the screen saved along with a state for load pickers, pixels are
+// row-major RGB
+#[derive(Clone, Debug, PartialEq)]
+pub struct Thumbnail {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Thumbnail {
+    // Scales a row-major RGB screenshot down to at most THUMBNAIL_WIDTH pixels across by
+    // averaging blocks of pixels, so thin hires lines fade instead of disappearing
+    pub fn from_rgb(width: usize, height: usize, rgb: &[u8]) -> Self {
+        let factor = (width / consts::THUMBNAIL_WIDTH).max(1);
+        let (small_width, small_height) = (width / factor, height / factor);
+        let mut pixels = Vec::with_capacity(small_width * small_height * 3);
+        for y in 0..small_height {
+            for x in 0..small_width {
+                for channel in 0..3 {
+                    let mut sum = 0;
+                    for dy in 0..factor {
+                        for dx in 0..factor {
+                            let pixel = (y * factor + dy) * width + x * factor + dx;
+                            sum += rgb[pixel * 3 + channel] as usize;
+                        }
+                    }
+                    pixels.push((sum / (factor * factor)) as u8);
+                }
+            }
+        }
+        Thumbnail {
+            width: small_width,
+            height: small_height,
+            pixels,
+        }
+    }
+}
+
+// In-memory copy of the machine state, backs save and load state in the frontends
+#[derive(Clone)]
+pub struct Snapshot {
+    stack: [u16; consts::STACK_SIZE],
+    registers: [u8; consts::REG_COUNT],
+    idx_register: u16,
+    pc: u16,
+    stack_pointer: u8,
+    delay_timer: u8,
+    sound_timer: u8,
+    pitch: Pitch,
+    ram: [u8; consts::RAM_BYTES],
+    display: ram::DisplayBuffer,
+    // Only added by frontends that save to disk, restoring ignores it
+    pub thumbnail: Option<Thumbnail>,
+}
+
+impl Snapshot {
+    pub fn capture(chip8: &processor::Processor) -> Self {
+        Snapshot {
+            stack: chip8.stack,
+            registers: chip8.registers,
+            idx_register: chip8.idx_register,
+            pc: chip8.pc,
+            stack_pointer: chip8.stack_pointer,
+            delay_timer: chip8.delay_timer,
+            sound_timer: chip8.sound_timer,
+            pitch: chip8.pitch,
+            ram: chip8.ram.buffer,
+            display: chip8.display_buffer.clone(),
+            thumbnail: None,
+        }
+    }
+
+    // The frontends hear about the new sound timer and pitch the same way as when the
+    // program sets them, so a beep that was playing stops and a restored one starts
+    pub fn restore(&self, chip8: &mut processor::Processor) {
+        chip8.stack = self.stack;
+        chip8.registers = self.registers;
+        chip8.idx_register = self.idx_register;
+        chip8.pc = self.pc;
+        chip8.stack_pointer = self.stack_pointer;
+        chip8.delay_timer = self.delay_timer;
+        chip8.sound_timer = self.sound_timer;
+        chip8.pitch = self.pitch;
+        chip8.ram.buffer = self.ram;
+        chip8.display_buffer = self.display.clone();
+        chip8.events.publish(Event::SoundTimerSet(self.sound_timer));
+        chip8.events.publish(Event::PitchSet(self.pitch));
+    }
+
+    // Save file for the ROM with the given SHA-1, see the container layout above
+    pub fn to_bytes(&self, rom_sha1: &str) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&rom_hash(rom_sha1));
+        push_chunk(&mut bytes, MACHINE_TAG, &self.machine_bytes());
+        if let Some(thumbnail) = &self.thumbnail {
+            let mut chunk = Vec::with_capacity(4 + thumbnail.pixels.len());
+            chunk.extend_from_slice(&(thumbnail.width as u16).to_le_bytes());
+            chunk.extend_from_slice(&(thumbnail.height as u16).to_le_bytes());
+            chunk.extend_from_slice(&thumbnail.pixels);
+            push_chunk(&mut bytes, THUMBNAIL_TAG, &chunk);
+        }
+        let checksum = sha1_smol::Sha1::from(&bytes).digest().bytes();
+        bytes.extend_from_slice(&checksum);
+        bytes
+    }
+
+    // Refuses anything that is not an intact state of the same ROM, so a shared or damaged
+    // file can never put the machine into a state the program did not get to on its own
+    pub fn from_bytes(bytes: &[u8], rom_sha1: &str) -> Result<Self, &'static str> {
+        if bytes.len() < HEADER_BYTES + CHECKSUM_BYTES || bytes[..MAGIC.len()] != MAGIC[..] {
+            return Err("Not a save state");
+        }
+        let version = match bytes[MAGIC.len()] {
+            version @ OLDEST_VERSION..=VERSION => version,
+            version if version > VERSION => {
+                return Err("Save state was written by a newer version of the emulator")
+            }
+            _ => return Err("Save state uses an older format that is no longer supported"),
+        };
+        let (contents, checksum) = bytes.split_at(bytes.len() - CHECKSUM_BYTES);
+        if sha1_smol::Sha1::from(contents).digest().bytes() != checksum {
+            return Err("Save state is corrupt");
+        }
+        if contents[MAGIC.len() + 1..HEADER_BYTES] != rom_hash(rom_sha1) {
+            return Err("Save state belongs to a different ROM");
+        }
+        let mut reader = Reader {
+            bytes: contents,
+            position: HEADER_BYTES,
+        };
+        let (mut snapshot, mut thumbnail) = (None, None);
+        while reader.position < contents.len() {
+            let tag = reader.take(CHUNK_TAG_BYTES)?;
+            let len = u32::from_le_bytes(reader.take(4)?.try_into().unwrap_or_default());
+            let chunk = reader.take(len as usize)?;
+            match tag {
+                MACHINE_TAG => snapshot = Some(Snapshot::from_machine_bytes(chunk, version)?),
+                THUMBNAIL_TAG => {
+                    let mut chunk = Reader {
+                        bytes: chunk,
+                        position: 0,
+                    };
+                    let (width, height) = (chunk.word()? as usize, chunk.word()? as usize);
+                    let pixels = chunk.take(width * height * 3)?.to_vec();
+                    thumbnail = Some(Thumbnail {
+                        width,
+                        height,
+                        pixels,
+                    });
+                }
+                // Chunks added by later versions are skipped
+                _ => {}
+            }
+        }
+        let mut snapshot = snapshot.ok_or("Save state has no machine state")?;
+        snapshot.thumbnail = thumbnail;
+        Ok(snapshot)
+    }
+
+    // Registers, memory and display, multi-byte values are little endian
+    fn machine_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for value in self.stack {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.registers);
+        bytes.extend_from_slice(&self.idx_register.to_le_bytes());
+        bytes.extend_from_slice(&self.pc.to_le_bytes());
+        bytes.extend_from_slice(&[self.stack_pointer, self.delay_timer, self.sound_timer]);
+        bytes.extend_from_slice(&self.ram);
+        bytes.push(match self.display.resolution {
+            ram::Resolution::Low => 0,
+            ram::Resolution::High => 1,
+            ram::Resolution::Eti660 => 2,
+        });
+        bytes.extend(self.display.buffer.iter().flatten());
+        match &self.display.colors {
+            Some(colors) => {
+                bytes.push(colors.background as u8);
+                bytes.extend(colors.foreground.iter().flatten());
+            }
+            // No CHIP-8X background index goes this high
+            None => bytes.push(u8::MAX),
+        }
+        bytes.push(self.pitch.0);
+        bytes
+    }
+
+    fn from_machine_bytes(bytes: &[u8], version: u8) -> Result<Self, &'static str> {
+        let mut reader = Reader { bytes, position: 0 };
+        let mut stack = [0; consts::STACK_SIZE];
+        for value in stack.iter_mut() {
+            *value = reader.word()?;
+        }
+        let mut registers = [0; consts::REG_COUNT];
+        registers.copy_from_slice(reader.take(consts::REG_COUNT)?);
+        let (idx_register, pc) = (reader.word()?, reader.word()?);
+        let (stack_pointer, delay_timer, sound_timer) =
+            (reader.byte()?, reader.byte()?, reader.byte()?);
+        if stack_pointer as usize > consts::STACK_SIZE {
+            return Err("Save state has an invalid stack pointer");
+        }
+        let mut memory = [0; consts::RAM_BYTES];
+        memory.copy_from_slice(reader.take(consts::RAM_BYTES)?);
+        let resolution = match reader.byte()? {
+            0 => ram::Resolution::Low,
+            1 => ram::Resolution::High,
+            2 => ram::Resolution::Eti660,
+            _ => return Err("Save state has an unknown display resolution"),
+        };
+        let mut display = ram::DisplayBuffer::new(resolution);
+        let width = display.width();
+        for row in display.buffer.iter_mut() {
+            row.copy_from_slice(reader.take(width)?);
+        }
+        let background = reader.byte()? as usize;
+        if background != u8::MAX as usize {
+            if background >= consts::CHIP8X_BACKGROUNDS.len() {
+                return Err("Save state has an unknown background color");
+            }
+            let colors = display.colors_mut();
+            colors.background = background;
+            for row in colors.foreground.iter_mut() {
+                row.copy_from_slice(reader.take(width)?);
+            }
+        }
+        let pitch = match version {
+            OLDEST_VERSION => Pitch::default(),
+            _ => Pitch(reader.byte()?),
+        };
+        if reader.position != bytes.len() {
+            return Err("Save state has trailing data");
+        }
+        Ok(Snapshot {
+            stack,
+            registers,
+            idx_register,
+            pc,
+            stack_pointer,
+            delay_timer,
+            sound_timer,
+            pitch,
+            ram: memory,
+            display,
+            thumbnail: None,
+        })
+    }
+
+    // Registers and display as a JSON object for state streaming, pixels are given row by
+    // row as one digit each
+    pub fn to_json(&self) -> String {
+        let list = |values: Vec<String>| values.join(",");
+        let pixels: String = self
+            .display
+            .buffer
+            .iter()
+            .flatten()
+            .map(|p| char::from_digit(*p as u32, 10).unwrap_or('0'))
+            .collect();
+        format!(
+            "{{\"pc\":{},\"i\":{},\"sp\":{},\"delay\":{},\"sound\":{},\"registers\":[{}],\"stack\":[{}],\"width\":{},\"height\":{},\"display\":\"{}\"}}",
+            self.pc,
+            self.idx_register,
+            self.stack_pointer,
+            self.delay_timer,
+            self.sound_timer,
+            list(self.registers.iter().map(|v| v.to_string()).collect()),
+            list(self.stack[..self.stack_pointer as usize].iter().map(|v| v.to_string()).collect()),
+            self.display.width(),
+            self.display.height(),
+            pixels
+        )
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + len)
+            .ok_or("Save state is truncated")?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, &'static str> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn word(&mut self) -> Result<u16, &'static str> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{push_chunk, Snapshot, Thumbnail, CHECKSUM_BYTES, HEADER_BYTES};
+    use crate::core::events::Event;
+    use crate::core::processor::{Pitch, Processor};
+    use crate::core::ram::Resolution;
+
+    const ROM: &str = "0a1b2c3d4e5f60718293a4b5c6d7e8f901234567";
+
+    #[test]
+    fn test_snapshot_json() {
+        let mut chip8: Processor = Default::default();
+        chip8.pc = 0x200;
+        chip8.registers[1] = 7;
+        chip8.display_buffer.buffer[0][1] = 1;
+        let json = Snapshot::capture(&chip8).to_json();
+        assert!(json.starts_with("{\"pc\":512,\"i\":0,\"sp\":0,"));
+        assert!(json.contains("\"registers\":[0,7,0,"));
+        assert!(json.contains("\"stack\":[],\"width\":64,\"height\":32,\"display\":\"01000"));
+    }
+
+    #[test]
+    fn test_snapshot_bytes() -> Result<(), &'static str> {
+        let mut chip8: Processor = Default::default();
+        chip8.pc = 0x2A4;
+        chip8.idx_register = 0x345;
+        chip8.stack[0] = 0x202;
+        chip8.stack_pointer = 1;
+        chip8.registers[0xF] = 1;
+        chip8.ram.buffer[0xFFF] = 0xAB;
+        chip8.display_buffer.set_resolution(Resolution::High);
+        chip8.display_buffer.buffer[63][127] = 1;
+        chip8.display_buffer.colors_mut().background = 2;
+        chip8.pitch = Pitch(112);
+        chip8.sound_timer = 20;
+        let bytes = Snapshot::capture(&chip8).to_bytes(ROM);
+
+        let mut restored: Processor = Default::default();
+        let events = restored.events.subscribe();
+        Snapshot::from_bytes(&bytes, ROM)?.restore(&mut restored);
+        assert_eq!((restored.sound_timer, restored.pitch), (20, Pitch(112)));
+        assert_eq!(events.try_recv(), Ok(Event::SoundTimerSet(20)));
+        assert_eq!(events.try_recv(), Ok(Event::PitchSet(Pitch(112))));
+        assert_eq!(restored.pc, 0x2A4);
+        assert_eq!(restored.idx_register, 0x345);
+        assert_eq!(restored.stack[..1], [0x202]);
+        assert_eq!(restored.stack_pointer, 1);
+        assert_eq!(restored.registers[0xF], 1);
+        assert_eq!(restored.ram.buffer[0xFFF], 0xAB);
+        assert_eq!(restored.display_buffer.resolution, Resolution::High);
+        assert_eq!(restored.display_buffer.buffer[63][127], 1);
+        assert_eq!(
+            restored.display_buffer.colors.map(|c| c.background),
+            Some(2)
+        );
+
+        let mut snapshot = Snapshot::capture(&chip8);
+        snapshot.thumbnail = Some(Thumbnail::from_rgb(2, 1, &[1, 2, 3, 4, 5, 6]));
+        let restored = Snapshot::from_bytes(&snapshot.to_bytes(ROM), ROM)?;
+        assert_eq!(restored.thumbnail, snapshot.thumbnail);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejected_states() {
+        let bytes = Snapshot::capture(&Processor::default()).to_bytes(ROM);
+        let error = |bytes: &[u8], rom| Snapshot::from_bytes(bytes, rom).err();
+        assert_eq!(error(b"C8SX", ROM), Some("Not a save state"));
+        let other = ROM.replace('a', "b");
+        assert_eq!(
+            error(&bytes, &other),
+            Some("Save state belongs to a different ROM")
+        );
+        let mut corrupt = bytes.clone();
+        corrupt[100] ^= 1;
+        assert_eq!(error(&corrupt, ROM), Some("Save state is corrupt"));
+        assert_eq!(
+            error(&bytes[..bytes.len() - 1], ROM),
+            Some("Save state is corrupt")
+        );
+        let mut newer = bytes.clone();
+        newer[4] += 1;
+        assert_eq!(
+            error(&newer, ROM),
+            Some("Save state was written by a newer version of the emulator")
+        );
+    }
+
+    #[test]
+    fn test_version_2_states() -> Result<(), &'static str> {
+        let mut chip8 = Processor::default();
+        chip8.pitch = Pitch(112);
+        chip8.registers[3] = 9;
+        let bytes = Snapshot::capture(&chip8).to_bytes(ROM);
+        // The same state as version 2 wrote it, MACH is the first chunk and lacks the pitch
+        let mut contents = bytes[..bytes.len() - CHECKSUM_BYTES].to_vec();
+        contents[4] = 2;
+        let len_at = HEADER_BYTES + 4;
+        let len = u32::from_le_bytes(contents[len_at..len_at + 4].try_into().unwrap());
+        contents[len_at..len_at + 4].copy_from_slice(&(len - 1).to_le_bytes());
+        contents.remove(len_at + 4 + len as usize - 1);
+        let checksum = sha1_smol::Sha1::from(&contents).digest().bytes();
+        contents.extend_from_slice(&checksum);
+
+        let mut restored = Processor::default();
+        restored.pitch = Pitch(0);
+        Snapshot::from_bytes(&contents, ROM)?.restore(&mut restored);
+        assert_eq!(
+            (restored.registers[3], restored.pitch),
+            (9, Pitch::default())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_chunks_are_skipped() -> Result<(), &'static str> {
+        let bytes = Snapshot::capture(&Processor::default()).to_bytes(ROM);
+        // A chunk from a later version, inserted before the checksum which is then redone
+        let mut contents = bytes[..bytes.len() - CHECKSUM_BYTES].to_vec();
+        push_chunk(&mut contents, b"NEW!", &[1, 2, 3]);
+        let checksum = sha1_smol::Sha1::from(&contents).digest().bytes();
+        contents.extend_from_slice(&checksum);
+        assert!(Snapshot::from_bytes(&contents, ROM)?.thumbnail.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        // 128x64 hires screen with one white pixel in the top left 2x2 block
+        let mut rgb = vec![0; 128 * 64 * 3];
+        rgb[..3].copy_from_slice(&[200, 100, 40]);
+        let thumbnail = Thumbnail::from_rgb(128, 64, &rgb);
+        assert_eq!((thumbnail.width, thumbnail.height), (64, 32));
+        assert_eq!(thumbnail.pixels[..6], [50, 25, 10, 0, 0, 0]);
+        assert_eq!(Thumbnail::from_rgb(64, 32, &rgb[..64 * 32 * 3]).width, 64);
+    }
+}
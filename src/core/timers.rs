@@ -0,0 +1,102 @@
+use crate::core::bus::Bus;
+use crate::core::clock::{Clock, FramePacer};
+use crate::core::keypad::KeyProvider;
+use crate::core::processor::Processor;
+use std::time::Duration;
+
+// Counts the delay and sound timers down on their own wall clock schedule, so they keep the
+// platform's rate however many frames the frontend manages to run. Processors without one
+// tick once per run_frame instead, which keeps headless runs and netplay deterministic
+pub struct TimerDriver {
+    pacer: FramePacer,
+}
+
+impl TimerDriver {
+    // One tick per period, normally a 60th of a second
+    pub fn new(period: Duration, clock: &impl Clock) -> Self {
+        TimerDriver {
+            pacer: FramePacer::new(period, clock),
+        }
+    }
+
+    // Hands the processor's timers over to the driver, run_frame stops ticking them
+    pub fn attach<B: Bus, K: KeyProvider>(&self, chip8: &mut Processor<B, K>) {
+        chip8.set_external_timers(true);
+    }
+
+    pub fn period(&self) -> Duration {
+        self.pacer.frame_period
+    }
+
+    // Ticks due since the last call, for frontends driving several processors in step
+    pub fn ticks(&mut self, clock: &impl Clock) -> u32 {
+        self.pacer.due(clock)
+    }
+
+    // Ticks the timers for the time passed and returns whether the buzzer should sound
+    pub fn update<B: Bus, K: KeyProvider>(
+        &mut self,
+        chip8: &mut Processor<B, K>,
+        clock: &impl Clock,
+    ) -> bool {
+        for _ in 0..self.ticks(clock) {
+            chip8.tick_timers();
+        }
+        chip8.sound_timer > 0
+    }
+
+    // Drops the time that passed while emulation was paused
+    pub fn resync(&mut self, clock: &impl Clock) {
+        self.pacer = FramePacer::new(self.pacer.frame_period, clock);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TimerDriver;
+    use crate::consts;
+    use crate::core::clock::ManualClock;
+    use crate::core::processor::Processor;
+    use std::time::Duration;
+
+    const PERIOD: Duration = Duration::from_nanos(1_000_000_000 / consts::TIMER_FREQ);
+
+    #[test]
+    fn test_timers_follow_the_clock() -> Result<(), &'static str> {
+        let mut clock = ManualClock::default();
+        let mut timers = TimerDriver::new(PERIOD, &clock);
+        let mut chip8 = Processor::builder().build();
+        timers.attach(&mut chip8);
+        // jump to itself
+        chip8.ram.buffer[0x200..0x202].copy_from_slice(&[0x12, 0x00]);
+        chip8.delay_timer = 10;
+        chip8.sound_timer = 2;
+        for _ in 0..5 {
+            chip8.run_frame(10)?;
+        }
+        assert_eq!(chip8.delay_timer, 10);
+        clock.advance(Duration::from_millis(50));
+        assert!(!timers.update(&mut chip8, &clock));
+        assert_eq!(chip8.delay_timer, 7);
+        // Paused time does not count
+        clock.advance(Duration::from_secs(1));
+        timers.resync(&clock);
+        assert!(!timers.update(&mut chip8, &clock));
+        assert_eq!(chip8.delay_timer, 7);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sound_gate() {
+        let mut clock = ManualClock::default();
+        let mut timers = TimerDriver::new(PERIOD, &clock);
+        let mut chip8 = Processor::builder().build();
+        timers.attach(&mut chip8);
+        chip8.sound_timer = 2;
+        assert!(timers.update(&mut chip8, &clock));
+        clock.advance(Duration::from_millis(17));
+        assert!(timers.update(&mut chip8, &clock));
+        clock.advance(Duration::from_millis(17));
+        assert!(!timers.update(&mut chip8, &clock));
+    }
+}
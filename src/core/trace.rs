@@ -0,0 +1,151 @@
+use crate::consts;
+use crate::core::instruction::Instruction;
+use crate::core::processor::Processor;
+
+// Machine state just before an instruction ran
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: u16,
+    pub index: u16,
+    pub registers: [u8; consts::REG_COUNT],
+}
+
+// The last TRACE_LENGTH instructions, overwritten in place so keeping it on costs no
+// allocations while running
+#[derive(Clone, Debug)]
+pub struct Trace {
+    entries: Vec<TraceEntry>,
+    // Where the next entry goes, the oldest one once the buffer has wrapped
+    next: usize,
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Trace {
+            entries: Vec::with_capacity(consts::TRACE_LENGTH),
+            next: 0,
+        }
+    }
+}
+
+impl Trace {
+    // Runs before every instruction. The processor is generic, so it is compiled in the
+    // crate using it, where this would otherwise stay a call into the library
+    #[inline]
+    pub fn push(&mut self, entry: TraceEntry) {
+        if self.entries.len() < consts::TRACE_LENGTH {
+            self.entries.push(entry);
+        } else {
+            self.entries[self.next] = entry;
+        }
+        self.next = (self.next + 1) % consts::TRACE_LENGTH;
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.next = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &TraceEntry> {
+        let (newer, older) = self.entries.split_at(self.next % self.entries.len().max(1));
+        older.iter().chain(newer)
+    }
+}
+
+// Plain text report of what the machine was doing when it failed, written next to the ROM
+// so it can be attached to a bug report
+pub fn crash_report(chip8: &Processor, rom: &str, error: &str) -> String {
+    let mut report = format!("CHIP-8 crash report\nROM: {}\nError: {}\n\n", rom, error);
+    let registers: Vec<String> = chip8
+        .registers
+        .iter()
+        .enumerate()
+        .map(|(i, v)| format!("V{:X}={:02X}", i, v))
+        .collect();
+    report += &format!(
+        "PC={:03X} I={:03X} SP={} DT={:02X} ST={:02X}\n{}\n",
+        chip8.pc,
+        chip8.idx_register,
+        chip8.stack_pointer,
+        chip8.delay_timer,
+        chip8.sound_timer,
+        registers.join(" ")
+    );
+    let stack: Vec<String> = chip8.stack[..chip8.stack_pointer as usize]
+        .iter()
+        .map(|address| format!("{:03X}", address))
+        .collect();
+    report += &format!("Stack: {}\n\n", stack.join(" "));
+    report += &format!("Last {} instructions, oldest first:\n", chip8.trace.len());
+    for entry in chip8.trace.iter() {
+        let registers: String = entry
+            .registers
+            .iter()
+            .map(|v| format!("{:02X}", v))
+            .collect();
+        report += &format!(
+            "{:03X}  {:04X}  {:<24} I={:03X} V={}\n",
+            entry.pc,
+            entry.opcode,
+            Instruction::decode(entry.opcode, chip8.quirks()).to_string(),
+            entry.index,
+            registers
+        );
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crash_report, Trace, TraceEntry};
+    use crate::consts;
+    use crate::core::processor::Processor;
+    use crate::core::rom::Rom;
+
+    #[test]
+    fn test_ring_buffer() {
+        let mut trace = Trace::default();
+        assert!(trace.is_empty());
+        for pc in 0..consts::TRACE_LENGTH as u16 + 3 {
+            trace.push(TraceEntry {
+                pc,
+                ..Default::default()
+            });
+        }
+        assert_eq!(trace.len(), consts::TRACE_LENGTH);
+        let pcs: Vec<u16> = trace.iter().map(|e| e.pc).collect();
+        assert_eq!(pcs[0], 3);
+        assert_eq!(
+            pcs[consts::TRACE_LENGTH - 1],
+            consts::TRACE_LENGTH as u16 + 2
+        );
+        assert!(pcs.windows(2).all(|w| w[0] + 1 == w[1]));
+    }
+
+    #[test]
+    fn test_crash_report() -> Result<(), &'static str> {
+        // 6005 (v0 := 5), 2206 (call), 0000 (never reached), FFFF (invalid)
+        let rom = Rom::from_bytes(&[0x60, 0x05, 0x22, 0x06, 0x00, 0x00, 0xFF, 0xFF])
+            .map_err(|_| "Could not build ROM")?;
+        let mut chip8 = Processor::default();
+        chip8.init_ram(&rom, &consts::FONT_SET, consts::PROG_OFFSET)?;
+        let error = chip8.run_frame(10).unwrap_err();
+        let report = crash_report(&chip8, "test.ch8", error);
+        assert!(report.contains("Error: Invalid instruction"));
+        assert!(report.contains("Stack: 204"));
+        assert!(report.contains("Last 3 instructions"));
+        assert!(report.contains("200  6005  LD V0, #05"));
+        assert!(report.contains("206  FFFF  DW #FFFF"));
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use crate::cli::Options;
+
+const BUNDLED: &str = include_str!("../roms/database.txt");
+
+// Each line holds a ROM's SHA-1 followed by the command line options it runs best with, a
+// comment after them usually names the game
+fn find_line<'a>(database: &'a str, sha1: &str) -> Option<&'a str> {
+    database.lines().find(|line| {
+        line.split_whitespace()
+            .next()
+            .is_some_and(|hash| hash.eq_ignore_ascii_case(sha1))
+    })
+}
+
+fn find(database: &str, sha1: &str) -> Option<Vec<String>> {
+    let line = find_line(database, sha1)?;
+    let options = line.split('#').next().unwrap_or_default();
+    Some(
+        options
+            .split_whitespace()
+            .skip(1)
+            .map(String::from)
+            .collect(),
+    )
+}
+
+fn read_user(path: Option<&str>) -> Result<String, Box<dyn std::error::Error>> {
+    match path {
+        Some(p) => Ok(std::fs::read_to_string(p)?),
+        None => Ok(String::new()),
+    }
+}
+
+// A ROM's options and comment as written in the database, for showing rather than running
+pub fn entry(
+    sha1: &str,
+    path: Option<&str>,
+) -> Result<Option<(String, String)>, Box<dyn std::error::Error>> {
+    let user = read_user(path)?;
+    let line = match find_line(&user, sha1).or_else(|| find_line(BUNDLED, sha1)) {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+    let (options, comment) = line.split_once('#').unwrap_or((line, ""));
+    let options: Vec<&str> = options.split_whitespace().skip(1).collect();
+    Ok(Some((options.join(" "), comment.trim().to_string())))
+}
+
+// Looks the ROM up in the user's database first and then in the one bundled with the emulator
+pub fn lookup(
+    sha1: &str,
+    path: Option<&str>,
+) -> Result<Option<Options>, Box<dyn std::error::Error>> {
+    let user = read_user(path)?;
+    match find(&user, sha1).or_else(|| find(BUNDLED, sha1)) {
+        Some(args) => Ok(Some(Options::parse(&args)?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{entry, find, lookup};
+    use crate::core::platform::Platform;
+
+    const IBM_LOGO: &str = "1ba58656810b67fd131eb9af3e3987863bf26c90";
+
+    #[test]
+    fn test_find() {
+        let database = "# comment\nABCDEF --ipf 20 --platform chip8 # Game\nabc --ipf 5\n";
+        assert_eq!(
+            find(database, "abcdef"),
+            Some(vec![
+                String::from("--ipf"),
+                String::from("20"),
+                String::from("--platform"),
+                String::from("chip8")
+            ])
+        );
+        assert_eq!(
+            find(database, "abc"),
+            Some(vec![String::from("--ipf"), String::from("5")])
+        );
+        assert_eq!(find(database, "ab"), None);
+    }
+
+    #[test]
+    fn test_lookup() -> Result<(), Box<dyn std::error::Error>> {
+        let bundled = lookup(IBM_LOGO, None)?.ok_or("IBM Logo is bundled")?;
+        assert_eq!(bundled.platform, Some(Platform::Modern));
+        assert!(lookup("0000", None)?.is_none());
+
+        // The user's database is searched before the bundled one
+        let path = std::env::temp_dir().join(format!("chip8-db-{}.txt", std::process::id()));
+        std::fs::write(
+            &path,
+            format!("{} --platform chip8 --ipf 7 # Mine\n", IBM_LOGO),
+        )?;
+        let user = lookup(IBM_LOGO, path.to_str())?.ok_or("Entry is missing")?;
+        assert_eq!(
+            (user.platform, user.cycles_per_frame),
+            (Some(Platform::Chip8), Some(7))
+        );
+        assert_eq!(
+            entry(IBM_LOGO, path.to_str())?,
+            Some((
+                String::from("--platform chip8 --ipf 7"),
+                String::from("Mine")
+            ))
+        );
+        std::fs::remove_file(&path)?;
+        assert!(lookup(IBM_LOGO, path.to_str()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use crate::cli::DisasmOptions;
+use crate::consts;
+use crate::core::instruction::Instruction;
+use crate::core::quirks::Quirks;
+use crate::core::rom::Rom;
+use crate::symbols::Symbols;
+use std::collections::{BTreeMap, BTreeSet};
+
+const DATA_BYTES_PER_LINE: usize = 8;
+
+// What the control flow trace learned about an address referenced by the program
+#[derive(Clone, Copy)]
+enum Reference {
+    Jump,
+    Call,
+    Data,
+}
+
+fn opcode_at(program: &[u8], address: u16) -> Option<u16> {
+    let offset = (address as usize).checked_sub(consts::PROG_OFFSET)?;
+    program
+        .get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+// Renders an opcode in the syntax understood by the assembler, None means it is not an
+// instruction the assembler can reproduce and gets written out as data
+fn render(opcode: u16, target: &dyn Fn(u16) -> String) -> Option<String> {
+    Instruction::decode(opcode, &Quirks::default()).to_octo(target)
+}
+
+// Follows every path from the entry point, anything never reached is treated as data
+fn trace(program: &[u8]) -> (BTreeSet<u16>, BTreeMap<u16, Reference>) {
+    let mut code = BTreeSet::new();
+    let mut references = BTreeMap::new();
+    let mut pending = vec![consts::PROG_OFFSET as u16];
+    let no_target = |_: u16| String::new();
+    while let Some(address) = pending.pop() {
+        if code.contains(&address) {
+            continue;
+        }
+        let opcode = match opcode_at(program, address) {
+            Some(op) if render(op, &no_target).is_some() => op,
+            _ => continue,
+        };
+        code.insert(address);
+        let next = address + 2;
+        let nnn = opcode & 0xFFF;
+        match opcode >> 12 {
+            0x0 if opcode == 0x00EE || opcode == 0x00FD => {}
+            0x1 => {
+                references.entry(nnn).or_insert(Reference::Jump);
+                pending.push(nnn);
+            }
+            0x2 => {
+                references.insert(nnn, Reference::Call);
+                pending.extend([nnn, next]);
+            }
+            // The destination depends on v0, so only the base gets a label
+            0xB => {
+                references.entry(nnn).or_insert(Reference::Jump);
+            }
+            0x3 | 0x4 | 0x5 | 0x9 | 0xE => pending.extend([next, next + 2]),
+            0xA => {
+                references.entry(nnn).or_insert(Reference::Data);
+                pending.push(next);
+            }
+            _ => pending.push(next),
+        }
+    }
+    (code, references)
+}
+
+pub fn disassemble(program: &[u8], symbols: &Symbols) -> String {
+    let (code, references) = trace(program);
+    let start = consts::PROG_OFFSET as u16;
+    let end = start + program.len() as u16;
+
+    // Instructions are laid out greedily, an address can only carry a label if it starts
+    // an instruction or data byte in that layout
+    let mut boundaries = BTreeSet::new();
+    let mut address = start;
+    while address < end {
+        boundaries.insert(address);
+        address += if code.contains(&address) && address + 1 < end {
+            2
+        } else {
+            1
+        };
+    }
+    let mut labels = BTreeMap::new();
+    for (&address, reference) in &references {
+        if boundaries.contains(&address) {
+            let name = match symbols.name(address) {
+                Some(name) => name.to_string(),
+                None => match reference {
+                    Reference::Jump => format!("label_{:03X}", address),
+                    Reference::Call => format!("sub_{:03X}", address),
+                    Reference::Data => format!("data_{:03X}", address),
+                },
+            };
+            labels.insert(address, name);
+        }
+    }
+    if boundaries.contains(&start) {
+        labels
+            .entry(start)
+            .or_insert_with(|| symbols.name(start).unwrap_or("main").to_string());
+    }
+    let target = |address: u16| match labels.get(&address) {
+        Some(name) => name.clone(),
+        None => format!("{:#05X}", address),
+    };
+
+    let mut output = String::new();
+    let mut data: Vec<String> = Vec::new();
+    let flush = |output: &mut String, data: &mut Vec<String>| {
+        if !data.is_empty() {
+            output.push_str(&format!("\t{}\n", data.join(" ")));
+            data.clear();
+        }
+    };
+    for &address in &boundaries {
+        if let Some(name) = labels.get(&address) {
+            flush(&mut output, &mut data);
+            output.push_str(&format!(": {}\n", name));
+        }
+        let instruction = match opcode_at(program, address) {
+            Some(opcode) if code.contains(&address) && address + 1 < end => render(opcode, &target),
+            _ => None,
+        };
+        match instruction {
+            Some(text) => {
+                flush(&mut output, &mut data);
+                output.push_str(&format!("\t{}\n", text));
+            }
+            None => {
+                let offset = (address - start) as usize;
+                data.push(format!("{:#04X}", program[offset]));
+                if data.len() == DATA_BYTES_PER_LINE {
+                    flush(&mut output, &mut data);
+                }
+            }
+        }
+    }
+    flush(&mut output, &mut data);
+    output
+}
+
+pub fn export(options: &DisasmOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let rom = Rom::new(&options.rom_path)?;
+    let symbols = match &options.symbols {
+        Some(path) => Symbols::load(path)?,
+        None => Symbols::for_rom(&options.rom_path)?,
+    };
+    let listing = disassemble(&rom.buffer[..rom.size], &symbols);
+    match &options.output {
+        Some(path) => std::fs::write(path, listing)?,
+        None => print!("{}", listing),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::disassemble;
+    use crate::assembler::assemble;
+    use crate::symbols::Symbols;
+
+    #[test]
+    fn test_disassemble_separates_data() -> Result<(), Box<dyn std::error::Error>> {
+        let program = assemble(
+            "
+            : main
+                i := smiley
+                sprite v0 v1 2
+                if v2 == 1 then draw
+                loop again
+            : draw return
+            : smiley 0x24 0x5A
+            ",
+        )?;
+        let listing = disassemble(&program, &Default::default());
+        assert!(listing.contains("i := data_20C"));
+        assert!(listing.contains(":call sub_20A"));
+        assert!(listing.contains(": data_20C\n\t0x24 0x5A\n"));
+        assert_eq!(assemble(&listing)?, program);
+        Ok(())
+    }
+
+    #[test]
+    fn test_disassemble_uses_symbols() -> Result<(), Box<dyn std::error::Error>> {
+        let program = [0x12, 0x02, 0x12, 0x02];
+        let symbols = Symbols::parse("loop_main 0x202")?;
+        let listing = disassemble(&program, &symbols);
+        assert_eq!(
+            listing,
+            ": main\n\tjump loop_main\n: loop_main\n\tjump loop_main\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_disassemble_round_trips_roms() -> Result<(), Box<dyn std::error::Error>> {
+        let roms = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+        for entry in std::fs::read_dir(roms)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "ch8") {
+                let program = std::fs::read(&path)?;
+                let listing = disassemble(&program, &Default::default());
+                assert_eq!(assemble(&listing)?, program, "{}", path.display());
+            }
+        }
+        Ok(())
+    }
+}
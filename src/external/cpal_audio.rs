@@ -0,0 +1,143 @@
+use crate::core::processor::Pitch;
+use crate::external::output::{AudioSettings, AudioSink, Oscillator, ScopeTap, SoundClock, Tone};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub struct CpalAudioDriver {
+    _stream: cpal::Stream,
+    playing: Arc<AtomicBool>,
+    // Picked up by the audio thread at the start of its next buffer
+    pitch: Arc<AtomicU8>,
+    sound: SoundClock,
+    scope: ScopeTap,
+    sample_rate: u32,
+    // Only known when a buffer size was asked for, cpal does not report its default
+    buffer: Option<u32>,
+    muted: bool,
+}
+
+impl CpalAudioDriver {
+    pub fn new(
+        tone: Tone,
+        volume: f32,
+        settings: AudioSettings,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let host = cpal::default_host();
+        let device = match host.default_output_device() {
+            Some(d) => d,
+            None => return Err("No audio output device available".into()),
+        };
+        let supported = device.default_output_config()?;
+        let mut config = supported.config();
+        config.sample_rate = cpal::SampleRate(settings.sample_rate as u32);
+        if let Some(samples) = settings.samples {
+            config.buffer_size = cpal::BufferSize::Fixed(samples as u32);
+        }
+        let playing = Arc::new(AtomicBool::new(false));
+        let (sound, scope) = (SoundClock::default(), ScopeTap::default());
+        let pitch = Arc::new(AtomicU8::new(Pitch::default().0));
+        let taps = (&sound, &scope, &pitch);
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::F32 => {
+                build_stream::<f32>(&device, &config, tone, volume, &playing, taps)?
+            }
+            cpal::SampleFormat::I16 => {
+                build_stream::<i16>(&device, &config, tone, volume, &playing, taps)?
+            }
+            cpal::SampleFormat::U16 => {
+                build_stream::<u16>(&device, &config, tone, volume, &playing, taps)?
+            }
+            _ => return Err("Unsupported audio sample format".into()),
+        };
+        stream.play()?;
+        Ok(CpalAudioDriver {
+            _stream: stream,
+            playing,
+            pitch,
+            sound,
+            scope,
+            sample_rate: config.sample_rate.0,
+            buffer: settings.samples.map(u32::from),
+            muted: false,
+        })
+    }
+}
+
+fn build_stream<T: SizedSample + FromSample<f32>>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    tone: Tone,
+    volume: f32,
+    playing: &Arc<AtomicBool>,
+    (sound, scope, pitch): (&SoundClock, &ScopeTap, &Arc<AtomicU8>),
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let channels = config.channels as usize;
+    let mut oscillator = Oscillator::new(tone, config.sample_rate.0 as i32, volume);
+    let playing = Arc::clone(playing);
+    let (sound, scope, pitch) = (sound.clone(), scope.clone(), Arc::clone(pitch));
+    let mut current = Pitch::default();
+    // Reused between callbacks so the audio thread does not allocate
+    let mut played = Vec::new();
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let on = playing.load(Ordering::Relaxed);
+            let wanted = Pitch(pitch.load(Ordering::Relaxed));
+            if wanted != current {
+                oscillator.set_pitch(wanted);
+                current = wanted;
+            }
+            played.clear();
+            for frame in data.chunks_mut(channels) {
+                // The clock only counts down while the device is not frozen
+                let value = match on && sound.take() {
+                    true => oscillator.next_sample(),
+                    false => 0.0,
+                };
+                played.push(value);
+                frame.fill(T::from_sample(value));
+            }
+            scope.record(played.iter().copied());
+        },
+        |err| eprintln!("Audio stream error: {}", err),
+        None,
+    )
+}
+
+impl AudioSink for CpalAudioDriver {
+    fn set_playing(&mut self, playing: bool) {
+        self.playing
+            .store(playing && !self.muted, Ordering::Relaxed);
+    }
+
+    fn sound_for(&mut self, ticks: u8, tick_period: Duration) {
+        self.sound.start(ticks, tick_period, self.sample_rate);
+    }
+
+    fn latency(&self) -> Duration {
+        let samples = self.buffer.unwrap_or_default();
+        Duration::from_secs_f64(samples as f64 / self.sample_rate as f64)
+    }
+
+    fn set_pitch(&mut self, pitch: Pitch) {
+        self.pitch.store(pitch.0, Ordering::Relaxed);
+    }
+
+    fn scope(&self) -> ScopeTap {
+        self.scope.clone()
+    }
+
+    fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        if muted {
+            self.sound.stop();
+        }
+    }
+
+    fn muted(&self) -> bool {
+        self.muted
+    }
+}
@@ -0,0 +1,189 @@
+use crate::consts;
+use crate::core::ram::KeyboardBuffer;
+use crate::external::input;
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Crowd play takes key presses from many people at once, one JSON object per line such as
+// {"key": "w", "user": "alice"}, read from stdin or from any number of TCP connections.
+// Keys are named like the host keyboard and mapped through the same layout
+#[derive(Debug, PartialEq)]
+pub struct KeyEvent {
+    pub key: usize,
+    pub user: Option<String>,
+}
+
+impl KeyEvent {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let value: serde_json::Value =
+            serde_json::from_str(line).map_err(|e| format!("Invalid event: {}", e))?;
+        let name = value["key"].as_str().ok_or("Event has no key")?;
+        let key = input::key_index(name).ok_or(format!("Unknown key {}", name))?;
+        let user = value["user"].as_str().map(String::from);
+        Ok(KeyEvent { key, user })
+    }
+}
+
+fn forward(reader: impl BufRead, events: Sender<KeyEvent>) {
+    for line in reader.lines().map_while(Result::ok) {
+        match KeyEvent::parse(&line) {
+            Ok(event) => {
+                if events.send(event).is_err() {
+                    break;
+                }
+            }
+            Err(e) => eprintln!("Crowd input: {}", e),
+        }
+    }
+}
+
+pub struct Crowd {
+    events: Receiver<KeyEvent>,
+    // Calls to apply each key stays held for
+    held: [u32; consts::KEYBOARD_SIZE],
+    accepted: VecDeque<Instant>,
+    // Only users still in their cooldown, see prune
+    last_by_user: HashMap<String, Instant>,
+}
+
+impl Crowd {
+    // The source is either - for stdin or an address to listen on
+    pub fn start(source: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let (sender, events) = mpsc::channel();
+        if source == "-" {
+            thread::spawn(move || forward(std::io::stdin().lock(), sender));
+        } else {
+            let listener = TcpListener::bind(source)?;
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let sender = sender.clone();
+                    thread::spawn(move || forward(BufReader::new(stream), sender));
+                }
+            });
+        }
+        Ok(Crowd::new(events))
+    }
+
+    fn new(events: Receiver<KeyEvent>) -> Self {
+        Crowd {
+            events,
+            held: [0; consts::KEYBOARD_SIZE],
+            accepted: VecDeque::new(),
+            last_by_user: HashMap::new(),
+        }
+    }
+
+    // Drops the event when the overall rate or the user's cooldown is exceeded
+    fn admit(&mut self, event: &KeyEvent, now: Instant) -> bool {
+        while let Some(&oldest) = self.accepted.front() {
+            if now.duration_since(oldest) < Duration::from_secs(1) {
+                break;
+            }
+            self.accepted.pop_front();
+        }
+        if self.accepted.len() >= consts::CROWD_KEYS_PER_SECOND {
+            return false;
+        }
+        if let Some(user) = &event.user {
+            let cooldown = Duration::from_millis(consts::CROWD_USER_COOLDOWN_MS);
+            if let Some(&last) = self.last_by_user.get(user) {
+                if now.duration_since(last) < cooldown {
+                    return false;
+                }
+            }
+            self.last_by_user.insert(user.clone(), now);
+        }
+        self.accepted.push_back(now);
+        true
+    }
+
+    // Users whose cooldown is over behave the same with or without an entry, dropping them
+    // keeps the map from growing with every name a public stream ever sees
+    fn prune(&mut self, now: Instant) {
+        let cooldown = Duration::from_millis(consts::CROWD_USER_COOLDOWN_MS);
+        self.last_by_user
+            .retain(|_, &mut last| now.duration_since(last) < cooldown);
+    }
+
+    // Called once per main loop iteration after the local keyboard has been polled
+    pub fn apply(&mut self, keyboard: &mut KeyboardBuffer) {
+        let now = Instant::now();
+        self.prune(now);
+        let events: Vec<KeyEvent> = self.events.try_iter().collect();
+        for event in events {
+            if self.admit(&event, now) {
+                self.held[event.key] = consts::CROWD_TAP_FRAMES;
+            }
+        }
+        for (key, frames) in self.held.iter_mut().enumerate() {
+            if *frames > 0 {
+                keyboard.set(key);
+                *frames -= 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Crowd, KeyEvent};
+    use crate::consts;
+    use crate::core::ram::KeyboardBuffer;
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_parse_key_event() -> Result<(), String> {
+        assert_eq!(
+            KeyEvent::parse(r#"{"key": "W", "user": "alice"}"#)?,
+            KeyEvent {
+                key: 0x5,
+                user: Some(String::from("alice"))
+            }
+        );
+        assert_eq!(KeyEvent::parse(r#"{"key": "x"}"#)?.key, 0x0);
+        assert!(KeyEvent::parse(r#"{"key": "p"}"#).is_err());
+        assert!(KeyEvent::parse("w").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_crowd_rate_limit() {
+        let (sender, events) = mpsc::channel();
+        let mut crowd = Crowd::new(events);
+        let now = Instant::now();
+        let event = |user: &str| KeyEvent {
+            key: 1,
+            user: Some(user.to_string()),
+        };
+        assert!(crowd.admit(&event("alice"), now));
+        assert!(!crowd.admit(&event("alice"), now));
+        for i in 1..consts::CROWD_KEYS_PER_SECOND {
+            assert!(crowd.admit(&event(&i.to_string()), now));
+        }
+        assert!(!crowd.admit(&event("bob"), now));
+        let later = now + Duration::from_millis(consts::CROWD_USER_COOLDOWN_MS);
+        crowd.prune(later - Duration::from_millis(1));
+        assert_eq!(crowd.last_by_user.len(), consts::CROWD_KEYS_PER_SECOND);
+        crowd.prune(later);
+        assert!(crowd.last_by_user.is_empty());
+
+        sender
+            .send(KeyEvent { key: 2, user: None })
+            .expect("receiver is alive");
+        let mut crowd = Crowd::new(crowd.events);
+        let mut keyboard: KeyboardBuffer = Default::default();
+        for _ in 0..consts::CROWD_TAP_FRAMES {
+            keyboard.keys = 0;
+            crowd.apply(&mut keyboard);
+            assert!(keyboard.is_pressed(2));
+        }
+        keyboard.keys = 0;
+        crowd.apply(&mut keyboard);
+        assert!(!keyboard.is_pressed(2));
+    }
+}
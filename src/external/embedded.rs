@@ -0,0 +1,151 @@
+use crate::core::ram::DisplayBuffer;
+use crate::core::screen::Screen;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+// Draws the display buffer onto any embedded-graphics target, e.g. an SSD1306 with
+// BinaryColor or an ST7789 with Rgb565. Each CHIP-8 pixel becomes a scale x scale block
+// starting at origin, so a 64x32 program fills a 128x64 panel at scale 2. Whatever falls
+// outside the target is clipped
+pub struct EmbeddedDisplay<D: DrawTarget> {
+    pub target: D,
+    pub on: D::Color,
+    pub off: D::Color,
+    pub scale: u32,
+    pub origin: Point,
+}
+
+impl<D: DrawTarget> EmbeddedDisplay<D> {
+    pub fn new(target: D, on: D::Color, off: D::Color) -> Self {
+        EmbeddedDisplay {
+            target,
+            on,
+            off,
+            scale: 1,
+            origin: Point::zero(),
+        }
+    }
+
+    // Largest scale at which the buffer still fits, centered on the target. A target smaller
+    // than the buffer shows its top left corner at scale 1
+    pub fn fit(&mut self, display: &DisplayBuffer) {
+        let size = self.target.bounding_box().size;
+        let (width, height) = (display.width() as u32, display.height() as u32);
+        self.scale = (size.width / width).min(size.height / height).max(1);
+        self.origin = self.target.bounding_box().top_left
+            + Point::new(
+                (size.width.saturating_sub(width * self.scale) / 2) as i32,
+                (size.height.saturating_sub(height * self.scale) / 2) as i32,
+            );
+    }
+
+    // Redraws the visible area in one contiguous fill, which most drivers turn into a single
+    // window write
+    pub fn draw(&mut self, display: &DisplayBuffer) -> Result<(), D::Error> {
+        let (scale, on, off) = (self.scale as usize, self.on, self.off);
+        let size = Size::new(
+            display.width() as u32 * self.scale,
+            display.height() as u32 * self.scale,
+        );
+        let area = Rectangle::new(self.origin, size).intersection(&self.target.bounding_box());
+        // Where the visible area starts within the scaled buffer
+        let start = area.top_left - self.origin;
+        let (xs, ys) = (start.x as usize, start.y as usize);
+        let (width, height) = (area.size.width as usize, area.size.height as usize);
+        let colors = (ys..ys + height).flat_map(|y| {
+            let row = &display.buffer[y / scale];
+            (xs..xs + width).map(move |x| match row[x / scale] {
+                0 => off,
+                _ => on,
+            })
+        });
+        self.target.fill_contiguous(&area, colors)
+    }
+}
+
+impl<D: DrawTarget> Screen for EmbeddedDisplay<D> {
+    type Error = D::Error;
+
+    fn show(&mut self, display: &DisplayBuffer) -> Result<(), Self::Error> {
+        self.draw(display)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EmbeddedDisplay;
+    use crate::core::ram::{DisplayBuffer, Resolution};
+    use crate::core::screen::Screen;
+    use embedded_graphics::mock_display::MockDisplay;
+    use embedded_graphics::pixelcolor::BinaryColor;
+    use embedded_graphics::prelude::*;
+
+    #[test]
+    fn test_draw_buffer() -> Result<(), core::convert::Infallible> {
+        let mut buffer = DisplayBuffer::default();
+        buffer.buffer[0][1] = 1;
+        buffer.buffer[31][63] = 1;
+        let mut display =
+            EmbeddedDisplay::new(MockDisplay::new(), BinaryColor::On, BinaryColor::Off);
+        display.fit(&buffer);
+        assert_eq!((display.scale, display.origin), (1, Point::new(0, 16)));
+
+        display.draw(&buffer)?;
+        assert_eq!(
+            display.target.get_pixel(Point::new(0, 16)),
+            Some(BinaryColor::Off)
+        );
+        assert_eq!(
+            display.target.get_pixel(Point::new(1, 16)),
+            Some(BinaryColor::On)
+        );
+        assert_eq!(
+            display.target.get_pixel(Point::new(63, 47)),
+            Some(BinaryColor::On)
+        );
+        assert_eq!(display.target.get_pixel(Point::new(0, 0)), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_clip_small_target() -> Result<(), core::convert::Infallible> {
+        // A hires buffer is wider than the 64x64 mock display
+        let mut buffer = DisplayBuffer::new(Resolution::High);
+        buffer.buffer[0][63] = 1;
+        buffer.buffer[0][64] = 1;
+        buffer.buffer[63][0] = 1;
+        let mut display =
+            EmbeddedDisplay::new(MockDisplay::new(), BinaryColor::On, BinaryColor::Off);
+        display.fit(&buffer);
+        assert_eq!((display.scale, display.origin), (1, Point::zero()));
+
+        display.show(&buffer)?;
+        assert_eq!(
+            display.target.get_pixel(Point::new(63, 0)),
+            Some(BinaryColor::On)
+        );
+        assert_eq!(
+            display.target.get_pixel(Point::new(0, 63)),
+            Some(BinaryColor::On)
+        );
+        assert_eq!(
+            display.target.get_pixel(Point::new(62, 0)),
+            Some(BinaryColor::Off)
+        );
+
+        // Moved partly off the top left, the clipped part still lines up
+        buffer.buffer[1][64] = 1;
+        display.origin = Point::new(-1, -1);
+        display.target = MockDisplay::new();
+        display.show(&buffer)?;
+        assert_eq!(
+            display.target.get_pixel(Point::new(63, 0)),
+            Some(BinaryColor::On)
+        );
+        assert_eq!(
+            display.target.get_pixel(Point::new(62, 0)),
+            Some(BinaryColor::Off)
+        );
+        Ok(())
+    }
+}
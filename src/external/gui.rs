@@ -0,0 +1,986 @@
+use crate::breakpoints::Breakpoints;
+use crate::callstack::CallStack;
+use crate::config::Config;
+use crate::consts;
+use crate::core::clock::{FramePacer, RealClock};
+use crate::core::events::{Event, SpriteDraw};
+use crate::core::quirks::Quirks;
+use crate::core::snapshot::{Snapshot, Thumbnail};
+use crate::core::timers::TimerDriver;
+use crate::core::{processor, ram, rom, trace};
+use crate::external::output::{self, AudioDriver, AudioSink, Palette, Tone};
+use crate::saves::{SaveSlots, SLOT_COUNT};
+use crate::symbols::Symbols;
+use eframe::egui;
+use eframe::egui::{Color32, ColorImage, Key, TextureHandle, TextureOptions};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+
+const KEY_MAP: [(Key, usize); consts::KEYBOARD_SIZE] = [
+    (Key::X, 0x0),
+    (Key::Num1, 0x1),
+    (Key::Num2, 0x2),
+    (Key::Num3, 0x3),
+    (Key::Q, 0x4),
+    (Key::W, 0x5),
+    (Key::E, 0x6),
+    (Key::A, 0x7),
+    (Key::S, 0x8),
+    (Key::D, 0x9),
+    (Key::Z, 0xA),
+    (Key::C, 0xB),
+    (Key::Num4, 0xC),
+    (Key::R, 0xD),
+    (Key::F, 0xE),
+    (Key::V, 0xF),
+];
+
+const MEMORY_ROWS: usize = 16;
+const MEMORY_ROW_BYTES: usize = 8;
+const SPRITE_CELL_SIZE: f32 = 12.0;
+// Save slot previews are drawn at this multiple of their stored size
+const PREVIEW_SCALE: f32 = 2.0;
+
+fn chip8x_color(index: u8) -> Color32 {
+    let (r, g, b) = consts::CHIP8X_PALETTE[index as usize];
+    Color32::from_rgb(r, g, b)
+}
+
+#[derive(PartialEq)]
+enum PanelPlacement {
+    Floating,
+    Docked,
+}
+
+struct DebugPanels {
+    registers: bool,
+    stack: bool,
+    memory: bool,
+    sprite: bool,
+    breakpoints: bool,
+    placement: PanelPlacement,
+    editor: MemoryEditor,
+    new_breakpoint: BreakpointInput,
+}
+
+// Condition being typed into the breakpoints panel and why the last one was rejected
+#[derive(Default)]
+struct BreakpointInput {
+    condition: String,
+    error: Option<String>,
+}
+
+// Byte being retyped in the memory panel, as the view it was clicked in and its address
+#[derive(Default)]
+struct MemoryEditor {
+    selected: Option<(usize, usize)>,
+    input: String,
+}
+
+// What the picker knows about one save slot, saved states may lack a preview
+enum SlotPreview {
+    Empty,
+    Saved(Option<TextureHandle>),
+}
+
+// Save and load window for the ROM's save slots, previews are read from disk when it opens
+// and after every save
+#[derive(Default)]
+struct SlotPicker {
+    open: bool,
+    previews: Vec<SlotPreview>,
+}
+
+pub struct EmulatorApp {
+    chip8: Option<processor::Processor>,
+    rom_path: String,
+    path_input: String,
+    show_open_dialog: bool,
+    paused: bool,
+    quirks: Quirks,
+    cycles_per_frame: usize,
+    clock: RealClock,
+    pacer: FramePacer,
+    timers: TimerDriver,
+    snapshot: Option<Snapshot>,
+    // Unavailable without a data directory
+    slots: Option<SaveSlots>,
+    slot_picker: SlotPicker,
+    foreground: Color32,
+    background: Color32,
+    panels: DebugPanels,
+    status: String,
+    texture: Option<TextureHandle>,
+    audio: Option<AudioDriver>,
+    // Given with --symbols, otherwise looked up next to each ROM as it is opened
+    symbols_path: Option<String>,
+    symbols: Symbols,
+    events: Option<Receiver<Event>>,
+    // Most recent DXYN, outlined on the display while the sprite panel is open
+    last_sprite: Option<SpriteDraw>,
+    calls: CallStack,
+    breakpoints: Breakpoints,
+    // Filled in by a register observer, the registers panel highlights the ones the last
+    // frame wrote
+    register_writes: Arc<AtomicU16>,
+    last_register_writes: u16,
+}
+
+impl EmulatorApp {
+    fn new(rom_path: Option<String>, symbols_path: Option<String>) -> Self {
+        // Audio is optional in the GUI, a missing device should not prevent playing
+        // A config that cannot be read is not worth failing over either
+        let config = Config::load().unwrap_or_default();
+        let audio = sdl2::init().ok().and_then(|context| {
+            AudioDriver::new(
+                &context,
+                Tone::default(),
+                consts::BUZZER_VOLUME,
+                config.audio,
+            )
+            .ok()
+        });
+        let clock = RealClock::default();
+        // Scaled by the accessibility speed
+        let frame_period = config.frame_period(consts::TIMER_FREQ);
+        let pacer = FramePacer::new(frame_period, &clock);
+        let timers = TimerDriver::new(frame_period, &clock);
+        let mut app = EmulatorApp {
+            chip8: None,
+            rom_path: String::new(),
+            path_input: String::new(),
+            show_open_dialog: false,
+            paused: false,
+            quirks: Default::default(),
+            cycles_per_frame: consts::CYCLES_PER_FRAME,
+            clock,
+            pacer,
+            timers,
+            snapshot: None,
+            slots: None,
+            slot_picker: Default::default(),
+            foreground: Color32::from_rgb(0, 255, 0),
+            background: Color32::BLACK,
+            panels: DebugPanels {
+                registers: false,
+                stack: false,
+                memory: false,
+                sprite: false,
+                breakpoints: false,
+                placement: PanelPlacement::Floating,
+                editor: Default::default(),
+                new_breakpoint: Default::default(),
+            },
+            status: String::from("No ROM loaded"),
+            texture: None,
+            audio,
+            symbols_path,
+            symbols: Default::default(),
+            events: None,
+            last_sprite: None,
+            calls: Default::default(),
+            breakpoints: Default::default(),
+            register_writes: Default::default(),
+            last_register_writes: 0,
+        };
+        if let Some(path) = rom_path {
+            app.load(&path);
+        }
+        app
+    }
+
+    fn load(&mut self, path: &str) {
+        let prog = match rom::Rom::new(path) {
+            Ok(p) => p,
+            Err(e) => {
+                self.status = format!("Could not open {}: {}", path, e);
+                return;
+            }
+        };
+        // The processor and its buffers are reused across games once created
+        let (events, breakpoints) = (&mut self.events, &mut self.breakpoints);
+        let register_writes = self.register_writes.clone();
+        let timers = &self.timers;
+        let chip8 = self.chip8.get_or_insert_with(|| {
+            let mut chip8 = processor::Processor::new(
+                ram::Ram {
+                    ..Default::default()
+                },
+                ram::DisplayBuffer {
+                    ..Default::default()
+                },
+                ram::KeyboardBuffer {
+                    ..Default::default()
+                },
+            );
+            *events = Some(chip8.events.subscribe());
+            breakpoints.attach(&mut chip8);
+            timers.attach(&mut chip8);
+            chip8.observers.on_register_write(move |register, _| {
+                register_writes.fetch_or(1 << register, Ordering::Relaxed);
+            });
+            chip8
+        });
+        if let Err(e) = chip8.load_rom(&prog) {
+            self.chip8 = None;
+            self.status = format!("Could not load {}: {}", path, e);
+            return;
+        }
+        self.rom_path = path.to_string();
+        self.path_input = path.to_string();
+        self.snapshot = None;
+        self.slots = SaveSlots::for_rom(&prog.sha1).ok();
+        self.slot_picker.previews.clear();
+        self.last_sprite = None;
+        self.calls.sync(chip8);
+        // The previous program may have retuned the buzzer
+        if let Some(audio) = self.audio.as_mut() {
+            audio.set_pitch(chip8.pitch);
+        }
+        self.paused = false;
+        self.status = format!("Running {}", path);
+        let symbols = match &self.symbols_path {
+            Some(p) => Symbols::load(p),
+            None => Symbols::for_rom(path),
+        };
+        self.symbols = symbols.unwrap_or_else(|e| {
+            self.status = format!("Running {} without symbols: {}", path, e);
+            Default::default()
+        });
+    }
+
+    fn reset(&mut self) {
+        if !self.rom_path.is_empty() {
+            let path = self.rom_path.clone();
+            self.load(&path);
+        }
+    }
+
+    fn save_state(&mut self) {
+        if let Some(chip8) = &self.chip8 {
+            self.snapshot = Some(Snapshot::capture(chip8));
+            self.status = String::from("State saved");
+        }
+    }
+
+    fn load_state(&mut self) {
+        if let (Some(chip8), Some(snapshot)) = (self.chip8.as_mut(), &self.snapshot) {
+            snapshot.restore(chip8);
+            self.calls.sync(chip8);
+            self.status = String::from("State loaded");
+        }
+    }
+
+    fn run_frame(&mut self, ctx: &egui::Context) {
+        let chip8 = match self.chip8.as_mut() {
+            Some(c) => c,
+            None => return,
+        };
+
+        ctx.input(|input| {
+            let keyboard = &mut chip8.keyboard_buffer;
+            for (key, index) in KEY_MAP {
+                if input.key_down(key) {
+                    keyboard.set(index);
+                } else {
+                    keyboard.clear(index);
+                }
+            }
+        });
+
+        chip8.set_quirks(self.quirks);
+
+        // egui repaints at the display refresh rate, so emulated frames are paced separately
+        for _ in 0..self.pacer.due(&self.clock) {
+            if self.paused {
+                continue;
+            }
+            let (breakpoints, mut hit) = (&self.breakpoints, None);
+            let stop = |c: &processor::Processor| {
+                hit = breakpoints.hit(c);
+                hit.is_some()
+            };
+            let result = chip8.run_frame_until(self.cycles_per_frame, stop);
+            self.last_register_writes = self.register_writes.swap(0, Ordering::Relaxed);
+            match result {
+                Ok(true) => {
+                    self.paused = true;
+                    let index = hit.unwrap_or_default();
+                    self.status = format!(
+                        "Breakpoint {} ({}) hit at {:03X}",
+                        index, breakpoints.breakpoints[index].source, chip8.pc
+                    );
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    self.paused = true;
+                    let path = output::screenshot_path(&self.rom_path).with_extension("crash.txt");
+                    let report = trace::crash_report(chip8, &self.rom_path, e);
+                    self.status = match std::fs::write(&path, report) {
+                        Ok(()) => format!("{}, paused. Crash report in {}", e, path.display()),
+                        Err(_) => format!("{}, paused", e),
+                    };
+                }
+            }
+            for event in self.events.iter().flat_map(|e| e.try_iter()) {
+                match event {
+                    Event::Halted => {
+                        self.paused = true;
+                        self.status = String::from("Program exited");
+                    }
+                    Event::SpriteDrawn(draw) => self.last_sprite = Some(draw),
+                    Event::SoundTimerSet(ticks) => {
+                        if let Some(audio) = self.audio.as_mut() {
+                            audio.sound_for(ticks, self.timers.period());
+                        }
+                    }
+                    Event::PitchSet(pitch) => {
+                        if let Some(audio) = self.audio.as_mut() {
+                            audio.set_pitch(pitch);
+                        }
+                    }
+                    _ => self.calls.handle(&event),
+                }
+            }
+        }
+
+        match self.paused {
+            true => self.timers.resync(&self.clock),
+            false => {
+                self.timers.update(chip8, &self.clock);
+            }
+        }
+        // The beep itself is timed by the audio callback, pausing only freezes it
+        if let Some(audio) = self.audio.as_mut() {
+            audio.set_playing(!self.paused);
+        }
+    }
+
+    fn refresh_slots(&mut self, ctx: &egui::Context) {
+        let slots = match &self.slots {
+            Some(s) => s,
+            None => return,
+        };
+        self.slot_picker.previews = (1..=SLOT_COUNT)
+            .map(|slot| match slots.load(slot) {
+                Ok(Some(state)) => SlotPreview::Saved(state.thumbnail.map(|t| {
+                    let image = ColorImage::from_rgb([t.width, t.height], &t.pixels);
+                    ctx.load_texture(format!("slot{}", slot), image, TextureOptions::NEAREST)
+                })),
+                Ok(None) => SlotPreview::Empty,
+                Err(e) => {
+                    self.status = format!("Could not read slot {}: {}", slot, e);
+                    SlotPreview::Empty
+                }
+            })
+            .collect();
+    }
+
+    fn save_slot(&mut self, ctx: &egui::Context, slot: usize) {
+        let (chip8, slots) = match (&self.chip8, &self.slots) {
+            (Some(c), Some(s)) => (c, s),
+            _ => return,
+        };
+        let image = self.frame_image();
+        let rgb: Vec<u8> = image
+            .pixels
+            .iter()
+            .flat_map(|c| [c.r(), c.g(), c.b()])
+            .collect();
+        let mut state = Snapshot::capture(chip8);
+        state.thumbnail = Some(Thumbnail::from_rgb(image.size[0], image.size[1], &rgb));
+        self.status = match slots.save(slot, &state) {
+            Ok(()) => format!("Saved slot {}", slot),
+            Err(e) => format!("Could not save slot {}: {}", slot, e),
+        };
+        self.refresh_slots(ctx);
+    }
+
+    fn load_slot(&mut self, slot: usize) {
+        let (chip8, slots) = match (self.chip8.as_mut(), &self.slots) {
+            (Some(c), Some(s)) => (c, s),
+            _ => return,
+        };
+        self.status = match slots.load(slot) {
+            Ok(Some(state)) => {
+                state.restore(chip8);
+                self.calls.sync(chip8);
+                format!("Loaded slot {}", slot)
+            }
+            Ok(None) => format!("Slot {} is empty", slot),
+            Err(e) => format!("Could not load slot {}: {}", slot, e),
+        };
+    }
+
+    fn slot_picker(&mut self, ctx: &egui::Context) {
+        if !self.slot_picker.open {
+            return;
+        }
+        if self.slot_picker.previews.is_empty() {
+            self.refresh_slots(ctx);
+        }
+        let (mut open, mut save, mut load) = (true, None, None);
+        egui::Window::new("Save Slots")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("slots_grid").show(ui, |ui| {
+                    for (i, preview) in self.slot_picker.previews.iter().enumerate() {
+                        let slot = i + 1;
+                        ui.vertical(|ui| {
+                            ui.label(format!("Slot {}", slot));
+                            let size = egui::vec2(
+                                consts::THUMBNAIL_WIDTH as f32 * PREVIEW_SCALE,
+                                consts::THUMBNAIL_WIDTH as f32 * PREVIEW_SCALE / 2.0,
+                            );
+                            match preview {
+                                // Kept at the screen's own aspect ratio, which is not 2:1 on
+                                // the ETI-660
+                                SlotPreview::Saved(Some(texture)) => {
+                                    let size = texture.size_vec2() * PREVIEW_SCALE;
+                                    ui.add(
+                                        egui::Image::new((texture.id(), size))
+                                            .fit_to_exact_size(size),
+                                    );
+                                }
+                                SlotPreview::Saved(None) => {
+                                    ui.add_sized(size, egui::Label::new("No preview"));
+                                }
+                                SlotPreview::Empty => {
+                                    ui.add_sized(size, egui::Label::new("Empty"));
+                                }
+                            }
+                            ui.horizontal(|ui| {
+                                if ui.button("Save").clicked() {
+                                    save = Some(slot);
+                                }
+                                let saved = matches!(preview, SlotPreview::Saved(_));
+                                if ui.add_enabled(saved, egui::Button::new("Load")).clicked() {
+                                    load = Some(slot);
+                                }
+                            });
+                        });
+                        if slot % (SLOT_COUNT / 2) == 0 {
+                            ui.end_row();
+                        }
+                    }
+                });
+            });
+        self.slot_picker.open = open;
+        if let Some(slot) = save {
+            self.save_slot(ctx, slot);
+        }
+        if let Some(slot) = load {
+            self.load_slot(slot);
+        }
+    }
+
+    fn frame_image(&self) -> ColorImage {
+        let chip8 = match &self.chip8 {
+            Some(chip8) => chip8,
+            None => {
+                return ColorImage::new(
+                    [consts::CHIP8_WIDTH, consts::CHIP8_HEIGHT],
+                    vec![self.background; consts::CHIP8_WIDTH * consts::CHIP8_HEIGHT],
+                )
+            }
+        };
+        let display_buffer = &chip8.display_buffer;
+        let (width, height) = (display_buffer.width(), display_buffer.height());
+        let mut pixels = vec![self.background; width * height];
+        for (y, row) in display_buffer.buffer.iter().enumerate() {
+            for (x, &col) in row.iter().enumerate() {
+                // CHIP-8X programs pick their own colors, which take priority over the palette
+                pixels[y * width + x] = match (&display_buffer.colors, col) {
+                    (Some(colors), 0) => chip8x_color(colors.background_color()),
+                    (Some(colors), _) => chip8x_color(colors.foreground[y][x]),
+                    (None, 0) => continue,
+                    (None, _) => self.foreground,
+                };
+            }
+        }
+        ColorImage::new([width, height], pixels)
+    }
+
+    fn menu_bar(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+            egui::MenuBar::new().ui(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    if ui.button("Open ROM…").clicked() {
+                        self.show_open_dialog = true;
+                        ui.close();
+                    }
+                    if ui.button("Exit").clicked() {
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                    }
+                });
+                ui.menu_button("Emulation", |ui| {
+                    let loaded = self.chip8.is_some();
+                    let pause_label = if self.paused { "Resume" } else { "Pause" };
+                    if ui
+                        .add_enabled(loaded, egui::Button::new(pause_label))
+                        .clicked()
+                    {
+                        self.paused = !self.paused;
+                        ui.close();
+                    }
+                    if ui.add_enabled(loaded, egui::Button::new("Reset")).clicked() {
+                        self.reset();
+                        ui.close();
+                    }
+                    ui.separator();
+                    if ui
+                        .add_enabled(loaded, egui::Button::new("Save State"))
+                        .clicked()
+                    {
+                        self.save_state();
+                        ui.close();
+                    }
+                    let restorable = loaded && self.snapshot.is_some();
+                    if ui
+                        .add_enabled(restorable, egui::Button::new("Load State"))
+                        .clicked()
+                    {
+                        self.load_state();
+                        ui.close();
+                    }
+                    let slots = loaded && self.slots.is_some();
+                    if ui
+                        .add_enabled(slots, egui::Button::new("Save Slots…"))
+                        .clicked()
+                    {
+                        self.slot_picker.open = true;
+                        self.slot_picker.previews.clear();
+                        ui.close();
+                    }
+                    ui.separator();
+                    ui.label("Instructions per frame");
+                    ui.add(
+                        egui::Slider::new(
+                            &mut self.cycles_per_frame,
+                            consts::MIN_CYCLES_PER_FRAME..=consts::MAX_CYCLES_PER_FRAME,
+                        )
+                        .logarithmic(true),
+                    );
+                });
+                ui.menu_button("Quirks", |ui| {
+                    for name in Quirks::NAMES {
+                        if let Some(flag) = self.quirks.flag_mut(name) {
+                            ui.checkbox(flag, name);
+                        }
+                    }
+                });
+                ui.menu_button("Palette", |ui| {
+                    for name in Palette::PRESETS {
+                        if ui.button(name).clicked() {
+                            if let Some(palette) = Palette::preset(name) {
+                                let rgb = |c: sdl2::pixels::Color| Color32::from_rgb(c.r, c.g, c.b);
+                                self.foreground = rgb(palette.foreground);
+                                self.background = rgb(palette.background);
+                            }
+                        }
+                    }
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.color_edit_button_srgba(&mut self.foreground);
+                        ui.label("Foreground");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.color_edit_button_srgba(&mut self.background);
+                        ui.label("Background");
+                    });
+                });
+                ui.menu_button("Debug", |ui| {
+                    ui.checkbox(&mut self.panels.registers, "Registers");
+                    ui.checkbox(&mut self.panels.stack, "Stack");
+                    ui.checkbox(&mut self.panels.memory, "Memory");
+                    ui.checkbox(&mut self.panels.sprite, "Sprite");
+                    ui.checkbox(&mut self.panels.breakpoints, "Breakpoints");
+                    ui.separator();
+                    ui.radio_value(
+                        &mut self.panels.placement,
+                        PanelPlacement::Floating,
+                        "Floating panels",
+                    );
+                    ui.radio_value(
+                        &mut self.panels.placement,
+                        PanelPlacement::Docked,
+                        "Docked panels",
+                    );
+                });
+            });
+        });
+    }
+
+    fn open_dialog(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_open_dialog;
+        let mut chosen = None;
+        egui::Window::new("Open ROM")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Path:");
+                    let edit = ui.text_edit_singleline(&mut self.path_input);
+                    let submitted =
+                        edit.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+                    if ui.button("Open").clicked() || submitted {
+                        chosen = Some(self.path_input.clone());
+                    }
+                });
+            });
+        self.show_open_dialog = open;
+        if let Some(path) = chosen {
+            self.show_open_dialog = false;
+            self.load(&path);
+        }
+    }
+
+    fn debug_panels(&mut self, ctx: &egui::Context) {
+        let chip8 = match self.chip8.as_mut() {
+            Some(c) => c,
+            None => return,
+        };
+        let (symbols, calls, breakpoints) = (&self.symbols, &self.calls, &mut self.breakpoints);
+        let written = self.last_register_writes;
+        let (last_sprite, foreground) = (self.last_sprite, self.foreground);
+        // Frames only run from run_frame, so memory is only ever edited between instructions,
+        // and only while paused so the program cannot race the user
+        let editable = self.paused;
+        let panels = &mut self.panels;
+        let (editor, input) = (&mut panels.editor, &mut panels.new_breakpoint);
+        match panels.placement {
+            PanelPlacement::Docked => {
+                let any = panels.registers
+                    || panels.stack
+                    || panels.memory
+                    || panels.sprite
+                    || panels.breakpoints;
+                if any {
+                    egui::SidePanel::right("debug_dock").show(ctx, |ui| {
+                        if panels.registers {
+                            ui.collapsing("Registers", |ui| {
+                                registers_view(ui, chip8, symbols, written)
+                            });
+                        }
+                        if panels.stack {
+                            ui.collapsing("Stack", |ui| stack_view(ui, chip8, calls, symbols));
+                        }
+                        if panels.memory {
+                            ui.collapsing("Memory", |ui| memory_view(ui, chip8, editor, editable));
+                        }
+                        if panels.sprite {
+                            ui.collapsing("Sprite", |ui| {
+                                sprite_view(ui, chip8, last_sprite, foreground)
+                            });
+                        }
+                        if panels.breakpoints {
+                            ui.collapsing("Breakpoints", |ui| {
+                                breakpoints_view(ui, breakpoints, input)
+                            });
+                        }
+                    });
+                }
+            }
+            PanelPlacement::Floating => {
+                egui::Window::new("Registers")
+                    .open(&mut panels.registers)
+                    .show(ctx, |ui| registers_view(ui, chip8, symbols, written));
+                egui::Window::new("Stack")
+                    .open(&mut panels.stack)
+                    .show(ctx, |ui| stack_view(ui, chip8, calls, symbols));
+                egui::Window::new("Memory")
+                    .open(&mut panels.memory)
+                    .show(ctx, |ui| memory_view(ui, chip8, editor, editable));
+                egui::Window::new("Sprite")
+                    .open(&mut panels.sprite)
+                    .show(ctx, |ui| sprite_view(ui, chip8, last_sprite, foreground));
+                egui::Window::new("Breakpoints")
+                    .open(&mut panels.breakpoints)
+                    .show(ctx, |ui| breakpoints_view(ui, breakpoints, input));
+            }
+        }
+    }
+}
+
+fn registers_view(
+    ui: &mut egui::Ui,
+    chip8: &processor::Processor,
+    symbols: &Symbols,
+    written: u16,
+) {
+    egui::Grid::new("registers_grid").show(ui, |ui| {
+        for (i, value) in chip8.registers.iter().enumerate() {
+            ui.monospace(format!("V{:X}", i));
+            let text = egui::RichText::new(format!("{:02X}", value)).monospace();
+            ui.label(match written & (1 << i) {
+                0 => text,
+                _ => text.color(Color32::YELLOW),
+            });
+            if i % 4 == 3 {
+                ui.end_row();
+            }
+        }
+        ui.monospace("I");
+        ui.monospace(format!("{:03X}", chip8.idx_register));
+        ui.monospace("PC");
+        ui.monospace(format!("{:03X}", chip8.pc));
+        ui.end_row();
+        ui.monospace("DT");
+        ui.monospace(format!("{:02X}", chip8.delay_timer));
+        ui.monospace("ST");
+        ui.monospace(format!("{:02X}", chip8.sound_timer));
+        ui.end_row();
+    });
+    for (register, address) in [("PC", chip8.pc), ("I", chip8.idx_register)] {
+        if let Some(name) = symbols.name(address) {
+            ui.monospace(format!("{} at {}", register, name));
+        }
+    }
+}
+
+// Conditions such as pc==0x3A4 && v[3]>10 or writes such as write mem 0x300 0x30F, see
+// breakpoints.rs for the syntax
+fn breakpoints_view(ui: &mut egui::Ui, breakpoints: &mut Breakpoints, input: &mut BreakpointInput) {
+    let mut removed = None;
+    for (i, breakpoint) in breakpoints.breakpoints.iter().enumerate() {
+        ui.horizontal(|ui| {
+            if ui.small_button("x").clicked() {
+                removed = Some(i);
+            }
+            ui.monospace(format!("{}: {}", i, breakpoint.source));
+        });
+    }
+    if let Some(i) = removed {
+        let _ = breakpoints.remove(i);
+    }
+    ui.horizontal(|ui| {
+        let edit = ui.text_edit_singleline(&mut input.condition);
+        let submitted = edit.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+        if ui.button("Add").clicked() || submitted {
+            input.error = breakpoints.add(&input.condition).err();
+            if input.error.is_none() {
+                input.condition.clear();
+            }
+        }
+    });
+    if let Some(error) = &input.error {
+        ui.colored_label(Color32::RED, error);
+    }
+}
+
+// Innermost subroutine first, each with where it was called from
+fn stack_view(
+    ui: &mut egui::Ui,
+    chip8: &processor::Processor,
+    calls: &CallStack,
+    symbols: &Symbols,
+) {
+    ui.monospace(format!("SP: {}", chip8.stack_pointer));
+    for (i, frame) in calls.frames.iter().enumerate().rev() {
+        let target = match frame.target {
+            Some(address) => symbols.describe(address),
+            None => String::from("?"),
+        };
+        ui.monospace(format!(
+            "{:2}: {} from {}",
+            i,
+            target,
+            symbols.describe(frame.call_site)
+        ));
+    }
+    if let Some(warning) = &calls.warning {
+        ui.colored_label(Color32::YELLOW, warning);
+    }
+}
+
+// Live hex dump around PC and I. While editable a byte can be clicked and retyped in hex,
+// Enter writes it and anything else discards the edit
+fn memory_view(
+    ui: &mut egui::Ui,
+    chip8: &mut processor::Processor,
+    editor: &mut MemoryEditor,
+    editable: bool,
+) {
+    if !editable {
+        editor.selected = None;
+    }
+    let views = [
+        ("PC", chip8.pc as usize),
+        ("I", chip8.idx_register as usize),
+    ];
+    for (view, (register, address)) in views.into_iter().enumerate() {
+        ui.label(format!("Around {}", register));
+        let (start, bytes) = chip8
+            .ram
+            .rows_around(address, MEMORY_ROWS / 2, MEMORY_ROW_BYTES);
+        // Copied so the rows can be drawn while an edit writes back to RAM
+        let bytes = bytes.to_vec();
+        for (row, values) in (start..)
+            .step_by(MEMORY_ROW_BYTES)
+            .zip(bytes.chunks(MEMORY_ROW_BYTES))
+        {
+            ui.horizontal(|ui| {
+                let marker = if (row..row + MEMORY_ROW_BYTES).contains(&address) {
+                    ">"
+                } else {
+                    " "
+                };
+                ui.monospace(format!("{}{:03X}:", marker, row));
+                for (at, value) in (row..).zip(values) {
+                    if editor.selected == Some((view, at)) {
+                        let edit = ui.add(
+                            egui::TextEdit::singleline(&mut editor.input)
+                                .font(egui::TextStyle::Monospace)
+                                .char_limit(2)
+                                .desired_width(16.0),
+                        );
+                        if edit.lost_focus() {
+                            let submitted = ui.input(|input| input.key_pressed(Key::Enter));
+                            match u8::from_str_radix(editor.input.trim(), 16) {
+                                Ok(value) if submitted => {
+                                    let _ = chip8.ram.write(at, value);
+                                }
+                                _ => {}
+                            }
+                            editor.selected = None;
+                        } else {
+                            edit.request_focus();
+                        }
+                    } else if editable {
+                        let label = egui::RichText::new(format!("{:02X}", value)).monospace();
+                        if ui.selectable_label(false, label).clicked() {
+                            editor.selected = Some((view, at));
+                            editor.input = format!("{:02X}", value);
+                        }
+                    } else {
+                        ui.monospace(format!("{:02X}", value));
+                    }
+                }
+            });
+        }
+    }
+    if editable {
+        ui.label("Click a byte to edit it");
+    }
+}
+
+// The sprite at I decoded as pixels, sized like the last DXYN or as the tallest regular
+// sprite before anything was drawn
+fn sprite_view(
+    ui: &mut egui::Ui,
+    chip8: &processor::Processor,
+    last: Option<SpriteDraw>,
+    foreground: Color32,
+) {
+    let (width, height) = last.map_or((8, 15), |d| (d.width, d.height));
+    ui.monospace(format!("I: {:03X}", chip8.idx_register));
+    if let Some(d) = last {
+        ui.monospace(format!(
+            "Last draw: {}x{} at ({}, {}) from {:03X}",
+            d.width, d.height, d.x, d.y, d.address
+        ));
+    }
+    let cell = SPRITE_CELL_SIZE;
+    let (response, painter) = ui.allocate_painter(
+        egui::vec2(width as f32 * cell, height as f32 * cell),
+        egui::Sense::hover(),
+    );
+    let address = chip8.idx_register as usize;
+    for row in 0..height {
+        for col in 0..width {
+            let byte = chip8.ram.read(address + row * width / 8 + col / 8);
+            let color = match byte {
+                Some(b) if b & (0x80 >> (col % 8)) != 0 => foreground,
+                _ => Color32::DARK_GRAY,
+            };
+            let min = response.rect.min + egui::vec2(col as f32 * cell, row as f32 * cell);
+            let rect = egui::Rect::from_min_size(min, egui::vec2(cell - 1.0, cell - 1.0));
+            painter.rect_filled(rect, 0.0, color);
+        }
+    }
+}
+
+impl eframe::App for EmulatorApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.run_frame(ctx);
+        self.menu_bar(ctx);
+        self.open_dialog(ctx);
+        self.slot_picker(ctx);
+        self.debug_panels(ctx);
+
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.label(&self.status);
+        });
+
+        let image = self.frame_image();
+        let texture = match self.texture.as_mut() {
+            Some(t) => {
+                t.set(image, TextureOptions::NEAREST);
+                t
+            }
+            None => {
+                self.texture
+                    .insert(ctx.load_texture("display", image, TextureOptions::NEAREST))
+            }
+        };
+
+        egui::CentralPanel::default()
+            .frame(egui::Frame::NONE.fill(self.background))
+            .show(ctx, |ui| {
+                let available = ui.available_size();
+                let scale = (available.x / consts::CHIP8_WIDTH as f32)
+                    .min(available.y / consts::CHIP8_HEIGHT as f32);
+                let size = egui::vec2(
+                    consts::CHIP8_WIDTH as f32 * scale,
+                    consts::CHIP8_HEIGHT as f32 * scale,
+                );
+                let image = ui.centered_and_justified(|ui| {
+                    ui.add(egui::Image::new((texture.id(), size)).fit_to_exact_size(size))
+                });
+                if let (true, Some(draw), Some(chip8)) =
+                    (self.panels.sprite, self.last_sprite, &self.chip8)
+                {
+                    // Clipped to the screen, a wrapped sprite only outlines the part before
+                    // the edge
+                    let (width, height) =
+                        (chip8.display_buffer.width(), chip8.display_buffer.height());
+                    let rect = image.inner.rect;
+                    let pixel = rect.width() / width as f32;
+                    let min = rect.min + egui::vec2(draw.x as f32, draw.y as f32) * pixel;
+                    let max = rect.min
+                        + egui::vec2(
+                            (draw.x + draw.width).min(width) as f32,
+                            (draw.y + draw.height).min(height) as f32,
+                        ) * pixel;
+                    ui.painter().rect_stroke(
+                        egui::Rect::from_min_max(min, max),
+                        0.0,
+                        egui::Stroke::new(2.0, Color32::RED),
+                        egui::StrokeKind::Outside,
+                    );
+                }
+            });
+
+        ctx.request_repaint();
+    }
+}
+
+pub fn run(
+    rom_path: Option<String>,
+    symbols_path: Option<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("CHIP-8")
+            .with_inner_size([consts::DISPL_WIDTH as f32, consts::DISPL_HEIGHT as f32]),
+        ..Default::default()
+    };
+    eframe::run_native(
+        "CHIP-8",
+        options,
+        Box::new(|_cc| Ok(Box::new(EmulatorApp::new(rom_path, symbols_path)))),
+    )?;
+    Ok(())
+}
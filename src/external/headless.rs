@@ -0,0 +1,118 @@
+use crate::core::events::Event;
+use crate::core::keypad::KeyScript;
+use crate::core::processor::Processor;
+use crate::external::output;
+
+// Process exit status when --until-halt gave up at --max-frames, errors exit with 1
+pub const TIMED_OUT: i32 = 2;
+
+// When a headless run stops, the program itself only ends it under --until-halt
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limits {
+    pub max_frames: Option<u64>,
+    pub until_halt: bool,
+}
+
+// Runs without a window, sound or input other than a key script, as fast as the host allows,
+// and returns the exit status for the shell: 0 when the run ended as asked, TIMED_OUT when
+// --until-halt ran out of frames first. after_frame runs after every frame, e.g. for cheats
+// and stats
+pub fn run(
+    chip8: &mut Processor,
+    cycles_per_frame: usize,
+    limits: Limits,
+    mut script: Option<KeyScript>,
+    mut after_frame: impl FnMut(&mut Processor),
+) -> Result<i32, &'static str> {
+    let events = chip8.events.subscribe();
+    loop {
+        if limits.max_frames.is_some_and(|max| chip8.frames >= max) {
+            return Ok(if limits.until_halt { TIMED_OUT } else { 0 });
+        }
+        if let Some(s) = script.as_mut() {
+            chip8.keyboard_buffer.keys = s.keys_at(chip8.frames);
+        }
+        chip8.run_frame(cycles_per_frame)?;
+        after_frame(chip8);
+        if events
+            .try_iter()
+            .any(|event| matches!(event, Event::Halted))
+        {
+            return Ok(0);
+        }
+    }
+}
+
+// The screen as text, one character per pixel, followed by the registers
+pub fn dump(chip8: &Processor) -> String {
+    let mut text = chip8.display_buffer.to_ascii();
+    for line in output::overlay_lines(chip8) {
+        text.push_str(&line);
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dump, run, Limits, TIMED_OUT};
+    use crate::core::keypad::KeyScript;
+    use crate::core::processor::Processor;
+
+    fn load(program: &[u8]) -> Processor {
+        let mut chip8 = Processor::builder().build();
+        chip8.ram.buffer[0x200..0x200 + program.len()].copy_from_slice(program);
+        chip8
+    }
+
+    #[test]
+    fn test_until_halt() -> Result<(), &'static str> {
+        // v0 := 7, exit
+        let mut chip8 = load(&[0x60, 0x07, 0x00, 0xFD]);
+        let limits = Limits {
+            max_frames: Some(10),
+            until_halt: true,
+        };
+        assert_eq!(run(&mut chip8, 10, limits, None, |_| {})?, 0);
+        assert_eq!(chip8.registers[0], 7);
+        // Jumping to itself never halts
+        let mut chip8 = load(&[0x12, 0x00]);
+        let mut after = 0;
+        assert_eq!(
+            run(&mut chip8, 10, limits, None, |_| after += 1)?,
+            TIMED_OUT
+        );
+        assert_eq!((chip8.frames, after), (10, 10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_frames() -> Result<(), &'static str> {
+        let mut chip8 = load(&[0x12, 0x00]);
+        let limits = Limits {
+            max_frames: Some(3),
+            until_halt: false,
+        };
+        assert_eq!(run(&mut chip8, 10, limits, None, |_| {})?, 0);
+        assert_eq!(chip8.frames, 3);
+        let text = dump(&chip8);
+        assert!(text.starts_with(&".".repeat(64)));
+        assert!(text.contains("V0"), "{}", text);
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_script() -> Result<(), &'static str> {
+        // v1 := key, exit
+        let mut chip8 = load(&[0xF1, 0x0A, 0x00, 0xFD]);
+        let script = KeyScript::parse("frame 5: press b").map_err(|_| "Invalid script")?;
+        let limits = Limits {
+            max_frames: Some(10),
+            until_halt: true,
+        };
+        assert_eq!(run(&mut chip8, 10, limits, Some(script), |_| {})?, 0);
+        // The key goes down before frame 5 runs, which then reaches the exit
+        assert_eq!((chip8.registers[1], chip8.frames), (0xB, 5));
+        Ok(())
+    }
+}
@@ -1,35 +1,443 @@
-use sdl2;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-
 use crate::consts;
-use crate::core::ram::KeyboardBuffer;
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::time::Duration;
+// Only the keyboard driver needs SDL, the layouts are also used by the other frontends
+#[cfg(feature = "sdl")]
+use {
+    crate::core::ram::KeyboardBuffer,
+    sdl2::event::Event,
+    sdl2::keyboard::{Keycode, Mod},
+    sdl2::mouse::MouseButton,
+    std::collections::HashMap,
+    std::time::Instant,
+};
+
+// Emulator controls that are handled by the frontend instead of the keypad
+pub enum Hotkey {
+    Screenshot,
+    ToggleMute,
+    SpeedUp,
+    SpeedDown,
+    ToggleFastForward,
+    ToggleCheats,
+    ToggleOverlay,
+    ToggleHeatmap,
+    ResetHeatmap,
+    ExportHeatmap,
+    ExportHistogram,
+    ExportCoverage,
+    ToggleHighContrast,
+    ToggleInverted,
+    BrightnessUp,
+    BrightnessDown,
+    ToggleScope,
+    // Slots numbered from 1, saved with Shift+F1-F10 and loaded with F1-F10
+    SaveSlot(usize),
+    LoadSlot(usize),
+}
+
+// Function keys are for save slots on their own and for the debugging tools with Ctrl held
+#[cfg(feature = "sdl")]
+const CTRL: Mod = Mod::LCTRLMOD.union(Mod::RCTRLMOD);
+
+#[cfg(feature = "sdl")]
+fn slot(keycode: Keycode) -> Option<usize> {
+    let slot = match keycode {
+        Keycode::F1 => 1,
+        Keycode::F2 => 2,
+        Keycode::F3 => 3,
+        Keycode::F4 => 4,
+        Keycode::F5 => 5,
+        Keycode::F6 => 6,
+        Keycode::F7 => 7,
+        Keycode::F8 => 8,
+        Keycode::F9 => 9,
+        Keycode::F10 => 10,
+        _ => return None,
+    };
+    Some(slot)
+}
+
+// Position of a host key, named as SDL does, in the CHIP-8 keypad
+pub fn key_index(name: &str) -> Option<usize> {
+    KeyLayout::default().index(name)
+}
+
+// Host keys for keypad keys 0 to F in order, named as SDL does. SDL names keys after the
+// system's keyboard layout, so each preset puts the keypad on the same physical keys, the
+// left 4x4 block, under its layout
+const LAYOUT_PRESETS: [(&str, [&str; consts::KEYBOARD_SIZE]); 5] = [
+    (
+        "qwerty",
+        [
+            "x", "1", "2", "3", "q", "w", "e", "a", "s", "d", "z", "c", "4", "r", "f", "v",
+        ],
+    ),
+    (
+        "azerty",
+        [
+            "x", "&", "é", "\"", "a", "z", "e", "q", "s", "d", "w", "c", "'", "r", "f", "v",
+        ],
+    ),
+    (
+        "qwertz",
+        [
+            "x", "1", "2", "3", "q", "w", "e", "a", "s", "d", "y", "c", "4", "r", "f", "v",
+        ],
+    ),
+    (
+        "dvorak",
+        [
+            "q", "1", "2", "3", "'", ",", ".", "a", "o", "e", ";", "j", "4", "p", "u", "k",
+        ],
+    ),
+    // Digits on the numpad keys of the same name, for left-handed players who keep the
+    // mouse on the left and play with the right hand
+    (
+        "numpad",
+        [
+            "Keypad 0",
+            "Keypad 1",
+            "Keypad 2",
+            "Keypad 3",
+            "Keypad 4",
+            "Keypad 5",
+            "Keypad 6",
+            "Keypad 7",
+            "Keypad 8",
+            "Keypad 9",
+            "Keypad /",
+            "Keypad *",
+            "Keypad -",
+            "Keypad +",
+            "Keypad Enter",
+            "Keypad .",
+        ],
+    ),
+];
+
+// Which host key presses each keypad key, chosen with --layout or spelled out with --keymap
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyLayout {
+    // Lowercase, in keypad order
+    pub keys: [String; consts::KEYBOARD_SIZE],
+}
+
+impl Default for KeyLayout {
+    fn default() -> Self {
+        KeyLayout {
+            keys: consts::KEY_LAYOUT.map(String::from),
+        }
+    }
+}
+
+impl KeyLayout {
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        LAYOUT_PRESETS.iter().map(|(name, _)| *name)
+    }
+
+    pub fn preset(name: &str) -> Result<Self, String> {
+        match LAYOUT_PRESETS
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        {
+            Some((_, keys)) => Ok(KeyLayout {
+                keys: keys.map(str::to_lowercase),
+            }),
+            None => Err(format!(
+                "Unknown layout {}, expected one of {}",
+                name,
+                KeyLayout::names().collect::<Vec<_>>().join(", ")
+            )),
+        }
+    }
+
+    // Host keys for keypad keys 0 to F in order, one character each, e.g. x123qweasdzc4rfv
+    // for the default layout
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let keys: Vec<String> = s.chars().map(|c| c.to_lowercase().collect()).collect();
+        if keys.iter().enumerate().any(|(i, k)| keys[..i].contains(k)) {
+            return Err(format!("Key map {} uses a key twice", s));
+        }
+        let keys = keys
+            .try_into()
+            .map_err(|_| format!("Key map {} should have {} keys", s, consts::KEYBOARD_SIZE))?;
+        Ok(KeyLayout { keys })
+    }
+
+    pub fn index(&self, name: &str) -> Option<usize> {
+        let name = name.to_lowercase();
+        self.keys.iter().position(|k| *k == name)
+    }
+}
+
+// Touch screens are split into a 4x4 grid of regions over the game, one per keypad key.
+// Given row by row as 16 hex digits with --touch-grid, the COSMAC VIP keypad by default
+#[derive(Clone, Debug, PartialEq)]
+pub struct TouchGrid {
+    pub keys: [usize; consts::KEYBOARD_SIZE],
+}
+
+impl Default for TouchGrid {
+    fn default() -> Self {
+        TouchGrid {
+            keys: consts::KEYPAD_GRID,
+        }
+    }
+}
+
+impl TouchGrid {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let keys: Vec<usize> = s
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as usize))
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("Touch grid {} should only have hex digits", s))?;
+        if keys.iter().enumerate().any(|(i, k)| keys[..i].contains(k)) {
+            return Err(format!("Touch grid {} uses a key twice", s));
+        }
+        let keys = keys.try_into().map_err(|_| {
+            format!(
+                "Touch grid {} should have {} keys",
+                s,
+                consts::KEYBOARD_SIZE
+            )
+        })?;
+        Ok(TouchGrid { keys })
+    }
 
+    // Key under a touch, with the position as a fraction of the game's width and height
+    pub fn key_at(&self, x: f32, y: f32) -> Option<usize> {
+        if !(0.0..1.0).contains(&x) || !(0.0..1.0).contains(&y) {
+            return None;
+        }
+        let (column, row) = ((x * 4.0) as usize, (y * 4.0) as usize);
+        Some(self.keys[row * 4 + column])
+    }
+}
+
+// A host key that taps a keypad key on and off for as long as it is held, for games where
+// a key has to be hammered. Given as HOST:KEY[:RATE], e.g. space:5:15 taps 5 fifteen times
+// a second while space is held
+#[derive(Clone, Debug, PartialEq)]
+pub struct Turbo {
+    // Named as SDL does
+    pub host_key: String,
+    pub key: usize,
+    // Taps per second
+    pub rate: u32,
+}
+
+impl Turbo {
+    // Each tap holds the key for half its period, starting down as soon as the host key is
+    pub fn pressed(&self, held_for: Duration) -> bool {
+        let half_period = 500_000_000 / self.rate as u128;
+        (held_for.as_nanos() / half_period).is_multiple_of(2)
+    }
+}
+
+impl std::str::FromStr for Turbo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let usage = || format!("Turbo {} should be HOST:KEY[:RATE]", s);
+        let mut parts = s.split(':');
+        let (host_key, key) = match (parts.next(), parts.next()) {
+            (Some(host), Some(key)) if !host.is_empty() => (host, key),
+            _ => return Err(usage()),
+        };
+        let key = match usize::from_str_radix(key, 16) {
+            Ok(key) if key < consts::KEYBOARD_SIZE => key,
+            _ => return Err(format!("Invalid keypad key {}", key)),
+        };
+        let rate = match parts.next() {
+            Some(rate) => match rate.parse() {
+                Ok(rate) if rate > 0 && rate <= consts::MAX_TURBO_RATE => rate,
+                _ => {
+                    return Err(format!(
+                        "Invalid turbo rate {}, expected 1 to {}",
+                        rate,
+                        consts::MAX_TURBO_RATE
+                    ))
+                }
+            },
+            None => consts::TURBO_RATE,
+        };
+        if parts.next().is_some() {
+            return Err(usage());
+        }
+        Ok(Turbo {
+            host_key: host_key.to_string(),
+            key,
+            rate,
+        })
+    }
+}
+
+#[cfg(feature = "sdl")]
 pub struct KeyboardDriver {
     events: sdl2::EventPump,
-    pub keyboard_buffer: Rc<RefCell<KeyboardBuffer>>,
+    pub layout: KeyLayout,
+    pub turbo: Option<Turbo>,
+    // When the turbo key went down, the taps are timed from here
+    turbo_since: Option<Instant>,
+    // Where the left mouse button is held, for the on-screen keypad
+    pub mouse: Option<(i32, i32)>,
+    // Fingers on a touch screen by id, as fractions of the window's width and height
+    pub touches: HashMap<i64, (f32, f32)>,
 }
 
+#[cfg(feature = "sdl")]
 impl KeyboardDriver {
-    pub fn new(
-        context: &sdl2::Sdl,
-        keyboard_buffer_: &Rc<RefCell<KeyboardBuffer>>,
-    ) -> Result<Self, &'static str> {
+    pub fn new(context: &sdl2::Sdl) -> Result<Self, &'static str> {
         Ok(KeyboardDriver {
             events: match context.event_pump() {
                 Ok(t) => t,
                 Err(_) => return Err("Could not obtain event context"),
             },
-            keyboard_buffer: Rc::clone(keyboard_buffer_),
+            layout: Default::default(),
+            turbo: None,
+            turbo_since: None,
+            mouse: None,
+            touches: HashMap::new(),
         })
     }
 
-    pub fn poll(&mut self) -> Result<(), &'static str> {
+    // Replaces the keypad with the keys held right now and returns the hotkeys pressed since
+    // the last poll
+    pub fn poll(&mut self, keyboard: &mut KeyboardBuffer) -> Result<Vec<Hotkey>, &'static str> {
+        let mut hotkeys = Vec::new();
         for event in self.events.poll_iter() {
             match event {
                 Event::Quit { .. } => return Err("Received quit event"),
+                Event::MouseButtonDown {
+                    mouse_btn: MouseButton::Left,
+                    x,
+                    y,
+                    ..
+                } => self.mouse = Some((x, y)),
+                Event::MouseMotion {
+                    mousestate, x, y, ..
+                } if mousestate.left() => self.mouse = Some((x, y)),
+                Event::MouseButtonUp {
+                    mouse_btn: MouseButton::Left,
+                    ..
+                } => self.mouse = None,
+                Event::FingerDown {
+                    finger_id, x, y, ..
+                }
+                | Event::FingerMotion {
+                    finger_id, x, y, ..
+                } => {
+                    self.touches.insert(finger_id, (x, y));
+                }
+                Event::FingerUp { finger_id, .. } => {
+                    self.touches.remove(&finger_id);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
+                    ..
+                } => hotkeys.push(Hotkey::Screenshot),
+                Event::KeyDown {
+                    keycode: Some(Keycode::M),
+                    repeat: false,
+                    ..
+                } => hotkeys.push(Hotkey::ToggleMute),
+                Event::KeyDown {
+                    keycode: Some(Keycode::PageUp),
+                    keymod,
+                    ..
+                } if keymod.intersects(CTRL) => hotkeys.push(Hotkey::BrightnessUp),
+                Event::KeyDown {
+                    keycode: Some(Keycode::PageDown),
+                    keymod,
+                    ..
+                } if keymod.intersects(CTRL) => hotkeys.push(Hotkey::BrightnessDown),
+                Event::KeyDown {
+                    keycode: Some(Keycode::PageUp),
+                    ..
+                } => hotkeys.push(Hotkey::SpeedUp),
+                Event::KeyDown {
+                    keycode: Some(Keycode::PageDown),
+                    ..
+                } => hotkeys.push(Hotkey::SpeedDown),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Tab),
+                    repeat: false,
+                    ..
+                } => hotkeys.push(Hotkey::ToggleFastForward),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if keymod.intersects(CTRL) => hotkeys.push(Hotkey::ToggleCheats),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F1),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if keymod.intersects(CTRL) => hotkeys.push(Hotkey::ToggleOverlay),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F2),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if keymod.intersects(CTRL) => hotkeys.push(Hotkey::ToggleHeatmap),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F3),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if keymod.intersects(CTRL) => hotkeys.push(Hotkey::ResetHeatmap),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F4),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if keymod.intersects(CTRL) => hotkeys.push(Hotkey::ExportHeatmap),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if keymod.intersects(CTRL) => hotkeys.push(Hotkey::ExportHistogram),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F6),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if keymod.intersects(CTRL) => hotkeys.push(Hotkey::ExportCoverage),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F7),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if keymod.intersects(CTRL) => hotkeys.push(Hotkey::ToggleHighContrast),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F8),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if keymod.intersects(CTRL) => hotkeys.push(Hotkey::ToggleInverted),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F10),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if keymod.intersects(CTRL) => hotkeys.push(Hotkey::ToggleScope),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    repeat: false,
+                    ..
+                } => match slot(keycode) {
+                    Some(slot) if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+                        hotkeys.push(Hotkey::SaveSlot(slot))
+                    }
+                    Some(slot) => hotkeys.push(Hotkey::LoadSlot(slot)),
+                    None => continue,
+                },
                 _ => continue,
             }
         }
@@ -41,34 +449,122 @@ impl KeyboardDriver {
             .filter_map(Keycode::from_scancode)
             .collect();
 
-        self.keyboard_buffer.borrow_mut().buffer = [0; consts::KEYBOARD_SIZE];
-
-        for key in keys {
-            let index = match key {
-                Keycode::Num1 => Some(0x1),
-                Keycode::Num2 => Some(0x2),
-                Keycode::Num3 => Some(0x3),
-                Keycode::Num4 => Some(0xC),
-                Keycode::Q => Some(0x4),
-                Keycode::W => Some(0x5),
-                Keycode::E => Some(0x6),
-                Keycode::R => Some(0xD),
-                Keycode::A => Some(0x7),
-                Keycode::S => Some(0x8),
-                Keycode::D => Some(0x9),
-                Keycode::F => Some(0xE),
-                Keycode::Z => Some(0xA),
-                Keycode::X => Some(0x0),
-                Keycode::C => Some(0xB),
-                Keycode::V => Some(0xF),
-                Keycode::Escape => return Err("Received interrupt, exiting..."),
-                _ => None,
-            };
-
-            if let Some(i) = index {
-                self.keyboard_buffer.borrow_mut().buffer[i] = 1;
+        keyboard.keys = 0;
+
+        for key in &keys {
+            if *key == Keycode::Escape {
+                return Err("Received interrupt, exiting...");
+            }
+            if let Some(i) = self.layout.index(&key.name()) {
+                keyboard.set(i);
             }
         }
+        let turbo = self.turbo.as_ref().filter(|t| {
+            keys.iter()
+                .any(|k| k.name().eq_ignore_ascii_case(&t.host_key))
+        });
+        match turbo {
+            Some(turbo) => {
+                let since = *self.turbo_since.get_or_insert_with(Instant::now);
+                if turbo.pressed(since.elapsed()) {
+                    keyboard.set(turbo.key);
+                }
+            }
+            None => self.turbo_since = None,
+        }
+        Ok(hotkeys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyLayout, TouchGrid, Turbo};
+    use crate::consts;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_turbo() {
+        let turbo: Turbo = "space:a:20".parse().unwrap();
+        assert_eq!(
+            (turbo.host_key.as_str(), turbo.key, turbo.rate),
+            ("space", 10, 20)
+        );
+        assert_eq!(
+            "x:5".parse::<Turbo>().map(|t| t.rate),
+            Ok(consts::TURBO_RATE)
+        );
+        assert_eq!("x:5:30".parse::<Turbo>().map(|t| t.rate), Ok(30));
+        for invalid in [
+            "space",
+            ":5",
+            "space:g",
+            "space:5:0",
+            "space:5:31",
+            "space:5:4000000000",
+            "space:5:10:1",
+        ] {
+            assert!(invalid.parse::<Turbo>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_layout() {
+        assert_eq!(
+            KeyLayout::parse("X123QWEASDZC4RFV"),
+            Ok(KeyLayout::default())
+        );
+        assert!(KeyLayout::parse("x123").is_err());
+        assert!(KeyLayout::parse("x123qweasdzc4rfx").is_err());
+    }
+
+    #[test]
+    fn test_layout_presets() -> Result<(), String> {
+        assert_eq!(KeyLayout::preset("QWERTY")?, KeyLayout::default());
+        for name in KeyLayout::names() {
+            let layout = KeyLayout::preset(name)?;
+            let keys = &layout.keys;
+            assert!(
+                keys.iter().enumerate().all(|(i, k)| !keys[..i].contains(k)),
+                "{}",
+                name
+            );
+        }
+        assert_eq!(KeyLayout::preset("azerty")?.index("É"), Some(2));
+        assert_eq!(KeyLayout::preset("qwertz")?.index("Y"), Some(0xA));
+        assert_eq!(
+            KeyLayout::preset("numpad")?.index("Keypad Enter"),
+            Some(0xE)
+        );
+        assert_eq!(KeyLayout::preset("dvorak")?.index("X"), None);
+        assert!(KeyLayout::preset("colemak").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_touch_grid() -> Result<(), String> {
+        assert_eq!(TouchGrid::parse("123c456d789ea0bf")?, TouchGrid::default());
+        let grid = TouchGrid::default();
+        assert_eq!(grid.key_at(0.0, 0.0), Some(0x1));
+        assert_eq!(grid.key_at(0.3, 0.9), Some(0x0));
+        assert_eq!(grid.key_at(0.99, 0.99), Some(0xF));
+        assert_eq!(grid.key_at(1.0, 0.5), None);
+        assert_eq!(grid.key_at(0.5, -0.1), None);
+        assert_eq!(
+            TouchGrid::parse("0123456789ABCDEF")?.key_at(0.8, 0.3),
+            Some(7)
+        );
+        assert!(TouchGrid::parse("0123").is_err());
+        assert!(TouchGrid::parse("0123456789ABCDEG").is_err());
+        assert!(TouchGrid::parse("0123456789ABCDEE").is_err());
         Ok(())
     }
+
+    #[test]
+    fn test_turbo_taps() {
+        let turbo: Turbo = "space:5:10".parse().unwrap();
+        let at = |millis| turbo.pressed(Duration::from_millis(millis));
+        assert!(at(0) && at(49));
+        assert!(!at(50) && !at(99));
+        assert!(at(100));
+    }
 }
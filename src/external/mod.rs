@@ -1,2 +1,20 @@
+#[cfg(feature = "cpal")]
+pub mod cpal_audio;
+pub mod crowd;
+#[cfg(feature = "embedded-graphics")]
+pub mod embedded;
+#[cfg(feature = "gui")]
+pub mod gui;
+pub mod headless;
 pub mod input;
+pub mod netplay;
 pub mod output;
+pub mod recorder;
+#[cfg(feature = "sdl")]
+pub mod remote;
+#[cfg(feature = "stats")]
+pub mod stats;
+#[cfg(feature = "websocket")]
+pub mod stream;
+#[cfg(feature = "tui")]
+pub mod tui;
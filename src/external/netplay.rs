@@ -0,0 +1,119 @@
+use crate::core::platform::Platform;
+use crate::core::quirks::Quirks;
+use crate::core::rom::Rom;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+// Lockstep netplay over TCP: before every frame both instances send their own keypad and
+// wait for the peer's, then run the frame with the union of both. As long as both start
+// from the same ROM, settings and RNG seed they stay in sync without exchanging anything
+// else
+#[derive(Clone, Debug, PartialEq)]
+pub enum Role {
+    Host(String),
+    Join(String),
+}
+
+pub struct Netplay {
+    stream: TcpStream,
+}
+
+// SHA-1 of everything besides the ROM that changes how a frame runs
+pub fn settings_digest(quirks: &Quirks, platform: Platform, cycles_per_frame: usize) -> String {
+    let settings = format!("{:?} {:?} {}", quirks, platform, cycles_per_frame);
+    sha1_smol::Sha1::from(settings).digest().to_string()
+}
+
+impl Netplay {
+    // Returns the connection and the RNG seed both sides must use. The host picks the seed
+    // and sends it along with the SHA-1 of its ROM and the settings digest, which the
+    // joining side checks
+    pub fn connect(
+        role: &Role,
+        rom: &Rom,
+        settings: &str,
+    ) -> Result<(Self, u64), Box<dyn std::error::Error>> {
+        let (mut stream, seed) = match role {
+            Role::Host(address) => {
+                println!("Waiting for a player to join on {}", address);
+                let (mut stream, peer) = TcpListener::bind(address)?.accept()?;
+                let seed: u64 = rand::random();
+                stream.write_all(&seed.to_be_bytes())?;
+                stream.write_all(rom.sha1.as_bytes())?;
+                stream.write_all(settings.as_bytes())?;
+                println!("{} joined", peer);
+                (stream, seed)
+            }
+            Role::Join(address) => {
+                let mut stream = TcpStream::connect(address)?;
+                let mut seed = [0; 8];
+                stream.read_exact(&mut seed)?;
+                let mut sha1 = vec![0; rom.sha1.len()];
+                stream.read_exact(&mut sha1)?;
+                if sha1 != rom.sha1.as_bytes() {
+                    return Err("The host is running a different ROM".into());
+                }
+                let mut digest = vec![0; settings.len()];
+                stream.read_exact(&mut digest)?;
+                if digest != settings.as_bytes() {
+                    return Err("The host uses different quirks, platform or speed".into());
+                }
+                (stream, u64::from_be_bytes(seed))
+            }
+        };
+        stream.set_nodelay(true)?;
+        stream.flush()?;
+        Ok((Netplay { stream }, seed))
+    }
+
+    // Blocks until the peer's keypad for the same frame arrives, keypads are sent as the
+    // KeyboardBuffer bitmask
+    pub fn exchange(&mut self, local: u16) -> Result<u16, Box<dyn std::error::Error>> {
+        self.stream.write_all(&local.to_be_bytes())?;
+        let mut remote = [0; 2];
+        self.stream.read_exact(&mut remote)?;
+        Ok(local | u16::from_be_bytes(remote))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{settings_digest, Netplay};
+    use crate::core::platform::Platform;
+    use crate::core::quirks::Quirks;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn test_settings_digest() {
+        let quirks = Quirks::default();
+        let digest = settings_digest(&quirks, Platform::default(), 10);
+        assert_eq!(digest.len(), 40);
+        assert_eq!(digest, settings_digest(&quirks, Platform::default(), 10));
+        assert_ne!(digest, settings_digest(&quirks, Platform::default(), 11));
+        let other = Quirks {
+            shift_vy: !quirks.shift_vy,
+            ..quirks
+        };
+        assert_ne!(digest, settings_digest(&other, Platform::default(), 10));
+        assert_ne!(digest, settings_digest(&quirks, Platform::Chip8, 10));
+    }
+
+    #[test]
+    fn test_exchange_keys() -> Result<(), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let mut join = Netplay {
+            stream: TcpStream::connect(listener.local_addr()?)?,
+        };
+        let mut host = Netplay {
+            stream: listener.accept()?.0,
+        };
+        // The host's keys go out first so one thread can play both sides
+        host.stream.write_all(&0x8000_u16.to_be_bytes())?;
+        assert_eq!(join.exchange(0x0002)?, 0x8002);
+        let mut keys = [0; 2];
+        host.stream.read_exact(&mut keys)?;
+        assert_eq!(u16::from_be_bytes(keys), 0x0002);
+        Ok(())
+    }
+}
@@ -1,29 +1,489 @@
 use crate::consts;
+use crate::core::platform::Platform;
+use crate::core::processor::{Pitch, Processor};
 use crate::core::ram::DisplayBuffer;
-use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::core::snapshot::Thumbnail;
+#[cfg(feature = "sdl")]
+pub use sdl2::pixels::Color;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+// The window and the SDL audio device, everything else here works without SDL
+#[cfg(feature = "sdl")]
+use {
+    crate::core::screen::Screen,
+    crate::external::input::TouchGrid,
+    crate::heatmap::{heat_color, Heatmap},
+    sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired},
+    sdl2::rect::{Point, Rect},
+    sdl2::render::{BlendMode, Canvas},
+    sdl2::video::Window,
+};
 
+// Stands in for SDL's color when building without it, with the same fields and constructors
+#[cfg(not(feature = "sdl"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+#[cfg(not(feature = "sdl"))]
+#[allow(non_snake_case)]
+impl Color {
+    pub const fn RGB(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, a: 0xFF }
+    }
+
+    pub const fn RGBA(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color { r, g, b, a }
+    }
+}
+
+// Colors for unset and set pixels, given on the command line as two hex RGB values. The
+// foreground is XO-CHIP plane 1, plane 2 and pixels set in both get colors of their own
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub foreground: Color,
+    pub background: Color,
+    pub plane2: Color,
+    pub overlap: Color,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            foreground: Color::RGB(0, 255, 0),
+            background: Color::RGB(0, 0, 0),
+            plane2: Color::RGB(0xFF, 0x40, 0x40),
+            overlap: Color::RGB(0xB4, 0xB4, 0x00),
+        }
+    }
+}
+
+impl Palette {
+    // Named palettes accepted by --palette. The colorblind presets keep a large luminance
+    // difference and pick hues from the Okabe-Ito set that stay distinct under each kind of
+    // color blindness, cb-safe works for all three. The plane colors are also spaced apart in
+    // luminance, so they can be told apart without relying on hue
+    pub const PRESETS: [&'static str; 5] = [
+        "classic",
+        "cb-safe",
+        "deuteranopia",
+        "protanopia",
+        "tritanopia",
+    ];
+
+    pub fn preset(name: &str) -> Option<Palette> {
+        // Foreground, background, plane 2 and overlap
+        let (foreground, background, plane2, overlap) = match name {
+            "classic" => return Some(Default::default()),
+            // Yellow on near black, with sky blue and a dark vermillion
+            "cb-safe" | "deuteranopia" => (0xF0E442, 0x101010, 0x56B4E9, 0xD55E00),
+            // Sky blue on black, red looks dark to protanopes so it is avoided altogether. The
+            // overlap is a darkened bluish green
+            "protanopia" => (0x56B4E9, 0x000000, 0xF0E442, 0x008C66),
+            // Vermillion on near black, blue and yellow are the pair tritanopes confuse so the
+            // planes are a light cyan and white
+            "tritanopia" => (0xE66F3C, 0x101010, 0x66CCEE, 0xFFFFFF),
+            _ => return None,
+        };
+        let rgb = |rgb: u32| Color::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8);
+        Some(Palette {
+            foreground: rgb(foreground),
+            background: rgb(background),
+            plane2: rgb(plane2),
+            overlap: rgb(overlap),
+        })
+    }
+
+    // Pixel values are the XO-CHIP plane bits, 1 and 2 for either plane and 3 for both
+    pub fn color(&self, value: u8) -> Result<Color, &'static str> {
+        match value {
+            0 => Ok(self.background),
+            1 => Ok(self.foreground),
+            2 => Ok(self.plane2),
+            3 => Ok(self.overlap),
+            _ => Err("Invalid pixel value, planes only go up to 2"),
+        }
+    }
+}
+
+fn parse_rgb(s: &str) -> Result<Color, String> {
+    match u32::from_str_radix(s, 16) {
+        Ok(rgb) if s.len() == 6 => Ok(Color::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)),
+        _ => Err(format!("Invalid color {}", s)),
+    }
+}
+
+impl std::str::FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(palette) = Palette::preset(s) {
+            return Ok(palette);
+        }
+        // The plane colors are optional, programs without XO-CHIP planes never show them
+        let colors: Vec<&str> = s.split(',').collect();
+        match colors[..] {
+            [foreground, background] => Ok(Palette {
+                foreground: parse_rgb(foreground)?,
+                background: parse_rgb(background)?,
+                ..Default::default()
+            }),
+            [foreground, background, plane2, overlap] => Ok(Palette {
+                foreground: parse_rgb(foreground)?,
+                background: parse_rgb(background)?,
+                plane2: parse_rgb(plane2)?,
+                overlap: parse_rgb(overlap)?,
+            }),
+            _ => Err(format!(
+                "Palette {} should be FOREGROUND,BACKGROUND[,PLANE2,OVERLAP] or one of {}",
+                s,
+                Palette::PRESETS.join(", ")
+            )),
+        }
+    }
+}
+
+// Viewing aids applied on top of the palette in the window only, screenshots, recordings and
+// save state previews keep the colors the program was shown in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DisplayMode {
+    // Pure white on black, overriding the palette and CHIP-8X colors
+    pub high_contrast: bool,
+    pub inverted: bool,
+    // Percent, from MIN_BRIGHTNESS up to 100
+    pub brightness: u8,
+}
+
+impl Default for DisplayMode {
+    fn default() -> Self {
+        DisplayMode {
+            high_contrast: false,
+            inverted: false,
+            brightness: 100,
+        }
+    }
+}
+
+impl DisplayMode {
+    pub fn color(
+        &self,
+        display: &DisplayBuffer,
+        palette: &Palette,
+        x: usize,
+        y: usize,
+    ) -> Result<Color, &'static str> {
+        let color = match self.high_contrast {
+            true if display.buffer[y][x] != 0 => Color::RGB(255, 255, 255),
+            true => Color::RGB(0, 0, 0),
+            false => display_color(display, palette, x, y)?,
+        };
+        let (r, g, b) = match self.inverted {
+            true => (255 - color.r, 255 - color.g, 255 - color.b),
+            false => (color.r, color.g, color.b),
+        };
+        let dim = |c: u8| (c as u16 * self.brightness as u16 / 100) as u8;
+        Ok(Color::RGB(dim(r), dim(g), dim(b)))
+    }
+
+    pub fn adjust_brightness(&mut self, brighter: bool) {
+        self.brightness = match brighter {
+            true => self.brightness.saturating_add(consts::BRIGHTNESS_STEP),
+            false => self.brightness.saturating_sub(consts::BRIGHTNESS_STEP),
+        }
+        .clamp(consts::MIN_BRIGHTNESS, 100);
+    }
+}
+
+// Uses the CHIP-8X color attributes once a program has set any, plain monochrome otherwise
+pub fn display_color(
+    display: &DisplayBuffer,
+    palette: &Palette,
+    x: usize,
+    y: usize,
+) -> Result<Color, &'static str> {
+    match &display.colors {
+        Some(colors) => {
+            let index = match display.buffer[y][x] {
+                0 => colors.background_color(),
+                _ => colors.foreground[y][x],
+            };
+            let (r, g, b) = consts::CHIP8X_PALETTE[index as usize];
+            Ok(Color::RGB(r, g, b))
+        }
+        None => palette.color(display.buffer[y][x]),
+    }
+}
+
+// Preview of the screen as shown, stored with save states
+pub fn thumbnail(display: &DisplayBuffer, palette: &Palette) -> Result<Thumbnail, &'static str> {
+    let (width, height) = (display.width(), display.height());
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let color = display_color(display, palette, x, y)?;
+            rgb.extend_from_slice(&[color.r, color.g, color.b]);
+        }
+    }
+    Ok(Thumbnail::from_rgb(width, height, &rgb))
+}
+
+// Writes an RGB image as PNG, pixels are row-major with 3 bytes per pixel
+pub fn write_png(
+    path: &Path,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()?;
+    Ok(())
+}
+
+// Screenshots are placed next to the ROM, named after it with a millisecond timestamp
+// File name without the extension, ROMs read from stdin have no name to go by
+fn rom_stem(rom_path: &str) -> Option<String> {
+    Path::new(rom_path)
+        .file_stem()
+        .filter(|_| rom_path != "-")
+        .map(|s| s.to_string_lossy().into_owned())
+}
+
+// The screen as PBM for a .pbm path and as text otherwise
+pub fn export_display(display: &DisplayBuffer, path: &str) -> std::io::Result<()> {
+    let text = match Path::new(path).extension() {
+        Some(extension) if extension.eq_ignore_ascii_case("pbm") => display.to_pbm(),
+        _ => display.to_ascii(),
+    };
+    std::fs::write(path, text)
+}
+
+pub fn screenshot_path(rom_path: &str) -> PathBuf {
+    let rom = Path::new(rom_path);
+    let stem = rom_stem(rom_path).unwrap_or_else(|| String::from("chip8"));
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    rom.with_file_name(format!("{}-{}.png", stem, timestamp))
+}
+
+// Letters for the overlay and messages that the hex font does not have, in the same 4x5
+// layout
+#[cfg(feature = "sdl")]
+const LABEL_GLYPHS: [(char, [u8; consts::FONT_GLYPH_BYTES]); 10] = [
+    ('I', [0xE0, 0x40, 0x40, 0x40, 0xE0]),
+    ('L', [0x80, 0x80, 0x80, 0x80, 0xF0]),
+    ('M', [0x90, 0xF0, 0xF0, 0x90, 0x90]),
+    ('O', [0xF0, 0x90, 0x90, 0x90, 0xF0]),
+    ('P', [0xE0, 0x90, 0xE0, 0x80, 0x80]),
+    ('R', [0xE0, 0x90, 0xE0, 0xA0, 0x90]),
+    ('S', [0xF0, 0x80, 0xF0, 0x10, 0xF0]),
+    ('T', [0xE0, 0x40, 0x40, 0x40, 0x40]),
+    ('V', [0x90, 0x90, 0x90, 0x90, 0x60]),
+    ('Y', [0x90, 0x90, 0x60, 0x40, 0x40]),
+];
+// Size of one overlay font pixel in window pixels
+#[cfg(feature = "sdl")]
+const OVERLAY_SCALE: u32 = 3;
+
+// Squares of size scale making up c with its top left corner at (left, top)
+#[cfg(feature = "sdl")]
+fn glyph_rects(c: char, left: u32, top: u32, scale: u32) -> Vec<Rect> {
+    let mut rects = Vec::new();
+    for (y, bits) in glyph(c).iter().enumerate() {
+        for x in (0..4).filter(|x| bits & (0x80 >> x) != 0) {
+            rects.push(Rect::new(
+                (left + x * scale) as i32,
+                (top + y as u32 * scale) as i32,
+                scale,
+                scale,
+            ));
+        }
+    }
+    rects
+}
+
+#[cfg(feature = "sdl")]
+fn glyph(c: char) -> [u8; consts::FONT_GLYPH_BYTES] {
+    let mut rows = [0; consts::FONT_GLYPH_BYTES];
+    match (c.to_digit(16), LABEL_GLYPHS.iter().find(|(l, _)| *l == c)) {
+        (Some(digit), _) => {
+            let start = digit as usize * consts::FONT_GLYPH_BYTES;
+            rows.copy_from_slice(&consts::FONT_SET[start..start + consts::FONT_GLYPH_BYTES]);
+        }
+        (None, Some((_, label))) => rows = *label,
+        (None, None) => {}
+    }
+    rows
+}
+
+// Machine state as shown by the overlay, one string per line
+pub fn overlay_lines(chip8: &Processor) -> Vec<String> {
+    let mut lines: Vec<String> = chip8
+        .registers
+        .chunks(4)
+        .enumerate()
+        .map(|(row, values)| {
+            let cells: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| format!("V{:X} {:02X}", row * 4 + i, v))
+                .collect();
+            cells.join(" ")
+        })
+        .collect();
+    lines.push(format!(
+        "I {:03X} PC {:03X} SP {:X}",
+        chip8.idx_register, chip8.pc, chip8.stack_pointer
+    ));
+    lines.push(format!(
+        "DT {:02X} ST {:02X}",
+        chip8.delay_timer, chip8.sound_timer
+    ));
+    lines
+}
+
+// The on-screen keypad, a 4x4 grid of square keys centered below the game
+#[cfg(feature = "sdl")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeypadPanel {
+    pub left: i32,
+    pub top: i32,
+    // Side of one key in window pixels
+    pub cell: u32,
+}
+
+#[cfg(feature = "sdl")]
+impl KeypadPanel {
+    pub fn new(window_width: u32, top: u32, cell: u32) -> Self {
+        KeypadPanel {
+            left: (window_width.saturating_sub(cell * 4) / 2) as i32,
+            top: top as i32,
+            cell,
+        }
+    }
+
+    pub fn height(&self) -> u32 {
+        self.cell * 4
+    }
+
+    // Window area of the key at a position in KEYPAD_GRID
+    pub fn key_rect(&self, position: usize) -> Rect {
+        let (column, row) = ((position % 4) as i32, (position / 4) as i32);
+        let cell = self.cell as i32;
+        Rect::new(
+            self.left + column * cell,
+            self.top + row * cell,
+            self.cell,
+            self.cell,
+        )
+    }
+
+    // Keypad key under a point in the window
+    pub fn key_at(&self, x: i32, y: i32) -> Option<usize> {
+        let (column, row) = ((x - self.left), (y - self.top));
+        let size = self.height() as i32;
+        if !(0..size).contains(&column) || !(0..size).contains(&row) {
+            return None;
+        }
+        let cell = self.cell as i32;
+        Some(consts::KEYPAD_GRID[(row / cell * 4 + column / cell) as usize])
+    }
+}
+
+// Everything the window title shows, the frontend only sets the title again when it changes
+#[derive(Clone, Debug, PartialEq)]
+pub struct TitleState {
+    pub rom_name: String,
+    pub platform: Platform,
+    pub cycles_per_frame: usize,
+    pub paused: bool,
+    pub fast_forward: bool,
+    pub recording: bool,
+}
+
+impl TitleState {
+    pub fn new(rom_path: &str, platform: Platform, cycles_per_frame: usize) -> Self {
+        TitleState {
+            rom_name: rom_stem(rom_path).unwrap_or_else(|| String::from("stdin")),
+            platform,
+            cycles_per_frame,
+            paused: false,
+            fast_forward: false,
+            recording: false,
+        }
+    }
+}
+
+impl fmt::Display for TitleState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} — CHIP-8 ({}, {} ipf)",
+            self.rom_name,
+            self.platform.name(),
+            self.cycles_per_frame
+        )?;
+        if self.paused {
+            write!(f, " [paused]")?;
+        }
+        if self.fast_forward {
+            write!(f, " [fast-forward]")?;
+        }
+        if self.recording {
+            write!(f, " [recording]")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sdl")]
 pub struct DisplayDriver {
     pub canvas: Canvas<Window>,
-    pub display_buffer: Rc<RefCell<DisplayBuffer>>,
+    pub palette: Palette,
+    pub mode: DisplayMode,
+    // Size of one pane in pixels, fixed by the display geometry the program starts with
+    pub width: u32,
+    pub height: u32,
+    // Instances shown side by side, each in its own pane
+    pub panes: u32,
+    // Below the panes when shown, the window grows to make room
+    pub keypad: Option<KeypadPanel>,
 }
 
+#[cfg(feature = "sdl")]
 impl DisplayDriver {
     pub fn new(
         context: &sdl2::Sdl,
-        display_buffer_: &Rc<RefCell<DisplayBuffer>>,
+        display_buffer: &DisplayBuffer,
+        palette: Palette,
+        panes: u32,
     ) -> Result<Self, &'static str> {
         let video_subsystem = match context.video() {
             Ok(v) => v,
             Err(_) => return Err("Could not obtain video context"),
         };
+        let (width, height) = (
+            display_buffer.width() as u32 * consts::SCALE_FACTOR,
+            display_buffer.height() as u32 * consts::SCALE_FACTOR,
+        );
         let window = video_subsystem
-            .window("CHIP-8 Window", consts::DISPL_WIDTH, consts::DISPL_HEIGHT)
+            .window("CHIP-8 Window", width * panes, height)
             .build()
             .unwrap();
         let mut canvas: Canvas<Window> = window.into_canvas().present_vsync().build().unwrap();
@@ -33,85 +493,694 @@ impl DisplayDriver {
 
         Ok(DisplayDriver {
             canvas,
-            display_buffer: Rc::clone(display_buffer_),
+            palette,
+            mode: Default::default(),
+            width,
+            height,
+            panes,
+            keypad: None,
         })
     }
-    pub fn draw(&mut self) -> Result<(), &'static str> {
-        for (y, row) in self.display_buffer.borrow_mut().buffer.iter().enumerate() {
-            for (x, &col) in row.iter().enumerate() {
-                let i = (x as u32) * consts::SCALE_FACTOR;
-                let j = (y as u32) * consts::SCALE_FACTOR;
-
-                self.canvas.set_draw_color(match col {
-                    0 => Color {
-                        r: 0,
-                        g: 0,
-                        b: 0,
-                        a: 0,
-                    },
-                    1 => Color {
-                        r: 0,
-                        g: 255,
-                        b: 0,
-                        a: 0,
-                    },
-                    _ => return Err("Invalid (non-binary) pixel value"),
-                });
-                let _ = self.canvas.fill_rect(Rect::new(
-                    i as i32,
-                    j as i32,
-                    consts::SCALE_FACTOR,
-                    consts::SCALE_FACTOR,
-                ));
+
+    pub fn show_keypad(&mut self) -> Result<(), &'static str> {
+        let panel = KeypadPanel::new(self.width * self.panes, self.height, self.height / 8);
+        self.canvas
+            .window_mut()
+            .set_size(self.width * self.panes, self.height + panel.height())
+            .map_err(|_| "Could not make room for the keypad")?;
+        self.keypad = Some(panel);
+        Ok(())
+    }
+
+    pub fn keypad_key(&self, x: i32, y: i32) -> Option<usize> {
+        self.keypad.and_then(|panel| panel.key_at(x, y))
+    }
+
+    // Touches land on the keypad when it is shown and on the grid over the game otherwise
+    pub fn touch_key(&self, grid: &TouchGrid, x: f32, y: f32) -> Option<usize> {
+        match self.keypad {
+            Some(panel) => {
+                let (width, height) = self.canvas.window().size();
+                panel.key_at((x * width as f32) as i32, (y * height as f32) as i32)
+            }
+            None => grid.key_at(x, y),
+        }
+    }
+
+    // Outlines the touch regions over the game, held ones in the foreground color
+    pub fn draw_touch_grid(&mut self, grid: &TouchGrid, pressed: u16) -> Result<(), &'static str> {
+        if self.keypad.is_some() {
+            return Ok(());
+        }
+        let (width, height) = (self.width * self.panes / 4, self.height / 4);
+        let scale = (height / 16).max(1);
+        for (position, &key) in grid.keys.iter().enumerate() {
+            let (left, top) = (
+                (position % 4) as u32 * width,
+                (position / 4) as u32 * height,
+            );
+            let color = match pressed & (1 << key) != 0 {
+                true => self.palette.foreground,
+                false => Color::RGB(128, 128, 128),
+            };
+            self.canvas.set_draw_color(color);
+            let digit = char::from_digit(key as u32, 16).unwrap_or('0');
+            let mut rects = glyph_rects(digit, left + 2 * scale, top + 2 * scale, scale);
+            rects.extend([
+                Rect::new(left as i32, top as i32, width, 1),
+                Rect::new(left as i32, top as i32, 1, height),
+            ]);
+            self.canvas
+                .fill_rects(&rects)
+                .map_err(|_| "Failed to draw touch grid")?;
+        }
+        Ok(())
+    }
+
+    // Held keys in the foreground color, keys the program checked during the last frame
+    // outlined
+    pub fn draw_keypad(&mut self, pressed: u16, polled: u16) -> Result<(), &'static str> {
+        let panel = match self.keypad {
+            Some(panel) => panel,
+            None => return Ok(()),
+        };
+        let width = self.width * self.panes;
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        let _ = self
+            .canvas
+            .fill_rect(Rect::new(0, panel.top, width, panel.height()));
+        let scale = (panel.cell / 8).max(1);
+        let border = (panel.cell / 16).max(1);
+        for (position, &key) in consts::KEYPAD_GRID.iter().enumerate() {
+            let rect = panel.key_rect(position);
+            let down = pressed & (1 << key) != 0;
+            if polled & (1 << key) != 0 {
+                self.canvas.set_draw_color(Color::RGB(255, 200, 0));
+                let _ = self.canvas.fill_rect(rect);
+            }
+            let inner = Rect::new(
+                rect.x() + border as i32,
+                rect.y() + border as i32,
+                rect.width() - 2 * border,
+                rect.height() - 2 * border,
+            );
+            let (fill, label) = match down {
+                true => (self.palette.foreground, self.palette.background),
+                false => (Color::RGB(48, 48, 48), Color::RGB(255, 255, 255)),
+            };
+            self.canvas.set_draw_color(fill);
+            let _ = self.canvas.fill_rect(inner);
+            // Glyphs are 4x5 font pixels, centered in the key
+            let left = rect.x() as u32 + (panel.cell - 4 * scale) / 2;
+            let top = rect.y() as u32 + (panel.cell - 5 * scale) / 2;
+            let digit = char::from_digit(key as u32, 16).unwrap_or('0');
+            self.canvas.set_draw_color(label);
+            self.canvas
+                .fill_rects(&glyph_rects(digit, left, top, scale))
+                .map_err(|_| "Failed to draw keypad")?;
+        }
+        Ok(())
+    }
+    pub fn set_title(&mut self, title: &TitleState) -> Result<(), &'static str> {
+        self.canvas
+            .window_mut()
+            .set_title(&title.to_string())
+            .map_err(|_| "Could not set the window title")
+    }
+    // Draws each instance into its own pane, left to right
+    pub fn draw(&mut self, displays: &[&DisplayBuffer]) -> Result<(), &'static str> {
+        for (pane, display_buffer) in displays.iter().enumerate() {
+            // The window size is fixed, so hires pixels are drawn at half the lores scale
+            let scale = self.width / display_buffer.width() as u32;
+            let offset = pane as u32 * self.width;
+            for (y, row) in display_buffer.buffer.iter().enumerate() {
+                for x in 0..row.len() {
+                    let i = offset + (x as u32) * scale;
+                    let j = (y as u32) * scale;
+
+                    self.canvas.set_draw_color(self.mode.color(
+                        display_buffer,
+                        &self.palette,
+                        x,
+                        y,
+                    )?);
+                    let _ = self
+                        .canvas
+                        .fill_rect(Rect::new(i as i32, j as i32, scale, scale));
+                }
             }
         }
         Ok(())
     }
+
+    // Writes lines of text in the top left corner over a translucent backdrop, using the
+    // CHIP-8 font so no font files are needed
+    pub fn draw_overlay(&mut self, lines: &[String]) -> Result<(), &'static str> {
+        self.draw_text(lines, (0, 0))
+    }
+
+    // One line along the bottom edge, for confirmations like a saved state
+    pub fn draw_message(&mut self, message: &str) -> Result<(), &'static str> {
+        let top = self.height - 8 * OVERLAY_SCALE;
+        self.draw_text(&[message.to_uppercase()], (0, top))
+    }
+
+    // Lines in the overlay font on a translucent backdrop with its top left corner at origin
+    fn draw_text(&mut self, lines: &[String], origin: (u32, u32)) -> Result<(), &'static str> {
+        let (advance, line_height) = (5 * OVERLAY_SCALE, 7 * OVERLAY_SCALE);
+        let columns = lines.iter().map(|l| l.len()).max().unwrap_or(0) as u32;
+        let backdrop = Rect::new(
+            origin.0 as i32,
+            origin.1 as i32,
+            columns * advance + OVERLAY_SCALE,
+            lines.len() as u32 * line_height + OVERLAY_SCALE,
+        );
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 192));
+        let filled = self.canvas.fill_rect(backdrop);
+        self.canvas.set_blend_mode(BlendMode::None);
+        filled.map_err(|_| "Failed to draw overlay")?;
+
+        let mut pixels = Vec::new();
+        for (row, line) in lines.iter().enumerate() {
+            for (column, c) in line.chars().enumerate() {
+                let (left, top) = (
+                    origin.0 + OVERLAY_SCALE + column as u32 * advance,
+                    origin.1 + OVERLAY_SCALE + row as u32 * line_height,
+                );
+                pixels.extend(glyph_rects(c, left, top, OVERLAY_SCALE));
+            }
+        }
+        self.canvas.set_draw_color(Color::RGB(255, 255, 255));
+        self.canvas
+            .fill_rects(&pixels)
+            .map_err(|_| "Failed to draw overlay")
+    }
+
+    // Tints the first pane by how often each pixel was drawn, pixels never drawn stay as they are
+    pub fn draw_heatmap(&mut self, heatmap: &Heatmap) -> Result<(), &'static str> {
+        let max = heatmap.max();
+        let scale = self.width / heatmap.width as u32;
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        for (i, &count) in heatmap.counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let (r, g, b) = heat_color(count, max);
+            let (x, y) = ((i % heatmap.width) as u32, (i / heatmap.width) as u32);
+            self.canvas.set_draw_color(Color::RGBA(r, g, b, 160));
+            let _ = self.canvas.fill_rect(Rect::new(
+                (x * scale) as i32,
+                (y * scale) as i32,
+                scale,
+                scale,
+            ));
+        }
+        self.canvas.set_blend_mode(BlendMode::None);
+        Ok(())
+    }
+
+    // A strip along the bottom of the first pane with the waveform scaled to its peak on top
+    // and the sound timer of the last SCOPE_FRAMES frames below it
+    pub fn draw_scope(&mut self, scope: &Scope) -> Result<(), &'static str> {
+        let (width, height) = (self.width, self.height / 3);
+        let top = (self.height - height) as i32;
+        let wave_height = height * 2 / 3;
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 192));
+        let filled = self.canvas.fill_rect(Rect::new(0, top, width, height));
+        self.canvas.set_blend_mode(BlendMode::None);
+        filled.map_err(|_| "Failed to draw oscilloscope")?;
+
+        let samples = scope.tap.samples();
+        let peak = samples
+            .iter()
+            .fold(f32::EPSILON, |peak, s| peak.max(s.abs()));
+        let middle = top + wave_height as i32 / 2;
+        let amplitude = (wave_height / 2).saturating_sub(1) as f32;
+        let points: Vec<Point> = samples
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let x = i as u32 * width / samples.len() as u32;
+                Point::new(x as i32, middle - (s / peak * amplitude) as i32)
+            })
+            .collect();
+        self.canvas.set_draw_color(Color::RGB(0, 255, 0));
+        self.canvas
+            .draw_lines(&points[..])
+            .map_err(|_| "Failed to draw oscilloscope")?;
+
+        let bar_width = (width / consts::SCOPE_FRAMES as u32).max(1);
+        let timer_height = height - wave_height;
+        let max = scope.timer.iter().copied().max().unwrap_or_default().max(1) as u32;
+        let bars: Vec<Rect> = scope
+            .timer
+            .iter()
+            .enumerate()
+            .filter(|(_, &value)| value > 0)
+            .map(|(i, &value)| {
+                let bar_height = (value as u32 * timer_height / max).max(1);
+                Rect::new(
+                    (i as u32 * bar_width) as i32,
+                    (self.height - bar_height) as i32,
+                    bar_width,
+                    bar_height,
+                )
+            })
+            .collect();
+        self.canvas.set_draw_color(Color::RGB(255, 200, 0));
+        self.canvas
+            .fill_rects(&bars)
+            .map_err(|_| "Failed to draw oscilloscope")
+    }
+
+    // Outlines the window so the buzzer can be noticed without hearing it
+    pub fn draw_sound_indicator(&mut self) -> Result<(), &'static str> {
+        let border = consts::SCALE_FACTOR / 2;
+        let (width, height) = (self.width * self.panes, self.height);
+        self.canvas.set_draw_color(Color::RGB(255, 200, 0));
+        let edges = [
+            Rect::new(0, 0, width, border),
+            Rect::new(0, (height - border) as i32, width, border),
+            Rect::new(0, 0, border, height),
+            Rect::new((width - border) as i32, 0, border, height),
+        ];
+        match self.canvas.fill_rects(&edges) {
+            Ok(_) => Ok(()),
+            Err(_) => Err("Failed to draw sound indicator"),
+        }
+    }
+
+    pub fn screenshot(
+        &self,
+        displays: &[&DisplayBuffer],
+        path: &Path,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (pane_width, height) = (self.width as usize, self.height as usize);
+        let width = pane_width * displays.len();
+        let mut pixels = vec![0; width * height * 3];
+        for (pane, display_buffer) in displays.iter().enumerate() {
+            let scale = pane_width / display_buffer.width();
+            for (y, row) in display_buffer.buffer.iter().enumerate() {
+                for x in 0..row.len() {
+                    let color = display_color(display_buffer, &self.palette, x, y)?;
+                    for j in y * scale..(y + 1) * scale {
+                        for i in x * scale..(x + 1) * scale {
+                            let offset = (j * width + pane * pane_width + i) * 3;
+                            pixels[offset..offset + 3]
+                                .copy_from_slice(&[color.r, color.g, color.b]);
+                        }
+                    }
+                }
+            }
+        }
+        write_png(path, width as u32, height as u32, &pixels)
+    }
+}
+
+// Shows a single instance without the overlays the main loop adds before presenting
+#[cfg(feature = "sdl")]
+impl Screen for DisplayDriver {
+    type Error = &'static str;
+
+    fn show(&mut self, display: &DisplayBuffer) -> Result<(), Self::Error> {
+        self.draw(&[display])?;
+        self.canvas.present();
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sine,
+    Noise,
 }
 
-// Based on https://github.com/Rust-SDL2/rust-sdl2/blob/master/examples/audio-squarewave.rs
-pub struct SquareWave {
+impl std::str::FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "square" => Ok(Waveform::Square),
+            "triangle" => Ok(Waveform::Triangle),
+            "sine" => Ok(Waveform::Sine),
+            "noise" => Ok(Waveform::Noise),
+            _ => Err(format!("Unknown waveform {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Tone {
+    pub waveform: Waveform,
+    pub frequency: f32,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Tone {
+            waveform: Waveform::Square,
+            frequency: consts::BUZZER_TONE,
+        }
+    }
+}
+
+// Samples of buzzer left, set by the main loop when the program loads the sound timer and
+// counted down by the audio callback, so a beep lasts exactly as long as the timer says no
+// matter how unevenly the frames run
+#[derive(Clone, Debug, Default)]
+pub struct SoundClock {
+    samples: Arc<AtomicU32>,
+}
+
+impl SoundClock {
+    pub fn start(&self, ticks: u8, tick_period: Duration, sample_rate: u32) {
+        let samples = (ticks as f64 * tick_period.as_secs_f64() * sample_rate as f64).round();
+        self.samples.store(samples as u32, Ordering::Relaxed);
+    }
+
+    pub fn stop(&self) {
+        self.samples.store(0, Ordering::Relaxed);
+    }
+
+    pub fn remaining(&self) -> u32 {
+        self.samples.load(Ordering::Relaxed)
+    }
+
+    // Called once per sample from the audio thread, returns whether the buzzer sounds for it
+    pub fn take(&self) -> bool {
+        self.samples
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok()
+    }
+}
+
+// The last SCOPE_SAMPLES samples the audio device played, shared with the audio callback.
+// Nothing is kept while the oscilloscope is hidden
+#[derive(Clone, Debug, Default)]
+pub struct ScopeTap {
+    samples: Arc<Mutex<VecDeque<f32>>>,
+    enabled: Arc<AtomicBool>,
+}
+
+impl ScopeTap {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            if let Ok(mut samples) = self.samples.lock() {
+                samples.clear();
+            }
+        }
+    }
+
+    // Called from the audio thread, which skips a buffer rather than wait for the lock
+    pub fn record(&self, samples: impl IntoIterator<Item = f32>) {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Ok(mut kept) = self.samples.try_lock() {
+            kept.extend(samples);
+            let excess = kept.len().saturating_sub(consts::SCOPE_SAMPLES);
+            kept.drain(..excess);
+        }
+    }
+
+    pub fn samples(&self) -> Vec<f32> {
+        match self.samples.lock() {
+            Ok(samples) => samples.iter().copied().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+// Oscilloscope overlay, the waveform comes from the audio backend's tap and the sound timer
+// is sampled by the main loop once per frame
+#[derive(Debug, Default)]
+pub struct Scope {
+    pub tap: ScopeTap,
+    pub timer: VecDeque<u8>,
+    pub shown: bool,
+}
+
+impl Scope {
+    pub fn new(tap: ScopeTap) -> Self {
+        Scope {
+            tap,
+            ..Default::default()
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.shown = !self.shown;
+        self.tap.set_enabled(self.shown);
+        self.timer.clear();
+    }
+
+    pub fn record_timer(&mut self, sound_timer: u8) {
+        if !self.shown {
+            return;
+        }
+        self.timer.push_back(sound_timer);
+        if self.timer.len() > consts::SCOPE_FRAMES {
+            self.timer.pop_front();
+        }
+    }
+}
+
+// Generalized from https://github.com/Rust-SDL2/rust-sdl2/blob/master/examples/audio-squarewave.rs
+pub struct Oscillator {
+    waveform: Waveform,
+    frequency: f32,
+    sample_rate: f32,
     phase_inc: f32,
     phase: f32,
     volume: f32,
+    noise: u32,
+    noise_level: f32,
+    // Gates the samples the audio callback produces, next_sample itself always sounds
+    clock: Option<SoundClock>,
+    scope: Option<ScopeTap>,
 }
 
-impl AudioCallback for SquareWave {
+impl Oscillator {
+    pub fn new(tone: Tone, sample_rate: i32, volume: f32) -> Self {
+        Oscillator {
+            waveform: tone.waveform,
+            frequency: tone.frequency,
+            sample_rate: sample_rate as f32,
+            phase_inc: tone.frequency / sample_rate as f32,
+            phase: 0.0,
+            volume,
+            noise: 0x1234_5678,
+            noise_level: 0.0,
+            clock: None,
+            scope: None,
+        }
+    }
+
+    pub fn with_scope(mut self, scope: ScopeTap) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: SoundClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    // Only the buzzer is covered, XO-CHIP audio patterns (F002) are not played yet. The tone
+    // itself follows the playback rate instead, so the configured frequency is what the
+    // default pitch sounds like
+    pub fn set_pitch(&mut self, pitch: Pitch) {
+        let scale = pitch.playback_rate() / consts::DEFAULT_PLAYBACK_RATE;
+        self.phase_inc = self.frequency * scale / self.sample_rate;
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let sample = match self.waveform {
+            Waveform::Square => {
+                if self.phase <= 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            Waveform::Sine => (self.phase * std::f32::consts::TAU).sin(),
+            Waveform::Noise => self.noise_level,
+        };
+        self.phase += self.phase_inc;
+        if self.phase >= 1.0 {
+            self.phase %= 1.0;
+            // Noise holds a new random level for every period, so its pitch follows the frequency
+            self.noise ^= self.noise << 13;
+            self.noise ^= self.noise >> 17;
+            self.noise ^= self.noise << 5;
+            self.noise_level = (self.noise as f32 / u32::MAX as f32) * 2.0 - 1.0;
+        }
+        sample * self.volume
+    }
+}
+
+#[cfg(feature = "sdl")]
+impl AudioCallback for Oscillator {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
+        self.fill(out);
+    }
+}
+
+impl Oscillator {
+    // What the audio device plays, silent while the clock has no samples left
+    pub fn fill(&mut self, out: &mut [f32]) {
         for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
+            *x = match &self.clock {
+                Some(clock) if !clock.take() => 0.0,
+                _ => self.next_sample(),
             };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+        }
+        if let Some(scope) = &self.scope {
+            scope.record(out.iter().copied());
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioBackend {
+    Sdl,
+    Cpal,
+    // No sound at all, also what the frontend falls back to without an audio device
+    Null,
+}
+
+impl std::str::FromStr for AudioBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sdl" => Ok(AudioBackend::Sdl),
+            "cpal" => Ok(AudioBackend::Cpal),
+            "none" => Ok(AudioBackend::Null),
+            _ => Err(format!("Unknown audio backend {}", s)),
+        }
+    }
+}
+
+// How the audio device is opened. Without a buffer size the driver picks one, which on some
+// systems is large enough to hear the beep start late
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AudioSettings {
+    pub sample_rate: i32,
+    pub samples: Option<u16>,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            sample_rate: consts::AUDIO_FREQ,
+            samples: None,
+        }
+    }
+}
+
+impl AudioSettings {
+    pub const LOW_LATENCY: AudioSettings = AudioSettings {
+        sample_rate: 48000,
+        samples: Some(256),
+    };
+
+    // Time one buffer takes to play, the delay between a beep starting and being heard
+    pub fn latency(&self) -> Option<Duration> {
+        self.samples
+            .map(|n| Duration::from_secs_f64(n as f64 / self.sample_rate as f64))
+    }
+}
+
+// Buzzer playback device, implemented per audio library so the frontend can pick one at runtime
+pub trait AudioSink {
+    // Runs or freezes the device, while running it sounds for whatever is left of the last
+    // sound_for call
+    fn set_playing(&mut self, playing: bool);
+    // Sounds the buzzer for a number of sound timer ticks, timed by the audio device's own
+    // sample clock
+    fn sound_for(&mut self, ticks: u8, tick_period: Duration);
+    // Length of the device buffer as opened, which may differ from what was asked for
+    fn latency(&self) -> Duration;
+    // Retunes the buzzer while it plays, for XO-CHIP's FX3A
+    fn set_pitch(&mut self, pitch: Pitch);
+    // What the device plays, for the oscilloscope
+    fn scope(&self) -> ScopeTap;
+    fn set_muted(&mut self, muted: bool);
+    fn muted(&self) -> bool;
+}
+
+// Plays nothing, for machines without a sound device such as CI runners
+#[derive(Debug, Default)]
+pub struct NullAudio {
+    muted: bool,
+    scope: ScopeTap,
+}
+
+impl AudioSink for NullAudio {
+    fn set_playing(&mut self, _playing: bool) {}
+
+    fn sound_for(&mut self, _ticks: u8, _tick_period: Duration) {}
+
+    fn latency(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn set_pitch(&mut self, _pitch: Pitch) {}
+
+    fn scope(&self) -> ScopeTap {
+        self.scope.clone()
+    }
+
+    fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    fn muted(&self) -> bool {
+        self.muted
+    }
+}
+
+#[cfg(feature = "sdl")]
 pub struct AudioDriver {
-    pub speaker: AudioDevice<SquareWave>,
-    pub sound_timer: Rc<RefCell<u8>>,
+    pub speaker: AudioDevice<Oscillator>,
+    sound: SoundClock,
+    scope: ScopeTap,
+    muted: bool,
 }
 
+#[cfg(feature = "sdl")]
 impl AudioDriver {
-    pub fn new(context: &sdl2::Sdl, sound_timer_: &Rc<RefCell<u8>>) -> Result<Self, &'static str> {
+    pub fn new(
+        context: &sdl2::Sdl,
+        tone: Tone,
+        volume: f32,
+        settings: AudioSettings,
+    ) -> Result<Self, &'static str> {
         let audio_subsystem = match context.audio() {
             Ok(r) => r,
             Err(_) => return Err("Could not obtain audio context"),
         };
+        let (sound, scope) = (SoundClock::default(), ScopeTap::default());
         let device = match audio_subsystem.open_playback(
             None,
             &AudioSpecDesired {
-                freq: Some(44100),
+                freq: Some(settings.sample_rate),
                 channels: Some(1),
-                samples: None,
+                samples: settings.samples,
             },
-            |spec| SquareWave {
-                phase_inc: 440.0 / spec.freq as f32,
-                phase: 0.0,
-                volume: 0.25,
+            |spec| {
+                Oscillator::new(tone, spec.freq, volume)
+                    .with_clock(sound.clone())
+                    .with_scope(scope.clone())
             },
         ) {
             Ok(r) => r,
@@ -119,7 +1188,266 @@ impl AudioDriver {
         };
         Ok(AudioDriver {
             speaker: device,
-            sound_timer: Rc::clone(sound_timer_),
+            sound,
+            scope,
+            muted: false,
         })
     }
 }
+
+#[cfg(feature = "sdl")]
+impl AudioSink for AudioDriver {
+    fn set_playing(&mut self, playing: bool) {
+        if playing && !self.muted {
+            self.speaker.resume();
+        } else {
+            self.speaker.pause();
+        }
+    }
+
+    fn sound_for(&mut self, ticks: u8, tick_period: Duration) {
+        let sample_rate = self.speaker.spec().freq as u32;
+        self.sound.start(ticks, tick_period, sample_rate);
+    }
+
+    fn latency(&self) -> Duration {
+        let spec = self.speaker.spec();
+        Duration::from_secs_f64(spec.samples as f64 / spec.freq as f64)
+    }
+
+    fn set_pitch(&mut self, pitch: Pitch) {
+        self.speaker.lock().set_pitch(pitch);
+    }
+
+    fn scope(&self) -> ScopeTap {
+        self.scope.clone()
+    }
+
+    // A beep cut off by muting is not finished once sound comes back
+    fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        if muted {
+            self.sound.stop();
+        }
+    }
+
+    fn muted(&self) -> bool {
+        self.muted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "sdl")]
+    use super::KeypadPanel;
+    use super::{
+        AudioBackend, AudioSettings, AudioSink, Color, DisplayMode, NullAudio, Oscillator, Palette,
+        Scope, ScopeTap, SoundClock, TitleState, Tone,
+    };
+    use crate::consts;
+    use crate::core::platform::Platform;
+    use crate::core::processor::Pitch;
+    use crate::core::ram::DisplayBuffer;
+    use std::time::Duration;
+
+    #[cfg(feature = "sdl")]
+    #[test]
+    fn test_keypad_panel() {
+        // 40 pixel keys centered in a 400 pixel wide window, starting 100 pixels down
+        let panel = KeypadPanel::new(400, 100, 40);
+        assert_eq!((panel.left, panel.height()), (120, 160));
+        assert_eq!(panel.key_at(120, 100), Some(0x1));
+        assert_eq!(panel.key_at(279, 259), Some(0xF));
+        assert_eq!(panel.key_at(170, 230), Some(0x0));
+        assert_eq!(panel.key_at(119, 150), None);
+        assert_eq!(panel.key_at(200, 260), None);
+        assert_eq!(panel.key_at(200, 99), None);
+        assert_eq!(panel.key_rect(13).x(), 160);
+    }
+
+    #[test]
+    fn test_null_audio() {
+        assert_eq!("none".parse(), Ok(AudioBackend::Null));
+        let mut audio = NullAudio::default();
+        audio.sound_for(10, Duration::from_millis(16));
+        audio.set_playing(true);
+        audio.set_muted(true);
+        assert!(audio.muted());
+        assert_eq!(audio.latency(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_audio_latency() {
+        assert_eq!(AudioSettings::default().latency(), None);
+        let settings = AudioSettings {
+            sample_rate: 48000,
+            samples: Some(480),
+        };
+        assert_eq!(settings.latency(), Some(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn test_pitch() {
+        assert_eq!(Pitch::default().playback_rate(), 4000.0);
+        assert_eq!(Pitch(112).playback_rate(), 8000.0);
+        assert_eq!(Pitch(16).playback_rate(), 2000.0);
+        // A 100Hz square wave at 800 samples a second flips every 4 samples, an octave up
+        // every 2
+        let tone = Tone {
+            frequency: 100.0,
+            ..Default::default()
+        };
+        let mut oscillator = Oscillator::new(tone, 800, 1.0);
+        let samples: Vec<f32> = (0..8).map(|_| oscillator.next_sample()).collect();
+        assert_eq!(samples, [1.0, 1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0]);
+        let mut oscillator = Oscillator::new(tone, 800, 1.0);
+        oscillator.set_pitch(Pitch(112));
+        let samples: Vec<f32> = (0..4).map(|_| oscillator.next_sample()).collect();
+        assert_eq!(samples, [1.0, 1.0, 1.0, -1.0]);
+    }
+
+    #[test]
+    fn test_scope() {
+        let mut scope = Scope::new(ScopeTap::default());
+        scope.tap.record([1.0, -1.0]);
+        scope.record_timer(5);
+        assert!(scope.tap.samples().is_empty() && scope.timer.is_empty());
+        scope.toggle();
+        scope
+            .tap
+            .record((0..consts::SCOPE_SAMPLES + 10).map(|i| i as f32));
+        let samples = scope.tap.samples();
+        assert_eq!(samples.len(), consts::SCOPE_SAMPLES);
+        assert_eq!(samples[0], 10.0);
+        for value in 0..=consts::SCOPE_FRAMES as u8 {
+            scope.record_timer(value);
+        }
+        assert_eq!(scope.timer.len(), consts::SCOPE_FRAMES);
+        assert_eq!(scope.timer.front(), Some(&1));
+        scope.toggle();
+        assert!(scope.tap.samples().is_empty() && scope.timer.is_empty());
+    }
+
+    #[test]
+    fn test_sound_clock() {
+        let clock = SoundClock::default();
+        assert!(!clock.take());
+        // Two 60Hz ticks at 6000 samples a second
+        clock.start(2, Duration::from_nanos(1_000_000_000 / 60), 6000);
+        assert_eq!(clock.remaining(), 200);
+        assert_eq!((0..250).filter(|_| clock.take()).count(), 200);
+        clock.start(1, Duration::from_millis(20), 6000);
+        clock.stop();
+        assert!(!clock.take());
+    }
+
+    #[test]
+    fn test_display_mode() -> Result<(), &'static str> {
+        let mut display: DisplayBuffer = Default::default();
+        display.buffer[0][0] = 1;
+        let palette = Palette::default();
+        let mut mode = DisplayMode::default();
+        assert_eq!(mode.color(&display, &palette, 0, 0)?, palette.foreground);
+        mode.high_contrast = true;
+        assert_eq!(
+            mode.color(&display, &palette, 0, 0)?,
+            Color::RGB(255, 255, 255)
+        );
+        mode.inverted = true;
+        assert_eq!(mode.color(&display, &palette, 0, 0)?, Color::RGB(0, 0, 0));
+        assert_eq!(
+            mode.color(&display, &palette, 1, 0)?,
+            Color::RGB(255, 255, 255)
+        );
+        mode.brightness = 50;
+        assert_eq!(
+            mode.color(&display, &palette, 1, 0)?,
+            Color::RGB(127, 127, 127)
+        );
+        for _ in 0..20 {
+            mode.adjust_brightness(false);
+        }
+        assert_eq!(mode.brightness, consts::MIN_BRIGHTNESS);
+        mode.adjust_brightness(true);
+        assert_eq!(
+            mode.brightness,
+            consts::MIN_BRIGHTNESS + consts::BRIGHTNESS_STEP
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_palette_presets() {
+        for name in Palette::PRESETS {
+            let palette: Palette = name.parse().unwrap();
+            assert_ne!(palette.foreground, palette.background);
+        }
+        assert_eq!(
+            "cb-safe".parse(),
+            Ok(Palette::preset("deuteranopia").unwrap())
+        );
+        assert_eq!(
+            "FFFFFF,000000".parse::<Palette>().map(|p| p.foreground),
+            Ok(Color::RGB(255, 255, 255))
+        );
+        assert_eq!(
+            "FFFFFF,000000,FF0000,0000FF"
+                .parse::<Palette>()
+                .map(|p| p.color(3)),
+            Ok(Ok(Color::RGB(0, 0, 255)))
+        );
+        assert!("sepia".parse::<Palette>().is_err());
+        assert!("FFFFFF,000000,FF0000".parse::<Palette>().is_err());
+    }
+
+    // WCAG contrast ratio between two colors, from 1 for the same luminance up to 21
+    fn contrast(a: Color, b: Color) -> f32 {
+        let luminance = |color: Color| {
+            let linear = |channel: u8| {
+                let c = channel as f32 / 255.0;
+                match c <= 0.04045 {
+                    true => c / 12.92,
+                    false => ((c + 0.055) / 1.055).powf(2.4),
+                }
+            };
+            0.2126 * linear(color.r) + 0.7152 * linear(color.g) + 0.0722 * linear(color.b)
+        };
+        let (a, b) = (luminance(a), luminance(b));
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    #[test]
+    fn test_palette_contrast() {
+        for name in Palette::PRESETS {
+            let palette = Palette::preset(name).unwrap();
+            let drawn = [palette.foreground, palette.plane2, palette.overlap];
+            // WCAG AA against the background, and far enough apart in luminance that the
+            // planes do not rely on hue alone
+            for (i, &color) in drawn.iter().enumerate() {
+                assert!(contrast(color, palette.background) >= 4.5, "{} {}", name, i);
+                for &other in &drawn[i + 1..] {
+                    assert!(contrast(color, other) >= 1.5, "{} {}", name, i);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_title() {
+        let mut title = TitleState::new("roms/Breakout.ch8", Platform::SchipLegacy, 30);
+        assert_eq!(title.to_string(), "Breakout — CHIP-8 (SCHIP 1.1, 30 ipf)");
+        title.paused = true;
+        title.recording = true;
+        assert_eq!(
+            title.to_string(),
+            "Breakout — CHIP-8 (SCHIP 1.1, 30 ipf) [paused] [recording]"
+        );
+        title.paused = false;
+        title.fast_forward = true;
+        assert_eq!(
+            title.to_string(),
+            "Breakout — CHIP-8 (SCHIP 1.1, 30 ipf) [fast-forward] [recording]"
+        );
+        assert_eq!(TitleState::new("-", Platform::Modern, 10).rom_name, "stdin");
+    }
+}
@@ -0,0 +1,290 @@
+use crate::consts;
+use crate::core::processor::Pitch;
+use crate::core::ram::DisplayBuffer;
+use crate::external::output::{display_color, Oscillator, Palette, Tone};
+use std::io::Write;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const RECORD_FPS: u32 = 60;
+const SAMPLES_PER_FRAME: usize = (consts::AUDIO_FREQ as u32 / RECORD_FPS) as usize;
+
+// Buzzer audio is handed to ffmpeg through a named pipe, since stdin already carries the video
+struct AudioPipe {
+    samples: Option<Sender<Vec<u8>>>,
+    writer: JoinHandle<()>,
+    #[cfg(unix)]
+    path: std::path::PathBuf,
+}
+
+#[cfg(unix)]
+impl AudioPipe {
+    fn create() -> Result<Self, Box<dyn std::error::Error>> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = std::env::temp_dir().join(format!("chip8-audio-{}.fifo", std::process::id()));
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        // SAFETY: c_path is a valid NUL terminated string for the duration of the call
+        if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        let (samples, receiver) = mpsc::channel::<Vec<u8>>();
+        let fifo_path = path.clone();
+        // Opening a FIFO for writing blocks until ffmpeg opens it, so keep it off the main loop
+        let writer = thread::spawn(move || {
+            if let Ok(mut fifo) = std::fs::OpenOptions::new().write(true).open(&fifo_path) {
+                for chunk in receiver {
+                    if fifo.write_all(&chunk).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(AudioPipe {
+            samples: Some(samples),
+            writer,
+            path,
+        })
+    }
+
+    fn input(&self) -> Vec<String> {
+        vec![
+            String::from("-f"),
+            String::from("s16le"),
+            String::from("-ar"),
+            consts::AUDIO_FREQ.to_string(),
+            String::from("-ac"),
+            String::from("1"),
+            String::from("-i"),
+            self.path.to_string_lossy().into_owned(),
+        ]
+    }
+
+    // Must be called once ffmpeg is gone, if it never opened the FIFO the writer is still
+    // blocked in open() and is released by briefly opening the read end
+    fn close(self) {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        if !self.writer.is_finished() {
+            let _ = std::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(&self.path);
+        }
+        let _ = self.writer.join();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(not(unix))]
+impl AudioPipe {
+    fn create() -> Result<Self, Box<dyn std::error::Error>> {
+        Err("Audio recording requires named pipes".into())
+    }
+
+    fn input(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn close(self) {
+        let _ = self.writer.join();
+    }
+}
+
+impl AudioPipe {
+    fn send(&self, chunk: Vec<u8>) {
+        if let Some(samples) = &self.samples {
+            let _ = samples.send(chunk);
+        }
+    }
+
+    // Closing the channel lets the writer drain and close the FIFO, which ffmpeg sees as EOF
+    fn end_stream(&mut self) {
+        self.samples = None;
+    }
+}
+
+pub struct Recorder {
+    ffmpeg: Child,
+    video: Option<ChildStdin>,
+    audio: Option<AudioPipe>,
+    started: Instant,
+    frames: u64,
+    width: usize,
+    height: usize,
+    palette: Palette,
+    buzzer: Oscillator,
+    // Set once ffmpeg was waited for, so dropping afterwards does nothing
+    finished: bool,
+}
+
+impl Recorder {
+    // The video size is fixed when ffmpeg starts, so frames are recorded at twice the starting
+    // geometry which leaves room for the program switching to hires
+    pub fn new(
+        path: &str,
+        display: &DisplayBuffer,
+        palette: Palette,
+        tone: Tone,
+        volume: f32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let (width, height) = (display.width() * 2, display.height() * 2);
+        let audio = AudioPipe::create().ok();
+        let mut args: Vec<String> = [
+            "-loglevel",
+            "error",
+            "-y",
+            "-f",
+            "rawvideo",
+            "-pixel_format",
+            "rgb24",
+            "-video_size",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        args.push(format!("{}x{}", width, height));
+        args.extend(["-framerate".to_string(), RECORD_FPS.to_string()]);
+        args.extend(["-i".to_string(), "-".to_string()]);
+        if let Some(pipe) = &audio {
+            args.extend(pipe.input());
+        }
+        args.extend([
+            "-vf".to_string(),
+            format!(
+                "scale=iw*{}:ih*{}:flags=neighbor",
+                consts::SCALE_FACTOR / 2,
+                consts::SCALE_FACTOR / 2
+            ),
+            path.to_string(),
+        ]);
+
+        let mut ffmpeg = match Command::new("ffmpeg")
+            .args(&args)
+            .stdin(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                if let Some(mut pipe) = audio {
+                    pipe.end_stream();
+                    pipe.close();
+                }
+                return Err(format!("Could not start ffmpeg: {}", e).into());
+            }
+        };
+        let video = ffmpeg.stdin.take();
+        Ok(Recorder {
+            ffmpeg,
+            video,
+            audio,
+            started: Instant::now(),
+            frames: 0,
+            width,
+            height,
+            palette,
+            buzzer: Oscillator::new(tone, consts::AUDIO_FREQ, volume),
+            finished: false,
+        })
+    }
+
+    // Emits as many fixed-rate frames as are due since recording started, repeating the
+    // current display contents so the video keeps wall-clock timing
+    pub fn capture(
+        &mut self,
+        display: &DisplayBuffer,
+        sound_on: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let due = self.started.elapsed().as_micros() as u64 * RECORD_FPS as u64
+            / Duration::from_secs(1).as_micros() as u64;
+        if due <= self.frames {
+            return Ok(());
+        }
+
+        let (scale_x, scale_y) = (self.width / display.width(), self.height / display.height());
+        let mut frame = Vec::with_capacity(self.width * self.height * 3);
+        for (y, row) in display.buffer.iter().enumerate() {
+            let mut line = Vec::with_capacity(self.width * 3);
+            for x in 0..row.len() {
+                let color = display_color(display, &self.palette, x, y)?;
+                for _ in 0..scale_x {
+                    line.extend_from_slice(&[color.r, color.g, color.b]);
+                }
+            }
+            for _ in 0..scale_y {
+                frame.extend_from_slice(&line);
+            }
+        }
+        // Keeps the stream aligned even if the geometry no longer divides the video size
+        frame.resize(self.width * self.height * 3, 0);
+
+        while self.frames < due {
+            if let Some(video) = self.video.as_mut() {
+                video.write_all(&frame)?;
+            }
+            if self.audio.is_some() {
+                let samples = self.buzzer_samples(sound_on);
+                if let Some(audio) = &self.audio {
+                    audio.send(samples);
+                }
+            }
+            self.frames += 1;
+        }
+        Ok(())
+    }
+
+    // Retunes the recorded buzzer the same way as the live one, see Oscillator::set_pitch
+    pub fn set_pitch(&mut self, pitch: Pitch) {
+        self.buzzer.set_pitch(pitch);
+    }
+
+    fn buzzer_samples(&mut self, sound_on: bool) -> Vec<u8> {
+        let mut samples = Vec::with_capacity(SAMPLES_PER_FRAME * 2);
+        for _ in 0..SAMPLES_PER_FRAME {
+            let sample = if sound_on {
+                (self.buzzer.next_sample() * i16::MAX as f32) as i16
+            } else {
+                0
+            };
+            samples.extend_from_slice(&sample.to_le_bytes());
+        }
+        samples
+    }
+
+    pub fn finish(mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.close()
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        drop(self.video.take());
+        if let Some(pipe) = self.audio.as_mut() {
+            pipe.end_stream();
+        }
+        let status = self.ffmpeg.wait()?;
+        if let Some(pipe) = self.audio.take() {
+            pipe.close();
+        }
+        if !status.success() {
+            return Err(format!("ffmpeg exited with {}", status).into());
+        }
+        Ok(())
+    }
+}
+
+// The main loop can end with an error, the file is closed then too instead of being left
+// truncated
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            eprintln!("Could not finish the recording: {}", e);
+        }
+    }
+}
@@ -0,0 +1,549 @@
+use crate::breakpoints::{Breakpoints, Expr};
+use crate::consts;
+use crate::core::coverage;
+use crate::core::processor::Processor;
+use crate::core::ram::KeyboardBuffer;
+use crate::core::snapshot::Snapshot;
+use crate::external::output::DisplayDriver;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+// Line protocol spoken by the control server and --repl, one command per line and one
+// reply line per command, either "ok", "ok <result>" or "error <reason>"
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    Pause,
+    Resume,
+    Step(usize),
+    Peek(usize),
+    Poke(usize, u8),
+    Press(usize),
+    Release(usize),
+    Registers,
+    Screenshot(String),
+    Histogram(String),
+    Coverage(String),
+    SaveState,
+    LoadState,
+    Break(String),
+    Delete(usize),
+    Breakpoints,
+    Print(Expr),
+    Memory(usize, usize),
+    // RAM to and from binary files, for hex editors and other offline tools
+    Dump(String, usize, usize),
+    Restore(String, usize, bool),
+}
+
+impl Request {
+    // Whether running it on one netplay peer would stop the lockstep or make the peers
+    // diverge. Breakpoints count since a hit pauses
+    pub fn changes_machine(&self) -> bool {
+        matches!(
+            self,
+            Request::Pause
+                | Request::Step(_)
+                | Request::Poke(..)
+                | Request::LoadState
+                | Request::Break(_)
+                | Request::Restore(..)
+        )
+    }
+}
+
+fn number(text: Option<&str>) -> Result<usize, String> {
+    let text = text.ok_or("Missing argument")?;
+    match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    }
+    .map_err(|_| format!("Invalid number {}", text))
+}
+
+fn address(text: Option<&str>) -> Result<usize, String> {
+    let address = number(text)?;
+    if address >= consts::RAM_BYTES {
+        return Err(format!("Address {:#X} is outside memory", address));
+    }
+    Ok(address)
+}
+
+fn range(address: usize, len: usize) -> Result<(usize, usize), String> {
+    match address.checked_add(len) {
+        Some(end) if end <= consts::RAM_BYTES => Ok((address, len)),
+        _ => Err(format!(
+            "{} bytes at {:#X} run past the end of memory",
+            len, address
+        )),
+    }
+}
+
+fn key(text: Option<&str>) -> Result<usize, String> {
+    let text = text.ok_or("Missing argument")?;
+    match usize::from_str_radix(text, 16) {
+        Ok(k) if k < consts::KEYBOARD_SIZE => Ok(k),
+        _ => Err(format!("Unknown key {}", text)),
+    }
+}
+
+impl FromStr for Request {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut words = line.split_whitespace();
+        let request = match words.next().unwrap_or_default() {
+            "pause" => Request::Pause,
+            "resume" | "run" => Request::Resume,
+            "step" => match words.next() {
+                Some(n) => Request::Step(number(Some(n))?),
+                None => Request::Step(1),
+            },
+            "peek" => Request::Peek(address(words.next())?),
+            "poke" => {
+                let address = address(words.next())?;
+                let value = number(words.next())?;
+                let value = u8::try_from(value).map_err(|_| format!("{} is not a byte", value))?;
+                Request::Poke(address, value)
+            }
+            "press" => Request::Press(key(words.next())?),
+            "release" => Request::Release(key(words.next())?),
+            "registers" => Request::Registers,
+            "screenshot" => Request::Screenshot(words.next().ok_or("Missing path")?.to_string()),
+            "histogram" => Request::Histogram(words.next().ok_or("Missing path")?.to_string()),
+            "coverage" => Request::Coverage(words.next().ok_or("Missing path")?.to_string()),
+            "save-state" => Request::SaveState,
+            "load-state" => Request::LoadState,
+            // The condition is the rest of the line, spaces included
+            "break" | "b" => {
+                let condition: Vec<&str> = words.by_ref().collect();
+                match condition[..] {
+                    [] => return Err(String::from("Missing condition")),
+                    // A bare address breaks when execution reaches it
+                    [address] if number(Some(address)).is_ok() => {
+                        Request::Break(format!("pc == {}", address))
+                    }
+                    _ => Request::Break(condition.join(" ")),
+                }
+            }
+            "delete" => Request::Delete(number(words.next())?),
+            "breakpoints" => Request::Breakpoints,
+            "print" | "p" => Request::Print(words.by_ref().collect::<Vec<_>>().join(" ").parse()?),
+            "memory" | "m" => {
+                let address = address(words.next())?;
+                let len = match words.next() {
+                    Some(n) => number(Some(n))?,
+                    None => MEMORY_DUMP_BYTES,
+                };
+                let (address, len) = range(address, len)?;
+                Request::Memory(address, len)
+            }
+            // All of memory unless a start and length are given
+            "dump" => {
+                let path = words.next().ok_or("Missing path")?.to_string();
+                let address = match words.next() {
+                    Some(a) => address(Some(a))?,
+                    None => 0,
+                };
+                let len = match words.next() {
+                    Some(n) => number(Some(n))?,
+                    None => consts::RAM_BYTES - address,
+                };
+                let (address, len) = range(address, len)?;
+                Request::Dump(path, address, len)
+            }
+            // The file goes back at the start of memory unless an address is given, its
+            // length is only known once it is read
+            "restore" => {
+                let path = words.next().ok_or("Missing path")?.to_string();
+                let mut next = words.next();
+                let address = match next {
+                    Some(a) if a != "force" => {
+                        next = words.next();
+                        address(Some(a))?
+                    }
+                    _ => 0,
+                };
+                let force = next == Some("force");
+                if let Some(extra) = next.filter(|_| !force) {
+                    return Err(format!("Unexpected argument {}", extra));
+                }
+                Request::Restore(path, address, force)
+            }
+            command => return Err(format!("Unknown command {}", command)),
+        };
+        match words.next() {
+            Some(extra) => Err(format!("Unexpected argument {}", extra)),
+            None => Ok(request),
+        }
+    }
+}
+
+// Bytes shown by memory when no length is given
+const MEMORY_DUMP_BYTES: usize = 16;
+
+type Pending = (Request, Sender<String>);
+
+// Serves one client, a TCP connection or the terminal, until it disconnects
+fn serve_client(
+    reader: impl BufRead,
+    mut writer: impl Write,
+    requests: Sender<Pending>,
+) -> io::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match line.parse::<Request>() {
+            Ok(request) => {
+                let (reply, response) = mpsc::channel();
+                if requests.send((request, reply)).is_err() {
+                    break;
+                }
+                response
+                    .recv()
+                    .unwrap_or_else(|_| String::from("error Emulator exited"))
+            }
+            Err(e) => format!("error {}", e),
+        };
+        writeln!(writer, "{}", reply)?;
+    }
+    Ok(())
+}
+
+// The processor is not shared across threads, so connections only parse commands and the
+// main loop executes them between frames
+pub struct RemoteControl {
+    requests: Receiver<Pending>,
+    // Cloned into every thread that accepts commands
+    sender: Sender<Pending>,
+    pub paused: bool,
+    // Keys held by clients, merged into the keypad after every poll of the real keyboard
+    keys: KeyboardBuffer,
+    snapshot: Option<Snapshot>,
+    // Checked after every instruction by the main loop, which pauses on a hit
+    pub breakpoints: Breakpoints,
+    // Set during netplay, where pausing or changing the machine would leave the peers out
+    // of step
+    pub lockstep: bool,
+}
+
+impl Default for RemoteControl {
+    fn default() -> Self {
+        let (sender, requests) = mpsc::channel();
+        RemoteControl {
+            requests,
+            sender,
+            paused: false,
+            keys: Default::default(),
+            snapshot: None,
+            breakpoints: Default::default(),
+            lockstep: false,
+        }
+    }
+}
+
+impl RemoteControl {
+    pub fn bind(&self, address: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind(address)?;
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    let writer = stream.try_clone()?;
+                    serve_client(BufReader::new(stream), writer, sender)
+                });
+            }
+        });
+        Ok(())
+    }
+
+    // Same protocol as bind for local tools, without opening a port. A socket left behind by
+    // an earlier run is replaced, any other file at the path is an error
+    #[cfg(unix)]
+    pub fn bind_socket(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    let writer = stream.try_clone()?;
+                    serve_client(BufReader::new(stream), writer, sender)
+                });
+            }
+        });
+        Ok(())
+    }
+
+    // Named pipes need the Windows API, which nothing here links against yet
+    #[cfg(not(unix))]
+    pub fn bind_socket(&self, _path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        Err("--control-socket is only supported on Unix, use --remote".into())
+    }
+
+    // Takes commands from the terminal while the window runs, the replies go to stdout
+    pub fn read_stdin(&self) {
+        let sender = self.sender.clone();
+        thread::spawn(move || serve_client(io::stdin().lock(), io::stdout(), sender));
+    }
+
+    pub fn hold_keys(&self, keyboard: &mut KeyboardBuffer) {
+        keyboard.keys |= self.keys.keys;
+    }
+
+    // Runs every pending command, returns whether the display may have changed
+    pub fn serve(&mut self, chip8: &mut Processor, display: &DisplayDriver) -> bool {
+        let mut dirty = false;
+        for (request, reply) in self.requests.try_iter().collect::<Vec<_>>() {
+            let response = match self.execute(request, chip8, display) {
+                Ok(Some(result)) => format!("ok {}", result),
+                Ok(None) => String::from("ok"),
+                Err(e) => format!("error {}", e),
+            };
+            dirty = true;
+            let _ = reply.send(response);
+        }
+        dirty
+    }
+
+    fn execute(
+        &mut self,
+        request: Request,
+        chip8: &mut Processor,
+        display: &DisplayDriver,
+    ) -> Result<Option<String>, String> {
+        if self.lockstep && request.changes_machine() {
+            return Err(String::from("Not available during netplay"));
+        }
+        match request {
+            Request::Pause => self.paused = true,
+            Request::Resume => self.paused = false,
+            Request::Step(count) => {
+                self.paused = true;
+                for _ in 0..count {
+                    chip8.cycle()?;
+                }
+                return Ok(Some(format!("{:#05X}", chip8.pc)));
+            }
+            Request::Peek(address) => {
+                return Ok(Some(format!("{:#04X}", chip8.ram.buffer[address])));
+            }
+            Request::Poke(address, value) => chip8.ram.buffer[address] = value,
+            Request::Press(key) => self.keys.set(key),
+            Request::Release(key) => self.keys.clear(key),
+            Request::Registers => {
+                let registers: Vec<String> = chip8
+                    .registers
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| format!("v{:x}={:#04X}", i, v))
+                    .collect();
+                return Ok(Some(format!(
+                    "pc={:#05X} i={:#05X} {}",
+                    chip8.pc,
+                    chip8.idx_register,
+                    registers.join(" ")
+                )));
+            }
+            Request::Screenshot(path) => display
+                .screenshot(&[&chip8.display_buffer], Path::new(&path))
+                .map_err(|e| e.to_string())?,
+            // Both need the histogram, the first request starts it counting
+            Request::Histogram(_) | Request::Coverage(_) if !chip8.histogram.enabled => {
+                chip8.histogram.enabled = true;
+                return Ok(Some(String::from("Counting started, ask again to save")));
+            }
+            Request::Histogram(path) => {
+                std::fs::write(&path, chip8.histogram.to_csv()).map_err(|e| e.to_string())?
+            }
+            Request::Coverage(path) => {
+                std::fs::write(&path, coverage::report(chip8, "ROM")).map_err(|e| e.to_string())?
+            }
+            Request::SaveState => self.snapshot = Some(Snapshot::capture(chip8)),
+            Request::LoadState => match &self.snapshot {
+                Some(snapshot) => snapshot.restore(chip8),
+                None => return Err(String::from("No saved state")),
+            },
+            Request::Break(condition) => {
+                self.breakpoints.add(&condition)?;
+                return Ok(Some((self.breakpoints.breakpoints.len() - 1).to_string()));
+            }
+            Request::Delete(index) => {
+                self.breakpoints.remove(index)?;
+            }
+            Request::Breakpoints => {
+                let list: Vec<String> = self
+                    .breakpoints
+                    .breakpoints
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| format!("{}: {}", i, b.source))
+                    .collect();
+                return Ok(Some(list.join("; ")));
+            }
+            Request::Print(expr) => {
+                let value = expr.evaluate(chip8);
+                return Ok(Some(format!("{:#X} ({})", value, value)));
+            }
+            Request::Memory(address, len) => {
+                let bytes: Vec<String> = chip8.ram.buffer[address..address + len]
+                    .iter()
+                    .map(|b| format!("{:02X}", b))
+                    .collect();
+                return Ok(Some(format!("{:#05X}: {}", address, bytes.join(" "))));
+            }
+            Request::Dump(path, address, len) => {
+                std::fs::write(&path, &chip8.ram.buffer[address..address + len])
+                    .map_err(|e| e.to_string())?;
+                return Ok(Some(format!("{} bytes", len)));
+            }
+            Request::Restore(path, address, force) => {
+                let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+                chip8.ram.load(address, &bytes, force)?;
+                return Ok(Some(format!("{} bytes at {:#05X}", bytes.len(), address)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use super::RemoteControl;
+    use super::Request;
+    use crate::breakpoints::Expr;
+    #[cfg(unix)]
+    use std::io::{BufRead, BufReader, Write};
+    #[cfg(unix)]
+    use std::os::unix::net::UnixStream;
+    #[cfg(unix)]
+    use std::time::Duration;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_control_socket() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join(format!("chip8-{}.sock", std::process::id()));
+        let path = path.to_str().ok_or("Invalid path")?;
+        let remote = RemoteControl::default();
+        remote.bind_socket(path)?;
+        // Binding again replaces the stale socket
+        remote.bind_socket(path)?;
+        let mut client = UnixStream::connect(path)?;
+        writeln!(client, "poke 0x300 7")?;
+        let (request, reply) = remote.requests.recv_timeout(Duration::from_secs(5))?;
+        assert_eq!(request, Request::Poke(0x300, 7));
+        reply.send(String::from("ok"))?;
+        let mut line = String::new();
+        BufReader::new(client).read_line(&mut line)?;
+        assert_eq!(line, "ok\n");
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_changes_machine() -> Result<(), String> {
+        for command in ["pause", "step", "poke 0x300 1", "load-state", "b 0x200"] {
+            assert!(command.parse::<Request>()?.changes_machine(), "{}", command);
+        }
+        for command in ["resume", "press 5", "registers", "peek 0x300", "save-state"] {
+            assert!(
+                !command.parse::<Request>()?.changes_machine(),
+                "{}",
+                command
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_requests() -> Result<(), String> {
+        assert_eq!("step".parse::<Request>()?, Request::Step(1));
+        assert_eq!("step 10".parse::<Request>()?, Request::Step(10));
+        assert_eq!("poke 0x3F2 9".parse::<Request>()?, Request::Poke(0x3F2, 9));
+        assert_eq!("press a".parse::<Request>()?, Request::Press(0xA));
+        assert!("poke 0x3F2 256".parse::<Request>().is_err());
+        assert!("peek 0x1000".parse::<Request>().is_err());
+        assert!("press 10".parse::<Request>().is_err());
+        assert!("pause now".parse::<Request>().is_err());
+        assert!("jump".parse::<Request>().is_err());
+        assert_eq!(
+            "break pc == 0x3A4 && v[3] > 10".parse::<Request>()?,
+            Request::Break(String::from("pc == 0x3A4 && v[3] > 10"))
+        );
+        assert_eq!(
+            "break write mem 0x300 0x30F".parse::<Request>()?,
+            Request::Break(String::from("write mem 0x300 0x30F"))
+        );
+        assert_eq!(
+            "histogram ops.csv".parse::<Request>()?,
+            Request::Histogram(String::from("ops.csv"))
+        );
+        assert!("histogram".parse::<Request>().is_err());
+        assert_eq!(
+            "coverage out.html".parse::<Request>()?,
+            Request::Coverage(String::from("out.html"))
+        );
+        assert!("break".parse::<Request>().is_err());
+        assert_eq!("delete 2".parse::<Request>()?, Request::Delete(2));
+        assert_eq!("run".parse::<Request>()?, Request::Resume);
+        assert_eq!(
+            "b 0x2A0".parse::<Request>()?,
+            Request::Break(String::from("pc == 0x2A0"))
+        );
+        assert_eq!(
+            "p v3".parse::<Request>()?,
+            Request::Print(Expr::Register(Box::new(Expr::Number(3))))
+        );
+        assert!("p".parse::<Request>().is_err());
+        assert_eq!("m 0x300 16".parse::<Request>()?, Request::Memory(0x300, 16));
+        assert_eq!("m 0x300".parse::<Request>()?, Request::Memory(0x300, 16));
+        assert!("m 0xFF8 16".parse::<Request>().is_err());
+        assert!("m 0x10 18446744073709551615".parse::<Request>().is_err());
+        assert!("dump x 0x10 18446744073709551615"
+            .parse::<Request>()
+            .is_err());
+        assert_eq!(
+            "dump ram.bin".parse::<Request>()?,
+            Request::Dump(String::from("ram.bin"), 0, 0x1000)
+        );
+        assert_eq!(
+            "dump ram.bin 0x200 0x100".parse::<Request>()?,
+            Request::Dump(String::from("ram.bin"), 0x200, 0x100)
+        );
+        assert_eq!(
+            "dump ram.bin 0xF00".parse::<Request>()?,
+            Request::Dump(String::from("ram.bin"), 0xF00, 0x100)
+        );
+        assert!("dump ram.bin 0xF00 0x101".parse::<Request>().is_err());
+        assert!("dump".parse::<Request>().is_err());
+        assert_eq!(
+            "restore ram.bin".parse::<Request>()?,
+            Request::Restore(String::from("ram.bin"), 0, false)
+        );
+        assert_eq!(
+            "restore ram.bin force".parse::<Request>()?,
+            Request::Restore(String::from("ram.bin"), 0, true)
+        );
+        assert_eq!(
+            "restore ram.bin 0x200".parse::<Request>()?,
+            Request::Restore(String::from("ram.bin"), 0x200, false)
+        );
+        assert!("restore ram.bin 0x200 now".parse::<Request>().is_err());
+        assert!("restore ram.bin 0x1000".parse::<Request>().is_err());
+        Ok(())
+    }
+}
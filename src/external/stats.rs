@@ -0,0 +1,107 @@
+use crate::core::processor::Processor;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+// Counters served as JSON by the stats endpoint, refreshed by the main loop every frame
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub instructions: u64,
+    pub frames: u64,
+    pub pc: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+impl Stats {
+    pub fn capture(chip8: &Processor) -> Self {
+        Stats {
+            instructions: chip8.instructions,
+            frames: chip8.frames,
+            pc: chip8.pc,
+            delay_timer: chip8.delay_timer,
+            sound_timer: chip8.sound_timer,
+        }
+    }
+
+    pub fn to_json(&self, uptime_secs: f64) -> String {
+        serde_json::json!({
+            "instructions": self.instructions,
+            "frames": self.frames,
+            "pc": self.pc,
+            "delay_timer": self.delay_timer,
+            "sound_timer": self.sound_timer,
+            "uptime_secs": uptime_secs,
+        })
+        .to_string()
+    }
+}
+
+fn respond(stream: TcpStream, stats: &Mutex<Stats>, started: Instant) -> std::io::Result<()> {
+    let mut request = String::new();
+    BufReader::new(&stream).read_line(&mut request)?;
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+    let (status, body) = match path {
+        "/" | "/stats" => {
+            let stats = *stats.lock().unwrap_or_else(|e| e.into_inner());
+            ("200 OK", stats.to_json(started.elapsed().as_secs_f64()))
+        }
+        _ => ("404 Not Found", String::from("{\"error\":\"not found\"}")),
+    };
+    write!(
+        &stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+// Minimal HTTP server answering GET /stats, meant for monitoring long unattended runs
+pub struct StatsServer {
+    stats: Arc<Mutex<Stats>>,
+}
+
+impl StatsServer {
+    pub fn bind(address: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind(address)?;
+        let stats: Arc<Mutex<Stats>> = Default::default();
+        let shared = Arc::clone(&stats);
+        let started = Instant::now();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = respond(stream, &shared, started);
+            }
+        });
+        Ok(StatsServer { stats })
+    }
+
+    pub fn update(&self, chip8: &Processor) {
+        if let Ok(mut stats) = self.stats.lock() {
+            *stats = Stats::capture(chip8);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stats;
+
+    #[test]
+    fn test_stats_json() -> Result<(), serde_json::Error> {
+        let stats = Stats {
+            instructions: 1200,
+            frames: 120,
+            pc: 0x2F4,
+            delay_timer: 3,
+            sound_timer: 0,
+        };
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json(2.0))?;
+        assert_eq!(json["instructions"], 1200);
+        assert_eq!(json["pc"], 0x2F4);
+        assert_eq!(json["uptime_secs"], 2.0);
+        Ok(())
+    }
+}
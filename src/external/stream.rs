@@ -0,0 +1,112 @@
+use crate::core::processor::Processor;
+use crate::core::snapshot::Snapshot;
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use tungstenite::Message;
+
+// How long a handshake or a send may take before the client is dropped
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// The newest frame not yet sent to one client. A client that falls behind skips frames
+// instead of queueing them
+#[derive(Default)]
+struct Mailbox {
+    frame: Mutex<Option<String>>,
+    ready: Condvar,
+    // Set by the client's thread once sending failed
+    closed: AtomicBool,
+}
+
+impl Mailbox {
+    fn post(&self, frame: String) {
+        if let Ok(mut latest) = self.frame.lock() {
+            *latest = Some(frame);
+            self.ready.notify_one();
+        }
+    }
+
+    // Blocks until a frame is posted
+    fn take(&self) -> Option<String> {
+        let latest = self.frame.lock().ok()?;
+        let mut latest = self.ready.wait_while(latest, |f| f.is_none()).ok()?;
+        latest.take()
+    }
+}
+
+// Mirrors the running machine to WebSocket clients, every published frame is sent to all of
+// them as a JSON text message. Control goes through the --remote line protocol
+pub struct StateStream {
+    clients: Arc<Mutex<Vec<Arc<Mailbox>>>>,
+}
+
+// Each client has its own thread, so neither a slow handshake nor a slow socket holds up
+// the others or the main loop
+fn serve(stream: TcpStream, clients: Arc<Mutex<Vec<Arc<Mailbox>>>>) {
+    if stream.set_read_timeout(Some(CLIENT_TIMEOUT)).is_err()
+        || stream.set_write_timeout(Some(CLIENT_TIMEOUT)).is_err()
+    {
+        return;
+    }
+    let Ok(mut socket) = tungstenite::accept(stream) else {
+        return;
+    };
+    let mailbox: Arc<Mailbox> = Default::default();
+    match clients.lock() {
+        Ok(mut clients) => clients.push(Arc::clone(&mailbox)),
+        Err(_) => return,
+    }
+    while let Some(frame) = mailbox.take() {
+        if socket.send(Message::Text(frame)).is_err() {
+            break;
+        }
+    }
+    mailbox.closed.store(true, Ordering::Relaxed);
+}
+
+impl StateStream {
+    pub fn bind(address: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind(address)?;
+        let clients: Arc<Mutex<Vec<Arc<Mailbox>>>> = Default::default();
+
+        let accepted = Arc::clone(&clients);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let clients = Arc::clone(&accepted);
+                thread::spawn(move || serve(stream, clients));
+            }
+        });
+        Ok(StateStream { clients })
+    }
+
+    // The state is only serialized while someone is watching
+    pub fn publish(&self, chip8: &Processor) {
+        let Ok(mut clients) = self.clients.lock() else {
+            return;
+        };
+        clients.retain(|client| !client.closed.load(Ordering::Relaxed));
+        if clients.is_empty() {
+            return;
+        }
+        let frame = Snapshot::capture(chip8).to_json();
+        for client in clients.iter() {
+            client.post(frame.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mailbox;
+
+    #[test]
+    fn test_only_latest_frame_is_kept() {
+        let mailbox = Mailbox::default();
+        mailbox.post(String::from("1"));
+        mailbox.post(String::from("2"));
+        assert_eq!(mailbox.take(), Some(String::from("2")));
+        assert!(mailbox.frame.lock().unwrap().is_none());
+    }
+}
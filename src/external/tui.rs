@@ -0,0 +1,360 @@
+use crate::breakpoints::Breakpoints;
+use crate::callstack::CallStack;
+use crate::consts;
+use crate::core::clock::{FramePacer, RealClock};
+use crate::core::events::Event;
+use crate::core::instruction::Instruction;
+use crate::core::processor::Processor;
+use crate::core::ram::DisplayBuffer;
+use crate::core::trace;
+use crate::external::output;
+use crate::symbols::Symbols;
+use ratatui::crossterm::event::{self, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+// Instructions listed before and after PC
+const DISASSEMBLY_CONTEXT: usize = 8;
+const MEMORY_ROWS: usize = 8;
+const MEMORY_ROW_BYTES: usize = 8;
+// Terminals only report presses, so a keypad key stays down for this many frames
+const KEY_HOLD_FRAMES: u8 = 6;
+
+// Full-screen terminal debugger, the same views as the gui debugger panels without a window
+struct Debugger {
+    chip8: Processor,
+    events: Receiver<Event>,
+    breakpoints: Breakpoints,
+    calls: CallStack,
+    symbols: Symbols,
+    rom_path: String,
+    paused: bool,
+    status: String,
+    // Frames left until each keypad key is released
+    held: [u8; consts::KEYBOARD_SIZE],
+}
+
+pub fn run(
+    mut chip8: Processor,
+    rom_path: &str,
+    symbols: Symbols,
+    cycles_per_frame: usize,
+    frame_period: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let events = chip8.events.subscribe();
+    let mut breakpoints: Breakpoints = Default::default();
+    breakpoints.attach(&mut chip8);
+    let mut calls: CallStack = Default::default();
+    calls.sync(&chip8);
+    let mut debugger = Debugger {
+        chip8,
+        events,
+        breakpoints,
+        calls,
+        symbols,
+        rom_path: rom_path.to_string(),
+        paused: false,
+        status: String::from("Running"),
+        held: [0; consts::KEYBOARD_SIZE],
+    };
+    // The terminal has to be handed back even when the loop fails
+    let mut terminal = ratatui::init();
+    let result = debugger.run(&mut terminal, cycles_per_frame, frame_period);
+    ratatui::restore();
+    result
+}
+
+impl Debugger {
+    fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        cycles_per_frame: usize,
+        frame_period: Duration,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let clock = RealClock::default();
+        let mut pacer = FramePacer::new(frame_period, &clock);
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            // Waiting for input doubles as the sleep until the next frame
+            if event::poll(pacer.until_next(&clock))? {
+                if let event::Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press && !self.key(key.code) {
+                        return Ok(());
+                    }
+                }
+            }
+            for _ in 0..pacer.due(&clock) {
+                if !self.paused {
+                    self.run_frame(cycles_per_frame);
+                }
+            }
+        }
+    }
+
+    // Returns false when the debugger should exit
+    fn key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Esc => return false,
+            KeyCode::Char(' ') => {
+                self.paused = !self.paused;
+                self.status = String::from(if self.paused { "Paused" } else { "Running" });
+            }
+            KeyCode::Enter if self.paused => {
+                if let Err(e) = self.chip8.cycle() {
+                    self.fail(e);
+                }
+                self.drain_events();
+            }
+            KeyCode::F(9) => self.toggle_breakpoint(),
+            KeyCode::Char(c) => {
+                let c = c.to_ascii_lowercase();
+                if let Some(key) = consts::KEY_LAYOUT.iter().position(|&k| k == c) {
+                    self.held[key] = KEY_HOLD_FRAMES;
+                    self.chip8.keyboard_buffer.set(key);
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn toggle_breakpoint(&mut self) {
+        let source = format!("pc == {:#05X}", self.chip8.pc);
+        let existing = self
+            .breakpoints
+            .breakpoints
+            .iter()
+            .position(|b| b.source == source);
+        self.status = match existing {
+            Some(index) => {
+                let _ = self.breakpoints.remove(index);
+                format!("Removed breakpoint at {:03X}", self.chip8.pc)
+            }
+            None => match self.breakpoints.add(&source) {
+                Ok(()) => format!("Breakpoint at {:03X}", self.chip8.pc),
+                Err(e) => e,
+            },
+        };
+    }
+
+    fn run_frame(&mut self, cycles_per_frame: usize) {
+        let (breakpoints, mut hit) = (&self.breakpoints, None);
+        let stop = |c: &Processor| {
+            hit = breakpoints.hit(c);
+            hit.is_some()
+        };
+        match self.chip8.run_frame_until(cycles_per_frame, stop) {
+            Ok(true) => {
+                self.paused = true;
+                let index = hit.unwrap_or_default();
+                self.status = format!(
+                    "Breakpoint {} ({}) hit at {:03X}",
+                    index, self.breakpoints.breakpoints[index].source, self.chip8.pc
+                );
+            }
+            Ok(false) => {}
+            Err(e) => self.fail(e),
+        }
+        self.drain_events();
+        for (key, frames) in self.held.iter_mut().enumerate() {
+            if *frames > 0 {
+                *frames -= 1;
+                if *frames == 0 {
+                    self.chip8.keyboard_buffer.clear(key);
+                }
+            }
+        }
+    }
+
+    fn fail(&mut self, error: &'static str) {
+        self.paused = true;
+        let path = output::screenshot_path(&self.rom_path).with_extension("crash.txt");
+        let report = trace::crash_report(&self.chip8, &self.rom_path, error);
+        self.status = match std::fs::write(&path, report) {
+            Ok(()) => format!("{}, paused. Crash report in {}", error, path.display()),
+            Err(_) => format!("{}, paused", error),
+        };
+    }
+
+    fn drain_events(&mut self) {
+        for event in self.events.try_iter() {
+            match event {
+                Event::Halted => {
+                    self.paused = true;
+                    self.status = String::from("Program exited");
+                }
+                _ => self.calls.handle(&event),
+            }
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let display = &self.chip8.display_buffer;
+        // Two pixel rows per character cell, plus the borders
+        let display_size = (
+            display.width() as u16 + 2,
+            display.height().div_ceil(2) as u16 + 2,
+        );
+        let [top, middle, status] = Layout::vertical([
+            Constraint::Length(display_size.1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [screen, registers] =
+            Layout::horizontal([Constraint::Length(display_size.0), Constraint::Min(0)]).areas(top);
+        let [code, stack, memory] = Layout::horizontal([
+            Constraint::Percentage(40),
+            Constraint::Percentage(25),
+            Constraint::Percentage(35),
+        ])
+        .areas(middle);
+
+        frame.render_widget(
+            Paragraph::new(display_lines(display)).block(Block::bordered().title("Display")),
+            screen,
+        );
+        let register_lines: Vec<Line> = output::overlay_lines(&self.chip8)
+            .into_iter()
+            .map(Line::from)
+            .collect();
+        frame.render_widget(
+            Paragraph::new(register_lines).block(Block::bordered().title("Registers")),
+            registers,
+        );
+        self.draw_disassembly(frame, code);
+        self.draw_stack(frame, stack);
+        self.draw_memory(frame, memory);
+        frame.render_widget(
+            Line::from(format!(
+                "{} | Esc quit, Space pause, Enter step, F9 breakpoint",
+                self.status
+            )),
+            status,
+        );
+    }
+
+    fn draw_disassembly(&self, frame: &mut Frame, area: Rect) {
+        let pc = self.chip8.pc as usize;
+        let start = pc
+            .saturating_sub(DISASSEMBLY_CONTEXT * consts::OP_CODE_BYTES)
+            .max(self.chip8.load_address().min(pc));
+        let mut lines = Vec::new();
+        for address in (start..consts::RAM_BYTES - 1)
+            .step_by(consts::OP_CODE_BYTES)
+            .take(DISASSEMBLY_CONTEXT * 2 + 1)
+        {
+            if let Some(name) = self.symbols.name(address as u16) {
+                lines.push(Line::from(format!("{}:", name)));
+            }
+            let opcode = u16::from_be_bytes([
+                self.chip8.ram.buffer[address],
+                self.chip8.ram.buffer[address + 1],
+            ]);
+            let breakpoint = format!("pc == {:#05X}", address);
+            let marker = match (
+                address == pc,
+                self.breakpoints
+                    .breakpoints
+                    .iter()
+                    .any(|b| b.source == breakpoint),
+            ) {
+                (true, _) => ">",
+                (false, true) => "*",
+                _ => " ",
+            };
+            let line = Line::from(format!(
+                "{}{:03X}  {:04X}  {}",
+                marker,
+                address,
+                opcode,
+                Instruction::decode(opcode, self.chip8.quirks())
+            ));
+            lines.push(if address == pc {
+                line.style(Style::default().fg(Color::Black).bg(Color::Yellow))
+            } else {
+                line
+            });
+        }
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Disassembly")),
+            area,
+        );
+    }
+
+    fn draw_stack(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = vec![Line::from(format!("SP: {}", self.chip8.stack_pointer))];
+        for (i, call) in self.calls.frames.iter().enumerate().rev() {
+            let target = match call.target {
+                Some(address) => self.symbols.describe(address),
+                None => String::from("?"),
+            };
+            lines.push(Line::from(format!(
+                "{:2}: {} from {}",
+                i,
+                target,
+                self.symbols.describe(call.call_site)
+            )));
+        }
+        if let Some(warning) = &self.calls.warning {
+            lines.push(Line::from(warning.as_str()).style(Style::default().fg(Color::Yellow)));
+        }
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Stack")),
+            area,
+        );
+    }
+
+    fn draw_memory(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = Vec::new();
+        for (register, address) in [
+            ("PC", self.chip8.pc as usize),
+            ("I", self.chip8.idx_register as usize),
+        ] {
+            lines.push(Line::from(format!("Around {}", register)));
+            let (start, bytes) =
+                self.chip8
+                    .ram
+                    .rows_around(address, MEMORY_ROWS / 2, MEMORY_ROW_BYTES);
+            for (row, values) in (start..)
+                .step_by(MEMORY_ROW_BYTES)
+                .zip(bytes.chunks(MEMORY_ROW_BYTES))
+            {
+                let hex: Vec<String> = values.iter().map(|b| format!("{:02X}", b)).collect();
+                lines.push(Line::from(format!("{:03X}  {}", row, hex.join(" "))));
+            }
+        }
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Memory")),
+            area,
+        );
+    }
+}
+
+// Half block characters fit two pixel rows into each line of text
+fn display_lines(display: &DisplayBuffer) -> Vec<Line<'static>> {
+    display
+        .buffer
+        .chunks(2)
+        .map(|rows| {
+            let text: String = (0..display.width())
+                .map(|x| {
+                    let top = rows[0][x] != 0;
+                    let bottom = rows.get(1).is_some_and(|row| row[x] != 0);
+                    match (top, bottom) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    }
+                })
+                .collect();
+            Line::from(text)
+        })
+        .collect()
+}
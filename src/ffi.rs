@@ -0,0 +1,195 @@
+// The requirements of each unsafe function are in the SAFETY comment above it
+#![allow(clippy::missing_safety_doc)]
+
+use crate::core::events::Event;
+use crate::core::platform::Platform;
+use crate::core::processor::Processor;
+use crate::core::rom::Rom;
+use std::sync::mpsc::Receiver;
+
+// C API for embedding the core in other frontends, see include/chip8.h. The machine is an
+// opaque handle, nothing SDL related crosses the boundary:
+//   Chip8 *c = chip8_new();
+//   chip8_load(c, rom, rom_len);
+//   chip8_set_keys(c, 1 << 0x5);
+//   chip8_step(c);
+//   const uint8_t *pixels = chip8_framebuffer(c, &width, &height);
+//   chip8_free(c);
+pub struct Chip8 {
+    processor: Processor,
+    events: Receiver<Event>,
+    cycles_per_frame: usize,
+    halted: bool,
+    // Flattened copy of the display, owned here so the pointer stays valid between calls
+    framebuffer: Vec<u8>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Chip8Status {
+    Ok,
+    Redraw,
+    Halted,
+    Error,
+}
+
+impl Chip8 {
+    fn new(platform: Platform) -> Self {
+        let mut processor = Processor::builder()
+            .quirks(platform.quirks())
+            .start_pc(platform.load_address())
+            .resolution(platform.resolution())
+            .fonts(platform.fonts())
+            .build();
+        let events = processor.events.subscribe();
+        Chip8 {
+            processor,
+            events,
+            cycles_per_frame: platform.cycles_per_frame(),
+            halted: false,
+            framebuffer: Vec::new(),
+        }
+    }
+
+    fn frame(&mut self) -> Chip8Status {
+        if self.processor.run_frame(self.cycles_per_frame).is_err() {
+            return Chip8Status::Error;
+        }
+        let mut redraw = false;
+        for event in self.events.try_iter() {
+            match event {
+                Event::FrameCompleted { redraw: r } => redraw |= r,
+                Event::Halted => self.halted = true,
+                _ => {}
+            }
+        }
+        match (self.halted, redraw) {
+            (true, _) => Chip8Status::Halted,
+            (false, true) => Chip8Status::Redraw,
+            (false, false) => Chip8Status::Ok,
+        }
+    }
+}
+
+// Creates a machine with the modern CHIP-8 defaults, free it with chip8_free
+#[no_mangle]
+pub extern "C" fn chip8_new() -> *mut Chip8 {
+    Box::into_raw(Box::new(Chip8::new(Platform::default())))
+}
+
+// SAFETY: chip8 must come from chip8_new and not have been freed already, null is ignored
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+// Resets the machine and loads len bytes of ROM from data
+// SAFETY: chip8 must be a live handle and data must point to at least len readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load(chip8: *mut Chip8, data: *const u8, len: usize) -> Chip8Status {
+    let (Some(chip8), false) = (chip8.as_mut(), data.is_null()) else {
+        return Chip8Status::Error;
+    };
+    let bytes = std::slice::from_raw_parts(data, len);
+    let Ok(rom) = Rom::from_bytes(bytes) else {
+        return Chip8Status::Error;
+    };
+    if chip8.processor.load_rom(&rom).is_err() {
+        return Chip8Status::Error;
+    }
+    chip8.halted = false;
+    Chip8Status::Ok
+}
+
+// Runs one 60Hz frame, returns Redraw when the display changed
+// SAFETY: chip8 must be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip8: *mut Chip8) -> Chip8Status {
+    match chip8.as_mut() {
+        Some(chip8) => chip8.frame(),
+        None => Chip8Status::Error,
+    }
+}
+
+// Returns width * height bytes, one per pixel row by row, valid until the next call on chip8
+// SAFETY: chip8 must be a live handle, width and height may be null
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(
+    chip8: *mut Chip8,
+    width: *mut usize,
+    height: *mut usize,
+) -> *const u8 {
+    let Some(chip8) = chip8.as_mut() else {
+        return std::ptr::null();
+    };
+    let display = &chip8.processor.display_buffer;
+    chip8.framebuffer.clear();
+    chip8
+        .framebuffer
+        .extend(display.buffer.iter().flatten().copied());
+    if let Some(width) = width.as_mut() {
+        *width = display.width();
+    }
+    if let Some(height) = height.as_mut() {
+        *height = display.height();
+    }
+    chip8.framebuffer.as_ptr()
+}
+
+// Bit n of keys is set while key n is held
+// SAFETY: chip8 must be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_keys(chip8: *mut Chip8, keys: u16) {
+    if let Some(chip8) = chip8.as_mut() {
+        chip8.processor.keyboard_buffer.keys = keys;
+    }
+}
+
+// Whether the buzzer should be sounding
+// SAFETY: chip8 must be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn chip8_sound_active(chip8: *const Chip8) -> bool {
+    chip8
+        .as_ref()
+        .is_some_and(|chip8| chip8.processor.sound_timer > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_frame() {
+        // 00E0 clear, A000 point I at the font, D005 draw "0", 1206 loop forever
+        let rom = [0x00, 0xE0, 0xA0, 0x00, 0xD0, 0x05, 0x12, 0x06];
+        unsafe {
+            let chip8 = chip8_new();
+            assert_eq!(chip8_load(chip8, rom.as_ptr(), rom.len()), Chip8Status::Ok);
+            chip8_set_keys(chip8, 0x8001);
+            assert!((*chip8).processor.keyboard_buffer.is_pressed(0xF));
+            assert_eq!(chip8_step(chip8), Chip8Status::Redraw);
+
+            let (mut width, mut height) = (0, 0);
+            let pixels = chip8_framebuffer(chip8, &mut width, &mut height);
+            assert_eq!((width, height), (64, 32));
+            let pixels = std::slice::from_raw_parts(pixels, width * height);
+            assert_eq!(pixels.iter().filter(|&&p| p != 0).count(), 14);
+            assert!(!chip8_sound_active(chip8));
+            chip8_free(chip8);
+
+            assert_eq!(chip8_step(std::ptr::null_mut()), Chip8Status::Error);
+        }
+    }
+    #[test]
+    fn test_header_up_to_date() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/chip8.h"));
+        let committed = include_str!("../include/chip8.h");
+        assert!(
+            generated == committed,
+            "include/chip8.h is out of date, copy it from {}",
+            env!("OUT_DIR")
+        );
+    }
+}
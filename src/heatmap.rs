@@ -0,0 +1,132 @@
+use crate::core::events::SpriteDraw;
+use crate::core::ram::{DisplayBuffer, Ram};
+
+// Counts how often each display pixel was drawn to by DXYN, which shows where a program
+// overdraws or erases and redraws sprites every frame, i.e. where it flickers
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Heatmap {
+    pub width: usize,
+    pub height: usize,
+    pub counts: Vec<u32>,
+}
+
+impl Heatmap {
+    pub fn new(display: &DisplayBuffer) -> Self {
+        let (width, height) = (display.width(), display.height());
+        Heatmap {
+            width,
+            height,
+            counts: vec![0; width * height],
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.counts.fill(0);
+    }
+
+    pub fn max(&self) -> u32 {
+        self.counts.iter().copied().max().unwrap_or(0)
+    }
+
+    // Adds the set pixels of a sprite, read back from RAM. Sprite data is read after the
+    // frame, so a program that rewrites it in between is counted with the new data
+    pub fn record(&mut self, draw: &SpriteDraw, ram: &Ram, display: &DisplayBuffer, wrap: bool) {
+        // A resolution switch clears the screen, so old counts no longer line up
+        if (display.width(), display.height()) != (self.width, self.height) {
+            *self = Heatmap::new(display);
+        }
+        let row_bytes = draw.width / 8;
+        for row in 0..draw.height {
+            for col in 0..draw.width {
+                let address = draw.address as usize + row * row_bytes + col / 8;
+                let set = ram.read(address).unwrap_or(0) & (0x80 >> (col % 8)) != 0;
+                let (mut x, mut y) = (draw.x + col, draw.y + row);
+                if x >= self.width || y >= self.height {
+                    if !wrap {
+                        continue;
+                    }
+                    (x, y) = (x % self.width, y % self.height);
+                }
+                if set {
+                    self.counts[y * self.width + x] += 1;
+                }
+            }
+        }
+    }
+
+    // Row-major RGB pixels, each heatmap cell scaled up to scale x scale
+    pub fn to_rgb(&self, scale: usize) -> Vec<u8> {
+        let max = self.max();
+        let width = self.width * scale;
+        let mut pixels = Vec::with_capacity(width * self.height * scale * 3);
+        for y in 0..self.height * scale {
+            for x in 0..width {
+                let (r, g, b) = heat_color(self.counts[(y / scale) * self.width + x / scale], max);
+                pixels.extend_from_slice(&[r, g, b]);
+            }
+        }
+        pixels
+    }
+}
+
+// Black for untouched pixels, then through red and yellow to white for the most drawn one
+pub fn heat_color(count: u32, max: u32) -> (u8, u8, u8) {
+    if count == 0 || max == 0 {
+        return (0, 0, 0);
+    }
+    let heat = count as u64 * 3 * 255 / max as u64;
+    let channel = |offset: u64| heat.saturating_sub(offset).min(255) as u8;
+    (channel(0), channel(255), channel(510))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{heat_color, Heatmap};
+    use crate::core::events::SpriteDraw;
+    use crate::core::ram::{DisplayBuffer, Ram, Resolution};
+
+    fn sprite(x: usize, y: usize) -> SpriteDraw {
+        SpriteDraw {
+            x,
+            y,
+            width: 8,
+            height: 2,
+            address: 0x300,
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let mut ram = Ram::default();
+        ram.buffer[0x300] = 0b1000_0001;
+        ram.buffer[0x301] = 0b0100_0000;
+        let display = DisplayBuffer::default();
+        let mut heatmap = Heatmap::new(&display);
+        heatmap.record(&sprite(0, 0), &ram, &display, false);
+        heatmap.record(&sprite(0, 0), &ram, &display, false);
+        assert_eq!(heatmap.counts[0], 2);
+        assert_eq!(heatmap.counts[7], 2);
+        assert_eq!(heatmap.counts[64 + 1], 2);
+        assert_eq!(heatmap.counts.iter().sum::<u32>(), 6);
+
+        // Clipped at the right edge unless the wrap quirk is on
+        heatmap.reset();
+        heatmap.record(&sprite(60, 31), &ram, &display, false);
+        assert_eq!(heatmap.counts.iter().sum::<u32>(), 1);
+        heatmap.record(&sprite(60, 31), &ram, &display, true);
+        assert_eq!(heatmap.counts[31 * 64 + 3], 1);
+        assert_eq!(heatmap.counts[61], 1);
+
+        let hires = DisplayBuffer::new(Resolution::High);
+        heatmap.record(&sprite(0, 0), &ram, &hires, false);
+        assert_eq!((heatmap.width, heatmap.max()), (128, 1));
+    }
+
+    #[test]
+    fn test_heat_color() {
+        assert_eq!(heat_color(0, 10), (0, 0, 0));
+        assert_eq!(heat_color(10, 10), (255, 255, 255));
+        assert_eq!(heat_color(1, 3), (255, 0, 0));
+        assert_eq!(heat_color(2, 3), (255, 255, 0));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::checker::{self, Extension};
+use crate::cli::InfoOptions;
+use crate::consts;
+use crate::core::instruction::Instruction;
+use crate::core::quirks::Quirks;
+use crate::core::rom::Rom;
+use crate::database;
+use crate::utils;
+
+// Entry point of `chip8 info`
+pub fn run(options: &InfoOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let rom = Rom::new(&options.rom_path)?;
+    let entry = database::entry(&rom.sha1, options.database.as_deref())?;
+    print!("{}", report(&options.rom_path, &rom, entry));
+    Ok(())
+}
+
+// Plain "Field: value" lines, easy to paste into a bug report or to grep through a collection
+pub fn report(path: &str, rom: &Rom, entry: Option<(String, String)>) -> String {
+    let program = &rom.buffer[..rom.size];
+    let mut lines = vec![
+        format!("File: {}", path),
+        format!("Size: {} bytes", rom.size),
+        format!("SHA-1: {}", rom.sha1),
+        format!("CRC32: {:08X}", utils::crc32(program)),
+    ];
+    if let Some(bytes) = program.get(..consts::OP_CODE_BYTES) {
+        let opcode = u16::from_be_bytes([bytes[0], bytes[1]]);
+        lines.push(format!(
+            "Entry: {:04X} {}",
+            opcode,
+            Instruction::decode(opcode, &Quirks::default())
+        ));
+    }
+    let check = checker::check(program);
+    lines.push(match check.extension {
+        Some(Extension::Chip8) | None => String::from("Platform: chip8 instructions only"),
+        Some(extension) => format!(
+            "Platform: {} (first needed at {:#05X})",
+            extension.platform(),
+            check.extension_address
+        ),
+    });
+    if rom.size > consts::RAM_BYTES - consts::PROG_OFFSET {
+        lines.push(String::from(
+            "Memory: larger than 4K, needs XO-CHIP's extended memory",
+        ));
+    }
+    lines.push(match entry {
+        Some((options, title)) => format!("Database: {} {}", title, options),
+        None => String::from("Database: not listed"),
+    });
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::report;
+    use crate::core::rom::Rom;
+    use crate::database;
+    use crate::utils;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(utils::crc32(b"123456789"), 0xCBF43926);
+    }
+
+    #[test]
+    fn test_report() -> Result<(), Box<dyn std::error::Error>> {
+        let rom = Rom::new("roms/IBM Logo.ch8")?;
+        let entry = database::entry(&rom.sha1, None)?;
+        let text = report("IBM Logo.ch8", &rom, entry);
+        assert!(text.contains("Size: 132 bytes"), "{}", text);
+        assert!(text.contains("Entry: 00E0 CLS"), "{}", text);
+        assert!(
+            text.contains("Platform: chip8 instructions only"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("Database: IBM Logo --platform modern"),
+            "{}",
+            text
+        );
+        Ok(())
+    }
+}
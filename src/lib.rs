@@ -0,0 +1,23 @@
+pub mod assembler;
+pub mod bench;
+pub mod breakpoints;
+pub mod callstack;
+pub mod cheats;
+pub mod checker;
+pub mod cli;
+pub mod config;
+pub mod consts;
+pub mod core;
+pub mod database;
+pub mod disassembler;
+pub mod external;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod heatmap;
+pub mod info;
+pub mod profiles;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod saves;
+pub mod symbols;
+pub mod utils;
@@ -1,65 +1,690 @@
-pub mod consts;
-pub mod core;
-pub mod external;
-pub mod utils;
-
-use crate::core::{processor, ram, rom};
-use crate::external::{input, output};
+use chip8::core::clock::Clock;
+use chip8::core::{clock, coverage, events, keypad, processor, rom, snapshot, timers, trace};
+use chip8::external::{crowd, headless, input, netplay, output, recorder, remote};
+use chip8::{
+    assembler, bench, cheats, checker, cli, config, consts, database, disassembler, heatmap, info,
+    profiles, saves, utils,
+};
 use std::env;
-use std::thread;
+use std::time::Duration;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
-    if args.len() <= 1 {
-        Err("Need to specify rom path")?;
+    let mut options = match cli::Command::parse(&args[1..])? {
+        cli::Command::Run(options) => *options,
+        cli::Command::Disasm(options) => return disassembler::export(&options),
+        cli::Command::Asm(options) => return assembler::export(&options),
+        cli::Command::Check(options) => return checker::run(&options),
+        cli::Command::Info(options) => return info::run(&options),
+        cli::Command::Bench(options) => return bench::run(*options),
+        cli::Command::Config(setting) => return config::run(setting),
+        cli::Command::Help => {
+            print!("{}", cli::USAGE);
+            return Ok(());
+        }
+    };
+
+    if options.gui {
+        #[cfg(feature = "gui")]
+        return chip8::external::gui::run(options.rom_path, options.symbols);
+        #[cfg(not(feature = "gui"))]
+        Err("Built without the gui feature")?;
     }
-    let rom_path = &args[1];
-    let prog = rom::Rom::new(rom_path.as_str())?;
 
-    let ram_ = ram::Ram {
-        ..Default::default()
+    let (rom_path, prog) = match &options.demo {
+        #[cfg(feature = "bundled-roms")]
+        Some(name) => match chip8::core::demos::find(name) {
+            Some(bytes) => (name.clone(), rom::Rom::from_bytes(bytes)?),
+            None => Err(format!(
+                "Unknown demo {}, available demos: {}",
+                name,
+                chip8::core::demos::names().join(", ")
+            ))?,
+        },
+        #[cfg(not(feature = "bundled-roms"))]
+        Some(_) => Err("Built without the bundled-roms feature")?,
+        None => match options.rom_path.clone() {
+            Some(p) => {
+                let prog = rom::Rom::new(p.as_str())?;
+                (p, prog)
+            }
+            None => Err("Need to specify rom path")?,
+        },
     };
+    if options.save_profile {
+        let path = profiles::save(&prog.sha1, &args[1..])?;
+        println!("Saved profile for {} to {}", rom_path, path.display());
+    } else if options.use_profile {
+        if let Some(profile) = profiles::load(&prog.sha1)? {
+            options.merge(profile);
+        }
+    }
+    if options.use_database {
+        if let Some(entry) = database::lookup(&prog.sha1, options.database.as_deref())? {
+            options.merge(entry);
+        }
+    }
+    let platform = options.platform();
+    let palette = options.palette.unwrap_or_default();
 
-    let display_ram_ = ram::DisplayBuffer {
-        ..Default::default()
+    let mut chip8 = processor::Processor::builder()
+        .quirks(options.quirks()?)
+        .start_pc(platform.load_address())
+        .resolution(platform.resolution())
+        .fonts(platform.fonts())
+        .build();
+    chip8.histogram.enabled = options.histogram;
+    chip8.load_rom(&prog)?;
+    // The accessibility speed stretches frames and timer ticks alike
+    let config = config::Config::load()?;
+    let frame_period = config.frame_period(platform.timer_freq());
+    let mut keyscript = options
+        .keyscript
+        .as_deref()
+        .map(keypad::KeyScript::load)
+        .transpose()?;
+    #[cfg(feature = "stats")]
+    let stats = match &options.stats {
+        Some(address) => Some(chip8::external::stats::StatsServer::bind(address)?),
+        None => None,
     };
-
-    let keyboard_buffer_ = ram::KeyboardBuffer {
-        ..Default::default()
+    #[cfg(not(feature = "stats"))]
+    if options.stats.is_some() {
+        Err("Built without the stats feature")?;
+    }
+    let mut cheats = match &options.cheats {
+        Some(path) => Some(cheats::Cheats::load(path)?),
+        None => cheats::Cheats::for_rom(&rom_path)?,
     };
+    if options.backend == cli::Backend::Headless {
+        // Everything else that talks to the running machine needs the window's main loop
+        for (flag, given) in [
+            ("--stream", options.stream.is_some()),
+            ("--remote", options.remote.is_some()),
+            ("--control-socket", options.control_socket.is_some()),
+            ("--repl", options.repl),
+            ("--crowd", options.crowd.is_some()),
+        ] {
+            if given {
+                Err(format!("{} cannot be used with --backend headless", flag))?;
+            }
+        }
+        let cycles_per_frame = options.cycles_per_frame();
+        let after_frame = |chip8: &mut processor::Processor| {
+            if let Some(c) = cheats.as_mut() {
+                c.apply(chip8);
+            }
+            #[cfg(feature = "stats")]
+            if let Some(s) = &stats {
+                s.update(chip8);
+            }
+        };
+        let status = headless::run(
+            &mut chip8,
+            cycles_per_frame,
+            options.limits,
+            keyscript,
+            after_frame,
+        )
+        .map_err(|e| crash_dump(&chip8, &rom_path, e))?;
+        if options.dump {
+            print!("{}", headless::dump(&chip8));
+        }
+        if let Some(path) = &options.dump_state {
+            std::fs::write(path, chip8.to_json_state())?;
+        }
+        if let Some(path) = &options.export_display {
+            output::export_display(&chip8.display_buffer, path)?;
+        }
+        std::process::exit(status);
+    }
+    if options.backend == cli::Backend::DebugTui {
+        #[cfg(feature = "tui")]
+        return chip8::external::tui::run(
+            chip8,
+            &rom_path,
+            match &options.symbols {
+                Some(path) => chip8::symbols::Symbols::load(path)?,
+                None => chip8::symbols::Symbols::for_rom(&rom_path)?,
+            },
+            options.cycles_per_frame(),
+            frame_period,
+        );
+        #[cfg(not(feature = "tui"))]
+        Err("Built without the tui feature")?;
+    }
 
     let sdl_context = sdl2::init()?;
-    let mut chip8 = processor::Processor::new(ram_, display_ram_, keyboard_buffer_);
-    let mut keyboard = input::KeyboardDriver::new(&sdl_context, &chip8.keyboard_buffer)?;
-    let mut display = output::DisplayDriver::new(&sdl_context, &chip8.display_buffer)?;
-    let audio = output::AudioDriver::new(&sdl_context, &chip8.sound_timer)?;
+    let events = chip8.events.subscribe();
+    let mut keyboard = input::KeyboardDriver::new(&sdl_context)?;
+    keyboard.turbo = options.turbo.clone();
+    if let Some(layout) = &options.keymap {
+        keyboard.layout = layout.clone();
+    }
+    let panes = if options.split_view() { 2 } else { 1 };
+    let mut display =
+        output::DisplayDriver::new(&sdl_context, &chip8.display_buffer, palette, panes)?;
+    display.mode = options.display_mode;
+    if options.keypad {
+        display.show_keypad()?;
+    }
+    let opened: Result<Box<dyn output::AudioSink>, String> = match options.audio_backend {
+        output::AudioBackend::Sdl => {
+            output::AudioDriver::new(&sdl_context, options.tone(), options.volume(), config.audio)
+                .map(|driver| Box::new(driver) as _)
+                .map_err(String::from)
+        }
+        #[cfg(feature = "cpal")]
+        output::AudioBackend::Cpal => chip8::external::cpal_audio::CpalAudioDriver::new(
+            options.tone(),
+            options.volume(),
+            config.audio,
+        )
+        .map(|driver| Box::new(driver) as _)
+        .map_err(|e| e.to_string()),
+        #[cfg(not(feature = "cpal"))]
+        output::AudioBackend::Cpal => Err("Built without the cpal feature")?,
+        output::AudioBackend::Null => Ok(Box::<output::NullAudio>::default()),
+    };
+    // Games still run without an audio device, CI machines often have none
+    let mut audio = opened.unwrap_or_else(|e| {
+        eprintln!("{}, continuing without sound", e);
+        Box::<output::NullAudio>::default()
+    });
+    audio.set_muted(options.mute);
+    // The device may not grant the buffer size the config asks for
+    if config.audio.samples.is_some() {
+        let latency = audio.latency().as_secs_f64() * 1000.0;
+        println!("Audio buffer latency {:.1} ms", latency);
+    }
+
+    let mut netplay = match &options.netplay {
+        Some(role) => {
+            let settings =
+                netplay::settings_digest(chip8.quirks(), platform, options.cycles_per_frame());
+            let (session, seed) = netplay::Netplay::connect(role, &prog, &settings)?;
+            chip8.seed_rng(seed);
+            Some(session)
+        }
+        None => None,
+    };
+
+    // The split view runs a second machine next to the first on the same input and RNG seed,
+    // so any divergence between the panes comes from the ROM or the quirks
+    let mut compare = match options.split_view() {
+        true if netplay.is_some() => Err("The split view cannot be used with netplay")?,
+        true => {
+            let compare_prog = options.compare.as_deref().map(rom::Rom::new).transpose()?;
+            let seed = rand::random();
+            let mut instance = processor::Processor::builder()
+                .quirks(options.compare_quirks()?)
+                .start_pc(platform.load_address())
+                .resolution(platform.resolution())
+                .fonts(platform.fonts())
+                .rng(seed)
+                .build();
+            instance.load_rom(compare_prog.as_ref().unwrap_or(&prog))?;
+            chip8.seed_rng(seed);
+            let compare_events = instance.events.subscribe();
+            Some((instance, compare_events))
+        }
+        false => None,
+    };
+
+    let mut recording = match &options.record {
+        Some(path) => Some(recorder::Recorder::new(
+            path,
+            &chip8.display_buffer,
+            palette,
+            options.tone(),
+            options.volume(),
+        )?),
+        None => None,
+    };
 
-    chip8.init_ram(&prog, &consts::FONT_SET)?;
+    let mut remote = match (&options.remote, &options.control_socket, options.repl) {
+        (None, None, false) => None,
+        (address, socket, repl) => {
+            let mut remote = remote::RemoteControl::default();
+            if let Some(address) = address {
+                remote.bind(address)?;
+            }
+            if let Some(socket) = socket {
+                remote.bind_socket(socket)?;
+            }
+            if repl {
+                if rom_path == "-" {
+                    Err("stdin is already used for the ROM")?;
+                }
+                remote.read_stdin();
+            }
+            remote.breakpoints.attach(&mut chip8);
+            remote.lockstep = netplay.is_some();
+            Some(remote)
+        }
+    };
+    #[cfg(feature = "websocket")]
+    let stream = match &options.stream {
+        Some(address) => Some(chip8::external::stream::StateStream::bind(address)?),
+        None => None,
+    };
+    #[cfg(not(feature = "websocket"))]
+    if options.stream.is_some() {
+        Err("Built without the websocket feature")?;
+    }
+    let mut crowd = match options.crowd.as_deref() {
+        Some("-") if rom_path == "-" => Err("stdin is already used for the ROM")?,
+        Some(source) => Some(crowd::Crowd::start(source)?),
+        None => None,
+    };
+    let mut clock = clock::RealClock::default();
+    let mut pacer = clock::FramePacer::new(frame_period, &clock);
+    // Netplay peers run in lockstep, so there the timers tick with the frames instead
+    let mut timers = match netplay {
+        Some(_) => None,
+        None => {
+            let timers = timers::TimerDriver::new(frame_period, &clock);
+            timers.attach(&mut chip8);
+            if let Some((instance, _)) = compare.as_mut() {
+                timers.attach(instance);
+            }
+            Some(timers)
+        }
+    };
+    let mut bell_shown = false;
+    let mut overlay = options.overlay;
+    let slots = saves::SaveSlots::for_rom(&prog.sha1);
+    match &slots {
+        Ok(_) if options.resume && netplay.is_some() => {
+            Err("A session cannot be resumed during netplay")?
+        }
+        Ok(s) if options.resume => match s.resume()? {
+            Some(state) => state.restore(&mut chip8),
+            None => println!("No saved session for {}, starting over", rom_path),
+        },
+        Ok(s) if s.autosave_path().is_file() => {
+            println!("Run with --resume to continue where {} was left", rom_path)
+        }
+        Err(e) if options.resume => Err(*e)?,
+        _ => {}
+    }
+    // Confirmation for the save slot hotkeys and when it was shown
+    let mut message: Option<(String, Duration)> = None;
+    // Draw counts are always collected so the heatmap covers the whole run once shown
+    let mut heat = heatmap::Heatmap::new(&chip8.display_buffer);
+    let mut heat_shown = false;
+    let mut scope = output::Scope::new(audio.scope());
+    let mut dirty = true;
+    let mut cycles_per_frame = options.cycles_per_frame();
+    let mut title = output::TitleState::new(&rom_path, platform, cycles_per_frame);
+    title.recording = recording.is_some();
+    display.set_title(&title)?;
+    // Programs that exit on their own are not saved for --resume, and an older save is removed
+    let mut halted = false;
+    'running: while let Ok(hotkeys) = keyboard.poll(&mut chip8.keyboard_buffer) {
+        if let Some(r) = remote.as_mut() {
+            r.hold_keys(&mut chip8.keyboard_buffer);
+            dirty |= r.serve(&mut chip8, &display);
+        }
+        if let Some(c) = crowd.as_mut() {
+            c.apply(&mut chip8.keyboard_buffer);
+        }
+        if let Some(key) = keyboard.mouse.and_then(|(x, y)| display.keypad_key(x, y)) {
+            chip8.keyboard_buffer.set(key);
+        }
+        for &(x, y) in keyboard.touches.values() {
+            if let Some(key) = display.touch_key(&options.touch_grid, x, y) {
+                chip8.keyboard_buffer.set(key);
+            }
+        }
+        let local_keys = chip8.keyboard_buffer.keys;
+        for hotkey in hotkeys {
+            match hotkey {
+                input::Hotkey::Screenshot => {
+                    let path = output::screenshot_path(&rom_path);
+                    match &compare {
+                        Some((instance, _)) => display.screenshot(
+                            &[&chip8.display_buffer, &instance.display_buffer],
+                            &path,
+                        )?,
+                        None => display.screenshot(&[&chip8.display_buffer], &path)?,
+                    }
+                    println!("Saved screenshot to {}", path.display());
+                }
+                input::Hotkey::ToggleHighContrast => {
+                    display.mode.high_contrast = !display.mode.high_contrast;
+                    dirty = true;
+                }
+                input::Hotkey::ToggleInverted => {
+                    display.mode.inverted = !display.mode.inverted;
+                    dirty = true;
+                }
+                input::Hotkey::BrightnessUp | input::Hotkey::BrightnessDown => {
+                    let brighter = matches!(hotkey, input::Hotkey::BrightnessUp);
+                    display.mode.adjust_brightness(brighter);
+                    message = Some((
+                        format!("Brightness {}%", display.mode.brightness),
+                        clock.now(),
+                    ));
+                    dirty = true;
+                }
+                input::Hotkey::ToggleMute => {
+                    let muted = audio.muted();
+                    audio.set_muted(!muted);
+                }
+                input::Hotkey::ToggleOverlay => {
+                    overlay = !overlay;
+                    dirty = true;
+                }
+                input::Hotkey::ToggleScope => {
+                    scope.toggle();
+                    dirty = true;
+                }
+                input::Hotkey::ToggleHeatmap => {
+                    heat_shown = !heat_shown;
+                    dirty = true;
+                }
+                input::Hotkey::ResetHeatmap => {
+                    heat.reset();
+                    dirty = true;
+                }
+                input::Hotkey::ExportHeatmap => {
+                    let path = output::screenshot_path(&rom_path).with_extension("heatmap.png");
+                    let scale = consts::HIRES_WIDTH / heat.width;
+                    output::write_png(
+                        &path,
+                        (heat.width * scale) as u32,
+                        (heat.height * scale) as u32,
+                        &heat.to_rgb(scale),
+                    )?;
+                    println!("Saved heatmap to {}", path.display());
+                }
+                // Counting only starts once asked for, the next press saves what it found
+                input::Hotkey::ExportHistogram | input::Hotkey::ExportCoverage
+                    if !chip8.histogram.enabled =>
+                {
+                    chip8.histogram.enabled = true;
+                    println!("Counting executed instructions, press again to save");
+                }
+                input::Hotkey::ExportHistogram => {
+                    let path = output::screenshot_path(&rom_path).with_extension("histogram.csv");
+                    std::fs::write(&path, chip8.histogram.to_csv())?;
+                    println!("Saved opcode histogram to {}", path.display());
+                }
+                input::Hotkey::SaveSlot(slot) => {
+                    let saved = match &slots {
+                        Ok(s) => s.save(slot, &capture(&chip8, &display.palette)),
+                        Err(e) => Err((*e).into()),
+                    };
+                    let text = match saved {
+                        Ok(()) => format!("Saved {}", slot),
+                        Err(e) => {
+                            eprintln!("Could not save slot {}: {}", slot, e);
+                            format!("Error {}", slot)
+                        }
+                    };
+                    message = Some((text, clock.now()));
+                }
+                // Netplay peers would drift apart, so states only load in local play
+                input::Hotkey::LoadSlot(_) if netplay.is_some() => {
+                    eprintln!("Save states cannot be loaded during netplay");
+                }
+                input::Hotkey::LoadSlot(slot) => {
+                    let loaded = match &slots {
+                        Ok(s) => s.load(slot),
+                        Err(e) => Err((*e).into()),
+                    };
+                    let text = match loaded {
+                        Ok(Some(state)) => {
+                            state.restore(&mut chip8);
+                            format!("Loaded {}", slot)
+                        }
+                        Ok(None) => format!("Empty {}", slot),
+                        Err(e) => {
+                            eprintln!("Could not load slot {}: {}", slot, e);
+                            format!("Error {}", slot)
+                        }
+                    };
+                    message = Some((text, clock.now()));
+                }
+                input::Hotkey::ExportCoverage => {
+                    let path = output::screenshot_path(&rom_path).with_extension("coverage.html");
+                    std::fs::write(&path, coverage::report(&chip8, &rom_path))?;
+                    println!("Saved coverage report to {}", path.display());
+                }
+                input::Hotkey::ToggleCheats if netplay.is_some() => {
+                    eprintln!("Cheats cannot be used during netplay");
+                }
+                input::Hotkey::ToggleCheats => {
+                    if let Some(c) = cheats.as_mut() {
+                        let enabled = c.toggle();
+                        println!("Cheats {}", if enabled { "on" } else { "off" });
+                    }
+                }
+                input::Hotkey::SpeedUp | input::Hotkey::SpeedDown if netplay.is_some() => {
+                    eprintln!("The speed cannot be changed during netplay");
+                }
+                input::Hotkey::SpeedUp | input::Hotkey::SpeedDown => {
+                    let faster = matches!(hotkey, input::Hotkey::SpeedUp);
+                    cycles_per_frame = utils::adjust_speed(cycles_per_frame, faster);
+                    println!("Speed: {} instructions per frame", cycles_per_frame);
+                }
+                input::Hotkey::ToggleFastForward if netplay.is_some() => {
+                    eprintln!("Netplay cannot be fast-forwarded");
+                }
+                input::Hotkey::ToggleFastForward => title.fast_forward = !title.fast_forward,
+            }
+        }
 
-    loop {
-        let keyboard_status = keyboard.poll().is_ok();
-        if !keyboard_status {
-            break;
-        }
-        let status = match chip8.cycle() {
-            Some(a) => a,
-            None => panic!("Failed during execution, exiting..."),
+        // Fast-forwarding runs several frames for every one that is due, timers included
+        let speedup = match title.fast_forward {
+            true => consts::FAST_FORWARD_FRAMES,
+            false => 1,
         };
-        if *audio.sound_timer.as_ref().borrow() > 0 {
-            audio.speaker.resume();
-        } else {
-            audio.speaker.pause();
+        for _ in 0..pacer.due(&clock) * speedup {
+            if remote.as_ref().is_some_and(|r| r.paused) {
+                continue;
+            }
+            let mut keys = local_keys;
+            if let Some(s) = keyscript.as_mut() {
+                keys |= s.keys_at(chip8.frames);
+                chip8.keyboard_buffer.keys = keys;
+            }
+            if let Some(n) = netplay.as_mut() {
+                chip8.keyboard_buffer.keys = n.exchange(keys)?;
+            }
+            match remote.as_mut() {
+                // Write breakpoints consume events, so checking continues even without any
+                Some(r) => {
+                    let (breakpoints, mut hit) = (&r.breakpoints, None);
+                    let stop = |c: &processor::Processor| {
+                        hit = breakpoints.hit(c);
+                        hit.is_some()
+                    };
+                    if chip8
+                        .run_frame_until(cycles_per_frame, stop)
+                        .map_err(|e| crash_dump(&chip8, &rom_path, e))?
+                    {
+                        println!(
+                            "Breakpoint {} hit at {:#05X}",
+                            hit.unwrap_or_default(),
+                            chip8.pc
+                        );
+                        r.paused = true;
+                        dirty = true;
+                        break;
+                    }
+                }
+                None => chip8
+                    .run_frame(cycles_per_frame)
+                    .map_err(|e| crash_dump(&chip8, &rom_path, e))?,
+            }
+            if let Some((instance, compare_events)) = compare.as_mut() {
+                instance.keyboard_buffer = chip8.keyboard_buffer;
+                let compare_path = options.compare.as_deref().unwrap_or_default();
+                instance
+                    .run_frame(cycles_per_frame)
+                    .map_err(|e| crash_dump(instance, compare_path, e))?;
+                for event in compare_events.try_iter() {
+                    if let events::Event::FrameCompleted { redraw } = event {
+                        dirty |= redraw;
+                    }
+                }
+            }
+            for event in events.try_iter() {
+                match event {
+                    events::Event::FrameCompleted { redraw } => dirty |= redraw,
+                    events::Event::Halted => {
+                        halted = true;
+                        break 'running;
+                    }
+                    events::Event::SpriteDrawn(draw) => heat.record(
+                        &draw,
+                        &chip8.ram,
+                        &chip8.display_buffer,
+                        chip8.quirks().wrap,
+                    ),
+                    events::Event::SoundTimerSet(ticks) => {
+                        audio.sound_for(ticks, frame_period / speedup)
+                    }
+                    events::Event::PitchSet(pitch) => {
+                        audio.set_pitch(pitch);
+                        if let Some(r) = recording.as_mut() {
+                            r.set_pitch(pitch);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(c) = cheats.as_mut() {
+                c.apply(&mut chip8);
+            }
         }
-        match status {
-            processor::CycleStatus::RedrawScreen => {
-                display.draw()?;
-                display.canvas.present();
+
+        #[cfg(feature = "websocket")]
+        if let Some(s) = &stream {
+            s.publish(&chip8);
+        }
+        #[cfg(feature = "stats")]
+        if let Some(s) = &stats {
+            s.update(&chip8);
+        }
+
+        if let Some(t) = timers.as_mut() {
+            if remote.as_ref().is_some_and(|r| r.paused) {
+                t.resync(&clock);
+            } else {
+                for _ in 0..t.ticks(&clock) * speedup {
+                    chip8.tick_timers();
+                    if let Some((instance, _)) = compare.as_mut() {
+                        instance.tick_timers();
+                    }
+                }
             }
-            _ => continue,
         }
-        thread::sleep(std::time::Duration::from_millis(
-            consts::CLOCK_PERIOD as u64,
-        ));
+        let shown = title.clone();
+        title.cycles_per_frame = cycles_per_frame;
+        title.paused = remote.as_ref().is_some_and(|r| r.paused);
+        if title != shown {
+            display.set_title(&title)?;
+        }
+        // The audio callback times the beep itself, the timer here only drives the recording,
+        // the visual bell and the oscilloscope
+        let sound_on = chip8.sound_timer > 0;
+        scope.record_timer(chip8.sound_timer);
+        audio.set_playing(!title.paused);
+        if let Some(r) = recording.as_mut() {
+            r.capture(&chip8.display_buffer, sound_on)?;
+        }
+        let show_bell = options.visual_bell && sound_on;
+        if message.as_ref().is_some_and(|(_, shown)| {
+            clock.now() - *shown >= Duration::from_millis(consts::MESSAGE_MILLIS)
+        }) {
+            message = None;
+            dirty = true;
+        }
+        // The registers, draw counts and waveform change on almost every frame, so the
+        // overlays redraw unconditionally
+        let overlays = overlay
+            || heat_shown
+            || scope.shown
+            || display.keypad.is_some()
+            || options.touch_overlay;
+        if dirty || overlays || message.is_some() || show_bell != bell_shown {
+            match &compare {
+                Some((instance, _)) => {
+                    display.draw(&[&chip8.display_buffer, &instance.display_buffer])?
+                }
+                None => display.draw(&[&chip8.display_buffer])?,
+            }
+            if heat_shown {
+                display.draw_heatmap(&heat)?;
+            }
+            if scope.shown {
+                display.draw_scope(&scope)?;
+            }
+            display.draw_keypad(chip8.keyboard_buffer.keys, chip8.polled_keys)?;
+            if options.touch_overlay {
+                display.draw_touch_grid(&options.touch_grid, chip8.keyboard_buffer.keys)?;
+            }
+            if show_bell {
+                display.draw_sound_indicator()?;
+            }
+            if overlay {
+                display.draw_overlay(&output::overlay_lines(&chip8))?;
+            }
+            if let Some((text, _)) = &message {
+                display.draw_message(text)?;
+            }
+            display.canvas.present();
+            bell_shown = show_bell;
+            dirty = false;
+        }
+        clock.sleep(pacer.until_next(&clock));
+    }
+    match (&slots, halted) {
+        (Ok(s), false) => {
+            if let Err(e) = s.autosave(&capture(&chip8, &display.palette)) {
+                eprintln!("Could not save the session: {}", e);
+            }
+        }
+        (Ok(s), true) => {
+            if let Err(e) = s.discard_autosave() {
+                eprintln!("Could not remove the saved session: {}", e);
+            }
+        }
+        _ => {}
+    }
+    if let Some(path) = &options.dump_state {
+        std::fs::write(path, chip8.to_json_state())?;
+    }
+    if let Some(path) = &options.export_display {
+        output::export_display(&chip8.display_buffer, path)?;
+    }
+    if let Some(r) = recording {
+        r.finish()?;
     }
     Ok(())
 }
+
+// State for the files written by save slots and on exit, with a preview of the screen
+fn capture(chip8: &processor::Processor, palette: &output::Palette) -> snapshot::Snapshot {
+    let mut state = snapshot::Snapshot::capture(chip8);
+    state.thumbnail = output::thumbnail(&chip8.display_buffer, palette).ok();
+    state
+}
+
+// Writes the recent instruction trace next to the ROM before giving up, so the failure can be
+// looked at after the window is gone
+fn crash_dump(
+    chip8: &processor::Processor,
+    rom_path: &str,
+    error: &'static str,
+) -> Box<dyn std::error::Error> {
+    let path = output::screenshot_path(rom_path).with_extension("crash.txt");
+    match std::fs::write(&path, trace::crash_report(chip8, rom_path, error)) {
+        Ok(()) => eprintln!("Crash report written to {}", path.display()),
+        Err(e) => eprintln!("Could not write crash report: {}", e),
+    }
+    error.into()
+}
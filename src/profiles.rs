@@ -0,0 +1,112 @@
+use crate::cli::Options;
+use crate::saves::data_dir;
+use std::path::PathBuf;
+
+// Options kept in a profile, each followed by its value. Everything else on the command line is
+// about the session rather than the game and is left out
+pub const PROFILE_FLAGS: [&str; 11] = [
+    "--platform",
+    "--ipf",
+    "--palette",
+    "--quirk",
+    "--no-quirk",
+    "--turbo",
+    "--keymap",
+    "--layout",
+    "--waveform",
+    "--tone",
+    "--volume",
+];
+
+// The user's own settings for one ROM, stored under its SHA-1 in the data directory as the
+// command line options to apply. They take priority over the ROM database and are in turn
+// overridden by the command line
+pub fn path(sha1: &str) -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("profiles").join(format!("{}.txt", sha1)))
+}
+
+pub fn load(sha1: &str) -> Result<Option<Options>, Box<dyn std::error::Error>> {
+    let path = match path(sha1) {
+        Some(p) if p.exists() => p,
+        _ => return Ok(None),
+    };
+    let text = std::fs::read_to_string(path)?;
+    let args: Vec<String> = text.split_whitespace().map(String::from).collect();
+    Ok(Some(Options::parse(&args)?))
+}
+
+// Replaces the ROM's profile with the profile options found in args
+pub fn save(sha1: &str, args: &[String]) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = path(sha1).ok_or("No data directory to keep profiles in")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, profile_args(args).join(" ") + "\n")?;
+    Ok(path)
+}
+
+pub fn profile_args(args: &[String]) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if PROFILE_FLAGS.contains(&arg.as_str()) {
+            kept.push(arg.clone());
+            kept.extend(args.next().cloned());
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::profile_args;
+    use crate::cli::Options;
+
+    #[test]
+    fn test_profile_args() -> Result<(), Box<dyn std::error::Error>> {
+        let args: Vec<String> = [
+            "game.ch8",
+            "--ipf",
+            "20",
+            "--record",
+            "out.gif",
+            "--quirk",
+            "wrap",
+            "--save-profile",
+            "--keymap",
+            "1234qwerasdfzxcv",
+            "--tone",
+            "660",
+            "--mute",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let kept = profile_args(&args);
+        assert_eq!(
+            kept,
+            [
+                "--ipf",
+                "20",
+                "--quirk",
+                "wrap",
+                "--keymap",
+                "1234qwerasdfzxcv",
+                "--tone",
+                "660"
+            ]
+        );
+        let profile = Options::parse(&kept)?;
+        let mut options = Options::parse(&args[..1])?;
+        options.merge(profile);
+        assert_eq!(options.cycles_per_frame, Some(20));
+        assert!(options.quirks()?.wrap);
+        assert_eq!(
+            options.keymap.as_ref().map(|k| k.keys[0].as_str()),
+            Some("1")
+        );
+        assert_eq!(options.tone().frequency, 660.0);
+        assert_eq!(options.volume(), crate::consts::BUZZER_VOLUME);
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use crate::consts;
+use crate::core::events::Event;
+use crate::core::platform::Platform;
+use crate::core::processor::Processor;
+use crate::core::rom::Rom;
+use pyo3::exceptions::{PyIndexError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::sync::mpsc::Receiver;
+
+// Headless machine for Python, e.g. as a reinforcement learning environment:
+//   c = chip8.Chip8("chip8")
+//   c.load_rom(open("pong.ch8", "rb").read())
+//   c.frame()
+//   pixels = numpy.frombuffer(c.display(), numpy.uint8).reshape(c.height, c.width)
+#[pyclass(unsendable, name = "Chip8")]
+pub struct PyChip8 {
+    processor: Processor,
+    events: Receiver<Event>,
+    cycles_per_frame: usize,
+    halted: bool,
+}
+
+fn runtime_error(e: impl ToString) -> PyErr {
+    PyRuntimeError::new_err(e.to_string())
+}
+
+#[pymethods]
+impl PyChip8 {
+    #[new]
+    #[pyo3(signature = (platform = "modern"))]
+    fn new(platform: &str) -> PyResult<Self> {
+        let platform: Platform = platform.parse().map_err(PyValueError::new_err)?;
+        let mut processor = Processor::builder()
+            .quirks(platform.quirks())
+            .start_pc(platform.load_address())
+            .resolution(platform.resolution())
+            .fonts(platform.fonts())
+            .build();
+        let events = processor.events.subscribe();
+        Ok(PyChip8 {
+            processor,
+            events,
+            cycles_per_frame: platform.cycles_per_frame(),
+            halted: false,
+        })
+    }
+
+    fn load_rom(&mut self, rom: &[u8]) -> PyResult<()> {
+        let rom = Rom::from_bytes(rom).map_err(runtime_error)?;
+        self.processor.load_rom(&rom).map_err(runtime_error)?;
+        self.halted = false;
+        Ok(())
+    }
+
+    // Executes a single instruction without ticking the timers
+    fn step(&mut self) -> PyResult<()> {
+        self.processor.cycle().map_err(runtime_error)?;
+        Ok(())
+    }
+
+    // Runs one 60Hz frame, returns whether the display changed
+    fn frame(&mut self) -> PyResult<bool> {
+        self.processor
+            .run_frame(self.cycles_per_frame)
+            .map_err(runtime_error)?;
+        let mut redraw = false;
+        for event in self.events.try_iter() {
+            match event {
+                Event::FrameCompleted { redraw: r } => redraw |= r,
+                Event::Halted => self.halted = true,
+                _ => {}
+            }
+        }
+        Ok(redraw)
+    }
+
+    #[pyo3(signature = (key, pressed = true))]
+    fn press_key(&mut self, key: usize, pressed: bool) -> PyResult<()> {
+        if key >= consts::KEYBOARD_SIZE {
+            return Err(PyIndexError::new_err("Keys go from 0 to 15"));
+        }
+        let keyboard = &mut self.processor.keyboard_buffer;
+        if pressed {
+            keyboard.set(key);
+        } else {
+            keyboard.clear(key);
+        }
+        Ok(())
+    }
+
+    // One byte per pixel, row by row
+    fn display<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let display = &self.processor.display_buffer;
+        let pixels: Vec<u8> = display.buffer.iter().flatten().copied().collect();
+        PyBytes::new(py, &pixels)
+    }
+
+    fn memory<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.processor.ram.buffer)
+    }
+
+    #[getter]
+    fn width(&self) -> usize {
+        self.processor.display_buffer.width()
+    }
+
+    #[getter]
+    fn height(&self) -> usize {
+        self.processor.display_buffer.height()
+    }
+
+    #[getter]
+    fn pc(&self) -> u16 {
+        self.processor.pc
+    }
+
+    #[getter]
+    fn registers(&self) -> Vec<u8> {
+        self.processor.registers.to_vec()
+    }
+
+    #[getter]
+    fn halted(&self) -> bool {
+        self.halted
+    }
+
+    #[getter]
+    fn get_cycles_per_frame(&self) -> usize {
+        self.cycles_per_frame
+    }
+
+    #[setter]
+    fn set_cycles_per_frame(&mut self, cycles: usize) {
+        self.cycles_per_frame =
+            cycles.clamp(consts::MIN_CYCLES_PER_FRAME, consts::MAX_CYCLES_PER_FRAME);
+    }
+}
+
+#[pymodule(name = "chip8")]
+fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyChip8>()
+}
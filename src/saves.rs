@@ -0,0 +1,129 @@
+use crate::core::snapshot::Snapshot;
+use std::path::{Path, PathBuf};
+
+// Number of save slots per ROM, bound to F1-F10
+pub const SLOT_COUNT: usize = 10;
+
+// Per-user directory for files the emulator keeps between runs:
+// $XDG_DATA_HOME/chip8, ~/.local/share/chip8 or %APPDATA%\chip8
+pub fn data_dir() -> Option<PathBuf> {
+    let var = |name| {
+        std::env::var_os(name)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    var("XDG_DATA_HOME")
+        .or_else(|| var("HOME").map(|home| home.join(".local").join("share")))
+        .or_else(|| var("APPDATA"))
+        .map(|dir| dir.join("chip8"))
+}
+
+// Save states of one ROM, kept under its SHA-1 so renaming or moving the file keeps them
+#[derive(Clone, Debug, PartialEq)]
+pub struct SaveSlots {
+    pub dir: PathBuf,
+    // Written into every state and checked on load
+    pub rom_sha1: String,
+}
+
+impl SaveSlots {
+    pub fn for_rom(sha1: &str) -> Result<Self, &'static str> {
+        let dir = data_dir().ok_or("No data directory to keep save states in")?;
+        Ok(SaveSlots {
+            dir: dir.join("saves").join(sha1),
+            rom_sha1: sha1.to_string(),
+        })
+    }
+
+    // Slots are numbered from 1 like the function keys
+    pub fn path(&self, slot: usize) -> PathBuf {
+        self.dir.join(format!("slot{}.state", slot))
+    }
+
+    // Written when the emulator is closed, read back by --resume
+    pub fn autosave_path(&self) -> PathBuf {
+        self.dir.join("autosave.state")
+    }
+
+    pub fn save(&self, slot: usize, snapshot: &Snapshot) -> Result<(), Box<dyn std::error::Error>> {
+        self.write(&self.path(slot), snapshot)
+    }
+
+    // None when nothing was saved to the slot yet
+    pub fn load(&self, slot: usize) -> Result<Option<Snapshot>, Box<dyn std::error::Error>> {
+        self.read(&self.path(slot))
+    }
+
+    pub fn autosave(&self, snapshot: &Snapshot) -> Result<(), Box<dyn std::error::Error>> {
+        self.write(&self.autosave_path(), snapshot)
+    }
+
+    pub fn resume(&self) -> Result<Option<Snapshot>, Box<dyn std::error::Error>> {
+        self.read(&self.autosave_path())
+    }
+
+    // For programs that exited, so --resume starts over instead of going back to an older
+    // session
+    pub fn discard_autosave(&self) -> std::io::Result<()> {
+        match std::fs::remove_file(self.autosave_path()) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    fn write(&self, path: &Path, snapshot: &Snapshot) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(path, snapshot.to_bytes(&self.rom_sha1))?;
+        Ok(())
+    }
+
+    fn read(&self, path: &Path) -> Result<Option<Snapshot>, Box<dyn std::error::Error>> {
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let bytes = std::fs::read(path)?;
+        Snapshot::from_bytes(&bytes, &self.rom_sha1)
+            .map(Some)
+            .map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SaveSlots;
+    use crate::core::processor::Processor;
+    use crate::core::snapshot::Snapshot;
+
+    #[test]
+    fn test_slots() -> Result<(), Box<dyn std::error::Error>> {
+        let slots = SaveSlots {
+            dir: std::env::temp_dir().join(format!("chip8-slots-{}", std::process::id())),
+            rom_sha1: String::from("0a1b2c3d4e5f60718293a4b5c6d7e8f901234567"),
+        };
+        let mut chip8: Processor = Default::default();
+        chip8.registers[3] = 42;
+        assert!(slots.load(2)?.is_none());
+        slots.save(2, &Snapshot::capture(&chip8))?;
+
+        let mut restored: Processor = Default::default();
+        slots
+            .load(2)?
+            .ok_or("Slot is empty")?
+            .restore(&mut restored);
+        assert_eq!(restored.registers[3], 42);
+
+        assert!(slots.resume()?.is_none());
+        chip8.registers[3] = 7;
+        slots.autosave(&Snapshot::capture(&chip8))?;
+        slots.resume()?.ok_or("No autosave")?.restore(&mut restored);
+        assert_eq!(restored.registers[3], 7);
+        slots.discard_autosave()?;
+        assert!(slots.resume()?.is_none());
+        slots.discard_autosave()?;
+
+        std::fs::write(slots.path(3), b"garbage")?;
+        assert!(slots.load(3).is_err());
+        std::fs::remove_dir_all(&slots.dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use crate::assembler;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// Maps addresses to label names so debugging output can show loop_main instead of 0x2F4
+#[derive(Clone, Debug, Default)]
+pub struct Symbols {
+    names: BTreeMap<u16, String>,
+}
+
+impl Symbols {
+    // Accepts Octo's symbol output (:const name value, one per line) as well as bare
+    // name value pairs, # starts a comment
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut symbols: Symbols = Default::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (name, value) = match fields[..] {
+                [] => continue,
+                [":const", name, value] | [name, value] => (name, value),
+                _ => return Err(format!("line {}: Expected a name and an address", i + 1)),
+            };
+            let address = match value.strip_prefix("0x") {
+                Some(hex) => u16::from_str_radix(hex, 16),
+                None => value.parse(),
+            }
+            .map_err(|_| format!("line {}: Invalid address {}", i + 1, value))?;
+            symbols.insert(address, name);
+        }
+        Ok(symbols)
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Symbols::parse(&text).map_err(|e| format!("{}: {}", path, e))?)
+    }
+
+    // Octo sources provide their own labels, other ROMs pick up a .sym file next to them
+    pub fn for_rom(rom_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        if rom_path.ends_with(".8o") {
+            let source = std::fs::read_to_string(rom_path)?;
+            let (_, labels) = assembler::assemble_with_labels(&source)?;
+            let mut symbols: Symbols = Default::default();
+            for (name, address) in &labels {
+                symbols.insert(*address, name);
+            }
+            return Ok(symbols);
+        }
+        let path = Path::new(rom_path).with_extension("sym");
+        match path.to_str() {
+            Some(p) if path.is_file() => Symbols::load(p),
+            _ => Ok(Default::default()),
+        }
+    }
+
+    // The first name given to an address wins, later aliases are ignored
+    pub fn insert(&mut self, address: u16, name: &str) {
+        self.names
+            .entry(address)
+            .or_insert_with(|| name.to_string());
+    }
+
+    pub fn name(&self, address: u16) -> Option<&str> {
+        self.names.get(&address).map(|n| n.as_str())
+    }
+
+    pub fn address(&self, name: &str) -> Option<u16> {
+        self.names
+            .iter()
+            .find(|(_, n)| n.as_str() == name)
+            .map(|(&address, _)| address)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    // Label name when there is one, otherwise the address in hex
+    pub fn describe(&self, address: u16) -> String {
+        match self.name(address) {
+            Some(name) => name.to_string(),
+            None => format!("{:#05X}", address),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symbols;
+
+    #[test]
+    fn test_parse_symbols() -> Result<(), String> {
+        let symbols = Symbols::parse(":const loop_main 0x2F4\n# comment\n\ndraw 512\n")?;
+        assert_eq!(symbols.name(0x2F4), Some("loop_main"));
+        assert_eq!(symbols.address("draw"), Some(0x200));
+        assert_eq!(symbols.describe(0x2F4), "loop_main");
+        assert_eq!(symbols.describe(0x2F6), "0x2F6");
+        assert!(Symbols::parse("draw\n").is_err());
+        assert!(Symbols::parse("draw zz\n").is_err());
+        Ok(())
+    }
+}
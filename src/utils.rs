@@ -1,4 +1,5 @@
 use crate::consts;
+#[inline]
 pub fn nibble_split(bytes: &[u8]) -> (u8, u8, u8, u8) {
     assert!(bytes.len() == consts::OP_CODE_BYTES);
     (
@@ -12,3 +13,46 @@ pub fn nibble_split(bytes: &[u8]) -> (u8, u8, u8, u8) {
 pub fn bounds_check(x: usize, y: usize, width: usize, height: usize) -> bool {
     x < width && y < height
 }
+
+// Steps the CPU speed by roughly 20% so the hotkeys stay useful across the whole range
+pub fn adjust_speed(cycles_per_frame: usize, faster: bool) -> usize {
+    let step = (cycles_per_frame / 5).max(1);
+    let adjusted = if faster {
+        cycles_per_frame + step
+    } else {
+        cycles_per_frame.saturating_sub(step)
+    };
+    adjusted.clamp(consts::MIN_CYCLES_PER_FRAME, consts::MAX_CYCLES_PER_FRAME)
+}
+
+// CRC-32 as used by zip and most ROM catalogs
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::adjust_speed;
+    use crate::consts;
+
+    #[test]
+    fn test_adjust_speed() {
+        assert_eq!(adjust_speed(10, true), 12);
+        assert_eq!(adjust_speed(10, false), 8);
+        // Small speeds still move by one instruction
+        assert_eq!(adjust_speed(3, true), 4);
+        assert_eq!(adjust_speed(2, false), 1);
+        let (min, max) = (consts::MIN_CYCLES_PER_FRAME, consts::MAX_CYCLES_PER_FRAME);
+        assert_eq!(adjust_speed(min, false), min);
+        assert_eq!(adjust_speed(max, true), max);
+        assert_eq!(adjust_speed(max - 1, true), max);
+        assert_eq!(adjust_speed(0, false), min);
+    }
+}
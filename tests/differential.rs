@@ -0,0 +1,184 @@
+// Runs ROMs on the emulator and on the reference interpreter in tests/oracle side by side,
+// comparing the whole machine after every instruction
+mod oracle;
+
+use chip8::consts;
+use chip8::core::processor::Processor;
+use chip8::core::quirks::Quirks;
+use chip8::core::rom;
+use oracle::{Oracle, Step};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// Timers tick once every this many instructions, like a frontend running at 10 per frame
+const STEPS_PER_TICK: usize = 10;
+
+fn profiles() -> [(Quirks, oracle::Quirks); 3] {
+    let vip = Quirks {
+        vf_reset: true,
+        shift_vy: true,
+        memory_increment: true,
+        ..Default::default()
+    };
+    let schip = Quirks {
+        jump_vx: true,
+        ..Default::default()
+    };
+    [vip, schip, Quirks::default()].map(|quirks| {
+        let reference = oracle::Quirks {
+            vf_reset: quirks.vf_reset,
+            shift_vy: quirks.shift_vy,
+            memory_increment: quirks.memory_increment,
+            jump_vx: quirks.jump_vx,
+        };
+        (quirks, reference)
+    })
+}
+
+fn mismatch(processor: &Processor, reference: &Oracle) -> Option<&'static str> {
+    let stack = &processor.stack[..processor.stack_pointer as usize];
+    let screen = processor
+        .display_buffer
+        .buffer
+        .iter()
+        .zip(reference.screen.iter())
+        .all(|(row, expected)| row.iter().zip(expected).all(|(&p, &e)| (p != 0) == e));
+    if processor.registers != reference.v {
+        Some("registers")
+    } else if processor.idx_register != reference.i {
+        Some("index register")
+    } else if processor.pc != reference.pc {
+        Some("program counter")
+    } else if stack != reference.stack.as_slice() {
+        Some("stack")
+    } else if (processor.delay_timer, processor.sound_timer) != (reference.delay, reference.sound) {
+        Some("timers")
+    } else if processor.ram.buffer != reference.memory {
+        Some("memory")
+    } else if !screen {
+        Some("display")
+    } else {
+        None
+    }
+}
+
+// Steps both machines until the program leaves what the oracle models, returning how many
+// instructions agreed. Keys are held or released at random every tick
+fn run(program: &[u8], quirks: (Quirks, oracle::Quirks), steps: usize, rng: &mut StdRng) -> usize {
+    let rom = rom::Rom::from_bytes(program).expect("Could not build ROM");
+    let mut processor = Processor::builder().quirks(quirks.0).rng(rng.gen()).build();
+    processor.load_rom(&rom).expect("Could not load ROM");
+    let mut reference = Oracle::new(program, quirks.1);
+    // The emulator also carries the SUPER-CHIP font, which the oracle never touches
+    reference.memory[consts::BIG_FONT_OFFSET..consts::BIG_FONT_OFFSET + consts::BIG_FONT_SET_SIZE]
+        .copy_from_slice(&consts::BIG_FONT_SET);
+
+    for step in 0..steps {
+        if step % STEPS_PER_TICK == 0 {
+            let keys = if rng.gen_bool(0.3) { rng.gen() } else { 0 };
+            processor.keyboard_buffer.keys = keys;
+            reference.keys = keys;
+            if step > 0 {
+                processor.tick_timers();
+                reference.tick();
+            }
+        }
+        let pc = processor.pc;
+        let Some(outcome) = reference.step() else {
+            return step;
+        };
+        if let Err(e) = processor.cycle() {
+            panic!(
+                "Emulator failed at {:#05X} where the oracle did not: {}",
+                pc, e
+            );
+        }
+        if let Step::Random { x, mask } = outcome {
+            let value = processor.registers[x];
+            assert_eq!(value & !mask, 0, "CXNN at {:#05X} ignored its mask", pc);
+            reference.v[x] = value;
+        }
+        if let Some(what) = mismatch(&processor, &reference) {
+            let opcode = u16::from_be_bytes([
+                reference.memory[pc as usize],
+                reference.memory[pc as usize + 1],
+            ]);
+            panic!("{} differ after {:04X} at {:#05X}", what, opcode, pc);
+        }
+    }
+    steps
+}
+
+// Random instructions from the original set with random operands, jumps and calls stay
+// inside the program so runs last long enough to be interesting
+fn random_program(rng: &mut StdRng) -> Vec<u8> {
+    let len = rng.gen_range(8..128);
+    let mut program = Vec::with_capacity(len * 2);
+    for _ in 0..len {
+        let (x, y, nn) = (
+            rng.gen_range(0..16_u16),
+            rng.gen_range(0..16_u16),
+            rng.gen::<u8>() as u16,
+        );
+        let target = 0x200 + rng.gen_range(0..len as u16) * 2;
+        let opcode = match rng.gen_range(0..20) {
+            0 => 0x00E0,
+            1 => 0x00EE,
+            2 => 0x1000 | target,
+            3 => 0x2000 | target,
+            4 => 0x3000 | x << 8 | nn,
+            5 => 0x4000 | x << 8 | nn,
+            6 => 0x5000 | x << 8 | y << 4,
+            7 | 8 => 0x6000 | x << 8 | nn,
+            9 => 0x7000 | x << 8 | nn,
+            10 | 11 => {
+                let n = [0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0xE][rng.gen_range(0..9)];
+                0x8000 | x << 8 | y << 4 | n
+            }
+            12 => 0x9000 | x << 8 | y << 4,
+            13 => 0xA000 | rng.gen_range(0..0x1000),
+            14 => 0xC000 | x << 8 | nn,
+            15 => 0xD000 | x << 8 | y << 4 | rng.gen_range(1..16),
+            16 => 0xE000 | x << 8 | [0x9E, 0xA1][rng.gen_range(0..2)],
+            _ => {
+                let nn =
+                    [0x07, 0x0A, 0x15, 0x18, 0x1E, 0x29, 0x33, 0x55, 0x65][rng.gen_range(0..9)];
+                0xF000 | x << 8 | nn
+            }
+        };
+        program.extend_from_slice(&opcode.to_be_bytes());
+    }
+    program
+}
+
+#[test]
+fn test_random_programs_match_oracle() {
+    let mut rng = StdRng::seed_from_u64(0x0AC1E);
+    let mut agreed = 0;
+    for _ in 0..100 {
+        let program = random_program(&mut rng);
+        for quirks in profiles() {
+            agreed += run(&program, quirks, 2_000, &mut rng);
+        }
+    }
+    // Guards against the generator drifting into programs that stop straight away
+    assert!(agreed > 30_000, "only {} instructions compared", agreed);
+}
+
+#[test]
+fn test_roms_match_oracle() {
+    let mut rng = StdRng::seed_from_u64(0x0AC1E);
+    for name in [
+        "IBM Logo",
+        "Maze",
+        "Pong",
+        "Sierpinski",
+        "Keypad Test",
+        "Audio Test",
+    ] {
+        let program = std::fs::read(format!("roms/{}.ch8", name)).expect("Missing ROM");
+        for quirks in profiles() {
+            run(&program, quirks, 5_000, &mut rng);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+// A deliberately plain CHIP-8 interpreter written straight from the instruction reference,
+// sharing no code with the emulator. It only knows the original 35 instructions and the
+// handful of quirks that change them, and gives up on anything else
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+
+const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60, 0x20, 0x20, 0x70, 0xF0, 0x10, 0xF0, 0x80, 0xF0, 0xF0,
+    0x10, 0xF0, 0x10, 0xF0, 0x90, 0x90, 0xF0, 0x10, 0x10, 0xF0, 0x80, 0xF0, 0x10, 0xF0, 0xF0, 0x80,
+    0xF0, 0x90, 0xF0, 0xF0, 0x10, 0x20, 0x40, 0x40, 0xF0, 0x90, 0xF0, 0x90, 0xF0, 0xF0, 0x90, 0xF0,
+    0x10, 0xF0, 0xF0, 0x90, 0xF0, 0x90, 0x90, 0xE0, 0x90, 0xE0, 0x90, 0xE0, 0xF0, 0x80, 0x80, 0x80,
+    0xF0, 0xE0, 0x90, 0x90, 0x90, 0xE0, 0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80,
+];
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Quirks {
+    pub vf_reset: bool,
+    pub shift_vy: bool,
+    pub memory_increment: bool,
+    pub jump_vx: bool,
+}
+
+// What a step did that the caller has to know about
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    Done,
+    // CXNN, the oracle has no RNG of its own so the caller supplies VX
+    Random { x: usize, mask: u8 },
+}
+
+pub struct Oracle {
+    pub memory: [u8; 4096],
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub stack: Vec<u16>,
+    pub delay: u8,
+    pub sound: u8,
+    pub screen: [[bool; WIDTH]; HEIGHT],
+    pub keys: u16,
+    pub quirks: Quirks,
+}
+
+impl Oracle {
+    pub fn new(program: &[u8], quirks: Quirks) -> Self {
+        let mut memory = [0; 4096];
+        memory[..FONT.len()].copy_from_slice(&FONT);
+        memory[0x200..0x200 + program.len()].copy_from_slice(program);
+        Oracle {
+            memory,
+            v: [0; 16],
+            i: 0,
+            pc: 0x200,
+            stack: Vec::new(),
+            delay: 0,
+            sound: 0,
+            screen: [[false; WIDTH]; HEIGHT],
+            keys: 0,
+            quirks,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        self.delay = self.delay.saturating_sub(1);
+        self.sound = self.sound.saturating_sub(1);
+    }
+
+    // Runs one instruction, None when it is outside what the oracle models or the program
+    // did something invalid, in which case the machine is left as it was
+    pub fn step(&mut self) -> Option<Step> {
+        let pc = self.pc as usize;
+        if pc + 1 >= self.memory.len() {
+            return None;
+        }
+        let opcode = (self.memory[pc] as u16) << 8 | self.memory[pc + 1] as u16;
+        let x = (opcode >> 8 & 0xF) as usize;
+        let y = (opcode >> 4 & 0xF) as usize;
+        let n = (opcode & 0xF) as u8;
+        let nn = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+        let mut next = self.pc + 2;
+        let mut step = Step::Done;
+
+        match opcode >> 12 {
+            0x0 if opcode == 0x00E0 => self.screen = [[false; WIDTH]; HEIGHT],
+            0x0 if opcode == 0x00EE => next = self.stack.pop()?,
+            0x1 => next = nnn,
+            0x2 => {
+                if self.stack.len() == 16 {
+                    return None;
+                }
+                self.stack.push(next);
+                next = nnn;
+            }
+            0x3 if self.v[x] == nn => next += 2,
+            0x3 => {}
+            0x4 if self.v[x] != nn => next += 2,
+            0x4 => {}
+            0x5 if n == 0 && self.v[x] == self.v[y] => next += 2,
+            0x5 if n == 0 => {}
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = self.v[x].wrapping_add(nn),
+            0x8 => {
+                let (vx, vy) = (self.v[x], self.v[y]);
+                let (result, flag) = match n {
+                    0x0 => (vy, None),
+                    0x1 => (vx | vy, self.quirks.vf_reset.then_some(0)),
+                    0x2 => (vx & vy, self.quirks.vf_reset.then_some(0)),
+                    0x3 => (vx ^ vy, self.quirks.vf_reset.then_some(0)),
+                    0x4 => {
+                        let sum = vx as u16 + vy as u16;
+                        (sum as u8, Some((sum > 0xFF) as u8))
+                    }
+                    0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+                    0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+                    0x6 => {
+                        let source = if self.quirks.shift_vy { vy } else { vx };
+                        (source >> 1, Some(source & 1))
+                    }
+                    0xE => {
+                        let source = if self.quirks.shift_vy { vy } else { vx };
+                        (source << 1, Some(source >> 7))
+                    }
+                    _ => return None,
+                };
+                self.v[x] = result;
+                if let Some(flag) = flag {
+                    self.v[0xF] = flag;
+                }
+            }
+            0x9 if n == 0 && self.v[x] != self.v[y] => next += 2,
+            0x9 if n == 0 => {}
+            0xA => self.i = nnn,
+            0xB if self.quirks.jump_vx => next = nnn + self.v[x] as u16,
+            0xB => next = nnn + self.v[0] as u16,
+            0xC => step = Step::Random { x, mask: nn },
+            0xD => {
+                if self.i as usize + n as usize > self.memory.len() {
+                    return None;
+                }
+                let (left, top) = (self.v[x] as usize % WIDTH, self.v[y] as usize % HEIGHT);
+                let mut collision = false;
+                for row in 0..n as usize {
+                    let sprite = self.memory[self.i as usize + row];
+                    for col in 0..8 {
+                        let (px, py) = (left + col, top + row);
+                        if px >= WIDTH || py >= HEIGHT || sprite & (0x80 >> col) == 0 {
+                            continue;
+                        }
+                        collision |= self.screen[py][px];
+                        self.screen[py][px] = !self.screen[py][px];
+                    }
+                }
+                self.v[0xF] = collision as u8;
+            }
+            0xE if nn == 0x9E && self.keys & 1 << (self.v[x] & 0xF) != 0 => next += 2,
+            0xE if nn == 0x9E => {}
+            0xE if nn == 0xA1 && self.keys & 1 << (self.v[x] & 0xF) == 0 => next += 2,
+            0xE if nn == 0xA1 => {}
+            0xF => match nn {
+                0x07 => self.v[x] = self.delay,
+                0x0A => match (0..16).find(|k| self.keys & 1 << k != 0) {
+                    Some(key) => self.v[x] = key,
+                    None => next = self.pc,
+                },
+                0x15 => self.delay = self.v[x],
+                0x18 => self.sound = self.v[x],
+                0x1E => self.i = self.i.wrapping_add(self.v[x] as u16),
+                0x29 => self.i = (self.v[x] & 0xF) as u16 * 5,
+                0x33 => {
+                    let i = self.i as usize;
+                    if i + 3 > self.memory.len() {
+                        return None;
+                    }
+                    let vx = self.v[x];
+                    self.memory[i..i + 3].copy_from_slice(&[vx / 100, vx / 10 % 10, vx % 10]);
+                }
+                0x55 | 0x65 => {
+                    let i = self.i as usize;
+                    if i + x + 1 > self.memory.len() {
+                        return None;
+                    }
+                    if nn == 0x55 {
+                        self.memory[i..=i + x].copy_from_slice(&self.v[..=x]);
+                    } else {
+                        self.v[..=x].copy_from_slice(&self.memory[i..=i + x]);
+                    }
+                    if self.quirks.memory_increment {
+                        self.i = self.i.wrapping_add(x as u16 + 1);
+                    }
+                }
+                _ => return None,
+            },
+            _ => return None,
+        }
+        self.pc = next;
+        Some(step)
+    }
+}